@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use cuentitos_parser::project::Project;
 use cuentitos_parser::Parser as CuentitosParser;
 use std::path::{Path, PathBuf};
 /// Cuentitos - A narrative game engine with probability at its core
@@ -14,11 +15,50 @@ struct Args {
 enum Commands {
     /// Run a Cuentitos script
     Run {
-        /// Path to the script file to run
+        /// Path to the script file to run, or to a project directory with a
+        /// `cuentitos.toml`
         script_path: PathBuf,
         /// Comma-separated list of inputs (e.g., "n,n,s,q")
         input_string: String,
+        /// Show the story in this language (e.g., "es-MX"), from `es-MX.csv`
+        /// and `es.csv` next to the script
+        #[arg(long)]
+        lang: Option<String>,
     },
+    /// Compile a Cuentitos script so it can be run without parsing
+    Compile {
+        /// Path to the script file to compile, or to a project directory
+        /// with a `cuentitos.toml`
+        script_path: PathBuf,
+        /// Where to write the compiled story. Binary output also writes the
+        /// strings file next to it, with a `.cstrings` extension
+        output_path: PathBuf,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Binary)]
+        format: OutputFormat,
+    },
+    /// Write the strings a player sees to a CSV file for translators
+    ExportStrings {
+        /// Path to the script file, or to a project directory
+        script_path: PathBuf,
+        /// Where to write the CSV file (e.g., en.csv)
+        csv_path: PathBuf,
+    },
+    /// Check a translated CSV file against the script
+    CheckStrings {
+        /// Path to the script file, or to a project directory
+        script_path: PathBuf,
+        /// The translated CSV file (e.g., es.csv)
+        csv_path: PathBuf,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputFormat {
+    /// A structure file and a strings file
+    Binary,
+    /// A single JSON document
+    Json,
 }
 
 fn main() {
@@ -28,168 +68,430 @@ fn main() {
         Commands::Run {
             script_path,
             input_string,
+            lang,
         } => {
-            // Read the script file
-            let script = match std::fs::read_to_string(&script_path) {
-                Ok(content) => content,
-                Err(err) => {
-                    eprintln!("Error reading script file: {}", err);
-                    std::process::exit(1);
-                }
+            // A project runs its entry script, so diagnostics and string
+            // tables are found next to it.
+            let project = script_path.is_dir().then(|| load_project(&script_path));
+            let (database, script_path) = match &project {
+                Some(project) => (parse_project(project), project.entry_path()),
+                None => (load_database(&script_path), script_path),
             };
 
             // Keep a copy of the script path for CLI-side diagnostics (e.g. `?`).
             let script_path_for_debug = script_path.clone();
-            // Create parser with file path
-            let mut parser = CuentitosParser::with_file(script_path);
-
-            // Parse it
-            match parser.parse(&script) {
-                Ok((database, warnings)) => {
-                    // Print warnings before running
-                    for warning in warnings {
-                        let file_name = warning
-                            .file
-                            .as_ref()
-                            .and_then(|p| p.file_name())
-                            .and_then(|n| n.to_str())
-                            .unwrap_or("<script>");
-                        println!(
-                            "{}:{}: WARNING: {}",
-                            file_name, warning.line, warning.message
-                        );
-                    }
 
-                    // Run in runtime
-                    let mut runtime = cuentitos_runtime::Runtime::with_file(
-                        database,
-                        script_path_for_debug.clone(),
-                    );
-                    runtime.run();
-
-                    // Track what we've rendered to avoid duplicates
-                    let mut last_rendered_idx = 0;
-
-                    // Process inputs
-                    let mut quit_requested = false;
-                    if !input_string.is_empty() {
-                        for input in input_string.split(',') {
-                            let trimmed = input.trim();
-
-                            // `?` is a CLI-level debug command: print variables
-                            // (or warn if none) without advancing the program counter.
-                            if trimmed == "?" {
-                                // Flush any pending path (e.g. initial START) so the debug
-                                // output appears at the right position in the transcript.
-                                render_path_from(&runtime, last_rendered_idx);
-                                last_rendered_idx = runtime.current_path().len();
-                                print_debug_variables(&runtime, script_path_for_debug.as_path());
-                                continue;
-                            }
+            // Run in runtime
+            let mut runtime =
+                cuentitos_runtime::Runtime::with_file(database, script_path_for_debug.clone());
+            if let Some(language) = project
+                .as_ref()
+                .and_then(|project| project.default_language.as_deref())
+            {
+                runtime.set_default_language(language);
+            }
+            if let Some(language) = &lang {
+                load_translations(&mut runtime, &script_path, language);
+            }
+            runtime.run();
+
+            // Track what we've rendered to avoid duplicates
+            let mut last_rendered_idx = 0;
+
+            // Process inputs
+            let mut quit_requested = false;
+            if !input_string.is_empty() {
+                for input in input_string.split(',') {
+                    let trimmed = input.trim();
+
+                    // `?` is a CLI-level debug command: print variables
+                    // (or warn if none) without advancing the program counter.
+                    if trimmed == "?" {
+                        // Flush any pending path (e.g. initial START) so the debug
+                        // output appears at the right position in the transcript.
+                        render_path_from(&runtime, last_rendered_idx);
+                        last_rendered_idx = runtime.current_path().len();
+                        print_debug_variables(&runtime, script_path_for_debug.as_path());
+                        continue;
+                    }
 
-                            // Auto-step before processing to reach options/content
-                            // This allows tests to use "1,s" or "q" instead of "n,1,s" or "n,q"
-                            // Only skip auto-step on first input if it's 'n' or 's'
-                            let is_option_number = trimmed.parse::<usize>().is_ok();
-                            let is_step_or_skip = matches!(trimmed, "n" | "s");
-                            let is_first_input = last_rendered_idx == 0;
-                            let need_auto_step =
-                                is_option_number || (is_first_input && !is_step_or_skip);
-                            let should_auto_step = need_auto_step
-                                && !runtime.is_waiting_for_option()
-                                && !runtime.has_ended();
-
-                            if should_auto_step {
-                                // Keep stepping until we hit options, can't continue,
-                                // or surface a runtime error.
-                                while !runtime.is_waiting_for_option()
-                                    && !runtime.has_ended()
-                                    && !runtime.has_error()
-                                    && runtime.step()
-                                {
-                                    // If we hit options after stepping, break
-                                    if runtime.is_waiting_for_option() {
-                                        break;
-                                    }
-                                }
-
-                                // Render new blocks that were stepped over
-                                render_path_from(&runtime, last_rendered_idx);
-                                last_rendered_idx = runtime.current_path().len();
-
-                                // Surface any runtime error *after* rendering so
-                                // story output already on stdout precedes the
-                                // error message.
-                                if report_runtime_error(&mut runtime) {
-                                    break;
-                                }
-
-                                // If we hit options, display them
-                                if runtime.is_waiting_for_option() {
-                                    display_options(&runtime, false);
-                                }
-                            }
+                    // `seed <n>` reseeds the RNG without advancing, so it
+                    // must run before the auto-step below rolls anything.
+                    if let Some(argument) = trimmed.strip_prefix("seed ") {
+                        match argument.trim().parse::<u64>() {
+                            Ok(seed) => runtime.set_seed(seed),
+                            Err(_) => println!("ERROR: Invalid seed: {}", argument.trim()),
+                        }
+                        continue;
+                    }
 
-                            // Check for quit after rendering current state
-                            if trimmed == "q" {
-                                // If we're at an option prompt, add newline after >
-                                if runtime.is_waiting_for_option() {
-                                    println!();
-                                }
-                                quit_requested = true;
+                    // Auto-step before processing to reach options/content
+                    // This allows tests to use "1,s" or "q" instead of "n,1,s" or "n,q"
+                    // Only skip auto-step on first input if it's 'n' or 's'
+                    let is_option_number = trimmed.parse::<usize>().is_ok();
+                    let is_step_or_skip = matches!(trimmed, "n" | "s");
+                    let is_first_input = last_rendered_idx == 0;
+                    let need_auto_step = is_option_number || (is_first_input && !is_step_or_skip);
+                    let should_auto_step =
+                        need_auto_step && !runtime.is_waiting_for_option() && !runtime.has_ended();
+
+                    if should_auto_step {
+                        // Keep stepping until we hit options, can't continue,
+                        // or surface a runtime error.
+                        while !runtime.is_waiting_for_option()
+                            && !runtime.has_ended()
+                            && !runtime.has_error()
+                            && runtime.step()
+                        {
+                            // If we hit options after stepping, break
+                            if runtime.is_waiting_for_option() {
                                 break;
                             }
+                        }
 
-                            // Track if we were already at options before processing
-                            let was_at_options = runtime.is_waiting_for_option();
-
-                            if !process_input(trimmed, &mut runtime) {
-                                break;
-                            }
+                        // Render new blocks that were stepped over
+                        render_path_from(&runtime, last_rendered_idx);
+                        last_rendered_idx = runtime.current_path().len();
 
-                            // Render any new blocks after processing input
-                            render_path_from(&runtime, last_rendered_idx);
-                            last_rendered_idx = runtime.current_path().len();
+                        // Surface any runtime error *after* rendering so
+                        // story output already on stdout precedes the
+                        // error message.
+                        if report_runtime_error(&mut runtime) {
+                            break;
+                        }
 
-                            // Runtime errors render *after* story output, on
-                            // stdout, then halt the input loop.
-                            if report_runtime_error(&mut runtime) {
-                                break;
-                            }
+                        // If we hit options, display them
+                        if runtime.is_waiting_for_option() {
+                            display_options(&runtime, false);
+                        }
+                    }
 
-                            // After processing, check if we're at options
-                            // Include parent text only if we were already at options (invalid input case)
-                            if runtime.is_waiting_for_option() {
-                                display_options(&runtime, was_at_options);
-                            }
+                    // Check for quit after rendering current state
+                    if trimmed == "q" {
+                        // If we're at an option prompt, add newline after >
+                        if runtime.is_waiting_for_option() {
+                            println!();
                         }
+                        quit_requested = true;
+                        break;
                     }
 
-                    // Final render - show any remaining blocks
-                    render_path_from(&runtime, last_rendered_idx);
-                    report_runtime_error(&mut runtime);
+                    // Track if we were already at options before processing
+                    let was_at_options = runtime.is_waiting_for_option();
 
-                    // If still waiting for options and we didn't quit, display them
-                    if runtime.is_waiting_for_option() && !quit_requested {
-                        display_options(&runtime, false);
+                    if !process_input(trimmed, &mut runtime) {
+                        break;
                     }
 
-                    // Print QUIT only if not at an option prompt
-                    if quit_requested && !runtime.is_waiting_for_option() {
-                        println!("QUIT");
+                    // Render any new blocks after processing input
+                    render_path_from(&runtime, last_rendered_idx);
+                    last_rendered_idx = runtime.current_path().len();
+
+                    // Runtime errors render *after* story output, on
+                    // stdout, then halt the input loop.
+                    if report_runtime_error(&mut runtime) {
+                        break;
                     }
 
-                    if runtime.has_ended() {
-                        runtime.stop();
-                    } else {
-                        eprintln!("\nWarning: Script did not reach the End block.");
+                    // After processing, check if we're at options
+                    // Include parent text only if we were already at options (invalid input case)
+                    if runtime.is_waiting_for_option() {
+                        display_options(&runtime, was_at_options);
                     }
                 }
-                Err(err) => {
-                    println!("{}", err);
-                    std::process::exit(1);
+            }
+
+            // Final render - show any remaining blocks
+            render_path_from(&runtime, last_rendered_idx);
+            report_runtime_error(&mut runtime);
+
+            // If still waiting for options and we didn't quit, display them
+            if runtime.is_waiting_for_option() && !quit_requested {
+                display_options(&runtime, false);
+            }
+
+            // Print QUIT only if not at an option prompt
+            if quit_requested && !runtime.is_waiting_for_option() {
+                println!("QUIT");
+            }
+
+            if runtime.has_ended() {
+                runtime.stop();
+            } else {
+                eprintln!("\nWarning: Script did not reach the End block.");
+            }
+        }
+        Commands::Compile {
+            script_path,
+            output_path,
+            format,
+        } => compile(&script_path, &output_path, format),
+        Commands::ExportStrings {
+            script_path,
+            csv_path,
+        } => export_strings(&script_path, &csv_path),
+        Commands::CheckStrings {
+            script_path,
+            csv_path,
+        } => check_strings(&script_path, &csv_path),
+    }
+}
+
+/// Load what `run` was given: a script, a structure file written by
+/// `compile` (with its strings file next to it), or a JSON document written
+/// by `compile --format json`. Exits on any error.
+fn load_database(path: &Path) -> cuentitos_common::Database {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("Error reading script file: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if bytes.starts_with(cuentitos_common::binary::MAGIC) {
+        let strings_path = cuentitos_compiler::strings_path(path);
+        let strings = match std::fs::read(&strings_path) {
+            Ok(strings) => strings,
+            Err(err) => {
+                eprintln!(
+                    "Error reading strings file {}: {}",
+                    strings_path.display(),
+                    err
+                );
+                std::process::exit(1);
+            }
+        };
+        let loaded = cuentitos_common::binary::read_structure(&bytes).and_then(|structure| {
+            let mut database = structure.database;
+            database.strings =
+                cuentitos_common::binary::read_strings(&strings, structure.checksum)?;
+            Ok(database)
+        });
+        return loaded.unwrap_or_else(|err| {
+            println!("{}", err);
+            std::process::exit(1);
+        });
+    }
+
+    let script = match String::from_utf8(bytes) {
+        Ok(script) => script,
+        Err(_) => {
+            eprintln!("Error reading script file: stream did not contain valid UTF-8");
+            std::process::exit(1);
+        }
+    };
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+    {
+        return cuentitos_common::json::from_json(&script).unwrap_or_else(|err| {
+            println!("{}", err);
+            std::process::exit(1);
+        });
+    }
+
+    parse_script(path, &script)
+}
+
+/// Parse `script`, printing warnings as `file:line: WARNING: ...`. Prints the
+/// error and exits if the script doesn't parse.
+fn parse_script(path: &Path, script: &str) -> cuentitos_common::Database {
+    let mut parser = CuentitosParser::with_file(path.to_path_buf());
+    match parser.parse(script) {
+        Ok((database, warnings)) => {
+            print_warnings(&warnings);
+            database
+        }
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_warnings(warnings: &[cuentitos_parser::Warning]) {
+    for warning in warnings {
+        let file_name = warning
+            .file
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("<script>");
+        println!(
+            "{}:{}: WARNING: {}",
+            file_name, warning.line, warning.message
+        );
+    }
+}
+
+/// Load the `cuentitos.toml` of the project in `directory`. Exits on any
+/// error.
+fn load_project(directory: &Path) -> Project {
+    Project::load(directory).unwrap_or_else(|err| {
+        println!("{}", err);
+        std::process::exit(1);
+    })
+}
+
+/// Parse the main script of `project`, printing warnings as in
+/// [`parse_script`]. Exits on any error.
+fn parse_project(project: &Project) -> cuentitos_common::Database {
+    match project.parse() {
+        Ok((database, warnings)) => {
+            print_warnings(&warnings);
+            database
+        }
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Read and parse the script at `path`, or the main script of the project
+/// in the directory at `path`, as the compiler does. Exits on any error.
+fn read_script(path: &Path) -> cuentitos_common::Database {
+    match cuentitos_compiler::parse(path) {
+        Ok((database, warnings)) => {
+            print_warnings(&warnings);
+            database
+        }
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handle `compile`: parse the script and write it in `format`.
+fn compile(script_path: &Path, output_path: &Path, format: OutputFormat) {
+    let database = read_script(script_path);
+
+    let written = match format {
+        OutputFormat::Binary => cuentitos_compiler::compile(&database).write(output_path),
+        OutputFormat::Json => {
+            std::fs::write(output_path, cuentitos_common::json::to_json(&database))
+        }
+    };
+    if let Err(err) = written {
+        eprintln!("Error writing compiled story: {}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Handle `run --lang`: load `<tag>.csv` from the script's directory for
+/// every tag of `language`'s fallback chain, print the problems found in
+/// them as warnings, and switch to `language`. Tags are matched to file
+/// names whatever their case. Exits if a file can't be read or no file
+/// matches.
+fn load_translations(runtime: &mut cuentitos_runtime::Runtime, script_path: &Path, language: &str) {
+    let directory = script_path.parent().unwrap_or(Path::new("."));
+    for tag in cuentitos_runtime::fallback_chain(language) {
+        let Some(csv_path) = find_csv(directory, &tag) else {
+            continue;
+        };
+        let file_name = csv_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("<csv>")
+            .to_string();
+        let csv = match std::fs::read_to_string(&csv_path) {
+            Ok(csv) => csv,
+            Err(err) => {
+                eprintln!("Error reading CSV file: {}", err);
+                std::process::exit(1);
+            }
+        };
+        match cuentitos_common::localization::import_csv(&runtime.database, &csv) {
+            Ok(mut translation) => {
+                // Missing strings fall back to the next tag, so only rows
+                // that are wrong are worth a warning.
+                let mut issues: Vec<_> = std::mem::take(&mut translation.issues)
+                    .into_iter()
+                    .filter(|issue| {
+                        !matches!(
+                            issue,
+                            cuentitos_common::localization::TranslationIssue::Missing { .. }
+                        )
+                    })
+                    .collect();
+                issues.extend(runtime.add_translation(&tag, translation));
+                for issue in issues {
+                    println!("{}: {}", file_name, issue);
                 }
             }
+            Err(err) => {
+                println!("{}:{}", file_name, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(err) = runtime.set_language(language) {
+        println!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// The `.csv` file in `directory` named after `tag`, ignoring case.
+fn find_csv(directory: &Path, tag: &str) -> Option<PathBuf> {
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+    std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+                && path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| stem.eq_ignore_ascii_case(tag))
+        })
+}
+
+/// Handle `export-strings`: write the script's string table.
+fn export_strings(script_path: &Path, csv_path: &Path) {
+    let database = read_script(script_path);
+    let csv = cuentitos_common::localization::export_csv(&database);
+    if let Err(err) = std::fs::write(csv_path, csv) {
+        eprintln!("Error writing CSV file: {}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Handle `check-strings`: print every missing, stale or orphaned row of a
+/// translated string table. Exits with `1` if there is any.
+fn check_strings(script_path: &Path, csv_path: &Path) {
+    let database = read_script(script_path);
+    let csv = match std::fs::read_to_string(csv_path) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Error reading CSV file: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let file_name = csv_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("<csv>");
+    match cuentitos_common::localization::import_csv(&database, &csv) {
+        Ok(translation) => {
+            for issue in &translation.issues {
+                println!("{}: {}", file_name, issue);
+            }
+            if !translation.issues.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            println!("{}:{}", file_name, err);
+            std::process::exit(1);
         }
     }
 }
@@ -202,11 +504,7 @@ fn process_input(input: &str, runtime: &mut cuentitos_runtime::Runtime) -> bool
         // Try to parse as option number
         if let Ok(choice) = trimmed.parse::<usize>() {
             // Get option text before selecting (selection clears current_options)
-            let option_text = runtime
-                .get_current_options()
-                .iter()
-                .find(|(num, _)| *num == choice)
-                .map(|(_, string_id)| runtime.database.strings[*string_id].clone());
+            let option_text = runtime.option_text(choice).map(str::to_string);
 
             match runtime.select_option(choice) {
                 Ok(()) => {
@@ -369,16 +667,24 @@ fn report_runtime_error(runtime: &mut cuentitos_runtime::Runtime) -> bool {
 
 fn render_path_from(runtime: &cuentitos_runtime::Runtime, start_idx: usize) {
     // Render all blocks from start_idx onwards in current_path
-    for &block_id in &runtime.current_path()[start_idx..] {
+    for (path_index, &block_id) in runtime.current_path().iter().enumerate().skip(start_idx) {
         let block = &runtime.database.blocks[block_id];
         match block.block_type {
             cuentitos_common::BlockType::Start => println!("START"),
             cuentitos_common::BlockType::String(id) => {
-                println!("{}", runtime.database.strings[id])
+                let text = runtime
+                    .path_text(path_index)
+                    .unwrap_or(&runtime.database.strings[id]);
+                // A line picked from a chance group shows the odds it was
+                // picked with, e.g. `(50/100) I open the door`.
+                match runtime.chance_odds(path_index) {
+                    Some((weight, total)) => println!("({}/{}) {}", weight, total, text),
+                    None => println!("{}", text),
+                }
             }
             cuentitos_common::BlockType::Section(section_id) => {
-                // Build the section path
-                let path = build_section_path(runtime, section_id);
+                // The section path, in the current language
+                let path = runtime.section_path_text(section_id);
                 println!("-> {}", path);
             }
             cuentitos_common::BlockType::GoTo(_)
@@ -396,11 +702,26 @@ fn render_path_from(runtime: &cuentitos_runtime::Runtime, start_idx: usize) {
                 // `set` is a side-effecting statement: stepping over it
                 // mutates a variable but emits no narrative output.
             }
+            cuentitos_common::BlockType::Call(_) => {
+                // The call already ran when it was stepped onto; whatever
+                // the function does happens on the game's side.
+            }
             cuentitos_common::BlockType::Requirement(_) => {
                 // `req` is a parent-gating statement: it never renders, and
                 // when it fails the runtime skips the parent so this branch
                 // is only reached on the (also-silent) passing case.
             }
+            cuentitos_common::BlockType::Chance(_) => {
+                // The chance marker is resolved before its block is entered;
+                // the odds are shown on the picked line itself.
+            }
+            cuentitos_common::BlockType::Bucket(_) => {
+                // A bucket only draws one of its cards; the drawn card
+                // renders on its own.
+            }
+            cuentitos_common::BlockType::Frequency(_) => {
+                // `freq` only changes its parent's weight for later rolls.
+            }
             cuentitos_common::BlockType::End => println!("END"),
         }
     }
@@ -414,30 +735,29 @@ fn display_options(runtime: &cuentitos_runtime::Runtime, include_parent: bool) {
         let option_block_ids = runtime.get_current_option_block_ids();
         if let Some(&first_option_block_id) = option_block_ids.first() {
             if let Some(parent_id) = runtime.database.blocks[first_option_block_id].parent_id {
-                let parent_block = &runtime.database.blocks[parent_id];
-                if let cuentitos_common::BlockType::String(string_id) = parent_block.block_type {
-                    println!("{}", runtime.database.strings[string_id]);
+                // Show the parent as it was printed, not rendered again
+                let parent_index = runtime
+                    .current_path()
+                    .iter()
+                    .rposition(|&block_id| block_id == parent_id);
+                if let Some(text) = parent_index.and_then(|index| runtime.path_text(index)) {
+                    println!("{}", text);
                 }
             }
         }
     }
 
-    for (num, string_id) in options {
-        println!("  {}. {}", num, runtime.database.strings[string_id]);
+    for (num, _) in options {
+        println!(
+            "  {}. {}",
+            num,
+            runtime.option_text(num).unwrap_or_default()
+        );
     }
     print!("> "); // Print > with space, responses continue on same line
     std::io::Write::flush(&mut std::io::stdout()).ok();
 }
 
-fn build_section_path(
-    runtime: &cuentitos_runtime::Runtime,
-    section_id: cuentitos_common::SectionId,
-) -> String {
-    // Simply get the path from the section metadata
-    let section = &runtime.database.sections[section_id];
-    runtime.database.strings[section.path].clone()
-}
-
 /// Handle the `?` CLI input: print each declared variable's current value in
 /// declaration order, or emit a line-0 warning if no variables are declared.
 fn print_debug_variables(runtime: &cuentitos_runtime::Runtime, script_path: &Path) {
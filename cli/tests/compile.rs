@@ -0,0 +1,105 @@
+//! `cuentitos compile`, and running what it writes.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const SCRIPT: &str = "--- variables\nint gold = 0\n---\n# hub: Hub\n  You have {gold} coins.\n    * Dig\n      set gold += 3\n      You dig.\n    * Leave\n  You leave with {gold} coins.";
+
+/// A directory of its own for each test, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("cuentitos-cli-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn script(&self) -> PathBuf {
+        let path = self.0.join("story.cuentitos");
+        std::fs::write(&path, SCRIPT).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn cuentitos(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cuentitos"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn compile(script: &Path, output: &Path, format: &str) {
+    let result = cuentitos(&[
+        "compile",
+        script.to_str().unwrap(),
+        output.to_str().unwrap(),
+        "--format",
+        format,
+    ]);
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stdout)
+    );
+}
+
+fn run(path: &Path) -> String {
+    let result = cuentitos(&["run", path.to_str().unwrap(), "1,s"]);
+    assert!(result.status.success());
+    String::from_utf8(result.stdout).unwrap()
+}
+
+#[test]
+fn compiled_binary_runs_like_the_script() {
+    let dir = TempDir::new("binary");
+    let script = dir.script();
+    let structure = dir.0.join("story.cbin");
+    compile(&script, &structure, "binary");
+
+    let bytes = std::fs::read(&structure).unwrap();
+    assert!(bytes.starts_with(cuentitos_common::binary::MAGIC));
+    assert!(dir.0.join("story.cstrings").exists());
+
+    let expected = run(&script);
+    assert!(expected.contains("You leave with 3 coins."), "{}", expected);
+    assert_eq!(run(&structure), expected);
+}
+
+#[test]
+fn compiled_json_runs_like_the_script() {
+    let dir = TempDir::new("json");
+    let script = dir.script();
+    let json = dir.0.join("story.json");
+    compile(&script, &json, "json");
+    assert!(std::fs::read_to_string(&json).unwrap().starts_with('{'));
+    assert_eq!(run(&json), run(&script));
+
+    // The extension is matched whatever its case.
+    let upper = dir.0.join("story.JSON");
+    std::fs::rename(&json, &upper).unwrap();
+    assert_eq!(run(&upper), run(&script));
+}
+
+#[test]
+fn compile_reports_script_errors() {
+    let dir = TempDir::new("errors");
+    let script = dir.0.join("bad.cuentitos");
+    std::fs::write(&script, "-> nowhere").unwrap();
+    let output = dir.0.join("bad.cbin");
+    let result = cuentitos(&[
+        "compile",
+        script.to_str().unwrap(),
+        output.to_str().unwrap(),
+    ]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).starts_with("bad.cuentitos:1: ERROR:"));
+    assert!(!output.exists());
+}
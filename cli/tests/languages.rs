@@ -0,0 +1,84 @@
+//! `cuentitos run --lang`, and the string tables it loads.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SCRIPT: &str = "Hello.\nGoodbye.";
+
+/// A directory of its own for each test, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("cuentitos-lang-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        let dir = TempDir(path);
+        std::fs::write(dir.0.join("story.cuentitos"), SCRIPT).unwrap();
+        dir
+    }
+
+    /// Write `<file_name>` as a string table translating both lines.
+    fn table(&self, file_name: &str, hello: &str, goodbye: &str) {
+        let keys = export(&self.0);
+        let rows: Vec<_> = keys
+            .iter()
+            .zip([("Hello.", hello), ("Goodbye.", goodbye)])
+            .map(|(key, (source, text))| format!("{},{},{}", key, source, text))
+            .collect();
+        let csv = format!("key,source,text\n{}\n", rows.join("\n"));
+        std::fs::write(self.0.join(file_name), csv).unwrap();
+    }
+
+    fn run(&self, language: &str) -> String {
+        let result = Command::new(env!("CARGO_BIN_EXE_cuentitos"))
+            .args(["run", "story.cuentitos", "s", "--lang", language])
+            .current_dir(&self.0)
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        String::from_utf8(result.stdout).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The keys `export-strings` gives the script's two lines.
+fn export(directory: &Path) -> Vec<String> {
+    let result = Command::new(env!("CARGO_BIN_EXE_cuentitos"))
+        .args(["export-strings", "story.cuentitos", "keys.csv"])
+        .current_dir(directory)
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    let csv = std::fs::read_to_string(directory.join("keys.csv")).unwrap();
+    std::fs::remove_file(directory.join("keys.csv")).unwrap();
+    csv.lines()
+        .skip(1)
+        .map(|line| line.split(',').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn tables_are_found_whatever_the_case_of_the_tag() {
+    let dir = TempDir::new("case");
+    dir.table("es-MX.csv", "Quiubo.", "");
+    dir.table("ES.csv", "Hola.", "Adiós.");
+    let output = dir.run("es-mx");
+    assert!(output.contains("Quiubo.\nAdiós."), "{}", output);
+}
+
+#[test]
+fn tables_follow_the_runtime_fallback_chain() {
+    // `es-x-pirate` falls back to `es`; `es-x` isn't a tag of its own.
+    let dir = TempDir::new("chain");
+    dir.table("es.csv", "Hola.", "Adiós.");
+    dir.table("es-x.csv", "Arr.", "Arr.");
+    let output = dir.run("es-x-pirate");
+    assert!(output.contains("Hola.\nAdiós."), "{}", output);
+    assert!(!output.contains("Arr."), "{}", output);
+}
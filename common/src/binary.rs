@@ -0,0 +1,335 @@
+//! The binary form of a [`Database`], split into two files so a game can load
+//! the story's logic first and its text later, or swap the text for another
+//! language without reloading the logic.
+//!
+//! - The structure file holds everything but the text: blocks, sections,
+//!   variables, conditions, chances and so on.
+//! - The strings file holds `Database::strings`, and the checksum of the
+//!   structure it was written for.
+//!
+//! Both files start with the same header:
+//!
+//! | Bytes | Contents |
+//! | --- | --- |
+//! | 9 | [`MAGIC`], `cuentitos` |
+//! | 1 | [`FileKind`], `S` for structure or `T` for strings |
+//! | 4 | [`FORMAT_VERSION`], little endian |
+//! | 8 | FNV-1a checksum of the payload, little endian |
+//!
+//! The payload follows, encoded with `postcard`. Once the format ships, any
+//! change to it bumps [`FORMAT_VERSION`], and the readers reject files
+//! written with another version instead of misreading them. A structure
+//! whose ids don't point inside it is rejected too, even when its checksum
+//! matches. The layout is documented in `docs/binary-format.md`.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Database;
+
+/// The first bytes of every file.
+pub const MAGIC: &[u8; 9] = b"cuentitos";
+
+/// The version of the payload layout written and read here.
+pub const FORMAT_VERSION: u32 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 8;
+
+/// Which of the two files a header belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Structure,
+    Strings,
+}
+
+impl FileKind {
+    fn tag(self) -> u8 {
+        match self {
+            FileKind::Structure => b'S',
+            FileKind::Strings => b'T',
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileKind::Structure => write!(f, "structure"),
+            FileKind::Strings => write!(f, "strings"),
+        }
+    }
+}
+
+/// Why a structure or strings file couldn't be read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryError {
+    /// The file doesn't start with a cuentitos header.
+    NotACuentitosFile,
+    /// A structure file was given where a strings file was expected, or the
+    /// other way around.
+    WrongFileKind { expected: FileKind },
+    /// Written with a different [`FORMAT_VERSION`].
+    UnsupportedVersion { found: u32 },
+    /// The payload doesn't match the checksum in the header: the file was
+    /// truncated or altered.
+    ChecksumMismatch { kind: FileKind },
+    /// The checksum matches but the payload can't be decoded.
+    Malformed { message: String },
+    /// The strings file was written for another structure, such as an older
+    /// build of the same story.
+    StringsMismatch,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryError::NotACuentitosFile => write!(f, "ERROR: Not a compiled cuentitos file."),
+            BinaryError::WrongFileKind { expected } => {
+                write!(f, "ERROR: Expected a cuentitos {} file.", expected)
+            }
+            BinaryError::UnsupportedVersion { found } => write!(
+                f,
+                "ERROR: Unsupported binary format version {} (expected {}).",
+                found, FORMAT_VERSION
+            ),
+            BinaryError::ChecksumMismatch { kind } => write!(
+                f,
+                "ERROR: Checksum mismatch in {} file, it is corrupt.",
+                kind
+            ),
+            BinaryError::Malformed { message } => {
+                write!(f, "ERROR: Malformed binary file: {}", message)
+            }
+            BinaryError::StringsMismatch => write!(
+                f,
+                "ERROR: The strings file was compiled from a different story."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl From<postcard::Error> for BinaryError {
+    fn from(err: postcard::Error) -> Self {
+        BinaryError::Malformed {
+            message: err.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StructurePayload {
+    string_count: usize,
+    database: Database,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StringsPayload {
+    structure_checksum: u64,
+    strings: Vec<String>,
+}
+
+/// A structure file, read with [`read_structure`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structure {
+    /// The story without its text. `strings` has one empty string per
+    /// string id until the strings file is read, so ids stay valid.
+    pub database: Database,
+    /// Identifies this structure; strings files carry it to say which
+    /// structure they belong to.
+    pub checksum: u64,
+}
+
+/// The two files of a compiled database, written by [`write`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Files {
+    pub structure: Vec<u8>,
+    pub strings: Vec<u8>,
+}
+
+/// FNV-1a, 64 bits. Not meant to resist tampering, only to catch truncated
+/// or damaged files.
+pub fn checksum(bytes: &[u8]) -> u64 {
+    crate::fnv::fnv1a(bytes)
+}
+
+/// Write `database` as a structure file and a strings file. The same
+/// database always gives the same bytes.
+pub fn write(database: &Database) -> Files {
+    let mut structure = database.clone();
+    structure.strings.clear();
+    let payload = StructurePayload {
+        string_count: database.strings.len(),
+        database: structure,
+    };
+    let payload = postcard::to_stdvec(&payload).expect("a database always serializes");
+    let structure_checksum = checksum(&payload);
+
+    Files {
+        structure: frame(FileKind::Structure, &payload),
+        strings: write_strings(&database.strings, structure_checksum),
+    }
+}
+
+/// Write a strings file for the structure with checksum
+/// `structure_checksum`.
+pub fn write_strings(strings: &[String], structure_checksum: u64) -> Vec<u8> {
+    let payload = StringsPayload {
+        structure_checksum,
+        strings: strings.to_vec(),
+    };
+    let payload = postcard::to_stdvec(&payload).expect("strings always serialize");
+    frame(FileKind::Strings, &payload)
+}
+
+/// Read a structure file written by [`write`].
+pub fn read_structure(bytes: &[u8]) -> Result<Structure, BinaryError> {
+    let payload = unframe(FileKind::Structure, bytes)?;
+    let StructurePayload {
+        string_count,
+        mut database,
+    } = postcard::from_bytes(payload)?;
+    database.strings = vec![String::new(); string_count];
+    database
+        .check_ids()
+        .map_err(|message| BinaryError::Malformed { message })?;
+    Ok(Structure {
+        database,
+        checksum: checksum(payload),
+    })
+}
+
+/// Read a strings file and check that it was written for the structure
+/// with checksum `structure_checksum`.
+pub fn read_strings(bytes: &[u8], structure_checksum: u64) -> Result<Vec<String>, BinaryError> {
+    let payload = unframe(FileKind::Strings, bytes)?;
+    let payload: StringsPayload = postcard::from_bytes(payload)?;
+    if payload.structure_checksum != structure_checksum {
+        return Err(BinaryError::StringsMismatch);
+    }
+    Ok(payload.strings)
+}
+
+fn frame(kind: FileKind, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(kind.tag());
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(payload).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Check the header of a `kind` file and return its payload.
+fn unframe(kind: FileKind, bytes: &[u8]) -> Result<&[u8], BinaryError> {
+    if bytes.len() < HEADER_LEN || !bytes.starts_with(MAGIC) {
+        return Err(BinaryError::NotACuentitosFile);
+    }
+    let (header, payload) = bytes.split_at(HEADER_LEN);
+    if header[MAGIC.len()] != kind.tag() {
+        return Err(BinaryError::WrongFileKind { expected: kind });
+    }
+    let version = u32::from_le_bytes(header[10..14].try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(BinaryError::UnsupportedVersion { found: version });
+    }
+    let expected = u64::from_le_bytes(header[14..22].try_into().unwrap());
+    if checksum(payload) != expected {
+        return Err(BinaryError::ChecksumMismatch { kind });
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, BlockType, Value, Variable};
+
+    fn database() -> Database {
+        let mut database = Database::new();
+        database.add_block(Block::new(BlockType::Start, None, 0));
+        let hello = database.add_string("Hello".to_string());
+        database.add_block(Block::new(BlockType::String(hello), Some(0), 0));
+        database.add_variable(Variable::new("mood", Value::Float(0.1)));
+        database.once_only_options.extend([2, 1]);
+        database.add_block(Block::new(BlockType::End, None, 0));
+        database
+    }
+
+    #[test]
+    fn round_trips_a_database_through_both_files() {
+        let files = write(&database());
+        assert!(files.structure.starts_with(b"cuentitosS\x01\0\0\0"));
+        assert!(files.strings.starts_with(b"cuentitosT\x01\0\0\0"));
+        assert_eq!(write(&database()), files);
+
+        let structure = read_structure(&files.structure).unwrap();
+        assert_eq!(structure.database.strings, vec![String::new()]);
+        let mut loaded = structure.database;
+        loaded.strings = read_strings(&files.strings, structure.checksum).unwrap();
+        assert_eq!(loaded, database());
+    }
+
+    #[test]
+    fn rejects_damaged_and_mismatched_files() {
+        let files = write(&database());
+        assert_eq!(
+            read_structure(b"not a story"),
+            Err(BinaryError::NotACuentitosFile)
+        );
+        assert_eq!(
+            read_structure(&files.strings),
+            Err(BinaryError::WrongFileKind {
+                expected: FileKind::Structure
+            })
+        );
+
+        let mut newer = files.structure.clone();
+        newer[10] = 99;
+        assert_eq!(
+            read_structure(&newer),
+            Err(BinaryError::UnsupportedVersion { found: 99 })
+        );
+
+        let mut damaged = files.structure.clone();
+        *damaged.last_mut().unwrap() ^= 0xff;
+        assert_eq!(
+            read_structure(&damaged),
+            Err(BinaryError::ChecksumMismatch {
+                kind: FileKind::Structure
+            })
+        );
+
+        let mut other = database();
+        other.strings[0] = "Hola".to_string();
+        other.add_variable(Variable::new("gold", Value::Integer(0)));
+        let other = write(&other);
+        let structure = read_structure(&files.structure).unwrap();
+        assert_eq!(
+            read_strings(&other.strings, structure.checksum),
+            Err(BinaryError::StringsMismatch)
+        );
+    }
+
+    #[test]
+    fn rejects_a_structure_with_ids_out_of_range() {
+        let mut broken = database();
+        broken.blocks[1].block_type = BlockType::String(4);
+        let files = write(&broken);
+        assert_eq!(
+            read_structure(&files.structure),
+            Err(BinaryError::Malformed {
+                message: "block 1 refers to string 4, but there are only 1".to_string()
+            })
+        );
+
+        let mut broken = database();
+        broken.blocks[0].children.push(9);
+        assert!(matches!(
+            read_structure(&write(&broken).structure),
+            Err(BinaryError::Malformed { .. })
+        ));
+    }
+}
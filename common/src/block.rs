@@ -1,8 +1,9 @@
-use crate::{RequirementId, SectionId, SetId, StringId};
+use crate::{BucketId, CallId, ChanceId, FrequencyId, RequirementId, SectionId, SetId, StringId};
 
 pub type BlockId = usize;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockType {
     Start,
     String(StringId),
@@ -14,11 +15,17 @@ pub enum BlockType {
     GoToRestart,
     GoToEnd,
     Set(SetId),
+    /// `call name(arguments)`: runs a function the game provides.
+    Call(CallId),
     Requirement(RequirementId),
+    Chance(ChanceId),
+    Bucket(BucketId),
+    Frequency(FrequencyId),
     End,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub block_type: BlockType,
     pub parent_id: Option<BlockId>,
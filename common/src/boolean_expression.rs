@@ -8,7 +8,9 @@
 //! then `and`, then `or`. Combinators short-circuit at evaluation time;
 //! see [`BooleanExpression::evaluate`].
 
-use crate::expression::{evaluate as evaluate_expression, EvaluationError, Expression};
+use crate::expression::{
+    evaluate as evaluate_expression, EvaluationContext, EvaluationError, Expression,
+};
 use crate::requirement_statement::RequirementStatement;
 use crate::value::Value;
 use crate::VariableId;
@@ -19,6 +21,7 @@ use crate::VariableId;
 /// Not `Eq`: leaves carry [`RequirementStatement`]s whose expressions may hold
 /// `Value::Float` literals, and `f64` has no total equality.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BooleanExpression {
     Comparison(RequirementStatement),
     And(Box<BooleanExpression>, Box<BooleanExpression>),
@@ -33,7 +36,7 @@ impl BooleanExpression {
     /// is already false never fires).
     pub fn evaluate<'v>(
         &'v self,
-        lookup: &dyn Fn(VariableId) -> &'v Value,
+        lookup: &dyn EvaluationContext<'v>,
     ) -> Result<bool, EvaluationError> {
         match self {
             BooleanExpression::Comparison(statement) => {
@@ -0,0 +1,57 @@
+//! Buckets carried by [`crate::BlockType::Bucket`].
+//!
+//! A `bucket` line groups the blocks nested under it into a deck of
+//! *cards*. Each time the runtime reaches the bucket it draws one card that
+//! hasn't been drawn yet, runs it, and skips the rest. Cards are drawn
+//! without replacement: once every card has been drawn the bucket is
+//! *exhausted*, and its [`RefillPolicy`] decides what happens next.
+//!
+//! `req` lines nested directly under the bucket gate the bucket itself, like
+//! on any other block; they are not cards.
+
+/// What a bucket does once every card has been drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RefillPolicy {
+    /// `bucket` / `bucket refill`: put every card back and keep drawing.
+    Refill,
+    /// `bucket once`: draw nothing, so the story falls through past the
+    /// bucket.
+    Once,
+}
+
+impl RefillPolicy {
+    /// The source-syntax word for this policy.
+    #[must_use]
+    pub fn keyword(self) -> &'static str {
+        match self {
+            RefillPolicy::Refill => "refill",
+            RefillPolicy::Once => "once",
+        }
+    }
+}
+
+/// Per-`bucket` metadata. Stored in `Database.buckets`; referenced from a
+/// [`crate::BlockType::Bucket`] block via its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bucket {
+    pub refill: RefillPolicy,
+}
+
+impl Bucket {
+    pub fn new(refill: RefillPolicy) -> Self {
+        Self { refill }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_matches_source_syntax() {
+        assert_eq!(RefillPolicy::Refill.keyword(), "refill");
+        assert_eq!(RefillPolicy::Once.keyword(), "once");
+    }
+}
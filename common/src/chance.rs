@@ -0,0 +1,106 @@
+//! Chance prefixes carried by [`crate::BlockType::Chance`].
+//!
+//! A block written as `(N) text` or `(N%) text` takes part in a *chance
+//! group*: the maximal run of consecutive siblings that all carry a chance
+//! prefix. Each time the runtime reaches the head of a group it rolls once
+//! and enters at most one member; the rest of the group is skipped.
+//!
+//! - `(N)` is a relative weight. A member is picked with probability
+//!   `N / sum(weights)`, so a weighted group always yields exactly one
+//!   member (unless every weight is zero).
+//! - `(expression)` is a relative weight too, computed from declared
+//!   variables every time the group is rolled: `(10 + fear * 2)`.
+//! - `(N%)` is an absolute percentage. The percentages of a group add up to
+//!   at most 100; the remainder is the chance that nothing in the group is
+//!   shown. A lone `(30%) ...` line is therefore a "one-off" that appears
+//!   three times out of ten.
+//!
+//! The two forms can't be mixed inside one group — the parser rejects it.
+//!
+//! A weighted member can also carry *frequency modifiers*: `freq <expression>`
+//! children ([`crate::BlockType::Frequency`]) that run when the member is
+//! shown and add their value to its weight for every later roll.
+
+use crate::expression::Expression;
+
+/// The chance prefix of a single block.
+///
+/// Not `Eq`: a computed weight carries an [`Expression`], which has no total
+/// equality.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Chance {
+    /// `(N)`: relative weight against the other members of the group.
+    Weight(u32),
+    /// `(N%)`: absolute probability out of 100.
+    Percentage(u32),
+    /// `(expression)`: relative weight evaluated at roll time. `source` is
+    /// the text between the parentheses, kept for display.
+    Computed {
+        expression: Expression,
+        source: String,
+    },
+}
+
+impl Chance {
+    /// The numeric payload of the literal forms, whether weight or
+    /// percentage. `None` for a computed weight, which is only known at
+    /// roll time.
+    #[must_use]
+    pub fn value(&self) -> Option<u32> {
+        match self {
+            Chance::Weight(value) | Chance::Percentage(value) => Some(*value),
+            Chance::Computed { .. } => None,
+        }
+    }
+
+    /// True for the `(N%)` form.
+    #[must_use]
+    pub fn is_percentage(&self) -> bool {
+        matches!(self, Chance::Percentage(_))
+    }
+}
+
+impl std::fmt::Display for Chance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chance::Weight(weight) => write!(f, "({weight})"),
+            Chance::Percentage(percentage) => write!(f, "({percentage}%)"),
+            Chance::Computed { source, .. } => write!(f, "({source})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_returns_payload_for_both_forms() {
+        assert_eq!(Chance::Weight(7).value(), Some(7));
+        assert_eq!(Chance::Percentage(30).value(), Some(30));
+    }
+
+    #[test]
+    fn computed_weight_has_no_fixed_value() {
+        let chance = Chance::Computed {
+            expression: Expression::Variable(0),
+            source: "fear".to_string(),
+        };
+        assert_eq!(chance.value(), None);
+        assert!(!chance.is_percentage());
+        assert_eq!(chance.to_string(), "(fear)");
+    }
+
+    #[test]
+    fn display_matches_source_syntax() {
+        assert_eq!(Chance::Weight(50).to_string(), "(50)");
+        assert_eq!(Chance::Percentage(25).to_string(), "(25%)");
+    }
+
+    #[test]
+    fn only_percentage_form_is_percentage() {
+        assert!(Chance::Percentage(10).is_percentage());
+        assert!(!Chance::Weight(10).is_percentage());
+    }
+}
@@ -1,21 +1,77 @@
-use crate::block::{Block, BlockId};
+use crate::block::{Block, BlockId, BlockType};
 use crate::boolean_expression::BooleanExpression;
+use crate::bucket::Bucket;
+use crate::chance::Chance;
+use crate::expression::Expression;
+use crate::function::{CallStatement, FunctionSignature};
 use crate::section::Section;
 use crate::set_statement::SetStatement;
+use crate::text::{TextAlternatives, TextFragment};
 use crate::variable::Variable;
-use crate::{RequirementId, SectionId, SetId, StringId, VariableId};
-use std::collections::HashMap;
+use crate::{
+    AlternativesId, BucketId, CallId, ChanceId, FileId, FrequencyId, FunctionId, RequirementId,
+    SectionId, SetId, StringId, VariableId,
+};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub blocks: Vec<Block>,
     pub strings: Vec<String>,
     pub sections: Vec<Section>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::map"))]
     pub section_registry: HashMap<String, SectionId>,
     pub variables: Vec<Variable>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::map"))]
     pub variable_registry: HashMap<String, VariableId>,
     pub sets: Vec<SetStatement>,
+    /// The functions the game provides that the script may call, referenced
+    /// from [`Expression::Call`] and [`CallStatement`].
+    pub functions: Vec<FunctionSignature>,
+    /// `call` lines, referenced from [`BlockType::Call`] blocks.
+    pub calls: Vec<CallStatement>,
     pub requirements: Vec<BooleanExpression>,
+    pub chances: Vec<Chance>,
+    pub buckets: Vec<Bucket>,
+    /// `freq` modifier expressions, referenced from
+    /// [`BlockType::Frequency`] blocks.
+    pub frequency_modifiers: Vec<Expression>,
+    /// Option blocks written with the `[once]` marker, which leave the menu
+    /// after they're picked. Every other option is sticky.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::set"))]
+    pub once_only_options: HashSet<BlockId>,
+    /// Option blocks written with the `[fallback]` marker, which are never
+    /// listed and are taken automatically when nothing else is visible.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::set"))]
+    pub fallback_options: HashSet<BlockId>,
+    /// The fragments of every text or option line that interpolates
+    /// variables, keyed by the line's string. Strings missing here are
+    /// printed as they are.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::map"))]
+    pub text_fragments: HashMap<StringId, Vec<TextFragment>>,
+    /// `{a|b|c}` fragments, referenced from
+    /// [`TextFragment::Alternatives`].
+    pub text_alternatives: Vec<TextAlternatives>,
+    /// The section path written in each `visits(path)` expression, referenced
+    /// from [`Expression::Visits`]. Kept as written for diagnostics.
+    pub visit_paths: Vec<String>,
+    /// The section each `visits(path)` expression counts, aligned with
+    /// `visit_paths`. Filled in once the whole script is parsed, so paths
+    /// can name sections written further down.
+    pub visit_targets: Vec<SectionId>,
+    /// The stable key of every translatable string, which string tables
+    /// match rows by. Written by the parser; see
+    /// [`crate::localization::string_keys`].
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::map"))]
+    pub string_keys: HashMap<StringId, String>,
+    /// The scripts pulled in with `include`, relative to the main script's
+    /// directory, in the order they were included.
+    pub files: Vec<String>,
+    /// The included script each block was read from, as an index into
+    /// `files`. Blocks missing here come from the main script.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::map"))]
+    pub block_files: HashMap<BlockId, FileId>,
 }
 
 impl Database {
@@ -28,7 +84,21 @@ impl Database {
             variables: Vec::new(),
             variable_registry: HashMap::new(),
             sets: Vec::new(),
+            functions: Vec::new(),
+            calls: Vec::new(),
             requirements: Vec::new(),
+            chances: Vec::new(),
+            buckets: Vec::new(),
+            frequency_modifiers: Vec::new(),
+            once_only_options: HashSet::new(),
+            fallback_options: HashSet::new(),
+            text_fragments: HashMap::new(),
+            text_alternatives: Vec::new(),
+            visit_paths: Vec::new(),
+            visit_targets: Vec::new(),
+            string_keys: HashMap::new(),
+            files: Vec::new(),
+            block_files: HashMap::new(),
         }
     }
 
@@ -38,12 +108,135 @@ impl Database {
         set_id
     }
 
+    /// Declare a function the script may call, replacing any declaration
+    /// with the same name.
+    pub fn declare_function(&mut self, signature: FunctionSignature) -> FunctionId {
+        match self.function_id(&signature.name) {
+            Some(function_id) => {
+                self.functions[function_id] = signature;
+                function_id
+            }
+            None => {
+                self.functions.push(signature);
+                self.functions.len() - 1
+            }
+        }
+    }
+
+    /// The [`FunctionId`] of the declared function `name`, if any.
+    pub fn function_id(&self, name: &str) -> Option<FunctionId> {
+        self.functions
+            .iter()
+            .position(|function| function.name == name)
+    }
+
+    pub fn add_call(&mut self, call: CallStatement) -> CallId {
+        let call_id = self.calls.len();
+        self.calls.push(call);
+        call_id
+    }
+
     pub fn add_requirement(&mut self, requirement: BooleanExpression) -> RequirementId {
         let requirement_id = self.requirements.len();
         self.requirements.push(requirement);
         requirement_id
     }
 
+    pub fn add_chance(&mut self, chance: Chance) -> ChanceId {
+        let chance_id = self.chances.len();
+        self.chances.push(chance);
+        chance_id
+    }
+
+    /// The [`ChanceId`] of the `(N)`/`(N%)` prefix carried by `block_id`, if
+    /// any. The parser attaches the [`BlockType::Chance`] marker as the
+    /// block's first child.
+    pub fn chance_id(&self, block_id: BlockId) -> Option<ChanceId> {
+        let &first_child = self.blocks.get(block_id)?.children.first()?;
+        match self.blocks[first_child].block_type {
+            BlockType::Chance(chance_id) => Some(chance_id),
+            _ => None,
+        }
+    }
+
+    /// The chance group `block_id` belongs to: the maximal run of consecutive
+    /// siblings that all carry a chance prefix, in source order. Empty when
+    /// `block_id` carries no prefix.
+    pub fn chance_group(&self, block_id: BlockId) -> &[BlockId] {
+        if self.chance_id(block_id).is_none() {
+            return &[];
+        }
+        let Some(parent_id) = self.blocks[block_id].parent_id else {
+            return &[];
+        };
+        let siblings = &self.blocks[parent_id].children;
+        let Some(position) = siblings.iter().position(|&id| id == block_id) else {
+            return &[];
+        };
+        let start = siblings[..position]
+            .iter()
+            .rposition(|&id| self.chance_id(id).is_none())
+            .map_or(0, |index| index + 1);
+        let end = siblings[position..]
+            .iter()
+            .position(|&id| self.chance_id(id).is_none())
+            .map_or(siblings.len(), |offset| position + offset);
+        &siblings[start..end]
+    }
+
+    pub fn add_bucket(&mut self, bucket: Bucket) -> BucketId {
+        let bucket_id = self.buckets.len();
+        self.buckets.push(bucket);
+        bucket_id
+    }
+
+    /// The cards of the bucket block `block_id`: its children in source
+    /// order, minus the silent `req`/chance/`freq` markers that belong to
+    /// the bucket itself. Empty when `block_id` isn't a bucket.
+    pub fn bucket_cards(&self, block_id: BlockId) -> Vec<BlockId> {
+        let Some(block) = self.blocks.get(block_id) else {
+            return Vec::new();
+        };
+        if !matches!(block.block_type, BlockType::Bucket(_)) {
+            return Vec::new();
+        }
+        block
+            .children
+            .iter()
+            .copied()
+            .filter(|&child_id| {
+                !matches!(
+                    self.blocks[child_id].block_type,
+                    BlockType::Requirement(_) | BlockType::Chance(_) | BlockType::Frequency(_)
+                )
+            })
+            .collect()
+    }
+
+    pub fn add_frequency_modifier(&mut self, modifier: Expression) -> FrequencyId {
+        let frequency_id = self.frequency_modifiers.len();
+        self.frequency_modifiers.push(modifier);
+        frequency_id
+    }
+
+    pub fn mark_once_only_option(&mut self, block_id: BlockId) {
+        self.once_only_options.insert(block_id);
+    }
+
+    /// Whether the option block `block_id` was written with `[once]`.
+    pub fn is_once_only_option(&self, block_id: BlockId) -> bool {
+        self.once_only_options.contains(&block_id)
+    }
+
+    pub fn mark_fallback_option(&mut self, block_id: BlockId) {
+        self.fallback_options.insert(block_id);
+    }
+
+    /// Whether the option block `block_id` was written with `[fallback]`.
+    pub fn is_fallback_option(&self, block_id: BlockId) -> bool {
+        self.fallback_options.contains(&block_id)
+    }
+
     pub fn add_variable(&mut self, variable: Variable) -> VariableId {
         let variable_id = self.variables.len();
         self.variable_registry
@@ -68,23 +261,276 @@ impl Database {
         block_id
     }
 
+    /// The included script `block_id` was read from, or `None` for the
+    /// main script.
+    pub fn block_file(&self, block_id: BlockId) -> Option<&str> {
+        let &file_id = self.block_files.get(&block_id)?;
+        Some(self.files[file_id].as_str())
+    }
+
     pub fn add_string(&mut self, string: String) -> StringId {
         let string_id = self.strings.len();
         self.strings.push(string);
         string_id
     }
 
+    /// Record the fragments `string_id` is printed from.
+    pub fn set_text_fragments(&mut self, string_id: StringId, fragments: Vec<TextFragment>) {
+        self.text_fragments.insert(string_id, fragments);
+    }
+
+    /// Record the key a string table matches `string_id` by.
+    pub fn set_string_key(&mut self, string_id: StringId, key: String) {
+        self.string_keys.insert(string_id, key);
+    }
+
+    pub fn add_text_alternatives(&mut self, alternatives: TextAlternatives) -> AlternativesId {
+        let alternatives_id = self.text_alternatives.len();
+        self.text_alternatives.push(alternatives);
+        alternatives_id
+    }
+
     pub fn add_section(&mut self, section: Section) -> SectionId {
         let section_id = self.sections.len();
         self.sections.push(section);
         section_id
     }
+
+    /// Check that every id the database holds points at something in it,
+    /// and that parents and children agree. The parser always builds such
+    /// a database; a loaded one is checked so a damaged file is an error
+    /// instead of a panic in the runtime. Returns what's wrong with the
+    /// first bad id found.
+    pub fn check_ids(&self) -> Result<(), String> {
+        let blocks = self.blocks.len();
+        for (block_id, block) in self.blocks.iter().enumerate() {
+            let owner = format!("block {}", block_id);
+            if let Some(parent_id) = block.parent_id {
+                if parent_id >= block_id {
+                    return Err(format!(
+                        "{} has parent {}, which doesn't come before it",
+                        owner, parent_id
+                    ));
+                }
+                if !self.blocks[parent_id].children.contains(&block_id) {
+                    return Err(format!(
+                        "{} has parent {}, which doesn't list it as a child",
+                        owner, parent_id
+                    ));
+                }
+            }
+            for &child_id in &block.children {
+                check_id(&owner, "block", child_id, blocks)?;
+                if self.blocks[child_id].parent_id != Some(block_id) {
+                    return Err(format!(
+                        "{} has child {}, whose parent is another block",
+                        owner, child_id
+                    ));
+                }
+            }
+            match block.block_type {
+                BlockType::String(id) | BlockType::Option(id) => {
+                    check_id(&owner, "string", id, self.strings.len())?
+                }
+                BlockType::Section(id) | BlockType::GoTo(id) | BlockType::GoToAndBack(id) => {
+                    check_id(&owner, "section", id, self.sections.len())?
+                }
+                BlockType::Set(id) => check_id(&owner, "set", id, self.sets.len())?,
+                BlockType::Call(id) => check_id(&owner, "call", id, self.calls.len())?,
+                BlockType::Requirement(id) => {
+                    check_id(&owner, "requirement", id, self.requirements.len())?
+                }
+                BlockType::Chance(id) => check_id(&owner, "chance", id, self.chances.len())?,
+                BlockType::Bucket(id) => check_id(&owner, "bucket", id, self.buckets.len())?,
+                BlockType::Frequency(id) => {
+                    check_id(&owner, "frequency", id, self.frequency_modifiers.len())?
+                }
+                BlockType::Start
+                | BlockType::GoToStart
+                | BlockType::GoToRestart
+                | BlockType::GoToEnd
+                | BlockType::End => {}
+            }
+        }
+
+        for (section_id, section) in self.sections.iter().enumerate() {
+            let owner = format!("section {}", section_id);
+            check_id(&owner, "block", section.block_id, blocks)?;
+            if self.blocks[section.block_id].block_type != BlockType::Section(section_id) {
+                return Err(format!(
+                    "{} starts at block {}, which isn't its section block",
+                    owner, section.block_id
+                ));
+            }
+            for string_id in [section.name, section.id, section.path, section.id_path] {
+                check_id(&owner, "string", string_id, self.strings.len())?;
+            }
+        }
+        for (name, &section_id) in &self.section_registry {
+            check_id(
+                &format!("section '{}'", name),
+                "section",
+                section_id,
+                self.sections.len(),
+            )?;
+        }
+        for (name, &variable_id) in &self.variable_registry {
+            check_id(
+                &format!("variable '{}'", name),
+                "variable",
+                variable_id,
+                self.variables.len(),
+            )?;
+        }
+
+        for (set_id, set) in self.sets.iter().enumerate() {
+            let owner = format!("set {}", set_id);
+            check_id(&owner, "variable", set.variable_id, self.variables.len())?;
+            self.check_expression(&owner, &set.expression)?;
+        }
+        for (call_id, call) in self.calls.iter().enumerate() {
+            let owner = format!("call {}", call_id);
+            check_id(&owner, "function", call.function, self.functions.len())?;
+            for argument in &call.arguments {
+                self.check_expression(&owner, argument)?;
+            }
+        }
+        for (requirement_id, requirement) in self.requirements.iter().enumerate() {
+            self.check_condition(&format!("requirement {}", requirement_id), requirement)?;
+        }
+        for (chance_id, chance) in self.chances.iter().enumerate() {
+            if let Chance::Computed { expression, .. } = chance {
+                self.check_expression(&format!("chance {}", chance_id), expression)?;
+            }
+        }
+        for (frequency_id, expression) in self.frequency_modifiers.iter().enumerate() {
+            self.check_expression(&format!("frequency {}", frequency_id), expression)?;
+        }
+
+        for &block_id in self.once_only_options.iter().chain(&self.fallback_options) {
+            check_id("an option marker", "block", block_id, blocks)?;
+        }
+        for (&string_id, fragments) in &self.text_fragments {
+            let owner = format!("string {}", string_id);
+            check_id(
+                "the text fragments",
+                "string",
+                string_id,
+                self.strings.len(),
+            )?;
+            self.check_fragments(&owner, fragments)?;
+        }
+        for (alternatives_id, alternatives) in self.text_alternatives.iter().enumerate() {
+            let owner = format!("alternatives {}", alternatives_id);
+            for branch in &alternatives.branches {
+                self.check_fragments(&owner, branch)?;
+            }
+        }
+        if self.visit_targets.len() != self.visit_paths.len() {
+            return Err(format!(
+                "{} visit targets for {} visit paths",
+                self.visit_targets.len(),
+                self.visit_paths.len()
+            ));
+        }
+        for (visits_id, &section_id) in self.visit_targets.iter().enumerate() {
+            check_id(
+                &format!("visits {}", visits_id),
+                "section",
+                section_id,
+                self.sections.len(),
+            )?;
+        }
+        for &string_id in self.string_keys.keys() {
+            check_id("the string keys", "string", string_id, self.strings.len())?;
+        }
+        for (&block_id, &file_id) in &self.block_files {
+            check_id("the block files", "block", block_id, blocks)?;
+            check_id(
+                &format!("block {}", block_id),
+                "file",
+                file_id,
+                self.files.len(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check_expression(&self, owner: &str, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::Literal(_) => Ok(()),
+            Expression::Variable(id) => check_id(owner, "variable", *id, self.variables.len()),
+            Expression::Visits(id) => check_id(owner, "visits", *id, self.visit_targets.len()),
+            Expression::Binary { left, right, .. } => {
+                self.check_expression(owner, left)?;
+                self.check_expression(owner, right)
+            }
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                check_id(owner, "function", *function, self.functions.len())?;
+                arguments
+                    .iter()
+                    .try_for_each(|argument| self.check_expression(owner, argument))
+            }
+        }
+    }
+
+    fn check_condition(&self, owner: &str, condition: &BooleanExpression) -> Result<(), String> {
+        match condition {
+            BooleanExpression::Comparison(statement) => {
+                self.check_expression(owner, &statement.left)?;
+                self.check_expression(owner, &statement.right)
+            }
+            BooleanExpression::And(left, right) | BooleanExpression::Or(left, right) => {
+                self.check_condition(owner, left)?;
+                self.check_condition(owner, right)
+            }
+            BooleanExpression::Not(inner) => self.check_condition(owner, inner),
+        }
+    }
+
+    fn check_fragments(&self, owner: &str, fragments: &[TextFragment]) -> Result<(), String> {
+        for fragment in fragments {
+            match fragment {
+                TextFragment::Literal(_) => {}
+                TextFragment::Variable(id) => {
+                    check_id(owner, "variable", *id, self.variables.len())?
+                }
+                TextFragment::Conditional {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    self.check_condition(owner, condition)?;
+                    self.check_fragments(owner, then)?;
+                    self.check_fragments(owner, otherwise)?;
+                }
+                TextFragment::Alternatives(id) => {
+                    check_id(owner, "alternatives", *id, self.text_alternatives.len())?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that `owner`'s reference to `what` number `id` is one of the `len`
+/// there are.
+fn check_id(owner: &str, what: &str, id: usize, len: usize) -> Result<(), String> {
+    if id >= len {
+        return Err(format!(
+            "{} refers to {} {}, but there are only {}",
+            owner, what, id, len
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::block::BlockType;
 
     #[test]
     fn test_database_add_block() {
@@ -112,6 +558,57 @@ mod tests {
         assert_eq!(db.strings[string_id], "test");
     }
 
+    #[test]
+    fn test_chance_group_spans_consecutive_prefixed_siblings() {
+        // root
+        //   |- plain
+        //   |- (1) a
+        //   |- (2) b
+        //   |- plain
+        //   |- (3) c
+        let mut db = Database::new();
+        let root_id = db.add_block(Block::new(BlockType::Start, None, 0));
+        let add_line = |db: &mut Database, chance: Option<Chance>| {
+            let block_id = db.add_block(Block::new(BlockType::String(0), Some(root_id), 1));
+            if let Some(chance) = chance {
+                let chance_id = db.add_chance(chance);
+                db.add_block(Block::new(BlockType::Chance(chance_id), Some(block_id), 2));
+            }
+            block_id
+        };
+        let plain_before = add_line(&mut db, None);
+        let a = add_line(&mut db, Some(Chance::Weight(1)));
+        let b = add_line(&mut db, Some(Chance::Weight(2)));
+        let plain_after = add_line(&mut db, None);
+        let c = add_line(&mut db, Some(Chance::Weight(3)));
+
+        assert_eq!(db.chance_id(a), Some(0));
+        assert_eq!(db.chance_id(plain_before), None);
+        assert_eq!(db.chance_group(a), &[a, b]);
+        assert_eq!(db.chance_group(b), &[a, b]);
+        assert_eq!(db.chance_group(c), &[c]);
+        assert!(db.chance_group(plain_after).is_empty());
+    }
+
+    #[test]
+    fn test_bucket_cards_skip_gating_markers() {
+        // root
+        //   |- bucket
+        //   |    |- req
+        //   |    |- a
+        //   |    |- b
+        let mut db = Database::new();
+        let root_id = db.add_block(Block::new(BlockType::Start, None, 0));
+        let bucket_id = db.add_bucket(Bucket::new(crate::RefillPolicy::Refill));
+        let bucket_block = db.add_block(Block::new(BlockType::Bucket(bucket_id), Some(root_id), 1));
+        db.add_block(Block::new(BlockType::Requirement(0), Some(bucket_block), 2));
+        let a = db.add_block(Block::new(BlockType::String(0), Some(bucket_block), 2));
+        let b = db.add_block(Block::new(BlockType::String(1), Some(bucket_block), 2));
+
+        assert_eq!(db.bucket_cards(bucket_block), vec![a, b]);
+        assert!(db.bucket_cards(a).is_empty());
+    }
+
     #[test]
     fn test_complex_hierarchy() {
         let mut db = Database::new();
@@ -3,7 +3,8 @@
 //!
 //! Identifiers are resolved to [`crate::VariableId`]s at parse time; the AST
 //! stored here is therefore self-contained and can be evaluated against a
-//! variable-value lookup without re-resolving names.
+//! variable-value lookup without re-resolving names. `visits(path)` reads are
+//! likewise resolved to an index into `Database.visit_targets`.
 //!
 //! The AST and the evaluator are polymorphic over [`Value`] kinds. Today only
 //! `Value::Integer` exists; adding `Boolean`/`Float`/`String` is additive —
@@ -13,10 +14,11 @@
 use std::borrow::Cow;
 
 use crate::value::{Value, ValueKind};
-use crate::VariableId;
+use crate::{FunctionId, VariableId, VisitsId};
 
 /// Operator carried by a binary expression node.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperator {
     Add,
     Subtract,
@@ -101,6 +103,7 @@ fn finite_float(value: f64) -> Result<Value, EvaluationError> {
 /// Not `Eq`: a `Literal` may hold a `Value::Float`, whose `f64` payload has no
 /// total equality. `PartialEq` is retained for tests and equality checks.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
     Literal(Value),
     Variable(VariableId),
@@ -109,6 +112,15 @@ pub enum Expression {
         left: Box<Expression>,
         right: Box<Expression>,
     },
+    /// `visits(path)`: how many times the section at
+    /// `Database.visit_targets[id]` has been entered. Always an integer.
+    Visits(VisitsId),
+    /// `name(arguments)`: the value a function the game provides returns,
+    /// `Database.functions[function]` declaring its kinds.
+    Call {
+        function: FunctionId,
+        arguments: Vec<Expression>,
+    },
 }
 
 /// Errors produced while evaluating a parsed expression at runtime.
@@ -137,6 +149,56 @@ pub enum EvaluationError {
     UnsetEnum {
         variable: VariableId,
     },
+    /// A script called a function the game never registered.
+    MissingFunction {
+        function: FunctionId,
+    },
+    /// The game registered a function with other parameter or return kinds
+    /// than the script declared it with.
+    FunctionSignatureMismatch {
+        function: FunctionId,
+    },
+    /// A function returned a value of another kind than it is declared to
+    /// return, or no value where one is expected. `found` is the kind it
+    /// returned, if any.
+    FunctionReturnMismatch {
+        function: FunctionId,
+        found: Option<ValueKind>,
+    },
+}
+
+/// What an expression reads while it is evaluated: variable values, visit
+/// counts for `visits(path)`, and the functions the game provides.
+///
+/// Any `Fn(VariableId) -> &Value` closure is a context with no visits and no
+/// functions, which suits places that can't use either at all, such as
+/// variable defaults. The runtime provides its own context with the real
+/// counts and the registered functions.
+pub trait EvaluationContext<'v> {
+    /// The current value of a declared variable.
+    fn value(&self, id: VariableId) -> &'v Value;
+    /// How many times the section counted by `visits(path)` number `id` has
+    /// been entered.
+    fn visits(&self, id: VisitsId) -> usize;
+    /// Run function `function` with already evaluated `arguments`, returning
+    /// what it returns, or `None` for a function without a return kind.
+    fn call(
+        &self,
+        function: FunctionId,
+        _arguments: &[Value],
+    ) -> Result<Option<Value>, EvaluationError> {
+        Err(EvaluationError::MissingFunction { function })
+    }
+}
+
+impl<'v, F: Fn(VariableId) -> &'v Value> EvaluationContext<'v> for F {
+    fn value(&self, id: VariableId) -> &'v Value {
+        self(id)
+    }
+
+    fn visits(&self, _id: VisitsId) -> usize {
+        0
+    }
 }
 
 /// Build the lookup closure expected by [`evaluate`] (and
@@ -148,8 +210,8 @@ pub fn variable_lookup<'v>(values: &'v [Value]) -> impl Fn(VariableId) -> &'v Va
     move |id: VariableId| &values[id]
 }
 
-/// Evaluate `expression` against the supplied per-variable lookup. The
-/// lookup returns a borrow of the current [`Value`] for a [`VariableId`];
+/// Evaluate `expression` against the supplied context. The context returns
+/// a borrow of the current [`Value`] for a [`VariableId`];
 /// the result is a [`Cow`] so that variable references and literals don't
 /// allocate, and only arithmetic combinations produce owned values. With
 /// `Value::Integer` (today's only variant) the cost is the same either way,
@@ -157,11 +219,11 @@ pub fn variable_lookup<'v>(values: &'v [Value]) -> impl Fn(VariableId) -> &'v Va
 /// per-lookup allocation.
 pub fn evaluate<'v>(
     expression: &'v Expression,
-    lookup: &dyn Fn(VariableId) -> &'v Value,
+    lookup: &dyn EvaluationContext<'v>,
 ) -> Result<Cow<'v, Value>, EvaluationError> {
     match expression {
         Expression::Literal(value) => Ok(Cow::Borrowed(value)),
-        Expression::Variable(id) => Ok(Cow::Borrowed(lookup(*id))),
+        Expression::Variable(id) => Ok(Cow::Borrowed(lookup.value(*id))),
         Expression::Binary {
             operator,
             left,
@@ -171,9 +233,37 @@ pub fn evaluate<'v>(
             let right_value = evaluate(right, lookup)?;
             operator.apply(&left_value, &right_value).map(Cow::Owned)
         }
+        Expression::Visits(id) => {
+            let visits =
+                i64::try_from(lookup.visits(*id)).map_err(|_| EvaluationError::Overflow)?;
+            Ok(Cow::Owned(Value::Integer(visits)))
+        }
+        Expression::Call {
+            function,
+            arguments,
+        } => match lookup.call(*function, &evaluate_arguments(arguments, lookup)?)? {
+            Some(value) => Ok(Cow::Owned(value)),
+            // Parse-time checks only let functions with a return kind into
+            // expressions, so a missing value is the function's fault.
+            None => Err(EvaluationError::FunctionReturnMismatch {
+                function: *function,
+                found: None,
+            }),
+        },
     }
 }
 
+/// Evaluate the arguments of a function call, left to right.
+pub fn evaluate_arguments<'v>(
+    arguments: &'v [Expression],
+    lookup: &dyn EvaluationContext<'v>,
+) -> Result<Vec<Value>, EvaluationError> {
+    arguments
+        .iter()
+        .map(|argument| evaluate(argument, lookup).map(Cow::into_owned))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -266,4 +356,56 @@ mod tests {
             }
         );
     }
+
+    /// A context whose only function, `double`, doubles its argument, and
+    /// whose second function returns nothing.
+    struct Doubler;
+
+    impl<'v> EvaluationContext<'v> for Doubler {
+        fn value(&self, _id: VariableId) -> &'v Value {
+            unreachable!("no variables")
+        }
+
+        fn visits(&self, _id: VisitsId) -> usize {
+            0
+        }
+
+        fn call(
+            &self,
+            function: FunctionId,
+            arguments: &[Value],
+        ) -> Result<Option<Value>, EvaluationError> {
+            match (function, arguments) {
+                (0, [Value::Integer(n)]) => Ok(Some(Value::Integer(n * 2))),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn call_evaluates_arguments_and_reads_the_result() {
+        let call = |function, argument| Expression::Call {
+            function,
+            arguments: vec![argument],
+        };
+        let nested = call(0, call(0, Expression::Literal(Value::Integer(3))));
+        assert_eq!(
+            evaluate(&nested, &Doubler).unwrap().into_owned(),
+            Value::Integer(12)
+        );
+        assert_eq!(
+            evaluate(&call(1, Expression::Literal(Value::Integer(3))), &Doubler),
+            Err(EvaluationError::FunctionReturnMismatch {
+                function: 1,
+                found: None
+            })
+        );
+        // Contexts that don't provide functions have none to call.
+        let values = [Value::Integer(1)];
+        let lookup = |id: VariableId| &values[id];
+        assert_eq!(
+            evaluate(&call(0, Expression::Variable(0)), &lookup),
+            Err(EvaluationError::MissingFunction { function: 0 })
+        );
+    }
 }
@@ -0,0 +1,7 @@
+/// FNV-1a, 64 bits. Not meant to resist tampering, only to catch truncated
+/// or damaged files and to derive short, stable ids from text.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
@@ -0,0 +1,44 @@
+use crate::expression::Expression;
+use crate::value::ValueKind;
+use crate::FunctionId;
+
+/// A function the game provides to scripts: its name, the kinds of its
+/// parameters, and the kind it returns. Functions without a return kind can
+/// only be run with `call`, not read in an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionSignature {
+    pub name: String,
+    pub parameters: Vec<ValueKind>,
+    pub returns: Option<ValueKind>,
+}
+
+impl FunctionSignature {
+    pub fn new(name: &str, parameters: &[ValueKind], returns: Option<ValueKind>) -> Self {
+        Self {
+            name: name.to_string(),
+            parameters: parameters.to_vec(),
+            returns,
+        }
+    }
+}
+
+/// A `call name(arguments)` line. Stored in `Database.calls`; referenced
+/// from a [`crate::BlockType::Call`] block via its index.
+///
+/// Not `Eq`: an argument may carry a `Value::Float` literal.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallStatement {
+    pub function: FunctionId,
+    pub arguments: Vec<Expression>,
+}
+
+impl CallStatement {
+    pub fn new(function: FunctionId, arguments: Vec<Expression>) -> Self {
+        Self {
+            function,
+            arguments,
+        }
+    }
+}
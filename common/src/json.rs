@@ -0,0 +1,172 @@
+//! The JSON form of a [`Database`], for tools and engines that load compiled
+//! stories without linking the parser.
+//!
+//! A document wraps the database with a format name and version:
+//!
+//! ```json
+//! { "format": "cuentitos", "version": 1, "database": { "blocks": [...], ... } }
+//! ```
+//!
+//! The layout of `database` is documented in `docs/json-format.md`. Once the
+//! format ships, any change to it bumps [`FORMAT_VERSION`], and [`from_json`]
+//! rejects documents written with another version instead of misreading
+//! them. It also rejects a database whose ids don't point inside it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Database;
+
+/// The value of `"format"` in every document.
+pub const FORMAT_NAME: &str = "cuentitos";
+
+/// The version of the `database` layout written by [`to_json`] and the only
+/// one [`from_json`] reads.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<'a> {
+    format: &'a str,
+    version: u32,
+    database: &'a Database,
+}
+
+/// Read first, so a document from another version is reported as such
+/// rather than as whatever field it happens to lay out differently.
+#[derive(Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct Body {
+    database: Database,
+}
+
+/// Why [`from_json`] couldn't load a document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    /// Not valid JSON, or not laid out as a database.
+    Malformed { message: String },
+    /// Valid JSON without `"format": "cuentitos"`.
+    NotACuentitosDocument,
+    /// Written with a different [`FORMAT_VERSION`].
+    UnsupportedVersion { found: u32 },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Malformed { message } => write!(f, "ERROR: Malformed JSON: {}", message),
+            JsonError::NotACuentitosDocument => {
+                write!(f, "ERROR: Not a cuentitos JSON document.")
+            }
+            JsonError::UnsupportedVersion { found } => write!(
+                f,
+                "ERROR: Unsupported JSON format version {} (expected {}).",
+                found, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(err: serde_json::Error) -> Self {
+        JsonError::Malformed {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Write `database` as a JSON document. Maps and sets are written in key
+/// order, so the same database always gives the same text.
+pub fn to_json(database: &Database) -> String {
+    let document = Document {
+        format: FORMAT_NAME,
+        version: FORMAT_VERSION,
+        database,
+    };
+    serde_json::to_string(&document).expect("a database always serializes")
+}
+
+/// Load a database written by [`to_json`]. The result runs exactly like the
+/// database that was written.
+pub fn from_json(json: &str) -> Result<Database, JsonError> {
+    let header: Header =
+        serde_json::from_str(json).map_err(|_| JsonError::NotACuentitosDocument)?;
+    if header.format != FORMAT_NAME {
+        return Err(JsonError::NotACuentitosDocument);
+    }
+    if header.version != FORMAT_VERSION {
+        return Err(JsonError::UnsupportedVersion {
+            found: header.version,
+        });
+    }
+    let body: Body = serde_json::from_str(json)?;
+    body.database
+        .check_ids()
+        .map_err(|message| JsonError::Malformed { message })?;
+    Ok(body.database)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, BlockType, Value, Variable};
+
+    fn database() -> Database {
+        let mut database = Database::new();
+        database.add_block(Block::new(BlockType::Start, None, 0));
+        database.add_variable(Variable::new("mood", Value::Float(0.1)));
+        database.once_only_options.extend([1, 0]);
+        database.add_block(Block::new(BlockType::End, None, 0));
+        database
+    }
+
+    #[test]
+    fn round_trips_a_database() {
+        let json = to_json(&database());
+        assert!(json.starts_with(r#"{"format":"cuentitos","version":1,"#));
+        assert!(json.contains(r#""once_only_options":[0,1]"#));
+        assert_eq!(from_json(&json), Ok(database()));
+    }
+
+    #[test]
+    fn rejects_other_documents_and_versions() {
+        assert_eq!(
+            from_json(r#"{"format":"cuentitos","version":99,"database":{}}"#),
+            Err(JsonError::UnsupportedVersion { found: 99 })
+        );
+        assert_eq!(
+            from_json(r#"{"name":"something else"}"#),
+            Err(JsonError::NotACuentitosDocument)
+        );
+        assert!(matches!(
+            from_json(r#"{"format":"cuentitos","version":1,"database":{}}"#),
+            Err(JsonError::Malformed { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_database_with_ids_out_of_range() {
+        let mut broken = database();
+        broken.once_only_options.insert(5);
+        assert_eq!(
+            from_json(&to_json(&broken)),
+            Err(JsonError::Malformed {
+                message: "an option marker refers to block 5, but there are only 2".to_string()
+            })
+        );
+
+        let mut broken = database();
+        broken.blocks[1].parent_id = Some(1);
+        assert!(matches!(
+            from_json(&to_json(&broken)),
+            Err(JsonError::Malformed { .. })
+        ));
+    }
+}
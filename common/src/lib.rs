@@ -1,12 +1,24 @@
+#[cfg(feature = "binary")]
+pub mod binary;
 pub mod block;
 pub mod boolean_expression;
+pub mod bucket;
+pub mod chance;
 pub mod database;
 pub mod expression;
+mod fnv;
+pub mod function;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod localization;
 pub mod path_resolver;
 pub mod requirement_statement;
 pub mod section;
 pub mod set_statement;
+#[cfg(feature = "serde")]
+mod sorted;
 pub mod test_case;
+pub mod text;
 pub mod value;
 pub mod variable;
 
@@ -15,15 +27,31 @@ pub type SectionId = usize;
 pub type VariableId = usize;
 pub type SetId = usize;
 pub type RequirementId = usize;
+pub type ChanceId = usize;
+pub type BucketId = usize;
+pub type FrequencyId = usize;
+pub type AlternativesId = usize;
+pub type VisitsId = usize;
+pub type FileId = usize;
+pub type FunctionId = usize;
+pub type CallId = usize;
 
 // Re-export commonly used types
 pub use block::{Block, BlockId, BlockType};
 pub use boolean_expression::BooleanExpression;
+pub use bucket::{Bucket, RefillPolicy};
+pub use chance::Chance;
 pub use database::Database;
-pub use expression::{evaluate, variable_lookup, BinaryOperator, EvaluationError, Expression};
+pub use expression::{
+    evaluate, evaluate_arguments, variable_lookup, BinaryOperator, EvaluationContext,
+    EvaluationError, Expression,
+};
+pub use fnv::fnv1a;
+pub use function::{CallStatement, FunctionSignature};
 pub use path_resolver::{PathResolutionError, PathResolver, ResolvedPath};
 pub use requirement_statement::{ComparisonOperator, RequirementStatement};
 pub use section::Section;
 pub use set_statement::{AssignmentOperator, SetStatement};
+pub use text::{AlternativesMode, TextAlternatives, TextFragment};
 pub use value::{Value, ValueKind};
 pub use variable::Variable;
@@ -0,0 +1,673 @@
+//! Translating a story's text, one string table per language.
+//!
+//! [`translatable_strings`] lists the strings a player sees: text lines,
+//! options and section display names. Section ids and paths are left out,
+//! since scripts and hosts refer to sections by them.
+//!
+//! Rows are matched to strings by [`string_keys`], not by string id, so a
+//! line added near the top of a script doesn't shift every translation
+//! below it. A line takes the key written at its end with `#id:key`, or else
+//! one derived from its section and its text:
+//!
+//! ```cuentitos
+//! # square: Square
+//!   Welcome back. #id:welcome
+//!   You have {gold} coins.
+//! ```
+//!
+//! With the `csv` feature, [`export_csv`] writes those strings as a CSV file
+//! that translators copy and fill in, one per language:
+//!
+//! ```csv
+//! key,source,text
+//! square:name,Square,Square
+//! welcome,Welcome back.,Welcome back.
+//! square:5535db8c,"You have {gold} coins.","You have {gold} coins."
+//! ```
+//!
+//! `source` is the text the row was translated from and `text` its
+//! translation. [`import_csv`] reads a translated file back and compares
+//! every row against the current script, so edits made after the
+//! translation show up as [`TranslationIssue`]s instead of wrong text. An
+//! edit gives an untagged line a new key; its old row is paired with it by
+//! section and reported stale.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+use crate::fnv::fnv1a;
+use crate::{BlockType, Database, SectionId, StringId};
+
+/// Every string a player can see, in id order.
+pub fn translatable_strings(database: &Database) -> Vec<StringId> {
+    let mut ids: Vec<StringId> = database
+        .blocks
+        .iter()
+        .filter_map(|block| match block.block_type {
+            BlockType::String(id) | BlockType::Option(id) => Some(id),
+            _ => None,
+        })
+        .chain(database.sections.iter().map(|section| section.name))
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// The key of every [`translatable_strings`] entry, in id order.
+///
+/// A string keeps the key recorded in `Database::string_keys`, from a
+/// `#id:key` tag. Otherwise it gets one derived from where it is and what it
+/// says, which survives edits anywhere else in the script:
+///
+/// - a section name, `hub \ cave:name`, from the section's id path;
+/// - a line or option, `hub \ cave:1a2b3c4d`, from its section's id path,
+///   or `START` before the first section, and a hash of its text.
+///
+/// The same text twice in one section gets `~2`, `~3` and so on after the
+/// first. Tagged keys can't hold `:` or `~`, so they never clash with a
+/// derived key.
+pub fn string_keys(database: &Database) -> BTreeMap<StringId, String> {
+    // Parents always come before their children, so their section is
+    // already known.
+    let mut section_of: Vec<Option<SectionId>> = Vec::with_capacity(database.blocks.len());
+    let mut locations = HashMap::new();
+    for block in &database.blocks {
+        let section = match block.block_type {
+            BlockType::Section(section_id) => Some(section_id),
+            _ => block.parent_id.and_then(|parent| section_of[parent]),
+        };
+        section_of.push(section);
+        if let BlockType::String(id) | BlockType::Option(id) = block.block_type {
+            locations.entry(id).or_insert(section);
+        }
+    }
+    let section_names: HashMap<StringId, SectionId> = database
+        .sections
+        .iter()
+        .enumerate()
+        .map(|(section_id, section)| (section.name, section_id))
+        .collect();
+    let id_path = |section: Option<SectionId>| match section {
+        Some(section_id) => database.strings[database.sections[section_id].id_path].as_str(),
+        None => "START",
+    };
+
+    let mut keys = BTreeMap::new();
+    let mut repeats: HashMap<String, usize> = HashMap::new();
+    for id in translatable_strings(database) {
+        if let Some(key) = database.string_keys.get(&id) {
+            keys.insert(id, key.clone());
+            continue;
+        }
+        let key = match section_names.get(&id) {
+            Some(&section_id) => format!("{}:name", id_path(Some(section_id))),
+            None => format!(
+                "{}:{:08x}",
+                id_path(locations.get(&id).copied().flatten()),
+                fnv1a(database.strings[id].as_bytes()) as u32
+            ),
+        };
+        let count = repeats.entry(key.clone()).or_insert(0);
+        *count += 1;
+        let key = match *count {
+            1 => key,
+            count => format!("{}~{}", key, count),
+        };
+        keys.insert(id, key);
+    }
+    keys
+}
+
+/// Whether `key` can be written in a `#id:key` tag: a letter or `_`, then
+/// letters, digits, `_` or `-`.
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// One row of a string table: the text a string had when it was translated
+/// and its translation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranslationRow {
+    /// The string's key, from [`string_keys`].
+    pub key: String,
+    pub source: String,
+    pub text: String,
+}
+
+/// A row that can't be used as it is, found by [`Translation::new`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranslationIssue {
+    /// A string has no row, or a row with no text.
+    Missing { key: String, source: String },
+    /// The string changed since it was translated. The translation is not
+    /// used until the row is updated.
+    Stale {
+        key: String,
+        /// The key the row was written with, which differs from `key` when
+        /// the edit changed a derived key.
+        row_key: String,
+        translated_from: String,
+        source: String,
+    },
+    /// The row is for a string the script no longer shows.
+    Orphaned {
+        key: String,
+        translated_from: String,
+    },
+    /// The translation can't be shown, e.g. its `{...}` names a variable
+    /// the script doesn't declare. Found by the runtime when it loads the
+    /// translation.
+    Invalid {
+        key: String,
+        text: String,
+        message: String,
+    },
+}
+
+impl TranslationIssue {
+    /// The key of the string the issue is about.
+    pub fn key(&self) -> &str {
+        match self {
+            TranslationIssue::Missing { key, .. }
+            | TranslationIssue::Stale { key, .. }
+            | TranslationIssue::Orphaned { key, .. }
+            | TranslationIssue::Invalid { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for TranslationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslationIssue::Missing { key, source } => {
+                write!(
+                    f,
+                    "WARNING: String '{}' is not translated: \"{}\"",
+                    key, source
+                )
+            }
+            TranslationIssue::Stale {
+                key,
+                row_key,
+                translated_from,
+                source,
+            } => {
+                write!(
+                    f,
+                    "WARNING: String '{}' changed since it was translated",
+                    key
+                )?;
+                if row_key != key {
+                    write!(f, " as '{}'", row_key)?;
+                }
+                write!(f, ", from \"{}\" to \"{}\"", translated_from, source)
+            }
+            TranslationIssue::Orphaned {
+                key,
+                translated_from,
+            } => write!(
+                f,
+                "WARNING: String '{}' is no longer in the script: \"{}\"",
+                key, translated_from
+            ),
+            TranslationIssue::Invalid { key, text, message } => write!(
+                f,
+                "WARNING: String '{}' has an invalid translation \"{}\": {}",
+                key, text, message
+            ),
+        }
+    }
+}
+
+/// The usable translations of a string table, and what's wrong with the
+/// rest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Translation {
+    /// Translated text by string id. Only holds rows that are up to date.
+    pub strings: HashMap<StringId, String>,
+    /// Every missing or stale row in string id order, then every orphaned
+    /// row in key order.
+    pub issues: Vec<TranslationIssue>,
+}
+
+impl Translation {
+    /// Match `rows` against the strings of `database` by key.
+    ///
+    /// An edited line gets a new derived key, so its row is left without a
+    /// string. Such rows are paired with the strings of the same section
+    /// that have no row and sit between the same matched rows, and are
+    /// reported stale instead of orphaned and missing.
+    pub fn new(database: &Database, rows: impl IntoIterator<Item = TranslationRow>) -> Self {
+        let rows: Vec<TranslationRow> = rows.into_iter().collect();
+        let by_key: HashMap<String, usize> = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| (row.key.clone(), index))
+            .collect();
+        let mut rows: Vec<Option<TranslationRow>> = rows.into_iter().map(Some).collect();
+        let mut strings = HashMap::new();
+
+        // Match by key first, remembering what each string got.
+        let mut matched = Vec::new();
+        let mut row_matches = HashMap::new();
+        for (id, key) in string_keys(database) {
+            let row = by_key.get(&key).and_then(|&index| {
+                row_matches.insert(index, matched.len());
+                rows[index].take()
+            });
+            matched.push((id, key, row));
+        }
+
+        // Pair the rows left over with the strings left without one. Both
+        // are in script order, so a leftover row is paired within the same
+        // gap between matched rows of its section: after the same matched
+        // string, or at the section's start.
+        let mut gaps: HashMap<(&str, Option<usize>), VecDeque<usize>> = HashMap::new();
+        let mut last_match: HashMap<&str, usize> = HashMap::new();
+        for (index, (_, key, row)) in matched.iter().enumerate() {
+            let Some(section) = edited_section(key) else {
+                continue;
+            };
+            match row {
+                Some(_) => {
+                    last_match.insert(section, index);
+                }
+                None => {
+                    let gap = (section, last_match.get(section).copied());
+                    gaps.entry(gap).or_default().push_back(index);
+                }
+            }
+        }
+        let mut last_match: HashMap<&str, usize> = HashMap::new();
+        let mut pairs = Vec::new();
+        for (row_index, row) in rows.iter().enumerate() {
+            if let Some(&index) = row_matches.get(&row_index) {
+                if let Some(section) = edited_section(&matched[index].1) {
+                    last_match.insert(section, index);
+                }
+                continue;
+            }
+            let Some(section) = row.as_ref().and_then(|row| edited_section(&row.key)) else {
+                continue;
+            };
+            let gap = (section, last_match.get(section).copied());
+            if let Some(index) = gaps.get_mut(&gap).and_then(VecDeque::pop_front) {
+                pairs.push((index, row_index));
+            }
+        }
+        for (index, row_index) in pairs {
+            matched[index].2 = rows[row_index].take();
+        }
+
+        let mut issues = Vec::new();
+        for (id, key, row) in matched {
+            let source = &database.strings[id];
+            match row {
+                Some(row) if row.source != *source || row.key != key => {
+                    issues.push(TranslationIssue::Stale {
+                        key,
+                        row_key: row.key,
+                        translated_from: row.source,
+                        source: source.clone(),
+                    });
+                }
+                Some(row) if !row.text.is_empty() => {
+                    strings.insert(id, row.text);
+                }
+                _ => {
+                    issues.push(TranslationIssue::Missing {
+                        key,
+                        source: source.clone(),
+                    });
+                }
+            }
+        }
+        let mut orphaned: Vec<TranslationRow> = rows.into_iter().flatten().collect();
+        orphaned.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+        for row in orphaned {
+            issues.push(TranslationIssue::Orphaned {
+                key: row.key,
+                translated_from: row.source,
+            });
+        }
+
+        Self { strings, issues }
+    }
+}
+
+/// The section id path of a key derived from a line's text, which changes
+/// when the line is edited. Tagged keys and section names keep their key
+/// through edits, so they have none.
+fn edited_section(key: &str) -> Option<&str> {
+    let (section, hash) = key.rsplit_once(':')?;
+    (hash != "name").then_some(section)
+}
+
+/// Why [`import_csv`] couldn't read a string table.
+#[cfg(feature = "csv")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvError {
+    /// The line of the CSV file, starting at 1.
+    pub line: u64,
+    pub message: String,
+}
+
+#[cfg(feature = "csv")]
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ERROR: {}", self.line, self.message)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl std::error::Error for CsvError {}
+
+#[cfg(feature = "csv")]
+const HEADER: [&str; 3] = ["key", "source", "text"];
+
+/// Write the string table of `database`'s own language: every
+/// [`string_keys`] entry, with its text as both `source` and `text`.
+#[cfg(feature = "csv")]
+pub fn export_csv(database: &Database) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(HEADER).expect("writing to memory");
+    for (id, key) in string_keys(database) {
+        let text = &database.strings[id];
+        writer
+            .write_record([key.as_str(), text, text])
+            .expect("writing to memory");
+    }
+    let bytes = writer.into_inner().expect("writing to memory");
+    String::from_utf8(bytes).expect("strings are UTF-8")
+}
+
+/// Read a string table written by [`export_csv`] and translated, and match
+/// it against `database`.
+#[cfg(feature = "csv")]
+pub fn import_csv(database: &Database, csv: &str) -> Result<Translation, CsvError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv.as_bytes());
+    let mut records = reader.records();
+
+    let header = records.next().transpose().map_err(csv_error)?;
+    if !header.is_some_and(|header| header.iter().eq(HEADER)) {
+        return Err(CsvError {
+            line: 1,
+            message: "Expected the header 'key,source,text'.".to_string(),
+        });
+    }
+
+    let mut rows = Vec::new();
+    let mut lines = HashMap::new();
+    for record in records {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map_or(0, |position| position.line());
+        let key = record[0].trim();
+        if key.is_empty() {
+            return Err(CsvError {
+                line,
+                message: "Missing string key.".to_string(),
+            });
+        }
+        if let Some(first) = lines.insert(key.to_string(), line) {
+            return Err(CsvError {
+                line,
+                message: format!("String '{}' already has a row on line {}.", key, first),
+            });
+        }
+        rows.push(TranslationRow {
+            key: key.to_string(),
+            source: record[1].to_string(),
+            text: record[2].to_string(),
+        });
+    }
+
+    Ok(Translation::new(database, rows))
+}
+
+#[cfg(feature = "csv")]
+fn csv_error(err: csv::Error) -> CsvError {
+    let line = err.position().map_or(0, |position| position.line());
+    let message = match err.kind() {
+        csv::ErrorKind::UnequalLengths { len, .. } => {
+            format!("Expected 3 columns, found {}.", len)
+        }
+        _ => format!("Malformed CSV: {}", err),
+    };
+    CsvError { line, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, Section};
+
+    fn database() -> Database {
+        let mut database = Database::new();
+        database.add_block(Block::new(BlockType::Start, None, 0));
+        let name = database.add_string("The Hub".to_string());
+        let id = database.add_string("hub".to_string());
+        let path = database.add_string("The Hub".to_string());
+        let id_path = database.add_string("hub".to_string());
+        let section = database.add_block(Block::new(BlockType::Section(0), Some(0), 0));
+        database.add_section(Section::new(section, name, id, path, id_path));
+        let line = database.add_string("Hello, \"friend\".".to_string());
+        database.add_block(Block::new(BlockType::String(line), Some(section), 1));
+        let option = database.add_string("Leave".to_string());
+        database.add_block(Block::new(BlockType::Option(option), Some(2), 2));
+        database.set_string_key(option, "leave".to_string());
+        database.add_block(Block::new(BlockType::End, None, 0));
+        database
+    }
+
+    fn row(key: &str, source: &str, text: &str) -> TranslationRow {
+        TranslationRow {
+            key: key.to_string(),
+            source: source.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn lists_only_strings_players_see() {
+        assert_eq!(translatable_strings(&database()), vec![0, 4, 5]);
+    }
+
+    #[test]
+    fn derives_keys_that_survive_edits_elsewhere() {
+        let keys = string_keys(&database());
+        assert_eq!(
+            keys.values().collect::<Vec<_>>(),
+            ["hub:name", "hub:457f4b9f", "leave"]
+        );
+
+        // A line added before the section, and a repeat of a line, shift
+        // string ids but leave existing keys alone.
+        let mut edited = Database::new();
+        edited.add_block(Block::new(BlockType::Start, None, 0));
+        let intro = edited.add_string("Once upon a time.".to_string());
+        edited.add_block(Block::new(BlockType::String(intro), Some(0), 0));
+        let name = edited.add_string("The Hub".to_string());
+        let id_path = edited.add_string("hub".to_string());
+        let section = edited.add_block(Block::new(BlockType::Section(0), Some(0), 0));
+        edited.add_section(Section::new(section, name, id_path, name, id_path));
+        for _ in 0..2 {
+            let line = edited.add_string("Hello, \"friend\".".to_string());
+            edited.add_block(Block::new(BlockType::String(line), Some(section), 1));
+        }
+        assert_eq!(
+            string_keys(&edited).into_values().collect::<Vec<_>>(),
+            [
+                "START:4a56dbf6".to_string(),
+                "hub:name".to_string(),
+                "hub:457f4b9f".to_string(),
+                "hub:457f4b9f~2".to_string(),
+            ]
+        );
+    }
+
+    /// A `hub` section holding `lines`.
+    fn hub(lines: &[&str]) -> Database {
+        let mut database = Database::new();
+        database.add_block(Block::new(BlockType::Start, None, 0));
+        let name = database.add_string("The Hub".to_string());
+        let id_path = database.add_string("hub".to_string());
+        let section = database.add_block(Block::new(BlockType::Section(0), Some(0), 0));
+        database.add_section(Section::new(section, name, id_path, name, id_path));
+        for line in lines {
+            let line = database.add_string(line.to_string());
+            database.add_block(Block::new(BlockType::String(line), Some(section), 1));
+        }
+        database
+    }
+
+    /// The rows of `database` with every line translated as `es: <text>`.
+    fn translated(database: &Database) -> Vec<TranslationRow> {
+        string_keys(database)
+            .into_iter()
+            .map(|(id, key)| {
+                let source = &database.strings[id];
+                row(&key, source, &format!("es: {}", source))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inserted_lines_keep_existing_translations() {
+        let rows = translated(&hub(&["One.", "Two.", "Three."]));
+        let edited = hub(&["Zero.", "One.", "Two.", "Three."]);
+        let translation = Translation::new(&edited, rows);
+        let mut texts: Vec<_> = translation.strings.values().cloned().collect();
+        texts.sort();
+        assert_eq!(texts, ["es: One.", "es: The Hub", "es: Three.", "es: Two."]);
+        assert_eq!(
+            translation.issues,
+            [TranslationIssue::Missing {
+                key: string_keys(&edited)[&2].clone(),
+                source: "Zero.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn edited_lines_are_stale_under_their_old_row() {
+        // The row of the edited line is paired with it, not with the line
+        // added above.
+        let original = hub(&["One.", "Two."]);
+        let rows = translated(&original);
+        let edited = hub(&["Zero.", "One.", "Two!"]);
+        let translation = Translation::new(&edited, rows);
+        assert_eq!(translation.strings[&3], "es: One.");
+        assert!(!translation.strings.contains_key(&4));
+        assert_eq!(
+            translation.issues,
+            [
+                TranslationIssue::Missing {
+                    key: string_keys(&edited)[&2].clone(),
+                    source: "Zero.".to_string(),
+                },
+                TranslationIssue::Stale {
+                    key: string_keys(&edited)[&4].clone(),
+                    row_key: string_keys(&original)[&3].clone(),
+                    translated_from: "Two.".to_string(),
+                    source: "Two!".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            translation.issues[1].to_string(),
+            format!(
+                "WARNING: String '{}' changed since it was translated as '{}', from \"Two.\" to \"Two!\"",
+                string_keys(&edited)[&4],
+                string_keys(&original)[&3]
+            )
+        );
+    }
+
+    #[test]
+    fn validates_key_tags() {
+        assert!(is_valid_key("welcome"));
+        assert!(is_valid_key("_intro-2"));
+        assert!(!is_valid_key("2nd"));
+        assert!(!is_valid_key("hub:name"));
+        assert!(!is_valid_key(""));
+    }
+
+    #[test]
+    fn reports_missing_stale_and_orphaned_rows() {
+        let translation = Translation::new(
+            &database(),
+            [
+                row("hub:name", "The Hub", "El Centro"),
+                row("hub:457f4b9f", "Hello, friend.", "Hola, amigo."),
+                row("leave", "Leave", ""),
+                row("gone", "Gone", "Ido"),
+            ],
+        );
+        assert_eq!(
+            translation.strings,
+            HashMap::from([(0, "El Centro".to_string())])
+        );
+        assert_eq!(
+            translation.issues,
+            vec![
+                TranslationIssue::Stale {
+                    key: "hub:457f4b9f".to_string(),
+                    row_key: "hub:457f4b9f".to_string(),
+                    translated_from: "Hello, friend.".to_string(),
+                    source: "Hello, \"friend\".".to_string(),
+                },
+                TranslationIssue::Missing {
+                    key: "leave".to_string(),
+                    source: "Leave".to_string(),
+                },
+                TranslationIssue::Orphaned {
+                    key: "gone".to_string(),
+                    translated_from: "Gone".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn exports_and_imports_csv() {
+        let csv = export_csv(&database());
+        assert_eq!(
+            csv,
+            "key,source,text\nhub:name,The Hub,The Hub\nhub:457f4b9f,\"Hello, \"\"friend\"\".\",\"Hello, \"\"friend\"\".\"\nleave,Leave,Leave\n"
+        );
+        let translated = csv.replace(",Leave\n", ",Salir\n");
+        let translation = import_csv(&database(), &translated).unwrap();
+        assert_eq!(translation.strings[&5], "Salir");
+        assert!(translation.issues.is_empty());
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn rejects_malformed_csv() {
+        let error = |csv: &str| import_csv(&database(), csv).unwrap_err().to_string();
+        assert_eq!(
+            error("id,source,text\n"),
+            "1: ERROR: Expected the header 'key,source,text'."
+        );
+        assert_eq!(
+            error("key,source,text\n ,Leave,Salir\n"),
+            "2: ERROR: Missing string key."
+        );
+        assert_eq!(
+            error("key,source,text\nleave,Leave\n"),
+            "2: ERROR: Expected 3 columns, found 2."
+        );
+        assert_eq!(
+            error("key,source,text\nleave,Leave,Salir\nleave,Leave,Irse\n"),
+            "3: ERROR: String 'leave' already has a row on line 2."
+        );
+    }
+}
@@ -3,6 +3,7 @@ use crate::value::Value;
 
 /// The comparison operator used by a `req` statement.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ComparisonOperator {
     Equal,
     NotEqual,
@@ -164,6 +165,7 @@ impl ComparisonOperator {
 /// Not `Eq`: the `left`/`right` expressions may carry `Value::Float` literals,
 /// whose `f64` payload has no total equality.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RequirementStatement {
     pub left: Expression,
     pub operator: ComparisonOperator,
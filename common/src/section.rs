@@ -2,6 +2,7 @@ use crate::{BlockId, StringId};
 
 /// Metadata for a section in the narrative
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Section {
     pub block_id: BlockId, // The block ID of the Section block
     pub name: StringId,    // The section's display name
@@ -3,6 +3,7 @@ use crate::VariableId;
 
 /// The assignment operator used by a `set` statement.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AssignmentOperator {
     Assign,
     AddAssign,
@@ -44,6 +45,7 @@ impl AssignmentOperator {
 /// Not `Eq`: `expression` may carry a `Value::Float` literal, whose `f64`
 /// payload has no total equality.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetStatement {
     pub variable_id: VariableId,
     pub operator: AssignmentOperator,
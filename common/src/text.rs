@@ -0,0 +1,68 @@
+//! Interpolated text.
+//!
+//! Text and option lines can print variable values inline, pick between two
+//! texts with a condition, and vary between visits:
+//!
+//! ```cuentitos
+//! You have {gold} coins.
+//! The door is {door_open ? open : shut}.
+//! {Hello|Hello again|Back already?}
+//! ```
+//!
+//! The parser splits such a line into [`TextFragment`]s and stores them in
+//! `Database.text_fragments`, keyed by the line's [`crate::StringId`]. The
+//! raw line stays in `Database.strings` untouched. Lines without braces have
+//! no fragments and print as they are.
+//!
+//! `{{` writes a literal `{`. A `}` outside an interpolation is plain text.
+
+use crate::boolean_expression::BooleanExpression;
+use crate::{AlternativesId, VariableId};
+
+/// One piece of an interpolated line, in source order.
+///
+/// Not `Eq`: a conditional carries a [`BooleanExpression`], which has no
+/// total equality.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextFragment {
+    /// Text printed as is, with `{{` already turned into `{`.
+    Literal(String),
+    /// `{name}`: the current value of a declared variable.
+    Variable(VariableId),
+    /// `{condition ? then : otherwise}`: one of two texts, picked with the
+    /// same conditions `req` uses. `otherwise` is empty when the `: ...`
+    /// part is left out.
+    Conditional {
+        condition: BooleanExpression,
+        then: Vec<TextFragment>,
+        otherwise: Vec<TextFragment>,
+    },
+    /// `{a|b|c}` and its `&`/`~` forms: one branch per visit, chosen by the
+    /// [`TextAlternatives`] stored at this index in
+    /// `Database.text_alternatives`.
+    Alternatives(AlternativesId),
+}
+
+/// How a set of alternatives picks its branch on each visit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlternativesMode {
+    /// `{a|b|c}`: one branch per visit in order, then the last one forever.
+    Sequence,
+    /// `{&a|b|c}`: one branch per visit in order, starting over after the
+    /// last.
+    Cycle,
+    /// `{~a|b|c}`: a random branch every visit, from the seeded runtime RNG.
+    Shuffle,
+}
+
+/// The branches of one `{a|b|c}` fragment. Stored in
+/// `Database.text_alternatives` so the runtime can count visits per
+/// fragment by its index.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextAlternatives {
+    pub mode: AlternativesMode,
+    pub branches: Vec<Vec<TextFragment>>,
+}
@@ -13,6 +13,7 @@
 /// A runtime value carried by an expression literal, variable cell, or
 /// evaluation result.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     Integer(i64),
     Boolean(bool),
@@ -184,6 +185,7 @@ pub fn format_float(value: f64) -> String {
 /// Type tag for [`Value`]. Used by the parse-time type inferrer to compare
 /// expected vs. found types without inspecting payloads.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValueKind {
     Integer,
     Boolean,
@@ -10,6 +10,7 @@ use crate::value::{Value, ValueKind};
 // Not `Eq`: `default` is a `Value`, which carries a non-`Eq` `f64` float
 // payload. `PartialEq` is enough for the equality checks in tests.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variable {
     pub name: String,
     pub default: Value,
@@ -0,0 +1,146 @@
+//! Compiles cuentitos scripts into the binary files a runtime loads without
+//! the parser: a structure file with the story's logic and a strings file
+//! with its text, as laid out in [`cuentitos_common::binary`].
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cuentitos_common::binary::{self, Files};
+use cuentitos_common::Database;
+use cuentitos_parser::project::{Project, ProjectError};
+use cuentitos_parser::{ParseError, Parser, Warning};
+
+/// Extension of structure files, `story.cbin`.
+pub const STRUCTURE_EXTENSION: &str = "cbin";
+
+/// Extension of strings files, `story.cstrings`.
+pub const STRINGS_EXTENSION: &str = "cstrings";
+
+/// A compiled story, ready to be written with [`Compiled::write`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compiled {
+    pub structure: Vec<u8>,
+    pub strings: Vec<u8>,
+}
+
+/// Why a script couldn't be compiled.
+#[derive(Debug)]
+pub enum CompileError {
+    /// The script couldn't be read.
+    Read { path: PathBuf, error: io::Error },
+    /// The script has errors.
+    Parse(ParseError),
+    /// The project manifest is invalid, or the project's scripts can't be
+    /// read or have errors.
+    Project(ProjectError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Read { path, error } => {
+                write!(f, "ERROR: Can't read '{}': {}", path.display(), error)
+            }
+            CompileError::Parse(error) => write!(f, "{}", error),
+            CompileError::Project(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<ParseError> for CompileError {
+    fn from(error: ParseError) -> Self {
+        CompileError::Parse(error)
+    }
+}
+
+/// Compile a parsed database. The same database always compiles to the
+/// same bytes.
+pub fn compile(database: &Database) -> Compiled {
+    let Files { structure, strings } = binary::write(database);
+    Compiled { structure, strings }
+}
+
+/// Parse the script at `path`, or the main script of the project in the
+/// directory at `path`, returning the parser's warnings along with the
+/// database to [`compile`].
+pub fn parse(path: &Path) -> Result<(Database, Vec<Warning>), CompileError> {
+    if path.is_dir() {
+        return Project::load(path)
+            .and_then(|project| project.parse())
+            .map_err(CompileError::Project);
+    }
+    let script = std::fs::read_to_string(path).map_err(|error| CompileError::Read {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let mut parser = Parser::with_file(path.to_path_buf());
+    Ok(parser.parse(script)?)
+}
+
+/// Where the strings file of the structure file at `structure_path` goes:
+/// next to it, with [`STRINGS_EXTENSION`].
+pub fn strings_path(structure_path: &Path) -> PathBuf {
+    structure_path.with_extension(STRINGS_EXTENSION)
+}
+
+impl Compiled {
+    /// Write the structure file to `structure_path` and the strings file
+    /// next to it, at [`strings_path`].
+    pub fn write(&self, structure_path: &Path) -> io::Result<()> {
+        std::fs::write(structure_path, &self.structure)?;
+        std::fs::write(strings_path(structure_path), &self.strings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_a_script_to_both_files() {
+        let dir = std::env::temp_dir().join(format!("cuentitos-compiler-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script_path = dir.join("story.cuentitos");
+        std::fs::write(&script_path, "# hub: Hub\n  Hello.\n  -> hub").unwrap();
+
+        let (database, warnings) = parse(&script_path).unwrap();
+        assert!(warnings.is_empty());
+        let compiled = compile(&database);
+        let structure_path = script_path.with_extension(STRUCTURE_EXTENSION);
+        compiled.write(&structure_path).unwrap();
+
+        let structure = binary::read_structure(&std::fs::read(&structure_path).unwrap()).unwrap();
+        let strings = std::fs::read(dir.join("story.cstrings")).unwrap();
+        let strings = binary::read_strings(&strings, structure.checksum).unwrap();
+        assert!(strings.contains(&"Hello.".to_string()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_unreadable_scripts_and_parse_errors() {
+        let missing = Path::new("does/not/exist.cuentitos");
+        assert!(matches!(parse(missing), Err(CompileError::Read { .. })));
+
+        let dir =
+            std::env::temp_dir().join(format!("cuentitos-compiler-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script_path = dir.join("bad.cuentitos");
+        std::fs::write(&script_path, "-> nowhere").unwrap();
+        let error = parse(&script_path).unwrap_err();
+        assert!(matches!(error, CompileError::Parse(_)));
+        assert!(error.to_string().starts_with("bad.cuentitos:1: ERROR:"));
+
+        std::fs::write(
+            dir.join("cuentitos.toml"),
+            "[story]\nentry = \"bad.cuentitos\"",
+        )
+        .unwrap();
+        let error = parse(&dir).unwrap_err();
+        assert!(matches!(error, CompileError::Project(_)));
+        assert!(error.to_string().starts_with("bad.cuentitos:1: ERROR:"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
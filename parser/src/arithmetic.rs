@@ -8,7 +8,9 @@
 //! additive       := multiplicative (`+`|`-` multiplicative)*
 //! multiplicative := unary (`*`|`/` unary)*
 //! unary          := `-` unary | `+` unary | primary
-//! primary        := integer | identifier | `(` additive `)`
+//! primary        := integer | identifier | visits | call | `(` additive `)`
+//! visits         := `visits(` section-path `)`
+//! call           := identifier `(` (additive (`,` additive)*)? `)`
 //! ```
 //!
 //! with the same operator precedence, the same `i64::MIN`-aware literal
@@ -28,7 +30,14 @@
 //! [`crate::expression`]: crate::expression
 //! [`crate::boolean_expression`]: crate::boolean_expression
 
-use cuentitos_common::{BinaryOperator, Expression, Value, VariableId};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use cuentitos_common::{
+    BinaryOperator, Expression, FunctionId, FunctionSignature, Value, ValueKind, VariableId,
+    VisitsId,
+};
 
 /// The arithmetic sublanguage's token alphabet — payload-free so the
 /// parser can pattern-match on it without copying identifier text.
@@ -41,12 +50,14 @@ pub enum ArithmeticTokenKind {
     Float,
     Str,
     Ident,
+    Visits,
     Plus,
     Minus,
     Star,
     Slash,
     LParen,
     RParen,
+    Comma,
 }
 
 /// A payload-carrying arithmetic token. Provided for callers that
@@ -63,12 +74,16 @@ pub enum ArithmeticToken {
     Float(f64),
     Str(String),
     Ident(String),
+    /// `visits(path)`, carrying the path between the parentheses.
+    Visits(String),
     Plus,
     Minus,
     Star,
     Slash,
     LParen,
     RParen,
+    /// Separates the arguments of a function call.
+    Comma,
 }
 
 impl ArithmeticToken {
@@ -79,12 +94,14 @@ impl ArithmeticToken {
             ArithmeticToken::Float(_) => ArithmeticTokenKind::Float,
             ArithmeticToken::Str(_) => ArithmeticTokenKind::Str,
             ArithmeticToken::Ident(_) => ArithmeticTokenKind::Ident,
+            ArithmeticToken::Visits(_) => ArithmeticTokenKind::Visits,
             ArithmeticToken::Plus => ArithmeticTokenKind::Plus,
             ArithmeticToken::Minus => ArithmeticTokenKind::Minus,
             ArithmeticToken::Star => ArithmeticTokenKind::Star,
             ArithmeticToken::Slash => ArithmeticTokenKind::Slash,
             ArithmeticToken::LParen => ArithmeticTokenKind::LParen,
             ArithmeticToken::RParen => ArithmeticTokenKind::RParen,
+            ArithmeticToken::Comma => ArithmeticTokenKind::Comma,
         }
     }
 }
@@ -111,6 +128,69 @@ pub enum ArithmeticError {
     /// stack to overflow. Callers map this onto their own
     /// depth-exceeded variant.
     ExpressionTooDeep,
+    /// A function call didn't match the functions the game declared.
+    Function(FunctionCallError),
+}
+
+/// Why a function call was rejected at parse time. Shared by every parser
+/// that reads expressions, and by `call` lines, so the wording is the same
+/// wherever a call is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionCallError {
+    /// No function with this name was declared.
+    Unknown { name: String },
+    /// The call passes another number of arguments than the function takes.
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A function without a return kind was read in an expression.
+    NoReturnValue { name: String },
+    /// Argument number `position`, counting from 1, has another kind than
+    /// the function's parameter. Found by type inference, once the
+    /// arguments are parsed.
+    ArgumentKind {
+        name: String,
+        position: usize,
+        expected: ValueKind,
+        found: ValueKind,
+    },
+}
+
+impl fmt::Display for FunctionCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionCallError::Unknown { name } => write!(f, "Unknown function '{}'.", name),
+            FunctionCallError::ArgumentCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function '{}' takes {} argument{}, found {}.",
+                name,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
+            FunctionCallError::NoReturnValue { name } => write!(
+                f,
+                "Function '{}' doesn't return a value, run it with 'call' instead.",
+                name
+            ),
+            FunctionCallError::ArgumentKind {
+                name,
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function '{}' takes {} as argument {}, found {}.",
+                name, expected, position, found
+            ),
+        }
+    }
 }
 
 /// Bridges the caller's token stream and identifier scope to the
@@ -169,8 +249,18 @@ pub trait ArithmeticSource {
     /// callers in this module `.expect()` on that since the surrounding
     /// `match` already verified the kind.
     fn take_ident(&mut self) -> Option<String>;
+    /// Consume the current `Visits` token and return its section path.
+    /// Returns `None` if the cursor is not pointing at a `Visits` token.
+    fn take_visits(&mut self) -> Option<String>;
     /// Resolve an identifier to a declared variable id.
     fn resolve(&self, name: &str) -> Option<VariableId>;
+    /// Register the section path of a `visits(path)` read. `None` where
+    /// visit counts can't be read (variable defaults), which the parser
+    /// reports as malformed.
+    fn visits(&self, path: &str) -> Option<VisitsId>;
+    /// Look up a declared function by name. `None` where no functions are
+    /// declared, which reports every call as unknown.
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)>;
     /// Bump the source's recursion counter before a stack-growing
     /// descent (non-literal unary `-`/`+`, or the `(` branch of
     /// `primary`). Returns [`ArithmeticError::ExpressionTooDeep`] once
@@ -305,11 +395,21 @@ fn parse_primary<S: ArithmeticSource>(stream: &mut S) -> Result<Expression, Arit
         }
         Some(ArithmeticTokenKind::Ident) => {
             let name = stream.take_ident().expect("peek_kind guarded this");
+            if stream.peek_kind() == Some(ArithmeticTokenKind::LParen) {
+                return parse_function_call(stream, name, true);
+            }
             match stream.resolve(&name) {
                 Some(id) => Ok(Expression::Variable(id)),
                 None => Err(ArithmeticError::UndefinedVariable { name }),
             }
         }
+        Some(ArithmeticTokenKind::Visits) => {
+            let path = stream.take_visits().expect("peek_kind guarded this");
+            stream
+                .visits(&path)
+                .map(Expression::Visits)
+                .ok_or(ArithmeticError::Malformed)
+        }
         Some(ArithmeticTokenKind::LParen) => {
             stream.advance();
             // Nested parens are the other stack-growing path: `(((((…)))))`
@@ -331,6 +431,97 @@ fn parse_primary<S: ArithmeticSource>(stream: &mut S) -> Result<Expression, Arit
     }
 }
 
+/// Parse the parenthesized arguments of a call to `name`, whose identifier
+/// was already consumed, and check them against its declaration. `as_value`
+/// is set when the call is read in an expression, which needs a function
+/// with a return kind; `call` lines run any function.
+pub fn parse_function_call<S: ArithmeticSource>(
+    stream: &mut S,
+    name: String,
+    as_value: bool,
+) -> Result<Expression, ArithmeticError> {
+    let Some((function, signature)) = stream.function(&name) else {
+        return Err(ArithmeticError::Function(FunctionCallError::Unknown {
+            name,
+        }));
+    };
+    if stream.peek_kind() != Some(ArithmeticTokenKind::LParen) {
+        return Err(ArithmeticError::Malformed);
+    }
+    stream.advance();
+    // Arguments are whole expressions, so a call nested in an argument
+    // recurses like a parenthesized group and shares its depth budget.
+    stream.enter_recursion()?;
+    let mut arguments = Vec::new();
+    if stream.peek_kind() != Some(ArithmeticTokenKind::RParen) {
+        loop {
+            arguments.push(parse_additive(stream)?);
+            match stream.peek_kind() {
+                Some(ArithmeticTokenKind::Comma) => stream.advance(),
+                Some(ArithmeticTokenKind::RParen) => break,
+                None => return Err(ArithmeticError::UnbalancedParentheses),
+                _ => return Err(ArithmeticError::Malformed),
+            }
+        }
+    }
+    stream.advance();
+    stream.leave_recursion();
+
+    if arguments.len() != signature.parameters.len() {
+        return Err(ArithmeticError::Function(
+            FunctionCallError::ArgumentCount {
+                name,
+                expected: signature.parameters.len(),
+                found: arguments.len(),
+            },
+        ));
+    }
+    if as_value && signature.returns.is_none() {
+        return Err(ArithmeticError::Function(
+            FunctionCallError::NoReturnValue { name },
+        ));
+    }
+    Ok(Expression::Call {
+        function,
+        arguments,
+    })
+}
+
+/// What follows an identifier spelled `visits` in a tokenizer.
+pub(crate) enum VisitsCall {
+    /// No `(` follows: `visits` is an ordinary identifier.
+    NotACall,
+    /// `visits(path)`, with the trimmed path.
+    Path(String),
+    /// A `(` with no `)`, or nothing between them.
+    Malformed,
+}
+
+/// Called by the tokenizers right after they consumed the identifier
+/// `visits`: lex the `(path)` that makes it a visit count. Section paths
+/// hold spaces and `\`, so everything up to the first `)` is the path.
+/// Nothing is consumed when no `(` follows.
+pub(crate) fn scan_visits_call(chars: &mut Peekable<Chars<'_>>) -> VisitsCall {
+    let mut lookahead = chars.clone();
+    while lookahead.next_if(|c| c.is_whitespace()).is_some() {}
+    if lookahead.next() != Some('(') {
+        return VisitsCall::NotACall;
+    }
+    let mut path = String::new();
+    for c in lookahead.by_ref() {
+        if c == ')' {
+            *chars = lookahead;
+            let path = path.trim();
+            if path.is_empty() {
+                return VisitsCall::Malformed;
+            }
+            return VisitsCall::Path(path.to_string());
+        }
+        path.push(c);
+    }
+    VisitsCall::Malformed
+}
+
 /// Compute `-(n as i64)` without intermediate overflow. The `i64::MIN`
 /// case is the only one whose absolute value doesn't fit in `i64`, so
 /// it gets a direct return; anything larger overflows and the error
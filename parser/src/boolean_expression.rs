@@ -22,12 +22,13 @@
 //! `Missing right operand for 'and' in 'req': 'x > 0 and'.`
 
 use cuentitos_common::{
-    BooleanExpression, ComparisonOperator, Expression, RequirementStatement, Value, ValueKind,
-    VariableId,
+    BooleanExpression, ComparisonOperator, Expression, FunctionId, FunctionSignature,
+    RequirementStatement, Value, ValueKind, VariableId, VisitsId,
 };
 
 use crate::arithmetic::{
-    parse_arithmetic_expression, ArithmeticError, ArithmeticSource, ArithmeticTokenKind,
+    parse_arithmetic_expression, scan_visits_call, ArithmeticError, ArithmeticSource,
+    ArithmeticTokenKind, FunctionCallError, VisitsCall,
 };
 use crate::string_literal::{scan_quoted_body, StringLiteralError};
 
@@ -55,6 +56,20 @@ pub trait VariableResolver {
     fn enum_variants(&self, _id: VariableId) -> Option<Vec<String>> {
         None
     }
+
+    /// Register the section path of a `visits(path)` read and return its
+    /// id. The default returns `None`, for resolvers that can't record
+    /// visit reads; the parser reports those as malformed.
+    fn visits(&self, _path: &str) -> Option<VisitsId> {
+        None
+    }
+
+    /// Look up a declared function by name. The default returns `None`, for
+    /// resolvers without functions; the parser reports those calls as
+    /// unknown.
+    fn function(&self, _name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        None
+    }
 }
 
 impl<F: Fn(&str) -> Option<VariableId>> VariableResolver for F {
@@ -84,7 +99,7 @@ pub fn parse_boolean_expression(
         Err(TokenizeError::DoubleEquals) => {
             return Err(BooleanParseError::DoubleEquals);
         }
-        Err(TokenizeError::MalformedStringLiteral) => {
+        Err(TokenizeError::MalformedStringLiteral | TokenizeError::MalformedVisits) => {
             return Err(BooleanParseError::Malformed);
         }
     };
@@ -172,6 +187,8 @@ pub enum BooleanParseError {
     /// surface a dedicated diagnostic instead of letting the parse fail
     /// later as "malformed."
     DoubleEquals,
+    /// A function call didn't match the declared functions.
+    Function(FunctionCallError),
 }
 
 /// Maximum nesting depth accepted in a `req` boolean expression. Each
@@ -219,6 +236,8 @@ enum Token {
     Float(f64),
     Str(String),
     Ident(String),
+    /// `visits(path)`, carrying the path between the parentheses.
+    Visits(String),
     LogicalAnd,
     LogicalOr,
     LogicalNot,
@@ -234,6 +253,7 @@ enum Token {
     GreaterOrEqual,
     LParen,
     RParen,
+    Comma,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -251,6 +271,9 @@ enum TokenizeError {
     /// so the author isn't left guessing at a generic "malformed
     /// expression" message.
     DoubleEquals,
+    /// `visits(` with no closing `)`, or with no path inside. Folds into the
+    /// generic malformed-expression message.
+    MalformedVisits,
 }
 
 fn tokenize(input: &str) -> Result<Vec<Token>, TokenizeError> {
@@ -286,6 +309,10 @@ fn tokenize(input: &str) -> Result<Vec<Token>, TokenizeError> {
                 chars.next();
                 tokens.push(Token::RParen);
             }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
             '>' => {
                 chars.next();
                 if matches!(chars.peek(), Some('=')) {
@@ -421,6 +448,11 @@ fn tokenize(input: &str) -> Result<Vec<Token>, TokenizeError> {
                     "not" => Token::LogicalNot,
                     "true" => Token::Bool(true),
                     "false" => Token::Bool(false),
+                    "visits" => match scan_visits_call(&mut chars) {
+                        VisitsCall::NotACall => Token::Ident(name),
+                        VisitsCall::Path(path) => Token::Visits(path),
+                        VisitsCall::Malformed => return Err(TokenizeError::MalformedVisits),
+                    },
                     _ => Token::Ident(name),
                 };
                 tokens.push(token);
@@ -655,7 +687,10 @@ impl<'a> BooleanParser<'a> {
                 | Token::Star
                 | Token::Slash
                 | Token::Int(_)
-                | Token::Ident(_) => {}
+                | Token::Ident(_)
+                | Token::Visits(_)
+                | Token::Str(_)
+                | Token::Comma => {}
                 Token::LParen => depth += 1,
                 Token::RParen => {
                     if depth == 0 {
@@ -753,18 +788,34 @@ impl<'a> BooleanParser<'a> {
     }
 
     /// True when `expression` is a bool-typed leaf eligible for the
-    /// truthiness shortcut: a `true`/`false` literal or a reference to a
-    /// bool variable. Arithmetic combinations and int variables are not —
-    /// they keep their existing bare-operand error so int `req` diagnostics
-    /// are unchanged.
+    /// truthiness shortcut: a `true`/`false` literal, a reference to a
+    /// bool variable, or a call to a function returning a bool
+    /// (`req has_item("key")`). Arithmetic combinations and int variables
+    /// are not — they keep their existing bare-operand error so int `req`
+    /// diagnostics are unchanged.
     fn expression_is_bool(&self, expression: &Expression) -> bool {
         match expression {
             Expression::Literal(Value::Boolean(_)) => true,
             Expression::Variable(id) => self.resolver.kind_of(*id) == Some(ValueKind::Boolean),
+            Expression::Call { function, .. } => {
+                self.function_returns(*function) == Some(ValueKind::Boolean)
+            }
             _ => false,
         }
     }
 
+    /// The declared return kind of function `id`. Every call in the parsed
+    /// tokens was resolved by name, so the name is found among them.
+    fn function_returns(&self, id: FunctionId) -> Option<ValueKind> {
+        self.tokens.iter().find_map(|token| match token {
+            Token::Ident(name) => match self.resolver.function(name) {
+                Some((function, signature)) if function == id => signature.returns,
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
     /// If `left` is an enum variable and the next token is a bare identifier
     /// naming one of that enum's declared variants, consume it and return the
     /// corresponding enum literal. Otherwise leave the position untouched and
@@ -802,12 +853,14 @@ impl<'a> ArithmeticSource for BooleanParser<'a> {
             Token::Float(_) => Some(ArithmeticTokenKind::Float),
             Token::Str(_) => Some(ArithmeticTokenKind::Str),
             Token::Ident(_) => Some(ArithmeticTokenKind::Ident),
+            Token::Visits(_) => Some(ArithmeticTokenKind::Visits),
             Token::Plus => Some(ArithmeticTokenKind::Plus),
             Token::Minus => Some(ArithmeticTokenKind::Minus),
             Token::Star => Some(ArithmeticTokenKind::Star),
             Token::Slash => Some(ArithmeticTokenKind::Slash),
             Token::LParen => Some(ArithmeticTokenKind::LParen),
             Token::RParen => Some(ArithmeticTokenKind::RParen),
+            Token::Comma => Some(ArithmeticTokenKind::Comma),
             // Logical and comparison tokens aren't part of the arithmetic
             // sublanguage — surface them as end-of-stream so the shared
             // parser stops cleanly and the boolean parser resumes from
@@ -865,10 +918,27 @@ impl<'a> ArithmeticSource for BooleanParser<'a> {
         Some(value)
     }
 
+    fn take_visits(&mut self) -> Option<String> {
+        let Token::Visits(path) = self.tokens.get(self.position)? else {
+            return None;
+        };
+        let value = path.clone();
+        self.position += 1;
+        Some(value)
+    }
+
     fn resolve(&self, name: &str) -> Option<VariableId> {
         self.resolver.resolve(name)
     }
 
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        self.resolver.visits(path)
+    }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        self.resolver.function(name)
+    }
+
     // Trait-side recursion bookkeeping. Reuses the boolean parser's
     // `depth` field so boolean nesting (`not`, `(...)` boolean groups)
     // and arithmetic nesting (`---x`, `((x))` arith LHS) share a single
@@ -907,6 +977,7 @@ fn map_arithmetic_error(error: ArithmeticError) -> BooleanParseError {
         // boolean and arithmetic nesting share one budget, so one
         // diagnostic suffices.
         ArithmeticError::ExpressionTooDeep => BooleanParseError::ExpressionTooDeep,
+        ArithmeticError::Function(error) => BooleanParseError::Function(error),
     }
 }
 
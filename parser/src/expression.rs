@@ -13,12 +13,13 @@
 //! The boolean-condition parser ([`crate::boolean_expression`]) uses the
 //! same shared body to handle arithmetic operands of comparisons.
 
-use cuentitos_common::{Expression, VariableId};
+use cuentitos_common::{Expression, FunctionId, FunctionSignature, VariableId, VisitsId};
 
 use crate::arithmetic::{
-    parse_arithmetic_expression, ArithmeticError, ArithmeticSource, ArithmeticToken,
-    ArithmeticTokenKind,
+    parse_arithmetic_expression, parse_function_call, scan_visits_call, ArithmeticError,
+    ArithmeticSource, ArithmeticToken, ArithmeticTokenKind, FunctionCallError, VisitsCall,
 };
+use crate::string_literal::scan_quoted_body;
 
 /// Errors produced while parsing or resolving an expression at parse time.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,11 +38,26 @@ pub enum ParseExpressionError {
     /// the caller can surface a float-specific overflow message instead of
     /// the integer wording. Carries the offending literal text.
     FloatOverflow { literal: String },
+    /// A function call didn't match the declared functions.
+    Function(FunctionCallError),
 }
 
 /// Look up a declared variable by name.
 pub trait VariableResolver {
     fn resolve(&self, name: &str) -> Option<VariableId>;
+
+    /// Register the section path of a `visits(path)` read and return its
+    /// id. The default returns `None`, for contexts where visit counts
+    /// can't be read.
+    fn visits(&self, _path: &str) -> Option<VisitsId> {
+        None
+    }
+
+    /// Look up a declared function by name. The default returns `None`, for
+    /// contexts where functions can't be called.
+    fn function(&self, _name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        None
+    }
 }
 
 impl<F: Fn(&str) -> Option<VariableId>> VariableResolver for F {
@@ -55,13 +71,7 @@ pub fn parse_expression(
     input: &str,
     resolver: &dyn VariableResolver,
 ) -> Result<Expression, ParseExpressionError> {
-    let tokens = tokenize(input).map_err(|err| match err {
-        TokenizeError::Malformed => ParseExpressionError::Malformed,
-        TokenizeError::LiteralOverflow(literal) => ParseExpressionError::Overflow { literal },
-        TokenizeError::FloatLiteralOverflow(literal) => {
-            ParseExpressionError::FloatOverflow { literal }
-        }
-    })?;
+    let tokens = tokenize(input).map_err(map_tokenize_error)?;
     if tokens.is_empty() {
         return Err(ParseExpressionError::Malformed);
     }
@@ -78,6 +88,46 @@ pub fn parse_expression(
     Ok(expression)
 }
 
+/// Parse `input` as the `name(arguments)` of a `call` line, which may run
+/// a function without a return kind.
+pub fn parse_call(
+    input: &str,
+    resolver: &dyn VariableResolver,
+) -> Result<(FunctionId, Vec<Expression>), ParseExpressionError> {
+    let tokens = tokenize(input).map_err(map_tokenize_error)?;
+    let Some(ArithmeticToken::Ident(name)) = tokens.first() else {
+        return Err(ParseExpressionError::Malformed);
+    };
+    let mut source = SliceArithmeticSource {
+        tokens: &tokens,
+        position: 1,
+        resolver,
+        depth: 0,
+    };
+    let call =
+        parse_function_call(&mut source, name.clone(), false).map_err(map_arithmetic_error)?;
+    if source.position != tokens.len() {
+        return Err(ParseExpressionError::Malformed);
+    }
+    match call {
+        Expression::Call {
+            function,
+            arguments,
+        } => Ok((function, arguments)),
+        _ => unreachable!("parse_function_call only builds calls"),
+    }
+}
+
+fn map_tokenize_error(error: TokenizeError) -> ParseExpressionError {
+    match error {
+        TokenizeError::Malformed => ParseExpressionError::Malformed,
+        TokenizeError::LiteralOverflow(literal) => ParseExpressionError::Overflow { literal },
+        TokenizeError::FloatLiteralOverflow(literal) => {
+            ParseExpressionError::FloatOverflow { literal }
+        }
+    }
+}
+
 fn map_arithmetic_error(error: ArithmeticError) -> ParseExpressionError {
     match error {
         ArithmeticError::Malformed | ArithmeticError::UnbalancedParentheses => {
@@ -93,6 +143,7 @@ fn map_arithmetic_error(error: ArithmeticError) -> ParseExpressionError {
         // is generic. A follow-up can add a typed variant if the set
         // path ever sees depth-related authoring mistakes in the wild.
         ArithmeticError::ExpressionTooDeep => ParseExpressionError::Malformed,
+        ArithmeticError::Function(error) => ParseExpressionError::Function(error),
     }
 }
 
@@ -145,11 +196,9 @@ impl<'a> ArithmeticSource for SliceArithmeticSource<'a> {
         Some(value)
     }
 
-    // This source's tokenizer never emits a `Str` token — `set` lexes its
-    // string RHS literals on a dedicated path before reaching the shared
-    // arithmetic body — so `peek_kind` never yields `Str` and this is never
-    // called in practice. Implemented to satisfy the trait; the body mirrors
-    // the other `take_*` methods so it stays correct if that ever changes.
+    // `set` lexes a string RHS literal on a dedicated path before reaching
+    // the shared arithmetic body, so a `Str` token here is a function call
+    // argument (`has_item("key")`) or a literal that inference rejects.
     fn take_string(&mut self) -> Option<String> {
         let ArithmeticToken::Str(value) = self.tokens.get(self.position)? else {
             return None;
@@ -168,10 +217,27 @@ impl<'a> ArithmeticSource for SliceArithmeticSource<'a> {
         Some(value)
     }
 
+    fn take_visits(&mut self) -> Option<String> {
+        let ArithmeticToken::Visits(path) = self.tokens.get(self.position)? else {
+            return None;
+        };
+        let value = path.clone();
+        self.position += 1;
+        Some(value)
+    }
+
     fn resolve(&self, name: &str) -> Option<VariableId> {
         self.resolver.resolve(name)
     }
 
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        self.resolver.visits(path)
+    }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        self.resolver.function(name)
+    }
+
     fn enter_recursion(&mut self) -> Result<(), ArithmeticError> {
         self.depth += 1;
         if self.depth > crate::boolean_expression::MAX_EXPRESSION_DEPTH {
@@ -202,7 +268,8 @@ impl<'a> ArithmeticSource for SliceArithmeticSource<'a> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum TokenizeError {
     /// Catch-all for unrecognized symbols (anything that isn't a known
-    /// operator, paren, identifier-start, or digit).
+    /// operator, paren, comma, quote, identifier-start, or digit), and for
+    /// string literals that are unterminated or carry an invalid escape.
     Malformed,
     /// An integer literal exceeded `u64`. Carries the offending text so
     /// the caller can surface it as a literal-overflow diagnostic.
@@ -247,6 +314,15 @@ fn tokenize(input: &str) -> Result<Vec<ArithmeticToken>, TokenizeError> {
                 chars.next();
                 tokens.push(ArithmeticToken::RParen);
             }
+            ',' => {
+                chars.next();
+                tokens.push(ArithmeticToken::Comma);
+            }
+            '"' => {
+                chars.next();
+                let value = scan_quoted_body(&mut chars).map_err(|_| TokenizeError::Malformed)?;
+                tokens.push(ArithmeticToken::Str(value));
+            }
             c if c.is_ascii_digit() => {
                 let mut buf = String::new();
                 while let Some(&digit) = chars.peek() {
@@ -320,6 +396,11 @@ fn tokenize(input: &str) -> Result<Vec<ArithmeticToken>, TokenizeError> {
                 match buf.as_str() {
                     "true" => tokens.push(ArithmeticToken::Bool(true)),
                     "false" => tokens.push(ArithmeticToken::Bool(false)),
+                    "visits" => match scan_visits_call(&mut chars) {
+                        VisitsCall::NotACall => tokens.push(ArithmeticToken::Ident(buf)),
+                        VisitsCall::Path(path) => tokens.push(ArithmeticToken::Visits(path)),
+                        VisitsCall::Malformed => return Err(TokenizeError::Malformed),
+                    },
                     _ => tokens.push(ArithmeticToken::Ident(buf)),
                 }
             }
@@ -1,13 +1,22 @@
+use crate::arithmetic::FunctionCallError;
 use crate::parsers::{
     go_to_section_and_back_parser::GoToSectionAndBackParser,
-    go_to_section_parser::GoToSectionParser, line_parser::LineParser, option_parser::OptionParser,
-    section_parser::SectionParser, FeatureParser, ParserContext,
+    go_to_section_parser::GoToSectionParser,
+    line_parser::LineParser,
+    option_parser::{OptionKind, OptionParser},
+    section_parser::SectionParser,
+    FeatureParser, ParserContext,
 };
+use crate::project::Project;
+use cuentitos_common::localization::string_keys;
 use cuentitos_common::*;
 use std::collections::HashMap;
 use std::fmt;
 use std::path::PathBuf;
 
+/// Where something was first defined: its script, if any, and line.
+type FirstDefinition = (Option<PathBuf>, usize);
+
 #[derive(Debug, Clone)]
 pub struct Warning {
     pub message: String,
@@ -26,12 +35,23 @@ pub struct Parser {
     option_parser: OptionParser,
     go_to_section_and_back_parser: GoToSectionAndBackParser,
     go_to_section_parser: GoToSectionParser,
-    // Track section names by parent_id -> (name -> first_line_number)
-    section_names_by_parent: HashMap<Option<BlockId>, HashMap<String, usize>>,
-    // Track section ids by parent_id -> (id -> first_line_number)
-    section_ids_by_parent: HashMap<Option<BlockId>, HashMap<String, usize>>,
+    // Track section names by parent_id -> (name -> first definition)
+    section_names_by_parent: HashMap<Option<BlockId>, HashMap<String, FirstDefinition>>,
+    // Track section ids by parent_id -> (id -> first definition)
+    section_ids_by_parent: HashMap<Option<BlockId>, HashMap<String, FirstDefinition>>,
     // Track goto paths temporarily during parsing (BlockId -> path)
     goto_paths: HashMap<BlockId, String>,
+    // Where each `visits(path)` in `Database.visit_paths` was read: the block
+    // its line created and the line number, for resolving the path
+    visit_sites: Vec<(BlockId, usize)>,
+    // Track `#id:key` tags on text and option lines (key -> first_line_number)
+    string_keys: HashMap<String, usize>,
+    // Where each included script was read from, by `FileId`
+    include_paths: Vec<PathBuf>,
+    // Project manifest, when parsing a project's main script
+    project: Option<Project>,
+    // Functions the game declared, copied into every parsed database
+    functions: Vec<FunctionSignature>,
     // Collect errors instead of returning immediately
     errors: Vec<ParseError>,
     // Collect warnings
@@ -67,6 +87,9 @@ pub enum ParseError {
         parent_name: String,
         file: Option<PathBuf>,
         line: usize,
+        /// The script of the first definition, which an included script
+        /// can make different from `file`.
+        previous_file: Option<PathBuf>,
         previous_line: usize,
     },
     InvalidGoToSection {
@@ -515,6 +538,318 @@ pub enum ParseError {
         file: Option<PathBuf>,
         line: usize,
     },
+    /// A chance prefix (`(50)` / `(50%)`) made up the whole line, leaving no
+    /// block for it to apply to.
+    ChanceWithoutContent {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `(N)` chance weight exceeded the supported range. Carries the
+    /// offending digits.
+    ChanceWeightOverflow {
+        literal: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `(N%)` chance percentage was above 100. Carries the offending digits.
+    ChancePercentageOutOfRange {
+        percentage: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A chance prefix was put in front of a line that can't be picked at
+    /// random — an option, a `req`, a `freq`, or a section header.
+    ChanceOnUnsupportedBlock {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A chance group mixed `(N)` weights with `(N%)` percentages. `line`
+    /// points at the first member whose form differs from the group head.
+    MixedChanceKinds {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// The `(N%)` percentages of a chance group added up to more than 100.
+    /// `line` points at the group head.
+    ChancePercentagesExceedHundred {
+        total: u64,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `bucket` line named a refill policy other than `refill` or `once`.
+    /// Carries the offending text.
+    UnknownBucketPolicy {
+        policy: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `bucket` line had no cards nested under it.
+    EmptyBucket {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A chance prefix was put on a bucket card. Cards are drawn by the
+    /// bucket, so a roll of their own would fight the draw.
+    ChanceOnBucketCard {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A computed `(expression)` chance weight produced a non-integer kind.
+    NonIntegerChanceWeight {
+        found: ValueKind,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `freq` line had no expression, or one that doesn't parse. Carries
+    /// the offending text.
+    MalformedFrequencyModifier {
+        expression: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `freq` expression produced a non-integer kind.
+    NonIntegerFrequencyModifier {
+        found: ValueKind,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `freq` line wasn't nested under a line with a `(N)` or
+    /// `(expression)` weight, so there is no weight for it to change.
+    FrequencyWithoutWeight {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A second `[fallback]` option in the same menu. Points at the second
+    /// one.
+    MultipleFallbackOptions {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `{` in a text or option line with no matching `}`.
+    UnclosedInterpolation {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// Something other than a variable name between `{` and `}`. Carries
+    /// the text between the braces.
+    MalformedInterpolation {
+        text: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// `visits(path)` named `START`, `RESTART` or `END` instead of a
+    /// section. Carries the path as written.
+    VisitsOfNonSection {
+        path: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// An `include` that can't be followed: indented, without a quoted
+    /// path, of a script already included, or of a script declaring
+    /// variables.
+    InvalidInclude {
+        message: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// An `include` of a script that can't be read.
+    IncludeNotFound {
+        path: String,
+        message: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// An `include` of a script that is including it. Carries the file
+    /// names from the first script of the cycle back to it.
+    IncludeCycle {
+        cycle: Vec<String>,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A variable of the main script's `--- variables` block that the
+    /// project manifest already declares.
+    ProjectVariableRedeclared {
+        name: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A second line tagged with the same `#id:key`.
+    DuplicateStringKey {
+        key: String,
+        file: Option<PathBuf>,
+        line: usize,
+        previous_line: usize,
+    },
+    /// A call to a function that isn't declared, with the wrong number of
+    /// arguments, or without a return value where one is needed.
+    FunctionCall {
+        error: FunctionCallError,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `call` line that isn't `call name(arguments)`.
+    MalformedCall {
+        expression: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A literal in a `call` argument exceeded the integer range. Parallel
+    /// to [`SetLiteralOverflow`](Self::SetLiteralOverflow).
+    CallLiteralOverflow {
+        literal: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// The operands of an arithmetic operator in a `call` argument have
+    /// different kinds. Parallel to
+    /// [`RequirementTypeMismatch`](Self::RequirementTypeMismatch).
+    CallTypeMismatch {
+        left: ValueKind,
+        right: ValueKind,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `--- externals` block with no closing `---`. Parallel to
+    /// [`UnterminatedVariablesBlock`](Self::UnterminatedVariablesBlock).
+    UnterminatedExternalsBlock {
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A line of a `--- externals` block that isn't
+    /// `name(kinds) [-> kind]`.
+    MalformedExternalDeclaration {
+        content: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A `--- externals` declaration naming a kind other than `int`,
+    /// `float`, `bool` or `string`.
+    UnknownExternalKind {
+        kind: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A function declared with a reserved word (`and`, `or`, `not`,
+    /// `visits`) as its name.
+    ReservedFunctionName {
+        name: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A second declaration of the same function in a `--- externals`
+    /// block.
+    DuplicateExternal {
+        name: String,
+        previous_line: usize,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+}
+
+/// Map a condition error to the [`ParseError`] reported for it. `source` is
+/// the whole `req` line (or text fragment), quoted when the condition is
+/// missing.
+fn requirement_error(
+    err: crate::parsers::requirement_parser::RequirementParseError,
+    source: &str,
+    file: Option<PathBuf>,
+    line: usize,
+) -> ParseError {
+    use crate::parsers::requirement_parser::RequirementParseError;
+    match err {
+        RequirementParseError::UndefinedVariable { name } => {
+            ParseError::UndefinedVariableReference { name, file, line }
+        }
+        RequirementParseError::MalformedExpression { expression } => {
+            ParseError::MalformedRequirementExpression {
+                expression,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::MissingCondition => ParseError::MalformedRequirementExpression {
+            expression: source.to_string(),
+            file,
+            line,
+        },
+        RequirementParseError::UnknownSymbol { symbol } => {
+            ParseError::UnknownSymbolInRequirement { symbol, file, line }
+        }
+        RequirementParseError::TypeMismatch { left, right } => {
+            ParseError::RequirementTypeMismatch {
+                left,
+                right,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::ComparisonTypeMismatch {
+            left_kind,
+            left_token,
+            right_kind,
+            right_token,
+        } => ParseError::RequirementComparisonTypeMismatch {
+            left_kind,
+            left_token,
+            right_kind,
+            right_token,
+            file,
+            line,
+        },
+        RequirementParseError::NonOrderedComparison { operator, kind } => {
+            ParseError::NonOrderedComparison {
+                operator,
+                kind,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::NonNumericArithmetic { kind } => {
+            ParseError::NonNumericArithmetic { kind, file, line }
+        }
+        RequirementParseError::LogicalBareIntegerOperand { operator } => {
+            ParseError::LogicalBareIntegerOperand {
+                operator,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::LogicalBareIntegerOperandOfNot => {
+            ParseError::LogicalBareIntegerOperandOfNot { file, line }
+        }
+        RequirementParseError::LogicalMissingLeftOperand { operator, source } => {
+            ParseError::LogicalMissingLeftOperand {
+                operator,
+                source,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::LogicalMissingRightOperand { operator, source } => {
+            ParseError::LogicalMissingRightOperand {
+                operator,
+                source,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::LogicalMissingNotOperand { source } => {
+            ParseError::LogicalMissingNotOperand { source, file, line }
+        }
+        RequirementParseError::LogicalUnbalancedParentheses { source } => {
+            ParseError::UnbalancedParentheses { source, file, line }
+        }
+        RequirementParseError::LiteralOverflow { literal } => {
+            ParseError::RequirementLiteralOverflow {
+                literal,
+                file,
+                line,
+            }
+        }
+        RequirementParseError::ExpressionTooDeep => ParseError::ExpressionTooDeep { file, line },
+        RequirementParseError::DoubleEquals => ParseError::DoubleEqualsInRequirement { file, line },
+        RequirementParseError::Function(error) => ParseError::FunctionCall { error, file, line },
+    }
 }
 
 /// Render the prefix used in `Display` error lines: the script's file name, or
@@ -579,17 +914,27 @@ impl fmt::Display for ParseError {
                 parent_name,
                 file,
                 line,
+                previous_file,
                 previous_line,
             } => {
                 write!(
                     f,
-                    "{}:{}: ERROR: Duplicate section name: '{}' already exists at this level under '{}'. Previously defined at line {}.",
+                    "{}:{}: ERROR: Duplicate section name: '{}' already exists at this level under '{}'. ",
                     file_prefix(file),
                     line,
                     name,
-                    parent_name,
-                    previous_line
-                )
+                    parent_name
+                )?;
+                if previous_file == file {
+                    write!(f, "Previously defined at line {}.", previous_line)
+                } else {
+                    write!(
+                        f,
+                        "Previously defined at {}:{}.",
+                        file_prefix(previous_file),
+                        previous_line
+                    )
+                }
             }
             ParseError::InvalidGoToSection {
                 message,
@@ -1331,117 +1676,604 @@ impl fmt::Display for ParseError {
                     enum_name
                 )
             }
-        }
-    }
-}
-
-impl Parser {
-    pub fn new() -> Self {
-        Self {
-            line_parser: LineParser::new(),
-            section_parser: SectionParser::new(),
-            option_parser: OptionParser::new(),
-            go_to_section_parser: GoToSectionParser::new(),
-            ..Self::default()
-        }
-    }
-
-    pub fn with_file(file_path: PathBuf) -> Self {
-        Self {
-            file_path: Some(file_path),
-            line_parser: LineParser::new(),
-            section_parser: SectionParser::new(),
-            option_parser: OptionParser::new(),
-            go_to_section_and_back_parser: GoToSectionAndBackParser::new(),
-            go_to_section_parser: GoToSectionParser::new(),
-            ..Self::default()
-        }
-    }
-
-    /// Helper to collect an error and skip the current line
-    fn collect_error_and_skip(&mut self, error: ParseError, context: &mut ParserContext) {
-        self.errors.push(error);
-        context.current_line += 1;
-    }
-
-    fn mark_non_option_child(&mut self, parent_id: Option<BlockId>) {
-        if let Some(parent_id) = parent_id {
-            self.seen_non_option_by_parent.insert(parent_id, true);
-        }
-    }
-
-    pub fn parse<A>(&mut self, script: A) -> Result<(Database, Vec<Warning>), ParseError>
-    where
-        A: AsRef<str>,
-    {
-        // Clear state from previous parse
-        self.warnings.clear();
-        self.errors.clear();
-        self.last_block_at_level.clear();
-        self.last_section_at_level.clear();
-        self.seen_non_option_by_parent.clear();
-        self.section_names_by_parent.clear();
-        self.section_ids_by_parent.clear();
-        self.goto_paths.clear();
-
-        let mut context = if let Some(file_path) = &self.file_path {
-            ParserContext::with_file(file_path.clone())
-        } else {
-            ParserContext::new()
-        };
-
-        // Add Start block
-        let start_block = Block::new(BlockType::Start, None, 0);
-        let start_id = context.database.add_block(start_block);
-        self.last_block_at_level.push(start_id);
-        self.last_section_at_level.push(start_id); // Start can be parent of top-level sections
-
-        // Collect lines so we can consume a leading `--- variables` block without
-        // disturbing line-number accounting for the rest of the script.
-        let collected: Vec<&str> = script.as_ref().lines().collect();
-        let mut skip_until_index: usize = 0;
-
-        // Find the first non-empty, non-comment line. If it is `--- variables`,
-        // parse that block before the main pass. Only a leading variables block
-        // is supported; anything else is treated as regular content and a
-        // mid-file occurrence surfaces as a warning in the main pass.
-        for (i, line) in collected.iter().copied().enumerate() {
-            let trimmed = line.trim();
-            if trimmed.is_empty() || Self::is_comment(line) {
-                continue;
-            }
-            if trimmed == "--- variables" {
-                let outcome = crate::parsers::variables_parser::parse_variables_block(
-                    &collected,
-                    i,
-                    &mut context.database,
-                    &self.file_path,
-                );
-                // `consumed_lines` always covers the full block span (or the
-                // rest of the file when the block is unterminated), so the
-                // main pass can resume cleanly in both success and error
-                // cases.
-                skip_until_index = i + outcome.consumed_lines;
-                for error in outcome.errors {
-                    self.errors.push(error);
-                }
+            ParseError::ChanceWithoutContent { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance prefix without content.",
+                    file_prefix(file),
+                    line
+                )
             }
-            break;
-        }
-
-        // Iterate through each line, accounting for any block consumed above.
-        for (line_index, line) in collected.iter().copied().enumerate() {
-            if line_index < skip_until_index {
-                context.current_line += 1;
+            ParseError::ChanceWeightOverflow {
+                literal,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance weight out of range: {}.",
+                    file_prefix(file),
+                    line,
+                    literal
+                )
+            }
+            ParseError::ChancePercentageOutOfRange {
+                percentage,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance percentage must be between 0% and 100%, found {}%.",
+                    file_prefix(file),
+                    line,
+                    percentage
+                )
+            }
+            ParseError::ChanceOnUnsupportedBlock { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance prefixes can't be used on options, requirements, frequency modifiers or sections.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::MixedChanceKinds { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Can't mix chance weights '(N)' and percentages '(N%)' in the same group.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::ChancePercentagesExceedHundred { total, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance percentages add up to {}%, more than 100%.",
+                    file_prefix(file),
+                    line,
+                    total
+                )
+            }
+            ParseError::UnknownBucketPolicy { policy, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Unknown bucket policy: '{}'. Expected 'refill' or 'once'.",
+                    file_prefix(file),
+                    line,
+                    policy
+                )
+            }
+            ParseError::EmptyBucket { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Bucket without cards.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::ChanceOnBucketCard { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance prefixes can't be used on bucket cards.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::NonIntegerChanceWeight { found, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Chance weight must be an integer expression, found {}.",
+                    file_prefix(file),
+                    line,
+                    found
+                )
+            }
+            ParseError::MalformedFrequencyModifier {
+                expression,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Malformed frequency modifier: '{}'.",
+                    file_prefix(file),
+                    line,
+                    expression
+                )
+            }
+            ParseError::NonIntegerFrequencyModifier { found, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Frequency modifier must be an integer expression, found {}.",
+                    file_prefix(file),
+                    line,
+                    found
+                )
+            }
+            ParseError::FrequencyWithoutWeight { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: 'freq' can only be used under a line with a chance weight '(N)'.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::MultipleFallbackOptions { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: A menu can only have one '[fallback]' option.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::UnclosedInterpolation { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Unclosed '{{': missing '}}'. Write '{{{{' for a literal '{{'.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::MalformedInterpolation { text, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Expected a variable name between '{{' and '}}', found '{}'.",
+                    file_prefix(file),
+                    line,
+                    text
+                )
+            }
+            ParseError::VisitsOfNonSection { path, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: 'visits' needs a section, found '{}'.",
+                    file_prefix(file),
+                    line,
+                    path
+                )
+            }
+            ParseError::InvalidInclude {
+                message,
+                file,
+                line,
+            } => write!(f, "{}:{}: ERROR: {}", file_prefix(file), line, message),
+            ParseError::IncludeNotFound {
+                path,
+                message,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Can't read included script '{}': {}",
+                    file_prefix(file),
+                    line,
+                    path,
+                    message
+                )
+            }
+            ParseError::ProjectVariableRedeclared { name, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Variable '{}' is already declared in {}.",
+                    file_prefix(file),
+                    line,
+                    name,
+                    crate::project::MANIFEST_FILE
+                )
+            }
+            ParseError::IncludeCycle { cycle, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Include cycle: {}.",
+                    file_prefix(file),
+                    line,
+                    cycle.join(" -> ")
+                )
+            }
+            ParseError::DuplicateStringKey {
+                key,
+                file,
+                line,
+                previous_line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Duplicate string key: '#id:{}' is already used at line {}.",
+                    file_prefix(file),
+                    line,
+                    key,
+                    previous_line
+                )
+            }
+            ParseError::FunctionCall { error, file, line } => {
+                write!(f, "{}:{}: ERROR: {}", file_prefix(file), line, error)
+            }
+            ParseError::MalformedCall {
+                expression,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Malformed 'call' statement: '{}'. ('call' is reserved at the start of a line; rephrase to use it in narrative text.)",
+                    file_prefix(file),
+                    line,
+                    expression
+                )
+            }
+            ParseError::CallLiteralOverflow {
+                literal,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Integer overflow in 'call' argument: literal '{}' exceeds the integer range.",
+                    file_prefix(file),
+                    line,
+                    literal
+                )
+            }
+            ParseError::CallTypeMismatch {
+                left,
+                right,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Type mismatch in 'call' argument: left side has type {} but right side has type {}.",
+                    file_prefix(file),
+                    line,
+                    left,
+                    right
+                )
+            }
+            ParseError::UnterminatedExternalsBlock { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Unterminated '--- externals' block: missing closing '---'.",
+                    file_prefix(file),
+                    line
+                )
+            }
+            ParseError::MalformedExternalDeclaration {
+                content,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Malformed function declaration: '{}'. Expected '<name>(<kinds>) [-> <kind>]'.",
+                    file_prefix(file),
+                    line,
+                    content
+                )
+            }
+            ParseError::UnknownExternalKind { kind, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Unknown kind '{}' in function declaration. Expected 'int', 'float', 'bool' or 'string'.",
+                    file_prefix(file),
+                    line,
+                    kind
+                )
+            }
+            ParseError::ReservedFunctionName { name, file, line } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Reserved keyword '{}' cannot be used as a function name.",
+                    file_prefix(file),
+                    line,
+                    name
+                )
+            }
+            ParseError::DuplicateExternal {
+                name,
+                previous_line,
+                file,
+                line,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: ERROR: Duplicate function name: '{}' already declared. Previously declared at line {}.",
+                    file_prefix(file),
+                    line,
+                    name,
+                    previous_line
+                )
+            }
+        }
+    }
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self {
+            line_parser: LineParser::new(),
+            section_parser: SectionParser::new(),
+            option_parser: OptionParser::new(),
+            go_to_section_parser: GoToSectionParser::new(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_file(file_path: PathBuf) -> Self {
+        Self {
+            file_path: Some(file_path),
+            line_parser: LineParser::new(),
+            section_parser: SectionParser::new(),
+            option_parser: OptionParser::new(),
+            go_to_section_and_back_parser: GoToSectionAndBackParser::new(),
+            go_to_section_parser: GoToSectionParser::new(),
+            ..Self::default()
+        }
+    }
+
+    /// A parser for the main script of `project`, declaring the manifest's
+    /// variables and looking up `include`s in its source directories too.
+    pub fn with_project(project: &Project) -> Self {
+        Self {
+            project: Some(project.clone()),
+            ..Self::with_file(project.entry_path())
+        }
+    }
+
+    /// Declare a function the game provides, so scripts can call it. Calls
+    /// are checked against the declarations: a script calling anything else,
+    /// or passing another number of arguments, doesn't parse. Declaring a
+    /// name again replaces its signature.
+    pub fn declare_function(&mut self, signature: FunctionSignature) {
+        match self
+            .functions
+            .iter_mut()
+            .find(|function| function.name == signature.name)
+        {
+            Some(function) => *function = signature,
+            None => self.functions.push(signature),
+        }
+    }
+
+    /// Tie the `visits(path)` reads of the line just processed to the last
+    /// block it created, so the paths resolve relative to its section.
+    fn record_visit_sites(&mut self, context: &ParserContext) {
+        let block_id = context.database.blocks.len() - 1;
+        let line = context.current_line.saturating_sub(1);
+        let read = context.database.visit_paths.len() - self.visit_sites.len();
+        self.visit_sites
+            .extend(std::iter::repeat_n((block_id, line), read));
+    }
+
+    /// Record that the blocks from `first_block` on were read from the
+    /// included script `file`.
+    fn record_block_files(
+        &self,
+        context: &mut ParserContext,
+        first_block: BlockId,
+        file: Option<FileId>,
+    ) {
+        if let Some(file) = file {
+            for block_id in first_block..context.database.blocks.len() {
+                context.database.block_files.insert(block_id, file);
+            }
+        }
+    }
+
+    /// The script `block_id` was read from, for diagnostics raised once
+    /// the whole story is parsed.
+    fn block_file(&self, database: &Database, block_id: BlockId) -> Option<PathBuf> {
+        match database.block_files.get(&block_id) {
+            Some(&file) => Some(self.include_paths[file].clone()),
+            None => self.file_path.clone(),
+        }
+    }
+
+    /// Helper to collect an error and skip the current line
+    fn collect_error_and_skip(&mut self, error: ParseError, context: &mut ParserContext) {
+        self.errors.push(error);
+        context.current_line += 1;
+    }
+
+    /// Split the text of `string_id` into interpolation fragments, if it has
+    /// any. A bad interpolation is reported, but the line is still added so
+    /// the blocks nested under it keep their parent.
+    fn record_interpolation(&mut self, string_id: StringId, context: &mut ParserContext) {
+        use crate::parsers::interpolation_parser::{parse_interpolation, InterpolationParseError};
+        let text = context.database.strings[string_id].clone();
+        match parse_interpolation(&text, &mut context.database) {
+            Ok(Some(fragments)) => context.database.set_text_fragments(string_id, fragments),
+            Ok(None) => {}
+            Err(err) => {
+                let file = self.file_path.clone();
+                let line = context.current_line;
+                self.errors.push(match err {
+                    InterpolationParseError::Unclosed => {
+                        ParseError::UnclosedInterpolation { file, line }
+                    }
+                    InterpolationParseError::Malformed { text } => {
+                        ParseError::MalformedInterpolation { text, file, line }
+                    }
+                    InterpolationParseError::UndefinedVariable { name } => {
+                        ParseError::UndefinedVariableReference { name, file, line }
+                    }
+                    InterpolationParseError::Condition { error } => {
+                        requirement_error(error, &text, file, line)
+                    }
+                });
+            }
+        }
+    }
+
+    /// Record the `#id:key` tag of the line that created `string_id`.
+    fn record_string_key(
+        &mut self,
+        string_id: StringId,
+        key: Option<String>,
+        context: &mut ParserContext,
+    ) {
+        let Some(key) = key else {
+            return;
+        };
+        if let Some(&previous_line) = self.string_keys.get(&key) {
+            self.errors.push(ParseError::DuplicateStringKey {
+                key,
+                file: self.file_path.clone(),
+                line: context.current_line,
+                previous_line,
+            });
+            return;
+        }
+        self.string_keys.insert(key.clone(), context.current_line);
+        context.database.set_string_key(string_id, key);
+    }
+
+    fn mark_non_option_child(&mut self, parent_id: Option<BlockId>) {
+        if let Some(parent_id) = parent_id {
+            self.seen_non_option_by_parent.insert(parent_id, true);
+        }
+    }
+
+    pub fn parse<A>(&mut self, script: A) -> Result<(Database, Vec<Warning>), ParseError>
+    where
+        A: AsRef<str>,
+    {
+        // Clear state from previous parse
+        self.warnings.clear();
+        self.errors.clear();
+        self.last_block_at_level.clear();
+        self.last_section_at_level.clear();
+        self.seen_non_option_by_parent.clear();
+        self.section_names_by_parent.clear();
+        self.section_ids_by_parent.clear();
+        self.goto_paths.clear();
+        self.visit_sites.clear();
+        self.string_keys.clear();
+        self.include_paths.clear();
+
+        let mut context = if let Some(file_path) = &self.file_path {
+            ParserContext::with_file(file_path.clone())
+        } else {
+            ParserContext::new()
+        };
+
+        // Add Start block
+        let start_block = Block::new(BlockType::Start, None, 0);
+        let start_id = context.database.add_block(start_block);
+        self.last_block_at_level.push(start_id);
+        self.last_section_at_level.push(start_id); // Start can be parent of top-level sections
+
+        for signature in &self.functions {
+            context.database.declare_function(signature.clone());
+        }
+
+        // The manifest's variables come first, so the main script's block
+        // can use them.
+        if let Some(project) = &self.project {
+            self.errors
+                .extend(crate::parsers::variables_parser::parse_project_variables(
+                    &project.variables,
+                    &mut context.database,
+                    &Some(project.manifest_path()),
+                ));
+        }
+
+        // Collect lines so we can consume the leading `--- variables` and
+        // `--- externals` blocks without disturbing line-number accounting for
+        // the rest of the script.
+        let collected: Vec<&str> = script.as_ref().lines().collect();
+        let mut skip_until_index: usize = 0;
+
+        // Find the first non-empty, non-comment line. If it opens a
+        // `--- variables` or `--- externals` block, parse that block before
+        // the main pass and look again after it, so the two can come in
+        // either order. Only leading blocks are supported; anything else is
+        // treated as regular content and a mid-file occurrence surfaces as a
+        // warning in the main pass.
+        let mut seen_variables = false;
+        let mut seen_externals = false;
+        let mut i = 0;
+        while i < collected.len() {
+            let line = collected[i];
+            let trimmed = line.trim();
+            if trimmed.is_empty() || Self::is_comment(line) {
+                i += 1;
                 continue;
             }
-            // `--- variables` is only valid as the first directive in the
-            // file. Anything later is almost certainly a user mistake.
-            if line.trim() == "--- variables" {
+            // `consumed_lines` always covers the full block span (or the
+            // rest of the file when the block is unterminated), so the main
+            // pass can resume cleanly in both success and error cases.
+            let (consumed_lines, errors) = if trimmed == "--- variables" && !seen_variables {
+                seen_variables = true;
+                let outcome = crate::parsers::variables_parser::parse_variables_block(
+                    &collected,
+                    i,
+                    &mut context.database,
+                    &self.file_path,
+                );
+                (outcome.consumed_lines, outcome.errors)
+            } else if trimmed == "--- externals" && !seen_externals {
+                seen_externals = true;
+                let outcome = crate::parsers::externals_parser::parse_externals_block(
+                    &collected,
+                    i,
+                    &mut context.database,
+                    &self.file_path,
+                );
+                (outcome.consumed_lines, outcome.errors)
+            } else {
+                break;
+            };
+            i += consumed_lines;
+            skip_until_index = i;
+            self.errors.extend(errors);
+        }
+
+        // Expand `include`s after the variables block, so every line of the
+        // story comes with the script and line number it was read from.
+        let main_file = self.file_path.clone();
+        let source_roots = self
+            .project
+            .as_ref()
+            .map(Project::source_paths)
+            .unwrap_or_default();
+        let includes = crate::parsers::include_parser::expand_includes(
+            &collected,
+            skip_until_index,
+            &main_file,
+            &source_roots,
+        );
+        self.errors.extend(includes.errors);
+        for included in includes.files {
+            self.include_paths.push(included.path);
+            context.database.files.push(included.relative_path);
+        }
+
+        let mut current_file: Option<FileId> = None;
+        let mut first_block_of_line = context.database.blocks.len();
+        for source in &includes.lines {
+            self.record_visit_sites(&context);
+            self.record_block_files(&mut context, first_block_of_line, current_file);
+            first_block_of_line = context.database.blocks.len();
+            if source.file != current_file {
+                current_file = source.file;
+                self.file_path = match current_file {
+                    Some(file) => Some(self.include_paths[file].clone()),
+                    None => main_file.clone(),
+                };
+                context.file_path = self.file_path.clone();
+            }
+            context.current_line = source.line;
+            let line = source.text.as_str();
+            // `--- variables` and `--- externals` are only valid at the start
+            // of the file. Anything later is almost certainly a user mistake.
+            if matches!(line.trim(), "--- variables" | "--- externals") {
                 self.warnings.push(Warning {
-                    message:
-                        "'--- variables' block must appear at the start of the file; treating as content."
-                            .to_string(),
+                    message: format!(
+                        "'{}' block must appear at the start of the file; treating as content.",
+                        line.trim()
+                    ),
                     file: self.file_path.clone(),
                     line: context.current_line,
                 });
@@ -1531,7 +2363,9 @@ impl Parser {
                 // Check for duplicate section display names
                 let names_map = self.section_names_by_parent.entry(parent_id).or_default();
 
-                if let Some(&previous_line) = names_map.get(&section_result.display_name) {
+                if let Some((previous_file, previous_line)) =
+                    names_map.get(&section_result.display_name).cloned()
+                {
                     // Get parent's display name for error message
                     let parent_name = if let Some(pid) = parent_id {
                         if pid == start_id {
@@ -1556,6 +2390,7 @@ impl Parser {
                             parent_name,
                             file: self.file_path.clone(),
                             line: context.current_line,
+                            previous_file,
                             previous_line,
                         },
                         &mut context,
@@ -1564,11 +2399,16 @@ impl Parser {
                 }
 
                 // Record this section display name
-                names_map.insert(section_result.display_name.clone(), context.current_line);
+                names_map.insert(
+                    section_result.display_name.clone(),
+                    (self.file_path.clone(), context.current_line),
+                );
 
                 // Check for duplicate section ids
                 let ids_map = self.section_ids_by_parent.entry(parent_id).or_default();
-                if let Some(&previous_line) = ids_map.get(&section_result.id) {
+                if let Some((previous_file, previous_line)) =
+                    ids_map.get(&section_result.id).cloned()
+                {
                     let parent_name = if let Some(pid) = parent_id {
                         if pid == start_id {
                             "<root>".to_string()
@@ -1591,6 +2431,7 @@ impl Parser {
                             parent_name,
                             file: self.file_path.clone(),
                             line: context.current_line,
+                            previous_file,
                             previous_line,
                         },
                         &mut context,
@@ -1599,7 +2440,10 @@ impl Parser {
                 }
 
                 // Record this section id
-                ids_map.insert(section_result.id.clone(), context.current_line);
+                ids_map.insert(
+                    section_result.id.clone(),
+                    (self.file_path.clone(), context.current_line),
+                );
 
                 // Build the full display path for this section
                 let path_string = self.build_section_path_during_parse(
@@ -1664,6 +2508,65 @@ impl Parser {
                 // Mark that parent has seen a non-option child
                 self.mark_non_option_child(parent_id);
             } else {
+                // A leading `(N)` / `(N%)` marks this line's block as a member
+                // of a chance group. Split the prefix off so the rest of the
+                // line is dispatched exactly as if it had none; the Chance
+                // child is attached once the block exists (end of this branch).
+                let (chance, content) = match crate::parsers::chance_parser::split_chance_prefix(
+                    content.trim(),
+                    &mut context.database,
+                ) {
+                    Ok(Some((chance, rest))) => (Some(chance), rest),
+                    Ok(None) => (None, content),
+                    Err(chance_err) => {
+                        use crate::parsers::chance_parser::ChanceParseError;
+                        let file = self.file_path.clone();
+                        let line = context.current_line;
+                        let parse_error = match chance_err {
+                            ChanceParseError::MissingContent => {
+                                ParseError::ChanceWithoutContent { file, line }
+                            }
+                            ChanceParseError::WeightOverflow { literal } => {
+                                ParseError::ChanceWeightOverflow {
+                                    literal,
+                                    file,
+                                    line,
+                                }
+                            }
+                            ChanceParseError::PercentageOutOfRange { percentage } => {
+                                ParseError::ChancePercentageOutOfRange {
+                                    percentage,
+                                    file,
+                                    line,
+                                }
+                            }
+                            ChanceParseError::NonIntegerWeight { found } => {
+                                ParseError::NonIntegerChanceWeight { found, file, line }
+                            }
+                            ChanceParseError::Function(error) => {
+                                ParseError::FunctionCall { error, file, line }
+                            }
+                        };
+                        self.collect_error_and_skip(parse_error, &mut context);
+                        continue;
+                    }
+                };
+                if chance.is_some()
+                    && (SectionParser::is_section(content)
+                        || OptionParser::is_option_line(content)
+                        || Self::looks_like_requirement_line(content)
+                        || crate::parsers::frequency_parser::is_frequency_line(content))
+                {
+                    self.collect_error_and_skip(
+                        ParseError::ChanceOnUnsupportedBlock {
+                            file: self.file_path.clone(),
+                            line: context.current_line,
+                        },
+                        &mut context,
+                    );
+                    continue;
+                }
+
                 // Try to parse as go-to-section-and-back first (before go-to-section)
                 let go_to_and_back_result = match self
                     .go_to_section_and_back_parser
@@ -1808,7 +2711,7 @@ impl Parser {
                         // as parse-time errors.
                         match crate::parsers::set_parser::parse_set(
                             content.trim(),
-                            &context.database,
+                            &mut context.database,
                         ) {
                             Ok(parsed) => {
                                 let parent_id = match self.resolve_parent_id(
@@ -1994,11 +2897,88 @@ impl Parser {
                                             line: context.current_line,
                                         }
                                     }
+                                    SetParseError::Function(error) => ParseError::FunctionCall {
+                                        error,
+                                        file: self.file_path.clone(),
+                                        line: context.current_line,
+                                    },
                                 };
                                 self.collect_error_and_skip(parse_error, &mut context);
                                 continue;
                             }
                         }
+                    } else if Self::looks_like_call_line(content.trim()) {
+                        // `call name(arguments)` runs a function the game
+                        // provides. The function must be declared and the
+                        // argument count must match its signature.
+                        use crate::parsers::call_parser::{parse_call, CallParseError};
+                        let file = self.file_path.clone();
+                        let line = context.current_line;
+                        let call = match parse_call(content.trim(), &mut context.database) {
+                            Ok(call) => call,
+                            Err(err) => {
+                                let parse_error = match err {
+                                    CallParseError::MalformedCall { expression } => {
+                                        ParseError::MalformedCall {
+                                            expression,
+                                            file,
+                                            line,
+                                        }
+                                    }
+                                    CallParseError::UndefinedVariable { name } => {
+                                        ParseError::UndefinedVariableReference { name, file, line }
+                                    }
+                                    CallParseError::LiteralOverflow { literal } => {
+                                        ParseError::CallLiteralOverflow {
+                                            literal,
+                                            file,
+                                            line,
+                                        }
+                                    }
+                                    CallParseError::Function(error) => {
+                                        ParseError::FunctionCall { error, file, line }
+                                    }
+                                    CallParseError::TypeMismatch { left, right } => {
+                                        ParseError::CallTypeMismatch {
+                                            left,
+                                            right,
+                                            file,
+                                            line,
+                                        }
+                                    }
+                                    CallParseError::NonNumericArithmetic { kind } => {
+                                        ParseError::NonNumericArithmetic { kind, file, line }
+                                    }
+                                };
+                                self.collect_error_and_skip(parse_error, &mut context);
+                                continue;
+                            }
+                        };
+                        let parent_id =
+                            match self.resolve_parent_id(level, content, context.current_line) {
+                                Ok(parent_id) => parent_id,
+                                Err(err) => {
+                                    self.collect_error_and_skip(err, &mut context);
+                                    continue;
+                                }
+                            };
+
+                        let call_id = context.database.add_call(call);
+                        let block = Block::with_line(
+                            BlockType::Call(call_id),
+                            parent_id,
+                            level,
+                            context.current_line,
+                        );
+                        let block_id = context.database.add_block(block);
+
+                        if level >= self.last_block_at_level.len() {
+                            self.last_block_at_level.push(block_id);
+                        } else {
+                            self.last_block_at_level[level] = block_id;
+                        }
+
+                        self.mark_non_option_child(parent_id);
                     } else if Self::looks_like_requirement_line(content.trim()) {
                         // `req` is a parent-gating statement: parse the
                         // condition, resolve identifiers, and surface
@@ -2019,7 +2999,7 @@ impl Parser {
 
                         match crate::parsers::requirement_parser::parse_requirement(
                             content.trim(),
-                            &context.database,
+                            &mut context.database,
                         ) {
                             Ok(parsed) => {
                                 let parent_id = match self.resolve_parent_id(
@@ -2053,119 +3033,147 @@ impl Parser {
                                 self.mark_non_option_child(parent_id);
                             }
                             Err(requirement_err) => {
-                                use crate::parsers::requirement_parser::RequirementParseError;
+                                let parse_error = requirement_error(
+                                    requirement_err,
+                                    content.trim(),
+                                    self.file_path.clone(),
+                                    context.current_line,
+                                );
+                                self.collect_error_and_skip(parse_error, &mut context);
+                                continue;
+                            }
+                        }
+                    } else if crate::parsers::frequency_parser::is_frequency_line(content.trim()) {
+                        // `freq <expression>` changes the weight of the line
+                        // it's nested under each time that line is shown. At
+                        // level 0 there is no such line; whether the parent
+                        // carries a weight is checked once the tree is built.
+                        if level == 0 {
+                            self.collect_error_and_skip(
+                                ParseError::FrequencyWithoutWeight {
+                                    file: self.file_path.clone(),
+                                    line: context.current_line,
+                                },
+                                &mut context,
+                            );
+                            continue;
+                        }
+
+                        let expression = match crate::parsers::frequency_parser::parse_frequency(
+                            content.trim(),
+                            &mut context.database,
+                        ) {
+                            Ok(expression) => expression,
+                            Err(frequency_err) => {
+                                use crate::parsers::frequency_parser::FrequencyParseError;
                                 let file = self.file_path.clone();
                                 let line = context.current_line;
-                                let parse_error = match requirement_err {
-                                    RequirementParseError::UndefinedVariable { name } => {
-                                        ParseError::UndefinedVariableReference { name, file, line }
-                                    }
-                                    RequirementParseError::MalformedExpression { expression } => {
-                                        ParseError::MalformedRequirementExpression {
+                                let parse_error = match frequency_err {
+                                    FrequencyParseError::MalformedExpression { expression } => {
+                                        ParseError::MalformedFrequencyModifier {
                                             expression,
                                             file,
                                             line,
                                         }
                                     }
-                                    RequirementParseError::MissingCondition => {
-                                        ParseError::MalformedRequirementExpression {
-                                            expression: content.trim().to_string(),
-                                            file,
-                                            line,
-                                        }
-                                    }
-                                    RequirementParseError::UnknownSymbol { symbol } => {
-                                        ParseError::UnknownSymbolInRequirement {
-                                            symbol,
-                                            file,
-                                            line,
-                                        }
-                                    }
-                                    RequirementParseError::TypeMismatch { left, right } => {
-                                        ParseError::RequirementTypeMismatch {
-                                            left,
-                                            right,
-                                            file,
-                                            line,
-                                        }
-                                    }
-                                    RequirementParseError::ComparisonTypeMismatch {
-                                        left_kind,
-                                        left_token,
-                                        right_kind,
-                                        right_token,
-                                    } => ParseError::RequirementComparisonTypeMismatch {
-                                        left_kind,
-                                        left_token,
-                                        right_kind,
-                                        right_token,
-                                        file,
-                                        line,
-                                    },
-                                    RequirementParseError::NonOrderedComparison {
-                                        operator,
-                                        kind,
-                                    } => ParseError::NonOrderedComparison {
-                                        operator,
-                                        kind,
-                                        file,
-                                        line,
-                                    },
-                                    RequirementParseError::NonNumericArithmetic { kind } => {
-                                        ParseError::NonNumericArithmetic { kind, file, line }
-                                    }
-                                    RequirementParseError::LogicalBareIntegerOperand {
-                                        operator,
-                                    } => ParseError::LogicalBareIntegerOperand {
-                                        operator,
-                                        file,
-                                        line,
-                                    },
-                                    RequirementParseError::LogicalBareIntegerOperandOfNot => {
-                                        ParseError::LogicalBareIntegerOperandOfNot { file, line }
-                                    }
-                                    RequirementParseError::LogicalMissingLeftOperand {
-                                        operator,
-                                        source,
-                                    } => ParseError::LogicalMissingLeftOperand {
-                                        operator,
-                                        source,
-                                        file,
-                                        line,
-                                    },
-                                    RequirementParseError::LogicalMissingRightOperand {
-                                        operator,
-                                        source,
-                                    } => ParseError::LogicalMissingRightOperand {
-                                        operator,
-                                        source,
-                                        file,
-                                        line,
-                                    },
-                                    RequirementParseError::LogicalMissingNotOperand { source } => {
-                                        ParseError::LogicalMissingNotOperand { source, file, line }
+                                    FrequencyParseError::UndefinedVariable { name } => {
+                                        ParseError::UndefinedVariableReference { name, file, line }
                                     }
-                                    RequirementParseError::LogicalUnbalancedParentheses {
-                                        source,
-                                    } => ParseError::UnbalancedParentheses { source, file, line },
-                                    RequirementParseError::LiteralOverflow { literal } => {
-                                        ParseError::RequirementLiteralOverflow {
+                                    FrequencyParseError::LiteralOverflow { literal } => {
+                                        ParseError::ChanceWeightOverflow {
                                             literal,
                                             file,
                                             line,
                                         }
                                     }
-                                    RequirementParseError::ExpressionTooDeep => {
-                                        ParseError::ExpressionTooDeep { file, line }
+                                    FrequencyParseError::NonInteger { found } => {
+                                        ParseError::NonIntegerFrequencyModifier {
+                                            found,
+                                            file,
+                                            line,
+                                        }
                                     }
-                                    RequirementParseError::DoubleEquals => {
-                                        ParseError::DoubleEqualsInRequirement { file, line }
+                                    FrequencyParseError::Function(error) => {
+                                        ParseError::FunctionCall { error, file, line }
                                     }
                                 };
                                 self.collect_error_and_skip(parse_error, &mut context);
                                 continue;
                             }
+                        };
+                        let parent_id =
+                            match self.resolve_parent_id(level, content, context.current_line) {
+                                Ok(parent_id) => parent_id,
+                                Err(err) => {
+                                    self.collect_error_and_skip(err, &mut context);
+                                    continue;
+                                }
+                            };
+
+                        let frequency_id = context.database.add_frequency_modifier(expression);
+                        let block = Block::with_line(
+                            BlockType::Frequency(frequency_id),
+                            parent_id,
+                            level,
+                            context.current_line,
+                        );
+                        let block_id = context.database.add_block(block);
+
+                        if level >= self.last_block_at_level.len() {
+                            self.last_block_at_level.push(block_id);
+                        } else {
+                            self.last_block_at_level[level] = block_id;
+                        }
+
+                        self.mark_non_option_child(parent_id);
+                    } else if crate::parsers::bucket_parser::is_bucket_line(content.trim()) {
+                        // `bucket [refill|once]` opens a deck whose cards are
+                        // the blocks nested under it.
+                        let bucket = match crate::parsers::bucket_parser::parse_bucket(
+                            content.trim(),
+                        ) {
+                            Ok(bucket) => bucket,
+                            Err(
+                                crate::parsers::bucket_parser::BucketParseError::UnknownPolicy {
+                                    policy,
+                                },
+                            ) => {
+                                self.collect_error_and_skip(
+                                    ParseError::UnknownBucketPolicy {
+                                        policy,
+                                        file: self.file_path.clone(),
+                                        line: context.current_line,
+                                    },
+                                    &mut context,
+                                );
+                                continue;
+                            }
+                        };
+                        let parent_id =
+                            match self.resolve_parent_id(level, content, context.current_line) {
+                                Ok(parent_id) => parent_id,
+                                Err(err) => {
+                                    self.collect_error_and_skip(err, &mut context);
+                                    continue;
+                                }
+                            };
+
+                        let bucket_id = context.database.add_bucket(bucket);
+                        let block = Block::with_line(
+                            BlockType::Bucket(bucket_id),
+                            parent_id,
+                            level,
+                            context.current_line,
+                        );
+                        let block_id = context.database.add_block(block);
+
+                        if level >= self.last_block_at_level.len() {
+                            self.last_block_at_level.push(block_id);
+                        } else {
+                            self.last_block_at_level[level] = block_id;
                         }
+
+                        self.mark_non_option_child(parent_id);
                     } else if OptionParser::is_option_line(content.trim()) {
                         // Parse as option
                         let result = match self.option_parser.parse(content.trim(), &mut context) {
@@ -2245,8 +3253,29 @@ impl Parser {
                             continue;
                         }
 
+                        // A menu takes at most one fallback. Options can't
+                        // follow other siblings, so every option child of
+                        // the parent belongs to this menu.
+                        if result.kind == OptionKind::Fallback
+                            && context.database.blocks[parent_id]
+                                .children
+                                .iter()
+                                .any(|&child_id| context.database.is_fallback_option(child_id))
+                        {
+                            self.collect_error_and_skip(
+                                ParseError::MultipleFallbackOptions {
+                                    file: self.file_path.clone(),
+                                    line: context.current_line,
+                                },
+                                &mut context,
+                            );
+                            continue;
+                        }
+
                         // Create option block
                         let string_id = context.database.add_string(result.text);
+                        self.record_interpolation(string_id, &mut context);
+                        self.record_string_key(string_id, result.key, &mut context);
                         let block = Block::with_line(
                             BlockType::Option(string_id),
                             Some(parent_id),
@@ -2254,6 +3283,11 @@ impl Parser {
                             context.current_line,
                         );
                         let block_id = context.database.add_block(block);
+                        match result.kind {
+                            OptionKind::Sticky => {}
+                            OptionKind::Once => context.database.mark_once_only_option(block_id),
+                            OptionKind::Fallback => context.database.mark_fallback_option(block_id),
+                        }
 
                         // Update last block at this level
                         if self.last_block_at_level.len() > level {
@@ -2276,6 +3310,8 @@ impl Parser {
 
                         // Create new block
                         let string_id = context.database.add_string(result.string);
+                        self.record_interpolation(string_id, &mut context);
+                        self.record_string_key(string_id, result.key, &mut context);
                         let block = Block::with_line(
                             BlockType::String(string_id),
                             parent_id,
@@ -2295,11 +3331,29 @@ impl Parser {
                         self.mark_non_option_child(parent_id);
                     }
                 }
+
+                // Every branch above that didn't `continue` left the block it
+                // created as the last block at this level.
+                if let Some(chance) = chance {
+                    let gated_block_id = self.last_block_at_level[level];
+                    let chance_id = context.database.add_chance(chance);
+                    let block = Block::with_line(
+                        BlockType::Chance(chance_id),
+                        Some(gated_block_id),
+                        level + 1,
+                        context.current_line,
+                    );
+                    context.database.add_block(block);
+                }
             }
 
             // Increment line counter after processing each non-empty line
             context.current_line += 1;
         }
+        self.record_visit_sites(&context);
+        self.record_block_files(&mut context, first_block_of_line, current_file);
+        self.file_path = main_file;
+        context.file_path = self.file_path.clone();
 
         // Add End block (with no parent)
         let end_block = Block::new(BlockType::End, None, 0);
@@ -2308,6 +3362,10 @@ impl Parser {
         // Run compile-time validation pass
         self.validate_and_resolve(&mut context)?;
 
+        // Every other translatable string gets a key derived from its
+        // section and text, now that every section is known.
+        context.database.string_keys = string_keys(&context.database).into_iter().collect();
+
         // Check if we collected any errors
         if !self.errors.is_empty() {
             if self.errors.len() == 1 {
@@ -2337,6 +3395,13 @@ impl Parser {
         crate::parsers::set_parser::is_set_line(content)
     }
 
+    /// Cheap pre-filter: does this trimmed line begin with the `call` keyword?
+    /// Lines that pass through here are then handed to
+    /// `call_parser::parse_call` for full validation.
+    fn looks_like_call_line(content: &str) -> bool {
+        crate::parsers::call_parser::is_call_line(content)
+    }
+
     /// Cheap pre-filter: does this trimmed line begin with the `req` keyword?
     /// Lines that pass through here are then handed to
     /// `requirement_parser::parse_requirement` for full validation.
@@ -2426,6 +3491,11 @@ impl Parser {
             self.detect_empty_sections(&context.database)?;
         }
 
+        self.validate_chance_groups(&context.database);
+        self.validate_buckets(&context.database);
+        self.validate_frequency_modifiers(&context.database);
+        self.resolve_visit_paths(context, &section_registry);
+
         // Collect GoTo and GoToAndBack blocks first to avoid borrow checker issues
         let goto_blocks: Vec<(BlockId, String, usize, bool)> = context
             .database
@@ -2451,11 +3521,13 @@ impl Parser {
             let containing_section = self.find_containing_section(&context.database, block_id);
 
             // Resolve the path
+            let file = self.block_file(&context.database, block_id);
             match self.resolve_path(
                 &path,
                 containing_section,
                 &section_registry,
                 &context.database,
+                &file,
                 line,
             ) {
                 Ok(resolved_type) => {
@@ -2476,7 +3548,7 @@ impl Parser {
                                         context.database.blocks[target_block_id].line;
                                     self.errors.push(ParseError::EmptySection {
                                         name: section_name.clone(),
-                                        file: self.file_path.clone(),
+                                        file: self.block_file(&context.database, target_block_id),
                                         line: section_line,
                                     });
                                 }
@@ -2487,7 +3559,7 @@ impl Parser {
                                 self.warnings.push(Warning {
                                     message: "<-> START will not return (restarts from beginning)"
                                         .to_string(),
-                                    file: self.file_path.clone(),
+                                    file: file.clone(),
                                     line,
                                 });
                                 BlockType::GoToStart
@@ -2495,7 +3567,7 @@ impl Parser {
                             BlockType::GoToRestart => {
                                 self.warnings.push(Warning {
                                     message: "<-> RESTART will not return (clears state and restarts from beginning)".to_string(),
-                                    file: self.file_path.clone(),
+                                    file: file.clone(),
                                     line,
                                 });
                                 BlockType::GoToRestart
@@ -2504,7 +3576,7 @@ impl Parser {
                                 self.warnings.push(Warning {
                                     message: "<-> END will not return (just end execution)"
                                         .to_string(),
-                                    file: self.file_path.clone(),
+                                    file: file.clone(),
                                     line,
                                 });
                                 BlockType::GoToEnd
@@ -2531,6 +3603,38 @@ impl Parser {
         Ok(())
     }
 
+    /// Resolve every `visits(path)` read to the section it counts, relative
+    /// to the section containing the line that read it.
+    fn resolve_visit_paths(
+        &mut self,
+        context: &mut ParserContext,
+        section_registry: &HashMap<String, SectionId>,
+    ) {
+        let mut targets = Vec::with_capacity(self.visit_sites.len());
+        let sites = std::mem::take(&mut self.visit_sites);
+        for (path, (block_id, line)) in context.database.visit_paths.iter().zip(sites) {
+            let containing_section = self.find_containing_section(&context.database, block_id);
+            let file = self.block_file(&context.database, block_id);
+            match self.resolve_path(
+                path,
+                containing_section,
+                section_registry,
+                &context.database,
+                &file,
+                line,
+            ) {
+                Ok(BlockType::GoTo(section_id)) => targets.push(section_id),
+                Ok(_) => self.errors.push(ParseError::VisitsOfNonSection {
+                    path: path.clone(),
+                    file,
+                    line,
+                }),
+                Err(e) => self.errors.push(e),
+            }
+        }
+        context.database.visit_targets = targets;
+    }
+
     /// Build the full hierarchical path for a section during parsing
     ///
     /// Uses last_section_at_level to build the path from parent sections.
@@ -2693,6 +3797,7 @@ impl Parser {
         containing_section: Option<BlockId>,
         registry: &HashMap<String, SectionId>,
         database: &Database,
+        file: &Option<PathBuf>,
         line: usize,
     ) -> Result<BlockType, ParseError> {
         let path = path.trim();
@@ -2716,7 +3821,7 @@ impl Parser {
             }
             return Err(ParseError::SectionNotFound {
                 path: path.to_string(),
-                file: self.file_path.clone(),
+                file: file.clone(),
                 line,
             });
         }
@@ -2777,13 +3882,13 @@ impl Parser {
                     current_section = self.find_parent_section(database, section_id);
                     if current_section.is_none() {
                         return Err(ParseError::NavigationAboveRoot {
-                            file: self.file_path.clone(),
+                            file: file.clone(),
                             line,
                         });
                     }
                 } else {
                     return Err(ParseError::NavigationAboveRoot {
-                        file: self.file_path.clone(),
+                        file: file.clone(),
                         line,
                     });
                 }
@@ -2832,7 +3937,7 @@ impl Parser {
 
         Err(ParseError::SectionNotFound {
             path: path.to_string(),
-            file: self.file_path.clone(),
+            file: file.clone(),
             line,
         })
     }
@@ -2907,7 +4012,7 @@ impl Parser {
 
     /// Validate that section names don't contain backslash and aren't reserved words
     fn validate_section_names(&mut self, database: &Database) -> Result<(), ParseError> {
-        for block in database.blocks.iter() {
+        for (block_id, block) in database.blocks.iter().enumerate() {
             if let BlockType::Section(section_id) = &block.block_type {
                 let section = &database.sections[*section_id];
                 let display_name = &database.strings[section.name];
@@ -2930,7 +4035,7 @@ impl Parser {
                             self.errors.push(ParseError::InvalidSectionName {
                                 message: format!("{} \"{}\" is reserved", label, name),
                                 name: name.clone(),
-                                file: self.file_path.clone(),
+                                file: self.block_file(database, block_id),
                                 line: block.line,
                             });
                         }
@@ -2942,7 +4047,7 @@ impl Parser {
                         self.errors.push(ParseError::InvalidSectionName {
                             message: format!("{}s cannot contain '\\' character", label),
                             name: name.clone(),
-                            file: self.file_path.clone(),
+                            file: self.block_file(database, block_id),
                             line: block.line,
                         });
                     }
@@ -2952,6 +4057,100 @@ impl Parser {
         Ok(())
     }
 
+    /// Validate every chance group: its members must all use the same form
+    /// (`(N)` weights or `(N%)` percentages), and percentages must not add up
+    /// to more than 100.
+    fn validate_chance_groups(&mut self, database: &Database) {
+        for (block_id, block) in database.blocks.iter().enumerate() {
+            let Some(head_chance_id) = database.chance_id(block_id) else {
+                continue;
+            };
+            let group = database.chance_group(block_id);
+            // Visit each group once, from its head.
+            if group.first() != Some(&block_id) {
+                continue;
+            }
+
+            let head_chance = &database.chances[head_chance_id];
+            let mixed_member = group.iter().find(|&&member_id| {
+                database.chance_id(member_id).is_some_and(|id| {
+                    database.chances[id].is_percentage() != head_chance.is_percentage()
+                })
+            });
+            if let Some(&member_id) = mixed_member {
+                self.errors.push(ParseError::MixedChanceKinds {
+                    file: self.block_file(database, member_id),
+                    line: database.blocks[member_id].line,
+                });
+                continue;
+            }
+
+            if head_chance.is_percentage() {
+                let total: u64 = group
+                    .iter()
+                    .filter_map(|&member_id| database.chance_id(member_id))
+                    .filter_map(|id| database.chances[id].value())
+                    .map(u64::from)
+                    .sum();
+                if total > 100 {
+                    self.errors
+                        .push(ParseError::ChancePercentagesExceedHundred {
+                            total,
+                            file: self.block_file(database, block_id),
+                            line: block.line,
+                        });
+                }
+            }
+        }
+    }
+
+    /// Validate every bucket: it must have at least one card, and its cards
+    /// can't carry chance prefixes of their own.
+    fn validate_buckets(&mut self, database: &Database) {
+        for (block_id, block) in database.blocks.iter().enumerate() {
+            if !matches!(block.block_type, BlockType::Bucket(_)) {
+                continue;
+            }
+            let cards = database.bucket_cards(block_id);
+            if cards.is_empty() {
+                self.errors.push(ParseError::EmptyBucket {
+                    file: self.block_file(database, block_id),
+                    line: block.line,
+                });
+                continue;
+            }
+            for card_id in cards {
+                if database.chance_id(card_id).is_some() {
+                    self.errors.push(ParseError::ChanceOnBucketCard {
+                        file: self.block_file(database, card_id),
+                        line: database.blocks[card_id].line,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Validate every `freq` line: the line it's nested under must carry a
+    /// weight, `(N)` or `(expression)`. Percentages are fixed odds, so they
+    /// have no weight to change.
+    fn validate_frequency_modifiers(&mut self, database: &Database) {
+        for (block_id, block) in database.blocks.iter().enumerate() {
+            if !matches!(block.block_type, BlockType::Frequency(_)) {
+                continue;
+            }
+            let weighted = block
+                .parent_id
+                .and_then(|parent_id| database.chance_id(parent_id))
+                .is_some_and(|chance_id| !database.chances[chance_id].is_percentage());
+            if !weighted {
+                self.errors.push(ParseError::FrequencyWithoutWeight {
+                    file: self.block_file(database, block_id),
+                    line: block.line,
+                });
+            }
+        }
+    }
+
     /// Detect empty sections
     fn detect_empty_sections(&mut self, database: &Database) -> Result<(), ParseError> {
         for (block_id, block) in database.blocks.iter().enumerate() {
@@ -2965,7 +4164,7 @@ impl Parser {
                 if !has_content {
                     self.errors.push(ParseError::EmptySection {
                         name: display_name.clone(),
-                        file: self.file_path.clone(),
+                        file: self.block_file(database, block_id),
                         line: block.line,
                     });
                 }
@@ -2974,12 +4173,14 @@ impl Parser {
         Ok(())
     }
 
-    /// Check if a section has any content (String, Set, or Goto blocks), recursively
+    /// Check if a section has any content (String, Set, Call, or Goto blocks), recursively
     fn section_has_content(database: &Database, section_id: BlockId) -> bool {
         for &child_id in &database.blocks[section_id].children {
             match &database.blocks[child_id].block_type {
                 BlockType::String(_)
                 | BlockType::Set(_)
+                | BlockType::Call(_)
+                | BlockType::Bucket(_)
                 | BlockType::GoTo(_)
                 | BlockType::GoToAndBack(_)
                 | BlockType::GoToStart
@@ -3024,6 +4225,20 @@ impl Parser {
     fn detect_unreachable_code(&mut self, database: &Database, goto_block_id: BlockId) {
         let goto_block = &database.blocks[goto_block_id];
 
+        // A chance-prefixed jump may not be taken, so whatever follows it is
+        // still reachable. Its only child is the Chance marker itself.
+        if database.chance_id(goto_block_id).is_some() {
+            return;
+        }
+
+        // Likewise a jump drawn as a bucket card: the other cards are drawn
+        // on later visits.
+        if goto_block.parent_id.is_some_and(|parent_id| {
+            matches!(database.blocks[parent_id].block_type, BlockType::Bucket(_))
+        }) {
+            return;
+        }
+
         // Check for sibling blocks after this one
         if let Some(parent_id) = goto_block.parent_id {
             let parent = &database.blocks[parent_id];
@@ -3036,7 +4251,7 @@ impl Parser {
                     if !matches!(sibling.block_type, BlockType::Section { .. }) {
                         self.warnings.push(Warning {
                             message: "Unreachable code after section jump".to_string(),
-                            file: self.file_path.clone(),
+                            file: self.block_file(database, sibling_id),
                             line: sibling.line,
                         });
                     }
@@ -3051,7 +4266,7 @@ impl Parser {
             if !matches!(child.block_type, BlockType::Section { .. }) {
                 self.warnings.push(Warning {
                     message: "Unreachable code after section jump".to_string(),
-                    file: self.file_path.clone(),
+                    file: self.block_file(database, child_id),
                     line: child.line,
                 });
             }
@@ -3617,4 +4832,443 @@ mod test {
         let warning = matched.expect("expected mid-file '--- variables' warning");
         assert_eq!(warning.line, 3, "warning should point at line 3");
     }
+
+    #[test]
+    fn chance_prefix_attaches_chance_marker_child() {
+        let script = "(50) Heads\n  It's heads.\n(50%) Tails";
+        let mut parser = Parser::new();
+        let result = parser.parse(script);
+        assert!(matches!(
+            result,
+            Err(ParseError::MixedChanceKinds { line: 3, .. })
+        ));
+
+        let script = "(50) Heads\n  It's heads.\n(25) Tails";
+        let (database, _warnings) = parser.parse(script).unwrap();
+
+        // START, Heads, Chance, It's heads., Tails, Chance, END
+        assert_eq!(database.blocks[1].block_type, BlockType::String(0));
+        assert_eq!(database.blocks[2].block_type, BlockType::Chance(0));
+        assert_eq!(database.blocks[2].parent_id, Some(1));
+        assert_eq!(database.blocks[1].children, vec![2, 3]);
+        assert_eq!(database.strings[0], "Heads");
+        assert_eq!(
+            database.chances,
+            vec![Chance::Weight(50), Chance::Weight(25)]
+        );
+        assert_eq!(database.chance_group(1), &[1, 4]);
+    }
+
+    #[test]
+    fn chance_prefixed_goto_does_not_warn_about_unreachable_siblings() {
+        let script = "(1) -> a\n(1) -> b\n# a\nA\n# b\nB";
+        let mut parser = Parser::new();
+        let (_database, warnings) = parser.parse(script).unwrap();
+        assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+    }
+
+    #[test]
+    fn bucket_line_adds_bucket_block_with_cards() {
+        let script = "bucket once\n  Ace.\n  King.\n    req false\nAfter.";
+        let mut parser = Parser::new();
+        let (database, _warnings) = parser.parse(script).unwrap();
+
+        // START, bucket, Ace., King., Requirement, After., END
+        assert_eq!(database.blocks[1].block_type, BlockType::Bucket(0));
+        assert_eq!(database.buckets, vec![Bucket::new(RefillPolicy::Once)]);
+        assert_eq!(database.blocks[1].children, vec![2, 3]);
+        assert_eq!(database.bucket_cards(1), vec![2, 3]);
+    }
+
+    #[test]
+    fn once_marker_is_recorded_per_option() {
+        let script = "Pick.\n  * [once] Gem\n  * [sticky] Look\n  * Leave";
+        let mut parser = Parser::new();
+        let (database, _warnings) = parser.parse(script).unwrap();
+
+        // START, Pick., Gem, Look, Leave, END
+        assert_eq!(database.strings[1], "Gem");
+        assert!(database.is_once_only_option(2));
+        assert!(!database.is_once_only_option(3));
+        assert!(!database.is_once_only_option(4));
+    }
+
+    #[test]
+    fn second_fallback_in_a_menu_is_an_error() {
+        let mut parser = Parser::new();
+        let (database, _warnings) = parser
+            .parse("Pick.\n  * Stay\n  * [fallback]\n    Gone.")
+            .unwrap();
+        assert!(database.is_fallback_option(3));
+        assert!(matches!(
+            parser.parse("Pick.\n  * [fallback]\n  * Stay\n  * [fallback] Again"),
+            Err(ParseError::MultipleFallbackOptions { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn bucket_errors_are_reported() {
+        let mut parser = Parser::new();
+        assert!(matches!(
+            parser.parse("bucket forever\n  Ace."),
+            Err(ParseError::UnknownBucketPolicy { line: 1, .. })
+        ));
+        assert!(matches!(
+            parser.parse("bucket\nAfter."),
+            Err(ParseError::EmptyBucket { line: 1, .. })
+        ));
+        assert!(matches!(
+            parser.parse("bucket\n  Ace.\n  (10) King."),
+            Err(ParseError::ChanceOnBucketCard { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn freq_line_attaches_frequency_marker_to_weighted_parent() {
+        let script = "--- variables\nint fear = 0\n---\n(1 + fear) Scream.\n  freq 2";
+        let mut parser = Parser::new();
+        let (database, _warnings) = parser.parse(script).unwrap();
+
+        // START, Scream, Chance, Frequency, END
+        assert!(matches!(
+            database.chances[0],
+            Chance::Computed { ref source, .. } if source == "1 + fear"
+        ));
+        assert_eq!(database.blocks[3].block_type, BlockType::Frequency(0));
+        assert_eq!(database.blocks[3].parent_id, Some(1));
+        assert_eq!(
+            database.frequency_modifiers,
+            vec![Expression::Literal(Value::Integer(2))]
+        );
+    }
+
+    #[test]
+    fn freq_errors_are_reported() {
+        let mut parser = Parser::new();
+        assert!(matches!(
+            parser.parse("(50%) Bird.\n  freq 1"),
+            Err(ParseError::FrequencyWithoutWeight { line: 2, .. })
+        ));
+        assert!(matches!(
+            parser.parse("freq 1"),
+            Err(ParseError::FrequencyWithoutWeight { line: 1, .. })
+        ));
+        assert!(matches!(
+            parser.parse("(1) Bird.\n  freq"),
+            Err(ParseError::MalformedFrequencyModifier { line: 2, .. })
+        ));
+        assert!(matches!(
+            parser.parse("--- variables\nfloat mood = 1.0\n---\n(mood) Smile."),
+            Err(ParseError::NonIntegerChanceWeight { line: 4, .. })
+        ));
+    }
+
+    fn parser_with_functions() -> Parser {
+        let mut parser = Parser::new();
+        parser.declare_function(FunctionSignature::new(
+            "play_sound",
+            &[ValueKind::String],
+            None,
+        ));
+        parser.declare_function(FunctionSignature::new(
+            "has_item",
+            &[ValueKind::String],
+            Some(ValueKind::Boolean),
+        ));
+        parser.declare_function(FunctionSignature::new(
+            "coins",
+            &[],
+            Some(ValueKind::Integer),
+        ));
+        parser
+    }
+
+    #[test]
+    fn call_line_and_function_reads_are_parsed() {
+        let script = "--- variables\nint gold = 0\n---\nYou knock.\n  call play_sound(\"door\")\n  req has_item(\"key\")\n  set gold = coins() + 1\n(coins()) Ring.";
+        let (database, _warnings) = parser_with_functions().parse(script).unwrap();
+
+        assert_eq!(database.functions.len(), 3);
+        // START, knock, call, req, set, Ring, chance, END
+        assert_eq!(database.blocks[2].block_type, BlockType::Call(0));
+        assert_eq!(database.blocks[2].parent_id, Some(1));
+        assert_eq!(
+            database.calls[0],
+            CallStatement::new(
+                0,
+                vec![Expression::Literal(Value::String("door".to_string()))]
+            )
+        );
+        assert_eq!(
+            database.sets[0].expression,
+            Expression::Binary {
+                left: Box::new(Expression::Call {
+                    function: 2,
+                    arguments: vec![]
+                }),
+                operator: BinaryOperator::Add,
+                right: Box::new(Expression::Literal(Value::Integer(1))),
+            }
+        );
+        assert!(matches!(
+            database.chances[0],
+            Chance::Computed { ref source, .. } if source == "coins()"
+        ));
+    }
+
+    #[test]
+    fn function_call_errors_are_reported() {
+        let mut parser = parser_with_functions();
+        let error = parser.parse("Hi.\n  call open_door()").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "<script>:2: ERROR: Unknown function 'open_door'."
+        );
+        let error = parser.parse("Hi.\n  req has_item()").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "<script>:2: ERROR: Function 'has_item' takes 1 argument, found 0."
+        );
+        let error = parser
+            .parse(
+                "--- variables\nbool heard = false\n---\nHi.\n  set heard = play_sound(\"door\")",
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "<script>:5: ERROR: Function 'play_sound' doesn't return a value, run it with 'call' instead."
+        );
+        assert!(matches!(
+            parser.parse("Hi.\n  call play_sound"),
+            Err(ParseError::MalformedCall { line: 2, .. })
+        ));
+        // Without the declaration, a chance prefix that looks like a call
+        // stays part of the text.
+        let (database, _warnings) = Parser::new().parse("(coins()) Ring.").unwrap();
+        assert!(database.chances.is_empty());
+    }
+
+    #[test]
+    fn externals_block_declares_functions_for_the_script() {
+        let script = "--- externals\nhas_item(string) -> bool\n---\n--- variables\nint gold = 0\n---\nLocked.\n  req has_item(\"key\")";
+        let (database, _warnings) = Parser::new().parse(script).unwrap();
+        assert_eq!(
+            database.functions,
+            vec![FunctionSignature::new(
+                "has_item",
+                &[ValueKind::String],
+                Some(ValueKind::Boolean)
+            )]
+        );
+        assert_eq!(database.variables.len(), 1);
+        // START, Locked, req, END
+        assert_eq!(database.blocks.len(), 4);
+
+        let (_, warnings) = Parser::new()
+            .parse("Hi.\n--- externals\nnow() -> int\n---")
+            .unwrap();
+        assert!(warnings.iter().any(|warning| warning
+            .message
+            .contains("'--- externals' block must appear at the start")));
+    }
+
+    #[test]
+    fn call_arguments_are_type_checked() {
+        let mut parser = parser_with_functions();
+        let error = parser.parse("Hi.\n  call play_sound(3)").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "<script>:2: ERROR: Function 'play_sound' takes string as argument 1, found integer."
+        );
+        let error = parser
+            .parse("--- variables\nbool lit = true\n---\nHi.\n  req has_item(lit)")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "<script>:5: ERROR: Function 'has_item' takes string as argument 1, found boolean."
+        );
+        assert!(matches!(
+            parser.parse("(coins() + has_item(1)) Ring."),
+            Err(ParseError::FunctionCall {
+                error: FunctionCallError::ArgumentKind { position: 1, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn string_keys_are_recorded() {
+        let mut parser = Parser::new();
+        let (database, _) = parser
+            .parse("# hub: Hub\n  Welcome. #id:welcome\n    * Leave #id:leave\n  Room #12\n  You win! #win\n  You win! #win")
+            .unwrap();
+        let key = |text: &str| {
+            let id = database.strings.iter().position(|s| s == text).unwrap();
+            database.string_keys[&id].clone()
+        };
+        assert_eq!(key("Welcome."), "welcome");
+        assert_eq!(key("Leave"), "leave");
+        assert_eq!(key("Hub"), "hub:name");
+        assert!(key("Room #12").starts_with("hub:"));
+        assert!(key("You win! #win").starts_with("hub:"));
+    }
+
+    #[test]
+    fn duplicate_string_keys_are_reported() {
+        let mut parser = Parser::new();
+        let error = parser
+            .parse("Hello. #id:greeting\nHi. #id:greeting")
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::DuplicateStringKey {
+                line: 2,
+                previous_line: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "<script>:2: ERROR: Duplicate string key: '#id:greeting' is already used at line 1."
+        );
+    }
+
+    /// Write `files` to a fresh directory and parse its `main.cuentitos`.
+    fn parse_files(name: &str, files: &[(&str, &str)]) -> Result<Database, ParseError> {
+        let dir = std::env::temp_dir().join(format!("cuentitos-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("chapters")).unwrap();
+        for (path, contents) in files {
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+        let main = dir.join("main.cuentitos");
+        let script = std::fs::read_to_string(&main).unwrap();
+        let result = Parser::with_file(main).parse(script);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.map(|(database, _)| database)
+    }
+
+    #[test]
+    fn included_scripts_are_merged_with_their_own_lines() {
+        let database = parse_files(
+            "include",
+            &[
+                (
+                    "main.cuentitos",
+                    "--- variables\nint gold = 1\n---\n# hub: Hub\n  -> cave\ninclude \"chapters/cave.cuentitos\"",
+                ),
+                (
+                    "chapters/cave.cuentitos",
+                    "// The cave\n# cave: Cave\n  You have {gold} coins.\n  include \"../nowhere\"",
+                ),
+            ],
+        );
+        assert!(matches!(
+            &database,
+            Err(ParseError::InvalidInclude { file: Some(file), line: 4, .. })
+                if file.ends_with("chapters/cave.cuentitos")
+        ));
+
+        let database = parse_files(
+            "include-ok",
+            &[
+                (
+                    "main.cuentitos",
+                    "--- variables\nint gold = 1\n---\n# hub: Hub\n  -> cave\ninclude \"chapters/cave.cuentitos\"",
+                ),
+                (
+                    "chapters/cave.cuentitos",
+                    "// The cave\n# cave: Cave\n  You have {gold} coins.\n  -> hub",
+                ),
+            ],
+        )
+        .unwrap();
+        assert_eq!(database.files, ["chapters/cave.cuentitos"]);
+        let cave = database.section_registry["cave"];
+        let block_id = database.sections[cave].block_id;
+        assert_eq!(database.blocks[block_id].line, 2);
+        assert_eq!(
+            database.block_file(block_id),
+            Some("chapters/cave.cuentitos")
+        );
+        assert_eq!(database.blocks[block_id + 1].line, 3);
+        assert_eq!(database.block_file(block_id - 1), None);
+    }
+
+    #[test]
+    fn include_errors_point_at_the_right_script() {
+        let error = parse_files(
+            "include-missing",
+            &[(
+                "main.cuentitos",
+                "Start.\ninclude \"chapters/none.cuentitos\"",
+            )],
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::IncludeNotFound { ref path, line: 2, .. } if path == "chapters/none.cuentitos"
+        ));
+
+        let error = parse_files(
+            "include-cycle",
+            &[
+                ("main.cuentitos", "Start.\ninclude \"chapters/a.cuentitos\""),
+                ("chapters/a.cuentitos", "A.\ninclude \"b.cuentitos\""),
+                ("chapters/b.cuentitos", "B.\ninclude \"a.cuentitos\""),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "b.cuentitos:2: ERROR: Include cycle: a.cuentitos -> b.cuentitos -> a.cuentitos."
+        );
+
+        let error = parse_files(
+            "include-twice",
+            &[
+                (
+                    "main.cuentitos",
+                    "include \"chapters/a.cuentitos\"\ninclude \"chapters/a.cuentitos\"",
+                ),
+                ("chapters/a.cuentitos", "A."),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "main.cuentitos:2: ERROR: 'chapters/a.cuentitos' is already included."
+        );
+
+        let error = parse_files(
+            "include-errors",
+            &[
+                ("main.cuentitos", "include \"chapters/a.cuentitos\""),
+                (
+                    "chapters/a.cuentitos",
+                    "# a: A\n  Hi.\n    * Bye\n  (10%) Huh.\n  (2) Eh.",
+                ),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a.cuentitos:5: ERROR: Can't mix chance weights '(N)' and percentages '(N%)' in the same group."
+        );
+
+        let error = parse_files(
+            "include-duplicate",
+            &[
+                (
+                    "main.cuentitos",
+                    "# cave: Cave\n  Dark.\ninclude \"chapters/a.cuentitos\"",
+                ),
+                ("chapters/a.cuentitos", "// Again\n# cave: Cave\n  Damp."),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a.cuentitos:2: ERROR: Duplicate section name: 'Cave' already exists at this level under '<root>'. Previously defined at main.cuentitos:1."
+        );
+    }
 }
@@ -0,0 +1,90 @@
+//! Parser for `bucket` lines.
+//!
+//! A bucket is declared by a line holding the `bucket` keyword, optionally
+//! followed by its refill policy:
+//!
+//! - `bucket` or `bucket refill`: refill once every card has been drawn.
+//! - `bucket once`: fall through once every card has been drawn.
+//!
+//! The cards are the blocks nested under the line; the main parser attaches
+//! them as children like it does for any other parent.
+
+use cuentitos_common::{Bucket, RefillPolicy};
+
+/// Errors specific to parsing a `bucket` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketParseError {
+    /// The word after `bucket` wasn't a known refill policy. Carries the
+    /// offending text.
+    UnknownPolicy { policy: String },
+}
+
+/// Cheap pre-filter: does this trimmed line begin with the `bucket` keyword?
+/// Like `set` and `req`, any line starting with the keyword followed by
+/// whitespace is claimed, so a misspelled policy is reported instead of
+/// silently rendering as text.
+pub fn is_bucket_line(content: &str) -> bool {
+    content == "bucket"
+        || content
+            .strip_prefix("bucket")
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_whitespace()))
+}
+
+/// Parse a `bucket` line (already trimmed of indentation).
+pub fn parse_bucket(content: &str) -> Result<Bucket, BucketParseError> {
+    let policy = content.strip_prefix("bucket").unwrap_or(content).trim();
+    let refill = match policy {
+        "" | "refill" => RefillPolicy::Refill,
+        "once" => RefillPolicy::Once,
+        other => {
+            return Err(BucketParseError::UnknownPolicy {
+                policy: other.to_string(),
+            })
+        }
+    };
+    Ok(Bucket::new(refill))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_bucket_lines() {
+        assert!(is_bucket_line("bucket"));
+        assert!(is_bucket_line("bucket once"));
+        assert!(is_bucket_line("bucket\trefill"));
+        assert!(!is_bucket_line("buckets of rain"));
+        assert!(!is_bucket_line("A bucket"));
+    }
+
+    #[test]
+    fn bare_bucket_refills() {
+        assert_eq!(
+            parse_bucket("bucket"),
+            Ok(Bucket::new(RefillPolicy::Refill))
+        );
+        assert_eq!(
+            parse_bucket("bucket refill"),
+            Ok(Bucket::new(RefillPolicy::Refill))
+        );
+    }
+
+    #[test]
+    fn once_policy_falls_through() {
+        assert_eq!(
+            parse_bucket("bucket   once"),
+            Ok(Bucket::new(RefillPolicy::Once))
+        );
+    }
+
+    #[test]
+    fn unknown_policy_is_an_error() {
+        assert_eq!(
+            parse_bucket("bucket of water"),
+            Err(BucketParseError::UnknownPolicy {
+                policy: "of water".to_string()
+            })
+        );
+    }
+}
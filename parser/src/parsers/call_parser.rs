@@ -0,0 +1,213 @@
+//! Parser for `call` lines.
+//!
+//! A `call` line runs a function the game provides, for its side effects:
+//!
+//! ```cuentitos
+//! You knock on the door.
+//!   call play_sound("door")
+//! ```
+//!
+//! The function must be declared, and the call must pass as many arguments
+//! as the declaration has parameters. Unlike a call read in an expression,
+//! the function doesn't have to return anything; whatever it returns is
+//! dropped.
+
+use cuentitos_common::{
+    CallStatement, Database, FunctionId, FunctionSignature, ValueKind, VariableId, VisitsId,
+};
+
+use crate::arithmetic::FunctionCallError;
+use crate::expression::{
+    parse_call as parse_call_expression, ParseExpressionError, VariableResolver,
+};
+use crate::parsers::type_inference::{check_arguments, TypeInferenceError};
+use crate::parsers::visit_paths::VisitPaths;
+
+/// Errors specific to parsing a `call` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallParseError {
+    /// Nothing after `call`, or something other than `name(arguments)`.
+    /// Carries the offending text.
+    MalformedCall { expression: String },
+    /// An argument named an undeclared variable.
+    UndefinedVariable { name: String },
+    /// A literal in an argument exceeded the integer range.
+    LiteralOverflow { literal: String },
+    /// The function isn't declared, takes another number of arguments, or
+    /// takes arguments of other kinds.
+    Function(FunctionCallError),
+    /// The operands of an arithmetic operator inside an argument have
+    /// different kinds.
+    TypeMismatch { left: ValueKind, right: ValueKind },
+    /// An arithmetic operator inside an argument was applied to a
+    /// non-numeric kind.
+    NonNumericArithmetic { kind: ValueKind },
+}
+
+/// Cheap pre-filter: does this trimmed line begin with the `call` keyword?
+/// Like `set` and `req`, the keyword is reserved at the start of a line, so
+/// a malformed call is reported instead of rendering as text.
+pub fn is_call_line(content: &str) -> bool {
+    content == "call"
+        || content
+            .strip_prefix("call")
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_whitespace()))
+}
+
+/// Parse a `call` line (already trimmed of indentation).
+pub fn parse_call(content: &str, database: &mut Database) -> Result<CallStatement, CallParseError> {
+    let rest = content.strip_prefix("call").unwrap_or(content).trim();
+    let visit_paths = VisitPaths::new(database);
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: &visit_paths,
+    };
+    let (function, arguments) =
+        parse_call_expression(rest, &resolver).map_err(|err| match err {
+            ParseExpressionError::UndefinedVariable { name } => {
+                CallParseError::UndefinedVariable { name }
+            }
+            ParseExpressionError::Overflow { literal } => {
+                CallParseError::LiteralOverflow { literal }
+            }
+            ParseExpressionError::Function(error) => CallParseError::Function(error),
+            ParseExpressionError::Malformed | ParseExpressionError::FloatOverflow { .. } => {
+                CallParseError::MalformedCall {
+                    expression: content.to_string(),
+                }
+            }
+        })?;
+    check_arguments(function, &arguments, database).map_err(|err| match err {
+        TypeInferenceError::Function(error) => CallParseError::Function(error),
+        TypeInferenceError::Mismatch { left, right, .. } => {
+            CallParseError::TypeMismatch { left, right }
+        }
+        TypeInferenceError::NonNumericArithmetic { kind, .. } => {
+            CallParseError::NonNumericArithmetic { kind }
+        }
+    })?;
+    visit_paths.commit(database);
+    Ok(CallStatement::new(function, arguments))
+}
+
+struct DatabaseResolver<'a> {
+    database: &'a Database,
+    visit_paths: &'a VisitPaths,
+}
+
+impl VariableResolver for DatabaseResolver<'_> {
+    fn resolve(&self, name: &str) -> Option<VariableId> {
+        self.database.variable_id(name)
+    }
+
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        Some(self.visit_paths.register(path))
+    }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        let id = self.database.function_id(name)?;
+        Some((id, self.database.functions[id].clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuentitos_common::{Expression, Value, Variable};
+
+    fn database() -> Database {
+        let mut database = Database::new();
+        database.add_variable(Variable::new_integer("gold", 3));
+        database.declare_function(FunctionSignature::new(
+            "play_sound",
+            &[ValueKind::String],
+            None,
+        ));
+        database.declare_function(FunctionSignature::new(
+            "give",
+            &[ValueKind::String, ValueKind::Integer],
+            Some(ValueKind::Boolean),
+        ));
+        database
+    }
+
+    #[test]
+    fn claims_keyword_lines_only() {
+        assert!(is_call_line("call play_sound(\"door\")"));
+        assert!(is_call_line("call"));
+        assert!(!is_call_line("called it"));
+        assert!(!is_call_line("Call me later."));
+    }
+
+    #[test]
+    fn parses_arguments_in_order() {
+        let mut database = database();
+        assert_eq!(
+            parse_call("call play_sound(\"door\")", &mut database),
+            Ok(CallStatement::new(
+                0,
+                vec![Expression::Literal(Value::String("door".to_string()))]
+            ))
+        );
+        let call = parse_call("call give( \"coin\" , gold + 1 )", &mut database).unwrap();
+        assert_eq!(call.function, 1);
+        assert_eq!(call.arguments.len(), 2);
+        assert_eq!(
+            call.arguments[0],
+            Expression::Literal(Value::String("coin".to_string()))
+        );
+    }
+
+    #[test]
+    fn reports_unknown_functions_and_wrong_arity() {
+        let mut database = database();
+        assert_eq!(
+            parse_call("call open_door()", &mut database),
+            Err(CallParseError::Function(FunctionCallError::Unknown {
+                name: "open_door".to_string()
+            }))
+        );
+        assert_eq!(
+            parse_call("call give(\"coin\")", &mut database),
+            Err(CallParseError::Function(FunctionCallError::ArgumentCount {
+                name: "give".to_string(),
+                expected: 2,
+                found: 1
+            }))
+        );
+        assert_eq!(
+            parse_call("call play_sound", &mut database),
+            Err(CallParseError::MalformedCall {
+                expression: "call play_sound".to_string()
+            })
+        );
+        assert_eq!(
+            parse_call("call play_sound(\"door\") now", &mut database),
+            Err(CallParseError::MalformedCall {
+                expression: "call play_sound(\"door\") now".to_string()
+            })
+        );
+        assert_eq!(
+            parse_call("call play_sound(noise)", &mut database),
+            Err(CallParseError::UndefinedVariable {
+                name: "noise".to_string()
+            })
+        );
+        assert_eq!(
+            parse_call("call give(\"coin\", \"two\")", &mut database),
+            Err(CallParseError::Function(FunctionCallError::ArgumentKind {
+                name: "give".to_string(),
+                position: 2,
+                expected: ValueKind::Integer,
+                found: ValueKind::String,
+            }))
+        );
+        assert_eq!(
+            parse_call("call give(\"coin\", gold + 1.5)", &mut database),
+            Err(CallParseError::TypeMismatch {
+                left: ValueKind::Integer,
+                right: ValueKind::Float,
+            })
+        );
+    }
+}
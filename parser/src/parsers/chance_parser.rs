@@ -0,0 +1,338 @@
+//! Parser for `(N)`, `(N%)` and `(expression)` chance prefixes.
+//!
+//! A chance prefix sits in front of an otherwise ordinary line and marks the
+//! block that line produces as a member of a chance group (see
+//! [`cuentitos_common::chance`]). This module only splits the prefix off;
+//! the main parser dispatches the remainder as usual and attaches a
+//! [`cuentitos_common::BlockType::Chance`] child to the resulting block.
+//!
+//! Anything that doesn't look like `(<digits>)` or `(<digits>%)` followed
+//! by whitespace is left alone, so text such as `(whispering) Hello` still
+//! renders verbatim. The one exception is a calculation over declared
+//! variables, `(10 + fear * 2)`: it becomes a computed weight, and so does
+//! one reading a visit count, `(1 + visits(cave))`. A parenthetical that
+//! doesn't parse, names an undeclared identifier or reads neither stays
+//! plain text.
+
+use cuentitos_common::{
+    Chance, Database, Expression, FunctionId, FunctionSignature, ValueKind, VariableId, VisitsId,
+};
+
+use crate::arithmetic::FunctionCallError;
+use crate::expression::{parse_expression, ParseExpressionError, VariableResolver};
+use crate::parsers::type_inference::infer_type;
+use crate::parsers::visit_paths::VisitPaths;
+
+/// Errors specific to parsing a chance prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChanceParseError {
+    /// The prefix was the whole line: `(50)` with nothing to gate.
+    MissingContent,
+    /// The weight didn't fit a `u32`. Carries the offending digits.
+    WeightOverflow { literal: String },
+    /// A percentage above 100.
+    PercentageOutOfRange { percentage: String },
+    /// A computed weight produced a value of a kind other than integer.
+    NonIntegerWeight { found: ValueKind },
+    /// A computed weight called a declared function the wrong way.
+    Function(FunctionCallError),
+}
+
+/// Split a leading chance prefix off `content` (already trimmed of
+/// indentation). Returns `Ok(None)` when the line carries no prefix, or the
+/// parsed [`Chance`] together with the rest of the line. `database` resolves
+/// the variables of a computed weight.
+pub fn split_chance_prefix<'c>(
+    content: &'c str,
+    database: &mut Database,
+) -> Result<Option<(Chance, &'c str)>, ChanceParseError> {
+    let Some(inner_and_rest) = content.strip_prefix('(') else {
+        return Ok(None);
+    };
+    let Some(close) = matching_close(inner_and_rest) else {
+        return Ok(None);
+    };
+    let inner = &inner_and_rest[..close];
+    let rest = &inner_and_rest[close + 1..];
+
+    let (digits, is_percentage) = match inner.strip_suffix('%') {
+        Some(digits) => (digits, true),
+        None => (inner, false),
+    };
+    let is_literal = !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+    let computed = if is_literal || is_percentage {
+        None
+    } else {
+        match parse_computed_weight(inner, database)? {
+            Some(computed) => Some(computed),
+            None => return Ok(None),
+        }
+    };
+    if !is_literal && computed.is_none() {
+        return Ok(None);
+    }
+
+    // The prefix must be separated from the content by whitespace; `(1)st`
+    // stays plain text.
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return Ok(None);
+    }
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Err(ChanceParseError::MissingContent);
+    }
+
+    if let Some((expression, visit_paths)) = computed {
+        visit_paths.commit(database);
+        let chance = Chance::Computed {
+            expression,
+            source: inner.trim().to_string(),
+        };
+        return Ok(Some((chance, rest)));
+    }
+
+    let Ok(value) = digits.parse::<u32>() else {
+        return Err(if is_percentage {
+            ChanceParseError::PercentageOutOfRange {
+                percentage: digits.to_string(),
+            }
+        } else {
+            ChanceParseError::WeightOverflow {
+                literal: digits.to_string(),
+            }
+        });
+    };
+
+    let chance = if is_percentage {
+        if value > 100 {
+            return Err(ChanceParseError::PercentageOutOfRange {
+                percentage: digits.to_string(),
+            });
+        }
+        Chance::Percentage(value)
+    } else {
+        Chance::Weight(value)
+    };
+
+    Ok(Some((chance, rest)))
+}
+
+/// Byte offset of the `)` closing the parenthesis opened just before
+/// `input`, skipping over nested pairs.
+fn matching_close(input: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, byte) in input.bytes().enumerate() {
+        match byte {
+            b'(' => depth += 1,
+            b')' if depth == 0 => return Some(index),
+            b')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the inside of a parenthetical as a computed weight. `Ok(None)`
+/// means "not a weight, leave the line as text": it doesn't parse, names an
+/// undeclared identifier, or reads no variable or visit count. The visit
+/// paths it read are only committed once the whole prefix is accepted.
+fn parse_computed_weight(
+    inner: &str,
+    database: &Database,
+) -> Result<Option<(Expression, VisitPaths)>, ChanceParseError> {
+    let visit_paths = VisitPaths::new(database);
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: &visit_paths,
+    };
+    let expression = match parse_expression(inner, &resolver) {
+        Ok(expression) => expression,
+        Err(ParseExpressionError::Overflow { literal }) => {
+            return Err(ChanceParseError::WeightOverflow { literal })
+        }
+        // An unknown name followed by `(` is prose like any other, but a
+        // declared function called the wrong way is a mistake to report.
+        Err(ParseExpressionError::Function(FunctionCallError::Unknown { .. })) => return Ok(None),
+        Err(ParseExpressionError::Function(error)) => {
+            return Err(ChanceParseError::Function(error))
+        }
+        Err(_) => return Ok(None),
+    };
+    if !reads_variable(&expression) {
+        return Ok(None);
+    }
+    match infer_type(&expression, database) {
+        Ok(ValueKind::Integer) => Ok(Some((expression, visit_paths))),
+        Ok(found) => Err(ChanceParseError::NonIntegerWeight { found }),
+        Err(err) => Err(match err.non_integer_kind() {
+            Ok(found) => ChanceParseError::NonIntegerWeight { found },
+            Err(error) => ChanceParseError::Function(error),
+        }),
+    }
+}
+
+fn reads_variable(expression: &Expression) -> bool {
+    match expression {
+        Expression::Literal(_) => false,
+        Expression::Variable(_) | Expression::Visits(_) | Expression::Call { .. } => true,
+        Expression::Binary { left, right, .. } => reads_variable(left) || reads_variable(right),
+    }
+}
+
+struct DatabaseResolver<'a> {
+    database: &'a Database,
+    visit_paths: &'a VisitPaths,
+}
+
+impl VariableResolver for DatabaseResolver<'_> {
+    fn resolve(&self, name: &str) -> Option<VariableId> {
+        self.database.variable_id(name)
+    }
+
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        Some(self.visit_paths.register(path))
+    }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        let id = self.database.function_id(name)?;
+        Some((id, self.database.functions[id].clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_weight_prefix() {
+        assert_eq!(
+            split_chance_prefix("(50) I open the door", &mut Database::default()),
+            Ok(Some((Chance::Weight(50), "I open the door")))
+        );
+    }
+
+    #[test]
+    fn splits_percentage_prefix() {
+        assert_eq!(
+            split_chance_prefix("(25%) A bird sings", &mut Database::default()),
+            Ok(Some((Chance::Percentage(25), "A bird sings")))
+        );
+    }
+
+    #[test]
+    fn keeps_the_rest_of_the_line_intact() {
+        assert_eq!(
+            split_chance_prefix("(1) -> cave", &mut Database::default()),
+            Ok(Some((Chance::Weight(1), "-> cave")))
+        );
+    }
+
+    #[test]
+    fn non_numeric_parenthetical_is_plain_text() {
+        assert_eq!(
+            split_chance_prefix("(whispering) Hello", &mut Database::default()),
+            Ok(None)
+        );
+        assert_eq!(
+            split_chance_prefix("() Hello", &mut Database::default()),
+            Ok(None)
+        );
+        assert_eq!(
+            split_chance_prefix("(%) Hello", &mut Database::default()),
+            Ok(None)
+        );
+        assert_eq!(
+            split_chance_prefix("(-5) Hello", &mut Database::default()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn prefix_without_whitespace_is_plain_text() {
+        assert_eq!(
+            split_chance_prefix("(1)st place", &mut Database::default()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn lines_without_parenthesis_are_ignored() {
+        assert_eq!(
+            split_chance_prefix("Plain text (50)", &mut Database::default()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn prefix_alone_is_an_error() {
+        assert_eq!(
+            split_chance_prefix("(50)", &mut Database::default()),
+            Err(ChanceParseError::MissingContent)
+        );
+        assert_eq!(
+            split_chance_prefix("(50%)   ", &mut Database::default()),
+            Err(ChanceParseError::MissingContent)
+        );
+    }
+
+    #[test]
+    fn percentage_above_hundred_is_an_error() {
+        assert_eq!(
+            split_chance_prefix("(101%) Too likely", &mut Database::default()),
+            Err(ChanceParseError::PercentageOutOfRange {
+                percentage: "101".to_string()
+            })
+        );
+    }
+
+    fn database_with(declarations: &str) -> Database {
+        let script = format!("--- variables\n{declarations}\n---\nStory.");
+        crate::parse(&script).unwrap().0
+    }
+
+    #[test]
+    fn splits_computed_weight_over_declared_variables() {
+        let mut database = database_with("int fear = 1");
+        let (chance, rest) = split_chance_prefix("(10 + fear * 2) You tremble.", &mut database)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rest, "You tremble.");
+        assert!(matches!(
+            chance,
+            Chance::Computed { ref source, .. } if source == "10 + fear * 2"
+        ));
+        assert!(matches!(
+            split_chance_prefix("((fear + 1) * 2) Deep.", &mut database),
+            Ok(Some((Chance::Computed { .. }, "Deep.")))
+        ));
+    }
+
+    #[test]
+    fn parenthetical_without_declared_variable_is_plain_text() {
+        let mut database = database_with("int fear = 1");
+        assert_eq!(split_chance_prefix("(low-key) Hi", &mut database), Ok(None));
+        assert_eq!(split_chance_prefix("(2 + 3) Hi", &mut database), Ok(None));
+        assert_eq!(split_chance_prefix("(fear)st Hi", &mut database), Ok(None));
+    }
+
+    #[test]
+    fn computed_weight_must_be_an_integer() {
+        let mut database = database_with("float mood = 1.0");
+        assert_eq!(
+            split_chance_prefix("(mood) Hi", &mut database),
+            Err(ChanceParseError::NonIntegerWeight {
+                found: ValueKind::Float
+            })
+        );
+    }
+
+    #[test]
+    fn weight_overflow_is_an_error() {
+        assert_eq!(
+            split_chance_prefix("(99999999999) Heavy", &mut Database::default()),
+            Err(ChanceParseError::WeightOverflow {
+                literal: "99999999999".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Parser for the `--- externals` block.
+//!
+//! The block declares the functions the game provides, so a script can be
+//! checked without the game that runs it:
+//!
+//! ```cuentitos
+//! --- externals
+//! play_sound(string)
+//! has_item(string) -> bool
+//! coins_found(int) -> int
+//! ---
+//! ```
+//!
+//! Each line is a function name, the kinds of its parameters in
+//! parentheses, and the kind it returns after `->`. A function without a
+//! return kind can only be run with `call`.
+
+use cuentitos_common::{Database, FunctionSignature, ValueKind};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::parsers::variables_parser::{is_reserved_keyword, is_valid_identifier};
+use crate::ParseError;
+
+/// Outcome of parsing a `--- externals` block. Parallel to
+/// [`VariablesBlockOutcome`](crate::parsers::variables_parser::VariablesBlockOutcome):
+/// `consumed_lines` spans the block through its closing `---`, or the rest
+/// of the file when the closing `---` is missing.
+#[derive(Debug)]
+pub struct ExternalsBlockOutcome {
+    pub consumed_lines: usize,
+    pub errors: Vec<ParseError>,
+}
+
+/// Parse a `--- externals` block starting at `start_line_index` (0-based
+/// index into `lines`). The caller must have already verified that
+/// `lines[start_line_index].trim() == "--- externals"`.
+///
+/// Declared functions are added to `database.functions`, replacing any the
+/// game declared under the same name. Parsing stops at the first error.
+pub fn parse_externals_block(
+    lines: &[&str],
+    start_line_index: usize,
+    database: &mut Database,
+    file_path: &Option<PathBuf>,
+) -> ExternalsBlockOutcome {
+    let Some(closing_line_index) = lines
+        .iter()
+        .enumerate()
+        .skip(start_line_index + 1)
+        .find(|(_, line)| line.trim() == "---")
+        .map(|(i, _)| i)
+    else {
+        return ExternalsBlockOutcome {
+            consumed_lines: lines.len() - start_line_index,
+            errors: vec![ParseError::UnterminatedExternalsBlock {
+                file: file_path.clone(),
+                line: start_line_index + 1,
+            }],
+        };
+    };
+
+    let mut declared_lines: HashMap<String, usize> = HashMap::new();
+    let mut errors = Vec::new();
+    for (index, line) in lines
+        .iter()
+        .enumerate()
+        .take(closing_line_index)
+        .skip(start_line_index + 1)
+    {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+        let line_number = index + 1;
+        let signature = match parse_declaration(trimmed) {
+            Ok(signature) => signature,
+            Err(error) => {
+                errors.push(error.into_parse_error(trimmed, file_path, line_number));
+                break;
+            }
+        };
+        if let Some(&previous_line) = declared_lines.get(&signature.name) {
+            errors.push(ParseError::DuplicateExternal {
+                name: signature.name,
+                previous_line,
+                file: file_path.clone(),
+                line: line_number,
+            });
+            break;
+        }
+        declared_lines.insert(signature.name.clone(), line_number);
+        database.declare_function(signature);
+    }
+
+    ExternalsBlockOutcome {
+        consumed_lines: closing_line_index - start_line_index + 1,
+        errors,
+    }
+}
+
+/// Why a single declaration didn't parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DeclarationError {
+    Malformed,
+    ReservedName { name: String },
+    UnknownKind { kind: String },
+}
+
+impl DeclarationError {
+    fn into_parse_error(
+        self,
+        content: &str,
+        file_path: &Option<PathBuf>,
+        line: usize,
+    ) -> ParseError {
+        let file = file_path.clone();
+        match self {
+            DeclarationError::Malformed => ParseError::MalformedExternalDeclaration {
+                content: content.to_string(),
+                file,
+                line,
+            },
+            DeclarationError::ReservedName { name } => {
+                ParseError::ReservedFunctionName { name, file, line }
+            }
+            DeclarationError::UnknownKind { kind } => {
+                ParseError::UnknownExternalKind { kind, file, line }
+            }
+        }
+    }
+}
+
+/// Parse `name(kind, ...) [-> kind]`.
+fn parse_declaration(declaration: &str) -> Result<FunctionSignature, DeclarationError> {
+    let (call, returns) = match declaration.split_once("->") {
+        Some((call, returns)) => (call.trim_end(), Some(parse_kind(returns.trim())?)),
+        None => (declaration, None),
+    };
+    let (name, parameters) = call
+        .strip_suffix(')')
+        .and_then(|call| call.split_once('('))
+        .ok_or(DeclarationError::Malformed)?;
+    let name = name.trim_end();
+    if !is_valid_identifier(name) {
+        return Err(DeclarationError::Malformed);
+    }
+    // `visits(path)` is read by the expression tokenizer before any
+    // function lookup, so a function named `visits` could never be called.
+    if is_reserved_keyword(name) || name == "visits" {
+        return Err(DeclarationError::ReservedName {
+            name: name.to_string(),
+        });
+    }
+    let parameters = match parameters.trim() {
+        "" => Vec::new(),
+        parameters => parameters
+            .split(',')
+            .map(|kind| parse_kind(kind.trim()))
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(FunctionSignature::new(name, &parameters, returns))
+}
+
+/// The kind named by a declaration keyword. Enums aren't accepted: each enum
+/// is its own set of values, so a bare `enum` wouldn't say which.
+fn parse_kind(keyword: &str) -> Result<ValueKind, DeclarationError> {
+    match keyword {
+        "int" => Ok(ValueKind::Integer),
+        "float" => Ok(ValueKind::Float),
+        "bool" => Ok(ValueKind::Boolean),
+        "string" => Ok(ValueKind::String),
+        "" => Err(DeclarationError::Malformed),
+        other => Err(DeclarationError::UnknownKind {
+            kind: other.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(block: &str) -> (Database, ExternalsBlockOutcome) {
+        let lines: Vec<&str> = block.lines().collect();
+        let mut database = Database::new();
+        let outcome = parse_externals_block(&lines, 0, &mut database, &None);
+        (database, outcome)
+    }
+
+    #[test]
+    fn declares_functions_in_order() {
+        let (database, outcome) = parse(
+            "--- externals\nplay_sound(string)\n\nhas_item( string ) -> bool\ngive(string, int)->float\nnow() -> int\n---\nText",
+        );
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.consumed_lines, 7);
+        assert_eq!(
+            database.functions,
+            vec![
+                FunctionSignature::new("play_sound", &[ValueKind::String], None),
+                FunctionSignature::new("has_item", &[ValueKind::String], Some(ValueKind::Boolean)),
+                FunctionSignature::new(
+                    "give",
+                    &[ValueKind::String, ValueKind::Integer],
+                    Some(ValueKind::Float)
+                ),
+                FunctionSignature::new("now", &[], Some(ValueKind::Integer)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_declarations() {
+        assert_eq!(
+            parse_declaration("play_sound"),
+            Err(DeclarationError::Malformed)
+        );
+        assert_eq!(
+            parse_declaration("play sound(string)"),
+            Err(DeclarationError::Malformed)
+        );
+        assert_eq!(
+            parse_declaration("give(string,)"),
+            Err(DeclarationError::Malformed)
+        );
+        assert_eq!(
+            parse_declaration("now() ->"),
+            Err(DeclarationError::Malformed)
+        );
+        assert_eq!(
+            parse_declaration("mood() -> enum"),
+            Err(DeclarationError::UnknownKind {
+                kind: "enum".to_string()
+            })
+        );
+        assert_eq!(
+            parse_declaration("visits(int) -> int"),
+            Err(DeclarationError::ReservedName {
+                name: "visits".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_and_missing_closing_line() {
+        let (_, outcome) = parse("--- externals\nnow() -> int\nnow() -> float\n---");
+        assert!(matches!(
+            outcome.errors.as_slice(),
+            [ParseError::DuplicateExternal {
+                previous_line: 2,
+                line: 3,
+                ..
+            }]
+        ));
+        let (_, outcome) = parse("--- externals\nnow() -> int\nText");
+        assert_eq!(outcome.consumed_lines, 3);
+        assert!(matches!(
+            outcome.errors.as_slice(),
+            [ParseError::UnterminatedExternalsBlock { line: 1, .. }]
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! Parser for `freq` lines.
+//!
+//! A frequency modifier is written under a weighted chance member:
+//!
+//! ```cuentitos
+//! (10) A crow lands nearby.
+//!   freq -5
+//! ```
+//!
+//! Each time the member is shown, the integer expression after `freq` is
+//! added to its weight for later rolls. Checking that the parent actually
+//! carries a weight happens in the main parser's validation pass, once the
+//! whole tree is known.
+
+use cuentitos_common::{
+    Database, Expression, FunctionId, FunctionSignature, ValueKind, VariableId, VisitsId,
+};
+
+use crate::arithmetic::FunctionCallError;
+use crate::expression::{parse_expression, ParseExpressionError, VariableResolver};
+use crate::parsers::type_inference::infer_type;
+use crate::parsers::visit_paths::VisitPaths;
+
+/// Errors specific to parsing a `freq` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrequencyParseError {
+    /// Nothing after `freq`, or an expression that doesn't parse. Carries
+    /// the offending text.
+    MalformedExpression { expression: String },
+    /// The expression named an undeclared variable.
+    UndefinedVariable { name: String },
+    /// A literal exceeded the integer range.
+    LiteralOverflow { literal: String },
+    /// The expression produced a value of a kind other than integer.
+    NonInteger { found: ValueKind },
+    /// The expression called a function the wrong way.
+    Function(FunctionCallError),
+}
+
+/// Cheap pre-filter: does this trimmed line begin with the `freq` keyword?
+/// Like `set` and `req`, the bare keyword is claimed too, so a missing
+/// expression is reported instead of rendering as text.
+pub fn is_frequency_line(content: &str) -> bool {
+    content == "freq"
+        || content
+            .strip_prefix("freq")
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_whitespace()))
+}
+
+/// Parse a `freq` line (already trimmed of indentation) into the expression
+/// whose value is added to the parent's weight.
+pub fn parse_frequency(
+    content: &str,
+    database: &mut Database,
+) -> Result<Expression, FrequencyParseError> {
+    let rest = content.strip_prefix("freq").unwrap_or(content).trim();
+    let visit_paths = VisitPaths::new(database);
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: &visit_paths,
+    };
+    let expression = parse_expression(rest, &resolver).map_err(|err| match err {
+        ParseExpressionError::UndefinedVariable { name } => {
+            FrequencyParseError::UndefinedVariable { name }
+        }
+        ParseExpressionError::Overflow { literal } => {
+            FrequencyParseError::LiteralOverflow { literal }
+        }
+        ParseExpressionError::Function(error) => FrequencyParseError::Function(error),
+        // A bare `freq` has nothing to quote; echo the whole line instead.
+        ParseExpressionError::Malformed | ParseExpressionError::FloatOverflow { .. } => {
+            FrequencyParseError::MalformedExpression {
+                expression: if rest.is_empty() { content } else { rest }.to_string(),
+            }
+        }
+    })?;
+    match infer_type(&expression, database) {
+        Ok(ValueKind::Integer) => {
+            visit_paths.commit(database);
+            Ok(expression)
+        }
+        Ok(found) => Err(FrequencyParseError::NonInteger { found }),
+        Err(err) => Err(match err.non_integer_kind() {
+            Ok(found) => FrequencyParseError::NonInteger { found },
+            Err(error) => FrequencyParseError::Function(error),
+        }),
+    }
+}
+
+struct DatabaseResolver<'a> {
+    database: &'a Database,
+    visit_paths: &'a VisitPaths,
+}
+
+impl VariableResolver for DatabaseResolver<'_> {
+    fn resolve(&self, name: &str) -> Option<VariableId> {
+        self.database.variable_id(name)
+    }
+
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        Some(self.visit_paths.register(path))
+    }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        let id = self.database.function_id(name)?;
+        Some((id, self.database.functions[id].clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuentitos_common::{BinaryOperator, Value};
+
+    #[test]
+    fn claims_keyword_lines_only() {
+        assert!(is_frequency_line("freq -5"));
+        assert!(is_frequency_line("freq"));
+        assert!(!is_frequency_line("frequently"));
+        assert!(!is_frequency_line("Freq -5"));
+    }
+
+    #[test]
+    fn parses_signed_expressions() {
+        let script = "--- variables\nint fear = 1\n---\nStory.";
+        let (mut database, _warnings) = crate::parse(script).unwrap();
+        assert_eq!(
+            parse_frequency("freq 3", &mut database),
+            Ok(Expression::Literal(Value::Integer(3)))
+        );
+        assert!(matches!(
+            parse_frequency("freq fear * 2", &mut database),
+            Ok(Expression::Binary {
+                operator: BinaryOperator::Multiply,
+                ..
+            })
+        ));
+        assert!(parse_frequency("freq -5", &mut database).is_ok());
+    }
+
+    #[test]
+    fn reports_bad_expressions() {
+        let script = "--- variables\nbool lit = true\n---\nStory.";
+        let (mut database, _warnings) = crate::parse(script).unwrap();
+        assert_eq!(
+            parse_frequency("freq", &mut database),
+            Err(FrequencyParseError::MalformedExpression {
+                expression: "freq".to_string()
+            })
+        );
+        assert_eq!(
+            parse_frequency("freq dread", &mut database),
+            Err(FrequencyParseError::UndefinedVariable {
+                name: "dread".to_string()
+            })
+        );
+        assert_eq!(
+            parse_frequency("freq lit", &mut database),
+            Err(FrequencyParseError::NonInteger {
+                found: ValueKind::Boolean
+            })
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! `include` directives, which pull another script into the story:
+//!
+//! ```cuentitos
+//! include "chapters/cave.cuentitos"
+//! ```
+//!
+//! Paths resolve from the directory of the script the directive is in, or
+//! from the working directory for a script parsed without a file, then
+//! from each source directory of the project manifest. The
+//! included script's lines take the directive's place, so its sections
+//! become top-level sections of the story. Every line keeps the file and
+//! line number it was read from, for blocks and diagnostics.
+//!
+//! Includes are expanded before the main pass. A script can't include one
+//! of the scripts including it, nor be included twice, and variables and
+//! external functions are only declared in the main script.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use cuentitos_common::FileId;
+
+use crate::string_literal::parse_string_literal;
+use crate::ParseError;
+
+/// A line of the story with its includes expanded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    /// The included script the line was read from, as an index into
+    /// [`IncludeOutcome::files`], or `None` for the main script.
+    pub file: Option<FileId>,
+    /// The line number in that script, starting at 1.
+    pub line: usize,
+    pub text: String,
+}
+
+/// An included script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludedFile {
+    /// Where it was read from, for diagnostics.
+    pub path: PathBuf,
+    /// Its path relative to the main script's directory, as recorded in
+    /// `Database::files`.
+    pub relative_path: String,
+}
+
+/// Outcome of [`expand_includes`].
+#[derive(Debug, Default)]
+pub struct IncludeOutcome {
+    pub lines: Vec<SourceLine>,
+    pub files: Vec<IncludedFile>,
+    /// Directives that couldn't be followed. Their lines are left out.
+    pub errors: Vec<ParseError>,
+}
+
+/// The path of an `include` line, if `line` is one. A line is an `include`
+/// when it starts with `include` and a quote, so text such as `include the
+/// dog` is left alone.
+pub fn parse_include(line: &str) -> Option<Result<String, String>> {
+    let rest = line.trim().strip_prefix("include")?;
+    let path = rest.trim_start();
+    if !path.starts_with('"') || path.len() == rest.len() {
+        return None;
+    }
+    Some(
+        parse_string_literal(path)
+            .map_err(|_| "Expected a quoted path after 'include'.".to_string())
+            .and_then(|path| {
+                if path.trim().is_empty() {
+                    Err("Expected a quoted path after 'include'.".to_string())
+                } else {
+                    Ok(path)
+                }
+            }),
+    )
+}
+
+/// Expand every `include` of the main script, whose lines from
+/// `first_index` on are `lines`, recursively. Scripts that aren't next to
+/// the script including them are looked up in `source_roots`, in order.
+pub fn expand_includes(
+    lines: &[&str],
+    first_index: usize,
+    file_path: &Option<PathBuf>,
+    source_roots: &[PathBuf],
+) -> IncludeOutcome {
+    let mut expander = Expander {
+        outcome: IncludeOutcome::default(),
+        chain: Vec::new(),
+        seen: HashSet::new(),
+        source_roots,
+        main_directory: file_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .and_then(|directory| std::fs::canonicalize(directory).ok()),
+    };
+    let main = file_path
+        .as_ref()
+        .and_then(|path| std::fs::canonicalize(path).ok());
+    if let Some(main) = main.clone() {
+        expander.chain.push(main.clone());
+        expander.seen.insert(main);
+    }
+    expander.expand(None, lines, first_index, file_path);
+    expander.outcome
+}
+
+struct Expander<'a> {
+    outcome: IncludeOutcome,
+    /// The scripts being expanded, from the main script down.
+    chain: Vec<PathBuf>,
+    /// Every script included so far, canonicalized.
+    seen: HashSet<PathBuf>,
+    source_roots: &'a [PathBuf],
+    /// The main script's directory, canonicalized, which scripts found in
+    /// a source directory are recorded relative to.
+    main_directory: Option<PathBuf>,
+}
+
+impl Expander<'_> {
+    fn expand(
+        &mut self,
+        file: Option<FileId>,
+        lines: &[&str],
+        first_index: usize,
+        file_path: &Option<PathBuf>,
+    ) {
+        for (index, text) in lines.iter().enumerate().skip(first_index) {
+            let line = index + 1;
+            let Some(include) = parse_include(text) else {
+                self.outcome.lines.push(SourceLine {
+                    file,
+                    line,
+                    text: text.to_string(),
+                });
+                continue;
+            };
+            let error = |message: &str| ParseError::InvalidInclude {
+                message: message.to_string(),
+                file: file_path.clone(),
+                line,
+            };
+            if text.starts_with(char::is_whitespace) {
+                self.outcome
+                    .errors
+                    .push(error("'include' can only be used at the top level."));
+                continue;
+            }
+            match include {
+                Ok(path) => self.include(file, &path, file_path, line),
+                Err(message) => self.outcome.errors.push(error(&message)),
+            }
+        }
+    }
+
+    /// Follow the `include` of `path` on `line` of `file`.
+    fn include(
+        &mut self,
+        file: Option<FileId>,
+        path: &str,
+        file_path: &Option<PathBuf>,
+        line: usize,
+    ) {
+        let directory = file_path
+            .as_ref()
+            .and_then(|file_path| file_path.parent())
+            .unwrap_or(Path::new(""));
+        let mut full_path = directory.join(path);
+        let mut in_source_root = false;
+        if !full_path.is_file() {
+            if let Some(found) = self
+                .source_roots
+                .iter()
+                .find(|source_root| source_root.join(path).is_file())
+            {
+                full_path = found.join(path);
+                in_source_root = true;
+            }
+        }
+        let contents = std::fs::canonicalize(&full_path).and_then(|canonical| {
+            std::fs::read_to_string(&canonical).map(|contents| (canonical, contents))
+        });
+        let (canonical, contents) = match contents {
+            Ok(result) => result,
+            Err(err) => {
+                self.outcome.errors.push(ParseError::IncludeNotFound {
+                    path: path.to_string(),
+                    message: err.to_string(),
+                    file: file_path.clone(),
+                    line,
+                });
+                return;
+            }
+        };
+
+        if let Some(start) = self.chain.iter().position(|other| *other == canonical) {
+            let cycle = self.chain[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|path| file_name(path))
+                .collect();
+            self.outcome.errors.push(ParseError::IncludeCycle {
+                cycle,
+                file: file_path.clone(),
+                line,
+            });
+            return;
+        }
+        if !self.seen.insert(canonical.clone()) {
+            self.outcome.errors.push(ParseError::InvalidInclude {
+                message: format!("'{}' is already included.", path),
+                file: file_path.clone(),
+                line,
+            });
+            return;
+        }
+
+        let relative_path = match (in_source_root, file) {
+            (true, _) => self
+                .main_directory
+                .as_ref()
+                .and_then(|main_directory| canonical.strip_prefix(main_directory).ok())
+                .map_or_else(|| full_path.clone(), Path::to_path_buf),
+            (false, Some(parent)) => Path::new(&self.outcome.files[parent].relative_path)
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(path),
+            (false, None) => PathBuf::from(path),
+        };
+        let included = self.outcome.files.len();
+        self.outcome.files.push(IncludedFile {
+            path: full_path.clone(),
+            relative_path: relative_path.display().to_string(),
+        });
+
+        let lines: Vec<&str> = contents.lines().collect();
+        let included_path = Some(full_path);
+        let first_line = lines
+            .iter()
+            .position(|line| !line.trim().is_empty() && !line.trim_start().starts_with("//"));
+        let declares = first_line.and_then(|index| match lines[index].trim() {
+            "--- variables" => Some((index, "Variables")),
+            "--- externals" => Some((index, "Functions")),
+            _ => None,
+        });
+        if let Some((index, what)) = declares {
+            self.outcome.errors.push(ParseError::InvalidInclude {
+                message: format!("{} can only be declared in the main script.", what),
+                file: included_path,
+                line: index + 1,
+            });
+            return;
+        }
+
+        self.chain.push(canonical);
+        self.expand(Some(included), &lines, 0, &included_path);
+        self.chain.pop();
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_include_lines() {
+        assert_eq!(
+            parse_include("include \"cave.cuentitos\""),
+            Some(Ok("cave.cuentitos".to_string()))
+        );
+        assert_eq!(parse_include("include the dog"), None);
+        assert_eq!(parse_include("included \"cave\""), None);
+        assert!(matches!(parse_include("include \"cave"), Some(Err(_))));
+        assert!(matches!(parse_include("include \"\""), Some(Err(_))));
+    }
+
+    #[test]
+    fn keeps_the_origin_of_every_line() {
+        let dir = std::env::temp_dir().join(format!("cuentitos-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("chapters")).unwrap();
+        std::fs::write(
+            dir.join("chapters/one.cuentitos"),
+            "One.\ninclude \"two.cuentitos\"",
+        )
+        .unwrap();
+        std::fs::write(dir.join("chapters/two.cuentitos"), "Two.").unwrap();
+
+        let main = "Start.\ninclude \"chapters/one.cuentitos\"\nEnd.";
+        let lines: Vec<&str> = main.lines().collect();
+        let outcome = expand_includes(&lines, 0, &Some(dir.join("main.cuentitos")), &[]);
+        assert!(outcome.errors.is_empty());
+        let origins: Vec<_> = outcome
+            .lines
+            .iter()
+            .map(|line| (line.file, line.line, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                (None, 1, "Start."),
+                (Some(0), 1, "One."),
+                (Some(1), 1, "Two."),
+                (None, 3, "End."),
+            ]
+        );
+        assert_eq!(outcome.files[1].relative_path, "chapters/two.cuentitos");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
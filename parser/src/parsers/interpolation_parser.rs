@@ -0,0 +1,351 @@
+//! Parser for `{...}` fragments in text and option lines.
+//!
+//! ```cuentitos
+//! You have {gold} coins.
+//! The door is {door_open ? open : shut}.
+//! {Hello|Hello again|Back already?}
+//! ```
+//!
+//! Between the braces goes one of:
+//!
+//! - a declared variable name;
+//! - `condition ? then : otherwise`, where the condition uses the `req`
+//!   grammar and `: otherwise` can be left out;
+//! - alternatives separated by `|`, optionally prefixed with `&` (cycle) or
+//!   `~` (shuffle).
+//!
+//! Branch texts are themselves text, so they can hold further fragments:
+//! `{met ? Hi, {name}. : Hello.}`. Inside braces, `?`, `:` and `|` only
+//! split at the outermost level, and a `}` always closes.
+//!
+//! `{{` writes a literal `{`; a `}` on its own is plain text.
+
+use cuentitos_common::{AlternativesMode, Database, TextAlternatives, TextFragment};
+
+use crate::parsers::requirement_parser::{parse_condition, RequirementParseError};
+use crate::parsers::variables_parser::is_valid_identifier;
+
+/// Errors specific to parsing interpolations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpolationParseError {
+    /// A `{` with no matching `}` on the same line.
+    Unclosed,
+    /// Something other than a variable name, condition or alternatives
+    /// between the braces, including nothing at all. Carries the text
+    /// between the braces.
+    Malformed { text: String },
+    /// The name isn't a declared variable.
+    UndefinedVariable { name: String },
+    /// The condition of a `{condition ? ...}` fragment didn't parse. Carries
+    /// the condition's own error, reported like a `req` would be.
+    Condition { error: RequirementParseError },
+}
+
+/// Split `text` into the fragments it is printed from. Returns `None` when
+/// the line has no `{` at all, so plain lines don't store fragments.
+///
+/// Alternatives are added to `database.text_alternatives` as they are
+/// found.
+pub fn parse_interpolation(
+    text: &str,
+    database: &mut Database,
+) -> Result<Option<Vec<TextFragment>>, InterpolationParseError> {
+    if !text.contains('{') {
+        return Ok(None);
+    }
+    parse_fragments(text, database).map(Some)
+}
+
+fn parse_fragments(
+    text: &str,
+    database: &mut Database,
+) -> Result<Vec<TextFragment>, InterpolationParseError> {
+    let mut fragments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        literal.push_str(&rest[..open]);
+        rest = &rest[open + 1..];
+
+        // `{{` is an escaped brace.
+        if let Some(after) = rest.strip_prefix('{') {
+            literal.push('{');
+            rest = after;
+            continue;
+        }
+
+        let close = matching_close(rest).ok_or(InterpolationParseError::Unclosed)?;
+        let fragment = parse_inner(&rest[..close], database)?;
+        if !literal.is_empty() {
+            fragments.push(TextFragment::Literal(std::mem::take(&mut literal)));
+        }
+        fragments.push(fragment);
+        rest = &rest[close + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        fragments.push(TextFragment::Literal(literal));
+    }
+    Ok(fragments)
+}
+
+/// Parse what sits between one pair of braces.
+fn parse_inner(
+    inner: &str,
+    database: &mut Database,
+) -> Result<TextFragment, InterpolationParseError> {
+    // `?` only makes a conditional when it comes before any `|`, so a
+    // question mark inside an alternative (`{Hi|Back already?}`) stays text.
+    let question = find_top_level(inner, '?');
+    let bar = find_top_level(inner, '|');
+    if let Some(question) = question.filter(|&question| bar.is_none_or(|bar| question < bar)) {
+        if inner[..question].trim().is_empty() {
+            return Err(InterpolationParseError::Malformed {
+                text: inner.to_string(),
+            });
+        }
+        let condition = parse_condition(&inner[..question], database)
+            .map_err(|error| InterpolationParseError::Condition { error })?
+            .expression;
+        let branches = &inner[question + 1..];
+        let (then, otherwise) = match find_top_level(branches, ':') {
+            Some(colon) => (&branches[..colon], &branches[colon + 1..]),
+            None => (branches, ""),
+        };
+        return Ok(TextFragment::Conditional {
+            condition,
+            then: parse_fragments(then.trim(), database)?,
+            otherwise: parse_fragments(otherwise.trim(), database)?,
+        });
+    }
+
+    if bar.is_some() {
+        let trimmed = inner.trim_start();
+        let (mode, body) = if let Some(body) = trimmed.strip_prefix('&') {
+            (AlternativesMode::Cycle, body)
+        } else if let Some(body) = trimmed.strip_prefix('~') {
+            (AlternativesMode::Shuffle, body)
+        } else {
+            (AlternativesMode::Sequence, trimmed)
+        };
+        let mut branches = Vec::new();
+        let mut rest = body;
+        loop {
+            let end = find_top_level(rest, '|').unwrap_or(rest.len());
+            branches.push(parse_fragments(rest[..end].trim(), database)?);
+            if end == rest.len() {
+                break;
+            }
+            rest = &rest[end + 1..];
+        }
+        let alternatives_id = database.add_text_alternatives(TextAlternatives { mode, branches });
+        return Ok(TextFragment::Alternatives(alternatives_id));
+    }
+
+    let name = inner.trim();
+    if !is_valid_identifier(name) {
+        return Err(InterpolationParseError::Malformed {
+            text: inner.to_string(),
+        });
+    }
+    database
+        .variable_id(name)
+        .map(TextFragment::Variable)
+        .ok_or_else(|| InterpolationParseError::UndefinedVariable {
+            name: name.to_string(),
+        })
+}
+
+/// The index of the `}` closing a fragment whose `{` was just consumed,
+/// skipping over nested fragments and `{{` escapes.
+fn matching_close(text: &str) -> Option<usize> {
+    scan_top_level(text, |character| character == '}')
+}
+
+/// The index of the first `separator` outside any nested fragment.
+fn find_top_level(text: &str, separator: char) -> Option<usize> {
+    scan_top_level(text, |character| character == separator)
+}
+
+/// The index of the first character outside nested fragments and `{{`
+/// escapes that `stop` accepts. A `}` that doesn't close a nested fragment
+/// counts as outside.
+fn scan_top_level(text: &str, stop: impl Fn(char) -> bool) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, character)) = chars.next() {
+        if depth == 0 && stop(character) {
+            return Some(index);
+        }
+        match character {
+            '{' if chars.peek().is_some_and(|&(_, next)| next == '{') => {
+                chars.next();
+            }
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuentitos_common::BooleanExpression;
+
+    fn database() -> Database {
+        let script =
+            "--- variables\nint gold = 3\nstring name = \"Ana\"\nbool met = false\n---\nStory.";
+        crate::parse(script).unwrap().0
+    }
+
+    fn literal(text: &str) -> TextFragment {
+        TextFragment::Literal(text.to_string())
+    }
+
+    #[test]
+    fn plain_lines_have_no_fragments() {
+        assert_eq!(
+            parse_interpolation("Hello } there.", &mut database()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn splits_variables_from_text() {
+        let mut database = database();
+        assert_eq!(
+            parse_interpolation("{name} has { gold } coins.", &mut database),
+            Ok(Some(vec![
+                TextFragment::Variable(1),
+                literal(" has "),
+                TextFragment::Variable(0),
+                literal(" coins."),
+            ]))
+        );
+        assert_eq!(
+            parse_interpolation("A {{brace}.", &mut database),
+            Ok(Some(vec![literal("A {brace}.")]))
+        );
+    }
+
+    #[test]
+    fn parses_conditionals_with_nested_fragments() {
+        let mut database = database();
+        let fragments = parse_interpolation("{met ? Hi, {name}. : Hello.}", &mut database)
+            .unwrap()
+            .unwrap();
+        let [TextFragment::Conditional {
+            condition,
+            then,
+            otherwise,
+        }] = fragments.as_slice()
+        else {
+            panic!("expected one conditional, got {fragments:?}");
+        };
+        assert!(matches!(condition, BooleanExpression::Comparison(_)));
+        assert_eq!(
+            then,
+            &vec![literal("Hi, "), TextFragment::Variable(1), literal(".")]
+        );
+        assert_eq!(otherwise, &vec![literal("Hello.")]);
+
+        let fragments = parse_interpolation("{gold > 1 ? rich}", &mut database)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            fragments.as_slice(),
+            [TextFragment::Conditional { otherwise, .. }] if otherwise.is_empty()
+        ));
+    }
+
+    #[test]
+    fn parses_alternatives() {
+        let mut database = database();
+        assert_eq!(
+            parse_interpolation("{Hi|Hi again} and {&red|{name}|}", &mut database),
+            Ok(Some(vec![
+                TextFragment::Alternatives(0),
+                literal(" and "),
+                TextFragment::Alternatives(1),
+            ]))
+        );
+        assert_eq!(
+            database.text_alternatives[0],
+            TextAlternatives {
+                mode: AlternativesMode::Sequence,
+                branches: vec![vec![literal("Hi")], vec![literal("Hi again")]],
+            }
+        );
+        assert_eq!(
+            database.text_alternatives[1],
+            TextAlternatives {
+                mode: AlternativesMode::Cycle,
+                branches: vec![
+                    vec![literal("red")],
+                    vec![TextFragment::Variable(1)],
+                    vec![],
+                ],
+            }
+        );
+        assert_eq!(
+            parse_interpolation("{Hi|Back already?}", &mut database),
+            Ok(Some(vec![TextFragment::Alternatives(2)]))
+        );
+        assert_eq!(
+            database.text_alternatives[2].branches[1],
+            vec![literal("Back already?")]
+        );
+        parse_interpolation("{~a|b}", &mut database).unwrap();
+        assert_eq!(
+            database.text_alternatives[3].mode,
+            AlternativesMode::Shuffle
+        );
+    }
+
+    #[test]
+    fn reports_bad_interpolations() {
+        let mut database = database();
+        assert_eq!(
+            parse_interpolation("You have {gold coins.", &mut database),
+            Err(InterpolationParseError::Unclosed)
+        );
+        assert_eq!(
+            parse_interpolation("You have {a|{gold} coins.", &mut database),
+            Err(InterpolationParseError::Unclosed)
+        );
+        assert_eq!(
+            parse_interpolation("You have {} coins.", &mut database),
+            Err(InterpolationParseError::Malformed {
+                text: String::new()
+            })
+        );
+        assert_eq!(
+            parse_interpolation("You have {gold + 1} coins.", &mut database),
+            Err(InterpolationParseError::Malformed {
+                text: "gold + 1".to_string()
+            })
+        );
+        assert_eq!(
+            parse_interpolation("You have {silver} coins.", &mut database),
+            Err(InterpolationParseError::UndefinedVariable {
+                name: "silver".to_string()
+            })
+        );
+        assert_eq!(
+            parse_interpolation("{ ? a : b}", &mut database),
+            Err(InterpolationParseError::Malformed {
+                text: " ? a : b".to_string()
+            })
+        );
+        assert_eq!(
+            parse_interpolation("{silver ? a : b}", &mut database),
+            Err(InterpolationParseError::Condition {
+                error: RequirementParseError::UndefinedVariable {
+                    name: "silver".to_string()
+                }
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use cuentitos_common::localization::is_valid_key;
+
 use super::{FeatureParser, ParserContext};
 use crate::ParseError;
 
@@ -9,6 +11,20 @@ pub struct LineParser;
 #[derive(Debug)]
 pub struct LineParseResult {
     pub string: String,
+    /// The string key given with a trailing `#id:key` tag, if any.
+    pub key: Option<String>,
+}
+
+/// Split a trailing `#id:key` tag off a text or option line. The tag must
+/// be the last word and hold a valid key, so hashtags such as `#12` or
+/// `#adventure` stay part of the text.
+pub fn split_key(input: &str) -> (&str, Option<&str>) {
+    if let Some((text, tag)) = input.rsplit_once(char::is_whitespace) {
+        if let Some(key) = tag.strip_prefix("#id:").filter(|key| is_valid_key(key)) {
+            return (text.trim_end(), Some(key));
+        }
+    }
+    (input, None)
 }
 
 impl LineParser {
@@ -26,8 +42,10 @@ impl FeatureParser for LineParser {
         input: &str,
         _context: &mut ParserContext,
     ) -> Result<Self::Output, Self::Error> {
+        let (text, key) = split_key(input);
         Ok(LineParseResult {
-            string: input.to_string(),
+            string: text.to_string(),
+            key: key.map(str::to_string),
         })
     }
 }
@@ -43,5 +61,24 @@ mod tests {
 
         let result = parser.parse("Hello, world!", &mut context).unwrap();
         assert_eq!(result.string, "Hello, world!");
+        assert_eq!(result.key, None);
+    }
+
+    #[test]
+    fn test_parse_key_tag() {
+        let parser = LineParser::new();
+        let mut context = ParserContext::new();
+
+        let result = parser
+            .parse("Welcome back. #id:welcome", &mut context)
+            .unwrap();
+        assert_eq!(result.string, "Welcome back.");
+        assert_eq!(result.key.as_deref(), Some("welcome"));
+
+        for text in ["Room #12", "Follow us on #adventure", "Bad #id:2nd"] {
+            let result = parser.parse(text, &mut context).unwrap();
+            assert_eq!(result.string, text);
+            assert_eq!(result.key, None);
+        }
     }
 }
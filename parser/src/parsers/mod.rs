@@ -1,7 +1,14 @@
 use cuentitos_common::*;
 
+pub mod bucket_parser;
+pub mod call_parser;
+pub mod chance_parser;
+pub mod externals_parser;
+pub mod frequency_parser;
 pub mod go_to_section_and_back_parser;
 pub mod go_to_section_parser;
+pub mod include_parser;
+pub mod interpolation_parser;
 pub mod line_parser;
 pub mod option_parser;
 pub mod requirement_parser;
@@ -9,6 +16,7 @@ pub mod section_parser;
 pub mod set_parser;
 pub mod type_inference;
 pub mod variables_parser;
+pub(crate) mod visit_paths;
 
 /// Represents the shared context between different parsers
 #[derive(Debug)]
@@ -1,3 +1,4 @@
+use super::line_parser::split_key;
 use super::{FeatureParser, ParserContext};
 use crate::ParseError;
 
@@ -5,10 +6,26 @@ use crate::ParseError;
 #[derive(Debug, Default)]
 pub struct OptionParser;
 
+/// How an option behaves across visits to its menu, set by the marker
+/// written after the `*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    /// Plain `*` or `* [sticky]`: offered every time.
+    Sticky,
+    /// `* [once]`: leaves the menu once picked.
+    Once,
+    /// `* [fallback]`: never listed; taken automatically when no other
+    /// option is visible.
+    Fallback,
+}
+
 /// The result of parsing an option line
 #[derive(Debug)]
 pub struct OptionParseResult {
     pub text: String,
+    pub kind: OptionKind,
+    /// The string key given with a trailing `#id:key` tag, if any.
+    pub key: Option<String>,
 }
 
 impl OptionParser {
@@ -41,9 +58,26 @@ impl FeatureParser for OptionParser {
         }
 
         // Extract option text after the `*` and trim it
-        let text = trimmed[1..].trim().to_string();
-
-        Ok(OptionParseResult { text })
+        let text = trimmed[1..].trim();
+
+        // An optional `[once]`/`[sticky]`/`[fallback]` marker comes right
+        // after the `*`. Any other bracketed word is part of the text.
+        let (text, kind) = if let Some(rest) = text.strip_prefix("[once]") {
+            (rest, OptionKind::Once)
+        } else if let Some(rest) = text.strip_prefix("[sticky]") {
+            (rest, OptionKind::Sticky)
+        } else if let Some(rest) = text.strip_prefix("[fallback]") {
+            (rest, OptionKind::Fallback)
+        } else {
+            (text, OptionKind::Sticky)
+        };
+
+        let (text, key) = split_key(text.trim());
+        Ok(OptionParseResult {
+            text: text.to_string(),
+            kind,
+            key: key.map(str::to_string),
+        })
     }
 }
 
@@ -87,6 +121,38 @@ mod tests {
         assert_eq!(result.text, "Go right");
     }
 
+    #[test]
+    fn test_parse_option_markers() {
+        let parser = OptionParser::new();
+        let mut context = ParserContext::new();
+
+        let result = parser.parse("* [once] Take the gem", &mut context).unwrap();
+        assert_eq!(result.text, "Take the gem");
+        assert_eq!(result.kind, OptionKind::Once);
+
+        let result = parser.parse("* [sticky] Wait", &mut context).unwrap();
+        assert_eq!(result.text, "Wait");
+        assert_eq!(result.kind, OptionKind::Sticky);
+
+        let result = parser.parse("* Wait", &mut context).unwrap();
+        assert_eq!(result.kind, OptionKind::Sticky);
+
+        let result = parser.parse("* [fallback]", &mut context).unwrap();
+        assert_eq!(result.text, "");
+        assert_eq!(result.kind, OptionKind::Fallback);
+
+        let result = parser.parse("* [aside] Whisper", &mut context).unwrap();
+        assert_eq!(result.text, "[aside] Whisper");
+        assert_eq!(result.kind, OptionKind::Sticky);
+
+        let result = parser
+            .parse("* [once] Take the gem #id:gem", &mut context)
+            .unwrap();
+        assert_eq!(result.text, "Take the gem");
+        assert_eq!(result.kind, OptionKind::Once);
+        assert_eq!(result.key.as_deref(), Some("gem"));
+    }
+
     #[test]
     fn test_parse_non_option_fails() {
         let parser = OptionParser::new();
@@ -12,14 +12,16 @@
 //!    variants ready for [`crate::ParseError`] formatting.
 
 use cuentitos_common::{
-    BooleanExpression, ComparisonOperator, Database, Expression, RequirementStatement, ValueKind,
-    VariableId,
+    BooleanExpression, ComparisonOperator, Database, Expression, FunctionId, FunctionSignature,
+    RequirementStatement, ValueKind, VariableId, VisitsId,
 };
 
+use crate::arithmetic::FunctionCallError;
 use crate::boolean_expression::{
     parse_boolean_expression, BooleanParseError, LogicalKeyword, VariableResolver,
 };
 use crate::parsers::type_inference::{infer_type, TypeInferenceError};
+use crate::parsers::visit_paths::VisitPaths;
 
 /// Result of parsing a `req` line.
 ///
@@ -95,6 +97,8 @@ pub enum RequirementParseError {
     /// this variant carries no payload because the caller knows the
     /// context. See [`BooleanParseError::DoubleEquals`].
     DoubleEquals,
+    /// The condition called a function the wrong way.
+    Function(FunctionCallError),
 }
 
 /// Try to parse `content` as a `req` statement.
@@ -111,7 +115,7 @@ pub enum RequirementParseError {
 /// `MissingCondition` fallback.
 pub(crate) fn parse_requirement(
     content: &str,
-    database: &Database,
+    database: &mut Database,
 ) -> Result<ParsedRequirement, RequirementParseError> {
     let rest = match strip_keyword(content, "req") {
         StripResult::Stripped(rest) => rest,
@@ -125,16 +129,31 @@ pub(crate) fn parse_requirement(
         }
     };
 
-    let payload = rest.trim();
+    parse_condition(rest, database)
+}
+
+/// Parse a bare condition, the part of a `req` line after the keyword. Also
+/// used for the condition of an inline `{condition ? then : otherwise}` text
+/// fragment, which shares the `req` grammar.
+pub(crate) fn parse_condition(
+    condition: &str,
+    database: &mut Database,
+) -> Result<ParsedRequirement, RequirementParseError> {
+    let payload = condition.trim();
     if payload.is_empty() {
         return Err(RequirementParseError::MissingCondition);
     }
 
-    let resolver = DatabaseResolver { database };
+    let visit_paths = VisitPaths::new(database);
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: &visit_paths,
+    };
     let expression = match parse_boolean_expression(payload, &resolver) {
         Ok(expression) => expression,
         Err(error) => return Err(map_boolean_error(error, payload)),
     };
+    visit_paths.commit(database);
 
     // Walk each leaf comparison so type/ordering errors surface with the
     // same diagnostics the single-comparison parser used to emit.
@@ -185,6 +204,7 @@ fn map_boolean_error(error: BooleanParseError, source: &str) -> RequirementParse
         }
         BooleanParseError::ExpressionTooDeep => RequirementParseError::ExpressionTooDeep,
         BooleanParseError::DoubleEquals => RequirementParseError::DoubleEquals,
+        BooleanParseError::Function(error) => RequirementParseError::Function(error),
         BooleanParseError::Malformed => RequirementParseError::MalformedExpression {
             expression: source.to_string(),
         },
@@ -259,6 +279,8 @@ fn render_operand(expression: &Expression, database: &Database) -> String {
         Expression::Variable(id) => database.variables[*id].name.clone(),
         Expression::Literal(value) => value.to_string(),
         Expression::Binary { .. } => "expression".to_string(),
+        Expression::Visits(id) => format!("visits({})", database.visit_paths[*id]),
+        Expression::Call { function, .. } => format!("{}(...)", database.functions[*function].name),
     }
 }
 
@@ -273,7 +295,7 @@ fn enum_variants_of(expression: &Expression, database: &Database) -> Option<Vec<
             .enum_variants()
             .map(<[String]>::to_vec),
         Expression::Literal(value) => value.enum_variants().map(<[String]>::to_vec),
-        Expression::Binary { .. } => None,
+        Expression::Binary { .. } | Expression::Visits(_) | Expression::Call { .. } => None,
     }
 }
 
@@ -288,11 +310,13 @@ fn infer_kind(
         TypeInferenceError::NonNumericArithmetic { kind, .. } => {
             RequirementParseError::NonNumericArithmetic { kind }
         }
+        TypeInferenceError::Function(error) => RequirementParseError::Function(error),
     })
 }
 
 struct DatabaseResolver<'a> {
     database: &'a Database,
+    visit_paths: &'a VisitPaths,
 }
 
 impl VariableResolver for DatabaseResolver<'_> {
@@ -300,6 +324,10 @@ impl VariableResolver for DatabaseResolver<'_> {
         self.database.variable_id(name)
     }
 
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        Some(self.visit_paths.register(path))
+    }
+
     fn kind_of(&self, id: VariableId) -> Option<ValueKind> {
         self.database
             .variables
@@ -314,6 +342,11 @@ impl VariableResolver for DatabaseResolver<'_> {
             .and_then(|variable| variable.default.enum_variants())
             .map(<[String]>::to_vec)
     }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        let id = self.database.function_id(name)?;
+        Some((id, self.database.functions[id].clone()))
+    }
 }
 
 /// Cheap predicate: does `content` (already trimmed of indentation)
@@ -379,7 +412,7 @@ mod tests {
 
     #[test]
     fn parses_each_comparison_operator() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         for (input, expected_operator) in [
             ("req x > 0", ComparisonOperator::Greater),
             ("req x < 0", ComparisonOperator::Less),
@@ -388,55 +421,68 @@ mod tests {
             ("req x = 0", ComparisonOperator::Equal),
             ("req x != 0", ComparisonOperator::NotEqual),
         ] {
-            let parsed = parse_requirement(input, &db).unwrap();
+            let parsed = parse_requirement(input, &mut db).unwrap();
             let stmt = assert_comparison(&parsed.expression, expected_operator);
             assert_eq!(stmt.left, Expression::Variable(0));
             assert_eq!(stmt.right, Expression::Literal(Value::Integer(0)));
         }
     }
 
+    #[test]
+    fn registers_visit_paths_only_when_the_line_parses() {
+        let mut db = db_with(&["x"]);
+        let parsed = parse_requirement("req visits(cave \\ entrance) > x", &mut db).unwrap();
+        let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Greater);
+        assert_eq!(stmt.left, Expression::Visits(0));
+        assert_eq!(db.visit_paths, vec!["cave \\ entrance".to_string()]);
+
+        assert!(parse_requirement("req visits(hub) > y", &mut db).is_err());
+        assert!(parse_requirement("req visits() > 0", &mut db).is_err());
+        assert_eq!(db.visit_paths.len(), 1);
+    }
+
     #[test]
     fn parses_logical_and() {
-        let db = db_with(&["x", "y"]);
-        let parsed = parse_requirement("req x > 0 and y > 0", &db).unwrap();
+        let mut db = db_with(&["x", "y"]);
+        let parsed = parse_requirement("req x > 0 and y > 0", &mut db).unwrap();
         assert!(matches!(parsed.expression, BooleanExpression::And(_, _)));
     }
 
     #[test]
     fn parses_logical_or() {
-        let db = db_with(&["x", "y"]);
-        let parsed = parse_requirement("req x > 0 or y > 0", &db).unwrap();
+        let mut db = db_with(&["x", "y"]);
+        let parsed = parse_requirement("req x > 0 or y > 0", &mut db).unwrap();
         assert!(matches!(parsed.expression, BooleanExpression::Or(_, _)));
     }
 
     #[test]
     fn parses_logical_not() {
-        let db = db_with(&["x"]);
-        let parsed = parse_requirement("req not x > 0", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_requirement("req not x > 0", &mut db).unwrap();
         assert!(matches!(parsed.expression, BooleanExpression::Not(_)));
     }
 
     #[test]
     fn parses_arithmetic_rhs() {
-        let db = db_with(&["x", "y"]);
-        let parsed = parse_requirement("req x > y + 1", &db).unwrap();
+        let mut db = db_with(&["x", "y"]);
+        let parsed = parse_requirement("req x > y + 1", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Greater);
         assert!(matches!(stmt.right, Expression::Binary { .. }));
     }
 
     #[test]
     fn parses_negative_literal_rhs() {
-        let db = db_with(&["x"]);
-        let parsed = parse_requirement("req x > -10", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_requirement("req x > -10", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Greater);
         assert_eq!(stmt.right, Expression::Literal(Value::Integer(-10)));
     }
 
     #[test]
     fn returns_undefined_for_lhs() {
-        let db = db_with(&[]);
+        let mut db = db_with(&[]);
         assert_eq!(
-            parse_requirement("req mana > 0", &db).unwrap_err(),
+            parse_requirement("req mana > 0", &mut db).unwrap_err(),
             RequirementParseError::UndefinedVariable {
                 name: "mana".to_string()
             }
@@ -445,9 +491,9 @@ mod tests {
 
     #[test]
     fn returns_undefined_for_rhs_inside_expression() {
-        let db = db_with(&["health"]);
+        let mut db = db_with(&["health"]);
         assert_eq!(
-            parse_requirement("req health > 5 + mana", &db).unwrap_err(),
+            parse_requirement("req health > 5 + mana", &mut db).unwrap_err(),
             RequirementParseError::UndefinedVariable {
                 name: "mana".to_string()
             }
@@ -456,9 +502,9 @@ mod tests {
 
     #[test]
     fn returns_malformed_for_dangling_operator() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_requirement("req x > 5 +", &db).unwrap_err(),
+            parse_requirement("req x > 5 +", &mut db).unwrap_err(),
             RequirementParseError::MalformedExpression {
                 expression: "x > 5 +".to_string()
             }
@@ -467,9 +513,9 @@ mod tests {
 
     #[test]
     fn returns_unknown_symbol_for_tilde() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_requirement("req x ~ 5", &db).unwrap_err(),
+            parse_requirement("req x ~ 5", &mut db).unwrap_err(),
             RequirementParseError::UnknownSymbol {
                 symbol: "~".to_string()
             }
@@ -481,9 +527,9 @@ mod tests {
         // `&` is the most common typo for `and` — surface it as an
         // unknown operator (not "unknown comparison operator", since `&`
         // isn't a comparison operator at all).
-        let db = db_with(&["x", "y"]);
+        let mut db = db_with(&["x", "y"]);
         assert_eq!(
-            parse_requirement("req x > 0 & y > 0", &db).unwrap_err(),
+            parse_requirement("req x > 0 & y > 0", &mut db).unwrap_err(),
             RequirementParseError::UnknownSymbol {
                 symbol: "&".to_string()
             }
@@ -501,26 +547,26 @@ mod tests {
 
     #[test]
     fn bare_keyword_is_missing_condition() {
-        let db = db_with(&[]);
+        let mut db = db_with(&[]);
         assert_eq!(
-            parse_requirement("req", &db).unwrap_err(),
+            parse_requirement("req", &mut db).unwrap_err(),
             RequirementParseError::MissingCondition
         );
     }
 
     #[test]
     fn tab_after_keyword_parses() {
-        let db = db_with(&["x"]);
-        let parsed = parse_requirement("req\tx > 0", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_requirement("req\tx > 0", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Greater);
         assert_eq!(stmt.left, Expression::Variable(0));
     }
 
     #[test]
     fn rejects_bare_integer_left_of_and() {
-        let db = db_with(&["health", "shield"]);
+        let mut db = db_with(&["health", "shield"]);
         assert_eq!(
-            parse_requirement("req health and shield > 0", &db).unwrap_err(),
+            parse_requirement("req health and shield > 0", &mut db).unwrap_err(),
             RequirementParseError::LogicalBareIntegerOperand {
                 operator: LogicalKeyword::And,
             }
@@ -529,9 +575,9 @@ mod tests {
 
     #[test]
     fn rejects_bare_integer_right_of_and() {
-        let db = db_with(&["x", "y"]);
+        let mut db = db_with(&["x", "y"]);
         assert_eq!(
-            parse_requirement("req x > 0 and y", &db).unwrap_err(),
+            parse_requirement("req x > 0 and y", &mut db).unwrap_err(),
             RequirementParseError::LogicalBareIntegerOperand {
                 operator: LogicalKeyword::And,
             }
@@ -540,9 +586,9 @@ mod tests {
 
     #[test]
     fn rejects_bare_integer_right_of_or() {
-        let db = db_with(&["x", "y"]);
+        let mut db = db_with(&["x", "y"]);
         assert_eq!(
-            parse_requirement("req x > 0 or y", &db).unwrap_err(),
+            parse_requirement("req x > 0 or y", &mut db).unwrap_err(),
             RequirementParseError::LogicalBareIntegerOperand {
                 operator: LogicalKeyword::Or,
             }
@@ -551,9 +597,9 @@ mod tests {
 
     #[test]
     fn rejects_missing_right_operand_and() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_requirement("req x > 0 and", &db).unwrap_err(),
+            parse_requirement("req x > 0 and", &mut db).unwrap_err(),
             RequirementParseError::LogicalMissingRightOperand {
                 operator: LogicalKeyword::And,
                 source: "x > 0 and".to_string(),
@@ -563,9 +609,9 @@ mod tests {
 
     #[test]
     fn rejects_unbalanced_open_paren() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_requirement("req (x > 0 and x < 10", &db).unwrap_err(),
+            parse_requirement("req (x > 0 and x < 10", &mut db).unwrap_err(),
             RequirementParseError::LogicalUnbalancedParentheses {
                 source: "(x > 0 and x < 10".to_string(),
             }
@@ -574,9 +620,9 @@ mod tests {
 
     #[test]
     fn rejects_literal_overflow_with_literal_in_error() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_requirement("req x > 99999999999999999999", &db).unwrap_err(),
+            parse_requirement("req x > 99999999999999999999", &mut db).unwrap_err(),
             RequirementParseError::LiteralOverflow {
                 literal: "99999999999999999999".to_string(),
             }
@@ -588,9 +634,9 @@ mod tests {
         // `==` is what C/Python users type instead of `=` for equality.
         // The dedicated error lets the caller emit a message that names
         // the right operator instead of falling through to "malformed".
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_requirement("req x == 5", &db).unwrap_err(),
+            parse_requirement("req x == 5", &mut db).unwrap_err(),
             RequirementParseError::DoubleEquals
         );
     }
@@ -609,8 +655,8 @@ mod tests {
     fn truthiness_shortcut_desugars_to_equals_true() {
         // `req flag` on a bool variable is the truthiness shortcut: it
         // desugars to `flag = true`.
-        let db = db_with_bools(&["flag"]);
-        let parsed = parse_requirement("req flag", &db).unwrap();
+        let mut db = db_with_bools(&["flag"]);
+        let parsed = parse_requirement("req flag", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Equal);
         assert_eq!(stmt.left, Expression::Variable(0));
         assert_eq!(stmt.right, Expression::Literal(Value::Boolean(true)));
@@ -618,8 +664,8 @@ mod tests {
 
     #[test]
     fn truthiness_shortcut_negated_with_not() {
-        let db = db_with_bools(&["flag"]);
-        let parsed = parse_requirement("req not flag", &db).unwrap();
+        let mut db = db_with_bools(&["flag"]);
+        let parsed = parse_requirement("req not flag", &mut db).unwrap();
         match parsed.expression {
             BooleanExpression::Not(inner) => {
                 assert_comparison(&inner, ComparisonOperator::Equal);
@@ -630,8 +676,8 @@ mod tests {
 
     #[test]
     fn truthiness_shortcuts_combine_with_and() {
-        let db = db_with_bools(&["a", "b"]);
-        let parsed = parse_requirement("req a and b", &db).unwrap();
+        let mut db = db_with_bools(&["a", "b"]);
+        let parsed = parse_requirement("req a and b", &mut db).unwrap();
         assert!(matches!(parsed.expression, BooleanExpression::And(_, _)));
     }
 
@@ -639,9 +685,9 @@ mod tests {
     fn bare_int_operand_is_not_truthiness() {
         // The truthiness shortcut is bool-only; a bare int operand keeps
         // its existing malformed-expression diagnostic.
-        let db = db_with(&["health"]);
+        let mut db = db_with(&["health"]);
         assert_eq!(
-            parse_requirement("req health", &db).unwrap_err(),
+            parse_requirement("req health", &mut db).unwrap_err(),
             RequirementParseError::MalformedExpression {
                 expression: "health".to_string(),
             }
@@ -650,8 +696,8 @@ mod tests {
 
     #[test]
     fn compares_bool_variable_to_literal() {
-        let db = db_with_bools(&["flag"]);
-        let parsed = parse_requirement("req flag = true", &db).unwrap();
+        let mut db = db_with_bools(&["flag"]);
+        let parsed = parse_requirement("req flag = true", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Equal);
         assert_eq!(stmt.left, Expression::Variable(0));
         assert_eq!(stmt.right, Expression::Literal(Value::Boolean(true)));
@@ -659,8 +705,8 @@ mod tests {
 
     #[test]
     fn compares_two_bool_variables() {
-        let db = db_with_bools(&["a", "b"]);
-        let parsed = parse_requirement("req a != b", &db).unwrap();
+        let mut db = db_with_bools(&["a", "b"]);
+        let parsed = parse_requirement("req a != b", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::NotEqual);
         assert_eq!(stmt.left, Expression::Variable(0));
         assert_eq!(stmt.right, Expression::Variable(1));
@@ -668,9 +714,9 @@ mod tests {
 
     #[test]
     fn ordering_operator_on_bool_is_rejected() {
-        let db = db_with_bools(&["flag"]);
+        let mut db = db_with_bools(&["flag"]);
         assert_eq!(
-            parse_requirement("req flag > false", &db).unwrap_err(),
+            parse_requirement("req flag > false", &mut db).unwrap_err(),
             RequirementParseError::NonOrderedComparison {
                 operator: ComparisonOperator::Greater,
                 kind: ValueKind::Boolean,
@@ -680,9 +726,9 @@ mod tests {
 
     #[test]
     fn comparing_bool_to_int_literal_is_type_mismatch() {
-        let db = db_with_bools(&["flag"]);
+        let mut db = db_with_bools(&["flag"]);
         assert_eq!(
-            parse_requirement("req flag = 1", &db).unwrap_err(),
+            parse_requirement("req flag = 1", &mut db).unwrap_err(),
             RequirementParseError::ComparisonTypeMismatch {
                 left_kind: ValueKind::Boolean,
                 left_token: "flag".to_string(),
@@ -697,7 +743,7 @@ mod tests {
         let mut db = db_with_bools(&["flag"]);
         db.add_variable(Variable::new_integer("health", 0));
         assert_eq!(
-            parse_requirement("req flag = health", &db).unwrap_err(),
+            parse_requirement("req flag = health", &mut db).unwrap_err(),
             RequirementParseError::ComparisonTypeMismatch {
                 left_kind: ValueKind::Boolean,
                 left_token: "flag".to_string(),
@@ -709,9 +755,9 @@ mod tests {
 
     #[test]
     fn truthiness_shortcut_on_undeclared_is_undefined_variable() {
-        let db = db_with_bools(&["flag"]);
+        let mut db = db_with_bools(&["flag"]);
         assert_eq!(
-            parse_requirement("req missing", &db).unwrap_err(),
+            parse_requirement("req missing", &mut db).unwrap_err(),
             RequirementParseError::UndefinedVariable {
                 name: "missing".to_string(),
             }
@@ -730,7 +776,7 @@ mod tests {
 
     #[test]
     fn parses_string_literal_rhs_for_equal_and_not_equal() {
-        let db = db_with_strings(&[("name", "Aria")]);
+        let mut db = db_with_strings(&[("name", "Aria")]);
         for (input, expected_operator, expected_literal) in [
             ("req name = \"Aria\"", ComparisonOperator::Equal, "Aria"),
             (
@@ -739,7 +785,7 @@ mod tests {
                 "Brenn",
             ),
         ] {
-            let parsed = parse_requirement(input, &db).unwrap();
+            let parsed = parse_requirement(input, &mut db).unwrap();
             let stmt = assert_comparison(&parsed.expression, expected_operator);
             assert_eq!(stmt.left, Expression::Variable(0));
             assert_eq!(
@@ -751,8 +797,8 @@ mod tests {
 
     #[test]
     fn string_literal_rhs_decodes_escapes() {
-        let db = db_with_strings(&[("greeting", "hi\nthere")]);
-        let parsed = parse_requirement("req greeting = \"hi\\nthere\"", &db).unwrap();
+        let mut db = db_with_strings(&[("greeting", "hi\nthere")]);
+        let parsed = parse_requirement("req greeting = \"hi\\nthere\"", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Equal);
         assert_eq!(
             stmt.right,
@@ -762,8 +808,8 @@ mod tests {
 
     #[test]
     fn parses_string_variable_on_both_sides() {
-        let db = db_with_strings(&[("a", "Aria"), ("b", "Aria")]);
-        let parsed = parse_requirement("req a = b", &db).unwrap();
+        let mut db = db_with_strings(&[("a", "Aria"), ("b", "Aria")]);
+        let parsed = parse_requirement("req a = b", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Equal);
         assert_eq!(stmt.left, Expression::Variable(0));
         assert_eq!(stmt.right, Expression::Variable(1));
@@ -771,9 +817,9 @@ mod tests {
 
     #[test]
     fn comparing_string_to_int_literal_is_type_mismatch() {
-        let db = db_with_strings(&[("name", "Aria")]);
+        let mut db = db_with_strings(&[("name", "Aria")]);
         assert_eq!(
-            parse_requirement("req name = 1", &db).unwrap_err(),
+            parse_requirement("req name = 1", &mut db).unwrap_err(),
             RequirementParseError::ComparisonTypeMismatch {
                 left_kind: ValueKind::String,
                 left_token: "name".to_string(),
@@ -785,9 +831,9 @@ mod tests {
 
     #[test]
     fn ordering_operator_on_strings_is_rejected() {
-        let db = db_with_strings(&[("name", "Aria")]);
+        let mut db = db_with_strings(&[("name", "Aria")]);
         assert_eq!(
-            parse_requirement("req name > \"Aaa\"", &db).unwrap_err(),
+            parse_requirement("req name > \"Aaa\"", &mut db).unwrap_err(),
             RequirementParseError::NonOrderedComparison {
                 operator: ComparisonOperator::Greater,
                 kind: ValueKind::String,
@@ -797,9 +843,9 @@ mod tests {
 
     #[test]
     fn bare_string_variable_has_no_truthiness_shortcut() {
-        let db = db_with_strings(&[("name", "Aria")]);
+        let mut db = db_with_strings(&[("name", "Aria")]);
         assert_eq!(
-            parse_requirement("req name", &db).unwrap_err(),
+            parse_requirement("req name", &mut db).unwrap_err(),
             RequirementParseError::MalformedExpression {
                 expression: "name".to_string(),
             }
@@ -808,9 +854,9 @@ mod tests {
 
     #[test]
     fn unterminated_string_literal_is_malformed() {
-        let db = db_with_strings(&[("name", "Aria")]);
+        let mut db = db_with_strings(&[("name", "Aria")]);
         assert_eq!(
-            parse_requirement("req name = \"Aria", &db).unwrap_err(),
+            parse_requirement("req name = \"Aria", &mut db).unwrap_err(),
             RequirementParseError::MalformedExpression {
                 expression: "name = \"Aria".to_string(),
             }
@@ -832,12 +878,12 @@ mod tests {
 
     #[test]
     fn parses_variant_literal_rhs_for_equal_and_not_equal() {
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         for (input, expected_operator, expected_variant) in [
             ("req mood = happy", ComparisonOperator::Equal, "happy"),
             ("req mood != sad", ComparisonOperator::NotEqual, "sad"),
         ] {
-            let parsed = parse_requirement(input, &db).unwrap();
+            let parsed = parse_requirement(input, &mut db).unwrap();
             let stmt = assert_comparison(&parsed.expression, expected_operator);
             assert_eq!(stmt.left, Expression::Variable(0));
             assert_eq!(
@@ -861,7 +907,7 @@ mod tests {
                 variants: vec!["sunny".to_string(), "happy".to_string()],
             },
         ));
-        let parsed = parse_requirement("req weather = happy", &db).unwrap();
+        let parsed = parse_requirement("req weather = happy", &mut db).unwrap();
         let stmt = assert_comparison(&parsed.expression, ComparisonOperator::Equal);
         assert_eq!(stmt.left, Expression::Variable(1));
         assert_eq!(
@@ -877,9 +923,9 @@ mod tests {
     fn unknown_variant_on_rhs_of_enum_req_is_undefined() {
         // A bare identifier that is not a variant (and not a variable) falls
         // through to the arithmetic body, which reports it as undefined.
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         assert_eq!(
-            parse_requirement("req mood = ecstatic", &db).unwrap_err(),
+            parse_requirement("req mood = ecstatic", &mut db).unwrap_err(),
             RequirementParseError::UndefinedVariable {
                 name: "ecstatic".to_string(),
             }
@@ -902,7 +948,7 @@ mod tests {
             ("req mood = other", ComparisonOperator::Equal),
             ("req mood != other", ComparisonOperator::NotEqual),
         ] {
-            let parsed = parse_requirement(input, &db).unwrap();
+            let parsed = parse_requirement(input, &mut db).unwrap();
             let stmt = assert_comparison(&parsed.expression, expected_operator);
             assert_eq!(stmt.left, Expression::Variable(0));
             assert_eq!(stmt.right, Expression::Variable(1));
@@ -922,7 +968,7 @@ mod tests {
             },
         ));
         assert_eq!(
-            parse_requirement("req mood = weather", &db).unwrap_err(),
+            parse_requirement("req mood = weather", &mut db).unwrap_err(),
             RequirementParseError::ComparisonTypeMismatch {
                 left_kind: ValueKind::Enum,
                 left_token: "mood".to_string(),
@@ -936,7 +982,7 @@ mod tests {
     fn ordering_operator_on_enum_is_rejected() {
         // Enums have no ordering; the four ordering operators are parse-time
         // errors on an enum comparison.
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         for (input, operator) in [
             ("req mood < happy", ComparisonOperator::Less),
             ("req mood <= happy", ComparisonOperator::LessOrEqual),
@@ -944,7 +990,7 @@ mod tests {
             ("req mood >= happy", ComparisonOperator::GreaterOrEqual),
         ] {
             assert_eq!(
-                parse_requirement(input, &db).unwrap_err(),
+                parse_requirement(input, &mut db).unwrap_err(),
                 RequirementParseError::NonOrderedComparison {
                     operator,
                     kind: ValueKind::Enum,
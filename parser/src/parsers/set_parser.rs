@@ -5,11 +5,16 @@
 //! inference happen at parse time; the expression is evaluated later by the
 //! runtime.
 
-use cuentitos_common::{AssignmentOperator, Database, Expression, Value, ValueKind, VariableId};
+use cuentitos_common::{
+    AssignmentOperator, Database, Expression, FunctionId, FunctionSignature, Value, ValueKind,
+    VariableId, VisitsId,
+};
 
+use crate::arithmetic::FunctionCallError;
 use crate::expression::{parse_expression, ParseExpressionError, VariableResolver};
 use crate::parsers::type_inference::{infer_type, TypeInferenceError};
 use crate::parsers::variables_parser::is_valid_identifier;
+use crate::parsers::visit_paths::VisitPaths;
 use crate::string_literal::StringLiteralError;
 
 /// Result of parsing a `set` line.
@@ -120,6 +125,8 @@ pub enum SetParseError {
     /// one of the target enum's declared variants (and not a variable of any
     /// kind either). Carries the offending value and the target enum's name.
     EnumInvalidVariant { value: String, enum_name: String },
+    /// The RHS called a function the wrong way.
+    Function(FunctionCallError),
 }
 
 /// Try to parse `content` as a `set` statement.
@@ -133,7 +140,10 @@ pub enum SetParseError {
 /// `pub(crate)` so the predicate-then-parse contract is enforced by
 /// crate-level visibility — external callers cannot bypass `is_set_line`
 /// and stumble into the misleading `MissingLhs` fallback.
-pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet, SetParseError> {
+pub(crate) fn parse_set(
+    content: &str,
+    database: &mut Database,
+) -> Result<ParsedSet, SetParseError> {
     let rest = match strip_keyword(content, "set") {
         StripResult::Stripped(rest) => rest,
         StripResult::BareKeyword => return Err(SetParseError::MissingLhs),
@@ -171,6 +181,9 @@ pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet,
     }
 
     let lhs_kind = database.variables[variable_id].kind();
+    // A function call is typed by its declared return kind, so it takes the
+    // shared arithmetic path whatever the target's kind.
+    let calls_function = starts_with_call(rhs);
 
     // A bool `set` has its own narrow RHS grammar: `true`, `false`, or a
     // reference to an earlier bool variable. Logical operators (`and`/`or`/
@@ -184,12 +197,14 @@ pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet,
                 kind: lhs_kind,
             });
         }
-        let expression = parse_bool_rhs(rhs, lhs, database)?;
-        return Ok(ParsedSet {
-            variable_id,
-            operator,
-            expression,
-        });
+        if !calls_function {
+            let expression = parse_bool_rhs(rhs, lhs, database)?;
+            return Ok(ParsedSet {
+                variable_id,
+                operator,
+                expression,
+            });
+        }
     }
 
     // A string `set` has its own narrow RHS grammar — a single double-quoted
@@ -204,12 +219,14 @@ pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet,
                 kind: lhs_kind,
             });
         }
-        let expression = parse_string_rhs(rhs, lhs, database)?;
-        return Ok(ParsedSet {
-            variable_id,
-            operator,
-            expression,
-        });
+        if !calls_function {
+            let expression = parse_string_rhs(rhs, lhs, database)?;
+            return Ok(ParsedSet {
+                variable_id,
+                operator,
+                expression,
+            });
+        }
     }
 
     // An enum `set` has the narrowest RHS grammar of all: a single bare
@@ -224,15 +241,21 @@ pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet,
                 kind: lhs_kind,
             });
         }
-        let expression = parse_enum_rhs(rhs, lhs, variable_id, database)?;
-        return Ok(ParsedSet {
-            variable_id,
-            operator,
-            expression,
-        });
+        if !calls_function {
+            let expression = parse_enum_rhs(rhs, lhs, variable_id, database)?;
+            return Ok(ParsedSet {
+                variable_id,
+                operator,
+                expression,
+            });
+        }
     }
 
-    let resolver = DatabaseResolver { database };
+    let visit_paths = VisitPaths::new(database);
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: Some(&visit_paths),
+    };
     let expression = match parse_expression(rhs, &resolver) {
         Ok(expression) => expression,
         Err(ParseExpressionError::UndefinedVariable { name }) => {
@@ -252,7 +275,11 @@ pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet,
                 expression: rhs.to_string(),
             });
         }
+        Err(ParseExpressionError::Function(error)) => {
+            return Err(SetParseError::Function(error));
+        }
     };
+    visit_paths.commit(database);
 
     let rhs_kind = match infer_type(&expression, database) {
         Ok(kind) => kind,
@@ -269,6 +296,7 @@ pub(crate) fn parse_set(content: &str, database: &Database) -> Result<ParsedSet,
                 kind,
             });
         }
+        Err(TypeInferenceError::Function(error)) => return Err(SetParseError::Function(error)),
     };
 
     if lhs_kind != rhs_kind {
@@ -332,6 +360,10 @@ fn first_non_float_leaf(
         Expression::Binary { left, right, .. } => {
             first_non_float_leaf(left, database).or_else(|| first_non_float_leaf(right, database))
         }
+        Expression::Visits(id) => Some((visits_token(*id, database), ValueKind::Integer)),
+        Expression::Call { function, .. } => {
+            call_token(*function, database).filter(|(_, kind)| *kind != ValueKind::Float)
+        }
     }
 }
 
@@ -388,7 +420,10 @@ fn parse_bool_rhs(rhs: &str, lhs: &str, database: &Database) -> Result<Expressio
     // in the diagnostic. If the expression can't even be parsed, fall back to
     // a generic type mismatch naming the whole RHS as an int (matching the
     // default-folder heuristic: unknown shape implies numeric).
-    let resolver = DatabaseResolver { database };
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: None,
+    };
     match parse_expression(trimmed, &resolver) {
         Ok(expression) => match first_non_bool_leaf(&expression, database) {
             Some((found_token, found)) => Err(SetParseError::BoolTypeMismatch {
@@ -432,6 +467,10 @@ fn first_non_bool_leaf(
         Expression::Binary { left, right, .. } => {
             first_non_bool_leaf(left, database).or_else(|| first_non_bool_leaf(right, database))
         }
+        Expression::Visits(id) => Some((visits_token(*id, database), ValueKind::Integer)),
+        Expression::Call { function, .. } => {
+            call_token(*function, database).filter(|(_, kind)| *kind != ValueKind::Boolean)
+        }
     }
 }
 
@@ -486,7 +525,10 @@ fn parse_string_rhs(
     // arithmetic body so a numeric/bool RHS (`1`, `count + 1`) surfaces a
     // type-mismatch that names the offending leaf; a genuine parse failure
     // (`"a" "b"` already handled above, `5 +`) is malformed.
-    let resolver = DatabaseResolver { database };
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: None,
+    };
     match parse_expression(rhs, &resolver) {
         Ok(expression) => match first_non_string_leaf(&expression, database) {
             Some((found_token, found)) => Err(SetParseError::StringTypeMismatch {
@@ -529,6 +571,10 @@ fn first_non_string_leaf(
         Expression::Binary { left, right, .. } => {
             first_non_string_leaf(left, database).or_else(|| first_non_string_leaf(right, database))
         }
+        Expression::Visits(id) => Some((visits_token(*id, database), ValueKind::Integer)),
+        Expression::Call { function, .. } => {
+            call_token(*function, database).filter(|(_, kind)| *kind != ValueKind::String)
+        }
     }
 }
 
@@ -593,7 +639,10 @@ fn parse_enum_rhs(
     // Fall back to the shared arithmetic body so a numeric RHS surfaces a
     // type-mismatch naming the offending leaf; a genuine parse failure is
     // malformed.
-    let resolver = DatabaseResolver { database };
+    let resolver = DatabaseResolver {
+        database,
+        visit_paths: None,
+    };
     match parse_expression(rhs, &resolver) {
         Ok(expression) => match first_leaf(&expression, database) {
             Some((found_token, found)) => Err(SetParseError::EnumTypeMismatch {
@@ -624,9 +673,32 @@ fn first_leaf(expression: &Expression, database: &Database) -> Option<(String, V
             Some((variable.name.clone(), variable.kind()))
         }
         Expression::Binary { left, .. } => first_leaf(left, database),
+        Expression::Visits(id) => Some((visits_token(*id, database), ValueKind::Integer)),
+        Expression::Call { function, .. } => call_token(*function, database),
     }
 }
 
+/// A `visits(path)` leaf as written, for naming it in a type mismatch.
+fn visits_token(id: VisitsId, database: &Database) -> String {
+    format!("visits({})", database.visit_paths[id])
+}
+
+/// A function call leaf, named by its function, with the kind it returns.
+fn call_token(id: FunctionId, database: &Database) -> Option<(String, ValueKind)> {
+    let function = &database.functions[id];
+    Some((format!("{}(...)", function.name), function.returns?))
+}
+
+/// Whether `rhs` opens with a call, `name(`, rather than a variable or a
+/// literal. `visits(path)` reads a visit count, not a function.
+fn starts_with_call(rhs: &str) -> bool {
+    let name_len = rhs
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(rhs.len());
+    let name = &rhs[..name_len];
+    is_valid_identifier(name) && name != "visits" && rhs[name_len..].trim_start().starts_with('(')
+}
+
 /// Cheap predicate: does `content` (already trimmed of indentation) begin
 /// with the `set` keyword followed by ASCII whitespace? Callers must
 /// filter with this before [`parse_set`] — calling `parse_set` on
@@ -665,14 +737,27 @@ fn strip_keyword<'a>(content: &'a str, keyword: &str) -> StripResult<'a> {
     }
 }
 
+/// `visit_paths` is `None` for the bool, string and enum right-hand sides,
+/// which only parse arithmetic to name a mismatched leaf and never keep it.
 struct DatabaseResolver<'a> {
     database: &'a Database,
+    visit_paths: Option<&'a VisitPaths>,
 }
 
 impl VariableResolver for DatabaseResolver<'_> {
     fn resolve(&self, name: &str) -> Option<VariableId> {
         self.database.variable_id(name)
     }
+
+    fn visits(&self, path: &str) -> Option<VisitsId> {
+        self.visit_paths
+            .map(|visit_paths| visit_paths.register(path))
+    }
+
+    fn function(&self, name: &str) -> Option<(FunctionId, FunctionSignature)> {
+        let id = self.database.function_id(name)?;
+        Some((id, self.database.functions[id].clone()))
+    }
 }
 
 /// Locate the assignment operator and split into `(lhs, op, rhs)`. Compound
@@ -728,8 +813,8 @@ mod tests {
 
     #[test]
     fn parses_plain_assignment() {
-        let db = db_with(&["x"]);
-        let parsed = parse_set("set x = 5", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_set("set x = 5", &mut db).unwrap();
         assert_eq!(parsed.variable_id, 0);
         assert_eq!(parsed.operator, AssignmentOperator::Assign);
         assert_eq!(parsed.expression, Expression::Literal(Value::Integer(5)));
@@ -737,30 +822,30 @@ mod tests {
 
     #[test]
     fn parses_compound_assignment() {
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         for (input, expected_operator) in [
             ("set x += 1", AssignmentOperator::AddAssign),
             ("set x -= 1", AssignmentOperator::SubtractAssign),
             ("set x *= 1", AssignmentOperator::MultiplyAssign),
             ("set x /= 1", AssignmentOperator::DivideAssign),
         ] {
-            let parsed = parse_set(input, &db).unwrap();
+            let parsed = parse_set(input, &mut db).unwrap();
             assert_eq!(parsed.operator, expected_operator, "input: {input}");
         }
     }
 
     #[test]
     fn parses_compound_with_no_whitespace() {
-        let db = db_with(&["x"]);
-        let parsed = parse_set("set x+=1", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_set("set x+=1", &mut db).unwrap();
         assert_eq!(parsed.operator, AssignmentOperator::AddAssign);
         assert_eq!(parsed.expression, Expression::Literal(Value::Integer(1)));
     }
 
     #[test]
     fn returns_undefined_for_lhs() {
-        let db = db_with(&["other"]);
-        let err = parse_set("set unknown = 1", &db).unwrap_err();
+        let mut db = db_with(&["other"]);
+        let err = parse_set("set unknown = 1", &mut db).unwrap_err();
         assert_eq!(
             err,
             SetParseError::UndefinedVariable {
@@ -771,8 +856,8 @@ mod tests {
 
     #[test]
     fn returns_undefined_for_rhs_variable() {
-        let db = db_with(&["score"]);
-        let err = parse_set("set score = health + 1", &db).unwrap_err();
+        let mut db = db_with(&["score"]);
+        let err = parse_set("set score = health + 1", &mut db).unwrap_err();
         assert_eq!(
             err,
             SetParseError::UndefinedVariable {
@@ -783,8 +868,8 @@ mod tests {
 
     #[test]
     fn returns_malformed_for_dangling_operator() {
-        let db = db_with(&["x"]);
-        let err = parse_set("set x = 5 +", &db).unwrap_err();
+        let mut db = db_with(&["x"]);
+        let err = parse_set("set x = 5 +", &mut db).unwrap_err();
         assert_eq!(
             err,
             SetParseError::MalformedExpression {
@@ -799,9 +884,9 @@ mod tests {
         // its text intact so the diagnostic can name it. Previously
         // collapsed into MalformedExpression because the set tokenizer
         // discarded the offending text on `u64::from_str` failure.
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_set("set x = 99999999999999999999", &db).unwrap_err(),
+            parse_set("set x = 99999999999999999999", &mut db).unwrap_err(),
             SetParseError::LiteralOverflow {
                 literal: "99999999999999999999".to_string(),
             }
@@ -813,9 +898,9 @@ mod tests {
         // `-9223372036854775809` = -(i64::MAX + 2). The magnitude fits
         // in u64 so the tokenizer accepts it, then `parse_unary` folds
         // the sign and `negate_u64_literal` catches the overflow.
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_set("set x = -9223372036854775809", &db).unwrap_err(),
+            parse_set("set x = -9223372036854775809", &mut db).unwrap_err(),
             SetParseError::LiteralOverflow {
                 literal: "-9223372036854775809".to_string(),
             }
@@ -826,9 +911,9 @@ mod tests {
     fn returns_literal_overflow_for_positive_one_above_i64_max() {
         // `9223372036854775808` = i64::MAX + 1. Fits in u64 but not i64.
         // Surfaces from the shared arith `parse_primary` int branch.
-        let db = db_with(&["x"]);
+        let mut db = db_with(&["x"]);
         assert_eq!(
-            parse_set("set x = 9223372036854775808", &db).unwrap_err(),
+            parse_set("set x = 9223372036854775808", &mut db).unwrap_err(),
             SetParseError::LiteralOverflow {
                 literal: "9223372036854775808".to_string(),
             }
@@ -846,8 +931,8 @@ mod tests {
 
     #[test]
     fn negative_literal_rhs_parses() {
-        let db = db_with(&["x"]);
-        let parsed = parse_set("set x = -50", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_set("set x = -50", &mut db).unwrap();
         assert_eq!(parsed.expression, Expression::Literal(Value::Integer(-50)));
     }
 
@@ -857,8 +942,8 @@ mod tests {
         // `looks_like_set_line` filter but failed `strip_prefix("set ")`
         // and triggered an `unreachable!()` panic. The keyword stripper
         // now accepts any ASCII whitespace.
-        let db = db_with(&["x"]);
-        let parsed = parse_set("set\tx = 5", &db).unwrap();
+        let mut db = db_with(&["x"]);
+        let parsed = parse_set("set\tx = 5", &mut db).unwrap();
         assert_eq!(parsed.variable_id, 0);
         assert_eq!(parsed.expression, Expression::Literal(Value::Integer(5)));
     }
@@ -871,23 +956,23 @@ mod tests {
 
     #[test]
     fn float_literal_rhs_parses() {
-        let db = db_with_float("x");
-        let parsed = parse_set("set x = 7.5", &db).unwrap();
+        let mut db = db_with_float("x");
+        let parsed = parse_set("set x = 7.5", &mut db).unwrap();
         assert_eq!(parsed.operator, AssignmentOperator::Assign);
         assert_eq!(parsed.expression, Expression::Literal(Value::Float(7.5)));
     }
 
     #[test]
     fn negative_float_literal_rhs_parses() {
-        let db = db_with_float("x");
-        let parsed = parse_set("set x = -7.5", &db).unwrap();
+        let mut db = db_with_float("x");
+        let parsed = parse_set("set x = -7.5", &mut db).unwrap();
         assert_eq!(parsed.expression, Expression::Literal(Value::Float(-7.5)));
     }
 
     #[test]
     fn float_compound_assignment_parses() {
-        let db = db_with_float("score");
-        let parsed = parse_set("set score /= 2.0", &db).unwrap();
+        let mut db = db_with_float("score");
+        let parsed = parse_set("set score /= 2.0", &mut db).unwrap();
         assert_eq!(parsed.operator, AssignmentOperator::DivideAssign);
         assert_eq!(parsed.expression, Expression::Literal(Value::Float(2.0)));
     }
@@ -901,7 +986,7 @@ mod tests {
         db.add_variable(Variable::new_integer("count", 3));
         db.add_variable(Variable::new("ratio", Value::Float(0.0)));
         assert_eq!(
-            parse_set("set ratio = count", &db).unwrap_err(),
+            parse_set("set ratio = count", &mut db).unwrap_err(),
             SetParseError::FloatTypeMismatch {
                 variable: "ratio".to_string(),
                 found_token: "count".to_string(),
@@ -915,10 +1000,10 @@ mod tests {
         // A lone float literal whose magnitude exceeds the largest finite
         // `f64` parses to infinity; the `set` rejects it as an overflow that
         // names the target variable, rather than storing the infinity.
-        let db = db_with_float("result");
+        let mut db = db_with_float("result");
         let literal = format!("1{}.0", "0".repeat(320));
         assert_eq!(
-            parse_set(&format!("set result = {literal}"), &db).unwrap_err(),
+            parse_set(&format!("set result = {literal}"), &mut db).unwrap_err(),
             SetParseError::FloatLiteralOverflow {
                 variable: "result".to_string(),
                 literal,
@@ -934,8 +1019,8 @@ mod tests {
 
     #[test]
     fn string_literal_rhs_parses() {
-        let db = db_with_string("name");
-        let parsed = parse_set("set name = \"Brenn\"", &db).unwrap();
+        let mut db = db_with_string("name");
+        let parsed = parse_set("set name = \"Brenn\"", &mut db).unwrap();
         assert_eq!(parsed.operator, AssignmentOperator::Assign);
         assert_eq!(
             parsed.expression,
@@ -945,8 +1030,8 @@ mod tests {
 
     #[test]
     fn string_literal_rhs_unescapes() {
-        let db = db_with_string("line");
-        let parsed = parse_set("set line = \"x\\ny\\\\z\\\"w\"", &db).unwrap();
+        let mut db = db_with_string("line");
+        let parsed = parse_set("set line = \"x\\ny\\\\z\\\"w\"", &mut db).unwrap();
         assert_eq!(
             parsed.expression,
             Expression::Literal(Value::String("x\ny\\z\"w".to_string()))
@@ -955,8 +1040,8 @@ mod tests {
 
     #[test]
     fn empty_string_literal_rhs_parses() {
-        let db = db_with_string("name");
-        let parsed = parse_set("set name = \"\"", &db).unwrap();
+        let mut db = db_with_string("name");
+        let parsed = parse_set("set name = \"\"", &mut db).unwrap();
         assert_eq!(
             parsed.expression,
             Expression::Literal(Value::String(String::new()))
@@ -967,25 +1052,25 @@ mod tests {
     fn string_variable_rhs_parses_as_reference() {
         let mut db = db_with_string("target");
         db.add_variable(Variable::new("source", Value::String("Aria".to_string())));
-        let parsed = parse_set("set target = source", &db).unwrap();
+        let parsed = parse_set("set target = source", &mut db).unwrap();
         assert_eq!(parsed.variable_id, 0);
         assert_eq!(parsed.expression, Expression::Variable(1));
     }
 
     #[test]
     fn unterminated_string_literal_rhs_is_rejected() {
-        let db = db_with_string("name");
+        let mut db = db_with_string("name");
         assert_eq!(
-            parse_set("set name = \"Brenn", &db).unwrap_err(),
+            parse_set("set name = \"Brenn", &mut db).unwrap_err(),
             SetParseError::UnterminatedStringLiteral
         );
     }
 
     #[test]
     fn invalid_escape_in_string_literal_rhs_is_rejected() {
-        let db = db_with_string("name");
+        let mut db = db_with_string("name");
         assert_eq!(
-            parse_set("set name = \"a\\qb\"", &db).unwrap_err(),
+            parse_set("set name = \"a\\qb\"", &mut db).unwrap_err(),
             SetParseError::InvalidStringEscape {
                 sequence: "\\q".to_string()
             }
@@ -996,15 +1081,15 @@ mod tests {
     fn trailing_token_after_string_literal_is_malformed() {
         // Concatenation and stray second literals are not special-cased; the
         // RHS simply fails to parse as a lone literal.
-        let db = db_with_string("name");
+        let mut db = db_with_string("name");
         assert_eq!(
-            parse_set("set name = \"a\" \"b\"", &db).unwrap_err(),
+            parse_set("set name = \"a\" \"b\"", &mut db).unwrap_err(),
             SetParseError::MalformedExpression {
                 expression: "\"a\" \"b\"".to_string()
             }
         );
         assert_eq!(
-            parse_set("set name = \"Hello, \" + \"world\"", &db).unwrap_err(),
+            parse_set("set name = \"Hello, \" + \"world\"", &mut db).unwrap_err(),
             SetParseError::MalformedExpression {
                 expression: "\"Hello, \" + \"world\"".to_string()
             }
@@ -1014,9 +1099,9 @@ mod tests {
     #[test]
     fn int_literal_rhs_of_string_set_is_a_type_mismatch() {
         // A bare int literal names itself, quoted, in the diagnostic.
-        let db = db_with_string("name");
+        let mut db = db_with_string("name");
         assert_eq!(
-            parse_set("set name = 1", &db).unwrap_err(),
+            parse_set("set name = 1", &mut db).unwrap_err(),
             SetParseError::StringTypeMismatch {
                 variable: "name".to_string(),
                 found_token: "'1'".to_string(),
@@ -1031,7 +1116,7 @@ mod tests {
         let mut db = db_with_string("name");
         db.add_variable(Variable::new_integer("count", 3));
         assert_eq!(
-            parse_set("set name = count", &db).unwrap_err(),
+            parse_set("set name = count", &mut db).unwrap_err(),
             SetParseError::StringTypeMismatch {
                 variable: "name".to_string(),
                 found_token: "count".to_string(),
@@ -1042,9 +1127,9 @@ mod tests {
 
     #[test]
     fn undeclared_variable_rhs_of_string_set_is_undefined() {
-        let db = db_with_string("name");
+        let mut db = db_with_string("name");
         assert_eq!(
-            parse_set("set name = ghost", &db).unwrap_err(),
+            parse_set("set name = ghost", &mut db).unwrap_err(),
             SetParseError::UndefinedVariable {
                 name: "ghost".to_string()
             }
@@ -1053,9 +1138,9 @@ mod tests {
 
     #[test]
     fn compound_assignment_on_string_is_rejected() {
-        let db = db_with_string("name");
+        let mut db = db_with_string("name");
         assert_eq!(
-            parse_set("set name += \"Brenn\"", &db).unwrap_err(),
+            parse_set("set name += \"Brenn\"", &mut db).unwrap_err(),
             SetParseError::CompoundAssignmentUnsupported {
                 operator: AssignmentOperator::AddAssign,
                 kind: ValueKind::String,
@@ -1078,8 +1163,8 @@ mod tests {
 
     #[test]
     fn variant_literal_rhs_parses_to_enum_literal() {
-        let db = db_with_enum("mood", &["happy", "sad", "angry"]);
-        let parsed = parse_set("set mood = sad", &db).unwrap();
+        let mut db = db_with_enum("mood", &["happy", "sad", "angry"]);
+        let parsed = parse_set("set mood = sad", &mut db).unwrap();
         assert_eq!(parsed.variable_id, 0);
         assert_eq!(parsed.operator, AssignmentOperator::Assign);
         assert_eq!(
@@ -1093,9 +1178,9 @@ mod tests {
 
     #[test]
     fn unknown_bare_identifier_rhs_is_invalid_variant() {
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         assert_eq!(
-            parse_set("set mood = ecstatic", &db).unwrap_err(),
+            parse_set("set mood = ecstatic", &mut db).unwrap_err(),
             SetParseError::EnumInvalidVariant {
                 value: "ecstatic".to_string(),
                 enum_name: "mood".to_string(),
@@ -1106,9 +1191,9 @@ mod tests {
     #[test]
     fn int_literal_rhs_of_enum_set_is_a_type_mismatch() {
         // A bare int literal names itself, quoted, in the diagnostic.
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         assert_eq!(
-            parse_set("set mood = 1", &db).unwrap_err(),
+            parse_set("set mood = 1", &mut db).unwrap_err(),
             SetParseError::EnumTypeMismatch {
                 variable: "mood".to_string(),
                 found_token: "'1'".to_string(),
@@ -1123,7 +1208,7 @@ mod tests {
         let mut db = db_with_enum("mood", &["happy", "sad"]);
         db.add_variable(Variable::new_integer("count", 3));
         assert_eq!(
-            parse_set("set mood = count", &db).unwrap_err(),
+            parse_set("set mood = count", &mut db).unwrap_err(),
             SetParseError::EnumTypeMismatch {
                 variable: "mood".to_string(),
                 found_token: "count".to_string(),
@@ -1136,9 +1221,9 @@ mod tests {
     fn string_literal_rhs_of_enum_set_is_a_type_mismatch() {
         // A double-quoted literal is named in its quoted source form, even
         // when its text matches a declared variant.
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         assert_eq!(
-            parse_set("set mood = \"happy\"", &db).unwrap_err(),
+            parse_set("set mood = \"happy\"", &mut db).unwrap_err(),
             SetParseError::EnumTypeMismatch {
                 variable: "mood".to_string(),
                 found_token: "'\"happy\"'".to_string(),
@@ -1159,7 +1244,7 @@ mod tests {
                 variants: vec!["sunny".to_string(), "happy".to_string()],
             },
         ));
-        let parsed = parse_set("set weather = happy", &db).unwrap();
+        let parsed = parse_set("set weather = happy", &mut db).unwrap();
         assert_eq!(parsed.variable_id, 1);
         assert_eq!(
             parsed.expression,
@@ -1172,9 +1257,9 @@ mod tests {
 
     #[test]
     fn compound_assignment_on_enum_is_rejected() {
-        let db = db_with_enum("mood", &["happy", "sad"]);
+        let mut db = db_with_enum("mood", &["happy", "sad"]);
         assert_eq!(
-            parse_set("set mood += happy", &db).unwrap_err(),
+            parse_set("set mood += happy", &mut db).unwrap_err(),
             SetParseError::CompoundAssignmentUnsupported {
                 operator: AssignmentOperator::AddAssign,
                 kind: ValueKind::Enum,
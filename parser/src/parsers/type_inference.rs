@@ -1,13 +1,16 @@
 //! Parse-time type inference for [`Expression`]s.
 //!
 //! Walks an [`Expression`] AST and returns the [`ValueKind`] it produces, or
-//! a [`TypeInferenceError`] when operands are inconsistent or an operator is
-//! applied to an unsupported kind. Today every variable and arithmetic
+//! a [`TypeInferenceError`] when operands are inconsistent, an operator is
+//! applied to an unsupported kind, or a function is called with arguments
+//! of other kinds than it declares. Today every variable and arithmetic
 //! operator is integer-typed, so the only way to trigger an error is by
 //! constructing an `Expression` directly in tests; the inferrer is wired now
 //! so adding `Boolean`/`Float`/`String` later is purely additive.
 
-use cuentitos_common::{BinaryOperator, Database, Expression, Value, ValueKind};
+use cuentitos_common::{BinaryOperator, Database, Expression, FunctionId, Value, ValueKind};
+
+use crate::arithmetic::FunctionCallError;
 
 /// Errors produced by [`infer_type`].
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -23,12 +26,37 @@ pub enum TypeInferenceError {
         operator: BinaryOperator,
         kind: ValueKind,
     },
+    /// A function call passed an argument of another kind than the
+    /// function declares. Always [`FunctionCallError::ArgumentKind`], so
+    /// callers report it like any other bad call.
+    Function(FunctionCallError),
+}
+
+impl TypeInferenceError {
+    /// The offending non-integer kind, for contexts that only accept
+    /// integer expressions (chance weights, frequency modifiers), or the
+    /// bad call of a [`Function`](Self::Function) error, which those
+    /// contexts report as it is.
+    pub fn non_integer_kind(self) -> Result<ValueKind, FunctionCallError> {
+        match self {
+            TypeInferenceError::Mismatch { left, right, .. } => {
+                if left == ValueKind::Integer {
+                    Ok(right)
+                } else {
+                    Ok(left)
+                }
+            }
+            TypeInferenceError::NonNumericArithmetic { kind, .. } => Ok(kind),
+            TypeInferenceError::Function(error) => Err(error),
+        }
+    }
 }
 
 /// Walk `expression` and return the [`ValueKind`] of the value it produces.
 ///
 /// `database` is consulted to look up the declared kind of any
-/// [`Expression::Variable`] reference.
+/// [`Expression::Variable`] reference, and the parameter and return kinds of
+/// any [`Expression::Call`].
 pub fn infer_type(
     expression: &Expression,
     database: &Database,
@@ -36,6 +64,16 @@ pub fn infer_type(
     match expression {
         Expression::Literal(value) => Ok(literal_kind(value)),
         Expression::Variable(id) => Ok(database.variables[*id].kind()),
+        Expression::Visits(_) => Ok(ValueKind::Integer),
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            check_arguments(*function, arguments, database)?;
+            Ok(database.functions[*function]
+                .returns
+                .expect("only functions with a return kind are parsed into expressions"))
+        }
         Expression::Binary {
             operator,
             left,
@@ -61,6 +99,32 @@ pub fn infer_type(
     }
 }
 
+/// Check that each argument of a call to `function` has the kind of the
+/// parameter it is passed as. The argument count was already checked when
+/// the call was parsed. Also used by `call` lines, whose function may not
+/// return anything.
+pub fn check_arguments(
+    function: FunctionId,
+    arguments: &[Expression],
+    database: &Database,
+) -> Result<(), TypeInferenceError> {
+    let signature = &database.functions[function];
+    for (index, (argument, &expected)) in arguments.iter().zip(&signature.parameters).enumerate() {
+        let found = infer_type(argument, database)?;
+        if found != expected {
+            return Err(TypeInferenceError::Function(
+                FunctionCallError::ArgumentKind {
+                    name: signature.name.clone(),
+                    position: index + 1,
+                    expected,
+                    found,
+                },
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn literal_kind(value: &Value) -> ValueKind {
     value.kind()
 }
@@ -102,4 +166,25 @@ mod tests {
         };
         assert_eq!(infer_type(&expression, &db).unwrap(), ValueKind::Integer);
     }
+
+    #[test]
+    fn non_integer_kind_separates_bad_calls() {
+        let mismatch = TypeInferenceError::Mismatch {
+            operator: BinaryOperator::Add,
+            left: ValueKind::Integer,
+            right: ValueKind::String,
+        };
+        assert_eq!(mismatch.non_integer_kind(), Ok(ValueKind::String));
+
+        let call = FunctionCallError::ArgumentKind {
+            name: "has_item".to_string(),
+            position: 1,
+            expected: ValueKind::String,
+            found: ValueKind::Integer,
+        };
+        assert_eq!(
+            TypeInferenceError::Function(call.clone()).non_integer_kind(),
+            Err(call)
+        );
+    }
 }
@@ -5,6 +5,7 @@ use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 use crate::expression::{parse_expression, ParseExpressionError};
+use crate::project::ProjectVariable;
 use crate::ParseError;
 
 /// Outcome of parsing a `--- variables` block.
@@ -68,48 +69,110 @@ pub fn parse_variables_block(
     // This set tolerates duplicates silently; duplicate *declaration* detection
     // happens in the main pass via `declared_lines`.
     let future_names = collect_future_names(lines, start_line_index + 1, closing_line_index);
-
-    // Second pass: parse and evaluate each declaration in order. `declared`
-    // maps each already-declared name to its folded default value, carrying
-    // both the value and (via `Value::kind`) its type so later defaults can
-    // reference earlier variables and type-check across kinds.
-    let mut declared_lines: HashMap<String, usize> = HashMap::new();
-    let mut declared: HashMap<String, Value> = HashMap::new();
-
-    for (offset, raw_line) in lines
+    let declarations = lines
         .iter()
         .copied()
         .enumerate()
         .take(closing_line_index)
         .skip(start_line_index + 1)
-    {
-        let line_number = offset + 1;
+        .map(|(offset, line)| (offset + 1, line));
+
+    VariablesBlockOutcome {
+        consumed_lines: block_span,
+        errors: parse_declarations(declarations, &future_names, database, file_path)
+            .err()
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Declare the `variables` of a project manifest, whose path is
+/// `file_path`, as if they were a `--- variables` block ahead of the main
+/// script's. Stops at the first error, as a block does.
+pub fn parse_project_variables(
+    variables: &[ProjectVariable],
+    database: &mut Database,
+    file_path: &Option<PathBuf>,
+) -> Vec<ParseError> {
+    let lines: Vec<&str> = variables
+        .iter()
+        .map(|variable| variable.declaration.trim())
+        .collect();
+    let future_names = collect_future_names(&lines, 0, lines.len());
+    let declarations = variables
+        .iter()
+        .zip(&lines)
+        .map(|(variable, line)| (variable.line, *line));
+    parse_declarations(declarations, &future_names, database, file_path)
+        .err()
+        .into_iter()
+        .collect()
+}
+
+/// Parse each `(line number, line)` declaration in order, stopping at the
+/// first error. Variables already in `database`, declared by the project
+/// manifest, can be referenced but not declared again.
+fn parse_declarations<'a>(
+    declarations: impl Iterator<Item = (usize, &'a str)>,
+    future_names: &HashSet<String>,
+    database: &mut Database,
+    file_path: &Option<PathBuf>,
+) -> Result<(), ParseError> {
+    // `declared` maps each already-declared name to its folded default value,
+    // carrying both the value and (via `Value::kind`) its type so later
+    // defaults can reference earlier variables and type-check across kinds.
+    let mut declared_lines: HashMap<String, usize> = HashMap::new();
+    let mut declared: HashMap<String, Value> = database
+        .variables
+        .iter()
+        .map(|variable| (variable.name.clone(), variable.default.clone()))
+        .collect();
+
+    for (line_number, raw_line) in declarations {
         let trimmed = raw_line.trim();
         if trimmed.is_empty() {
             continue;
         }
 
-        if let Err(error) = parse_one_declaration(
+        parse_one_declaration(
             raw_line,
             trimmed,
             line_number,
             file_path,
-            &future_names,
+            future_names,
             &mut declared_lines,
             &mut declared,
             database,
-        ) {
-            return VariablesBlockOutcome {
-                consumed_lines: block_span,
-                errors: vec![error],
-            };
-        }
+        )?;
     }
+    Ok(())
+}
 
-    VariablesBlockOutcome {
-        consumed_lines: block_span,
-        errors: Vec::new(),
+/// Reject a declaration of `name` when it was declared before: earlier in
+/// the same block, or by the project manifest.
+fn check_not_declared(
+    name: &str,
+    line_number: usize,
+    file_path: &Option<PathBuf>,
+    declared_lines: &HashMap<String, usize>,
+    declared: &HashMap<String, Value>,
+) -> Result<(), ParseError> {
+    if let Some(&previous_line) = declared_lines.get(name) {
+        return Err(ParseError::DuplicateVariable {
+            name: name.to_string(),
+            previous_line,
+            file: file_path.clone(),
+            line: line_number,
+        });
+    }
+    if declared.contains_key(name) {
+        return Err(ParseError::ProjectVariableRedeclared {
+            name: name.to_string(),
+            file: file_path.clone(),
+            line: line_number,
+        });
     }
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -202,14 +265,7 @@ fn parse_one_declaration(
         });
     }
 
-    if let Some(&previous_line) = declared_lines.get(name) {
-        return Err(ParseError::DuplicateVariable {
-            name: name.to_string(),
-            previous_line,
-            file: file_path.clone(),
-            line: line_number,
-        });
-    }
+    check_not_declared(name, line_number, file_path, declared_lines, declared)?;
 
     // Fold the default into a concrete `Value` of the declared kind. Each
     // kind owns its own default grammar: integers fold arithmetic; booleans
@@ -316,14 +372,7 @@ fn parse_enum_declaration(
         });
     }
 
-    if let Some(&previous_line) = declared_lines.get(name) {
-        return Err(ParseError::DuplicateVariable {
-            name: name.to_string(),
-            previous_line,
-            file: file_path.clone(),
-            line: line_number,
-        });
-    }
+    check_not_declared(name, line_number, file_path, declared_lines, declared)?;
 
     let raw_list = value_list_raw.unwrap();
 
@@ -1313,6 +1362,9 @@ pub fn evaluate_expression(
         Err(ParseExpressionError::UndefinedVariable { name }) => {
             return Err(EvalError::UndefinedVariable { name });
         }
+        // Defaults are folded at parse time, before the game can answer a
+        // call, so the resolver declares no functions.
+        Err(ParseExpressionError::Function(_)) => return Err(EvalError::Malformed),
     };
 
     match evaluate(&expression_ast, &|id: VariableId| &values[id]) {
@@ -1351,6 +1403,12 @@ pub fn evaluate_expression(
         Err(EvaluationError::UnsetEnum { .. }) => {
             unreachable!("integer-default fold never reads an enum")
         }
+        // The resolver declares no functions, so the fold never calls one.
+        Err(
+            EvaluationError::MissingFunction { .. }
+            | EvaluationError::FunctionSignatureMismatch { .. }
+            | EvaluationError::FunctionReturnMismatch { .. },
+        ) => unreachable!("integer-default fold never calls a function"),
     }
 }
 
@@ -0,0 +1,45 @@
+//! Section paths collected from `visits(path)` reads.
+//!
+//! ```cuentitos
+//! req visits(cave \ entrance) > 0
+//! ```
+//!
+//! A path can name a section written further down the script, so it can't
+//! be resolved while its line is parsed. Each read gets an id into
+//! `Database.visit_paths` instead, and the main parser resolves every path
+//! to a section once the whole tree is known.
+
+use std::cell::RefCell;
+
+use cuentitos_common::{Database, VisitsId};
+
+/// The paths registered while one line is parsed. Resolvers only hold a
+/// shared borrow of the database, so they register here, and the line's
+/// parser hands the paths to the database with [`VisitPaths::commit`] once
+/// the line parsed. Dropping it instead forgets them, so a line that fails
+/// to parse doesn't also report its paths.
+pub(crate) struct VisitPaths {
+    first_id: VisitsId,
+    paths: RefCell<Vec<String>>,
+}
+
+impl VisitPaths {
+    pub(crate) fn new(database: &Database) -> Self {
+        Self {
+            first_id: database.visit_paths.len(),
+            paths: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Reserve the id the path will have once committed.
+    pub(crate) fn register(&self, path: &str) -> VisitsId {
+        let mut paths = self.paths.borrow_mut();
+        paths.push(path.to_string());
+        self.first_id + paths.len() - 1
+    }
+
+    pub(crate) fn commit(self, database: &mut Database) {
+        debug_assert_eq!(database.visit_paths.len(), self.first_id);
+        database.visit_paths.extend(self.paths.into_inner());
+    }
+}
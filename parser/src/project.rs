@@ -0,0 +1,363 @@
+//! Project manifests, `cuentitos.toml`, for stories split across scripts:
+//!
+//! ```toml
+//! [story]
+//! entry = "main.cuentitos"
+//! sources = ["shared"]
+//! variables = ["int gold = 10", "enum mood = calm, angry"]
+//!
+//! [i18n]
+//! default_language = "en"
+//! ```
+//!
+//! Every path is relative to the directory of the manifest. `entry` is the
+//! main script, `main.cuentitos` when left out. `sources` are directories
+//! an `include` is also looked up in when the script isn't found next to
+//! the script including it. `variables` are declared for every script, in
+//! the syntax of a `--- variables` block, before the main script's own
+//! block. `default_language` is the language the scripts are written in.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{ParseError, Parser, Warning};
+use cuentitos_common::Database;
+
+/// File name of the manifest in a project directory.
+pub const MANIFEST_FILE: &str = "cuentitos.toml";
+
+/// Entry script of a manifest that doesn't name one.
+pub const DEFAULT_ENTRY: &str = "main.cuentitos";
+
+/// A loaded `cuentitos.toml`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    /// The directory the manifest is in.
+    pub directory: PathBuf,
+    /// The main script, relative to `directory`.
+    pub entry: PathBuf,
+    /// Directories `include` paths are also resolved from, relative to
+    /// `directory`.
+    pub sources: Vec<PathBuf>,
+    /// Declarations shared by every script, with the manifest line each
+    /// one is on.
+    pub variables: Vec<ProjectVariable>,
+    /// The language the scripts are written in, if the manifest sets it.
+    pub default_language: Option<String>,
+}
+
+/// A variable declaration of the manifest, such as `int gold = 10`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectVariable {
+    pub declaration: String,
+    pub line: usize,
+}
+
+/// Why a project couldn't be loaded or parsed.
+#[derive(Debug)]
+pub enum ProjectError {
+    /// The manifest or the main script couldn't be read.
+    Read { path: PathBuf, error: io::Error },
+    /// The manifest isn't valid.
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// The scripts have errors.
+    Parse(ParseError),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectError::Read { path, error } => {
+                write!(f, "ERROR: Can't read '{}': {}", path.display(), error)
+            }
+            ProjectError::Invalid {
+                path,
+                line,
+                message,
+            } => write!(
+                f,
+                "{}:{}: ERROR: {}",
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(MANIFEST_FILE),
+                line,
+                message
+            ),
+            ProjectError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl From<ParseError> for ProjectError {
+    fn from(error: ParseError) -> Self {
+        ProjectError::Parse(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    story: StoryTable,
+    #[serde(default)]
+    i18n: I18nTable,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoryTable {
+    entry: Option<Spanned<String>>,
+    #[serde(default)]
+    sources: Vec<Spanned<String>>,
+    #[serde(default)]
+    variables: Vec<Spanned<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct I18nTable {
+    default_language: Option<String>,
+}
+
+impl Project {
+    /// Whether `directory` has a manifest.
+    pub fn is_project(directory: &Path) -> bool {
+        directory.join(MANIFEST_FILE).is_file()
+    }
+
+    /// Load the manifest of the project in `directory`.
+    pub fn load(directory: &Path) -> Result<Project, ProjectError> {
+        let path = directory.join(MANIFEST_FILE);
+        let contents = std::fs::read_to_string(&path).map_err(|error| ProjectError::Read {
+            path: path.clone(),
+            error,
+        })?;
+        let (project, source_lines) = read_manifest(directory, &contents)?;
+
+        for (source, line) in project.sources.iter().zip(source_lines) {
+            if !directory.join(source).is_dir() {
+                return Err(ProjectError::Invalid {
+                    path,
+                    line,
+                    message: format!("Source directory '{}' doesn't exist.", source.display()),
+                });
+            }
+        }
+        Ok(project)
+    }
+
+    /// Read a manifest of the project in `directory` from `contents`,
+    /// without checking the paths it names.
+    pub fn from_toml(directory: &Path, contents: &str) -> Result<Project, ProjectError> {
+        read_manifest(directory, contents).map(|(project, _)| project)
+    }
+
+    /// The manifest's path.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.directory.join(MANIFEST_FILE)
+    }
+
+    /// The main script's path.
+    pub fn entry_path(&self) -> PathBuf {
+        self.directory.join(&self.entry)
+    }
+
+    /// The source directories' paths.
+    pub fn source_paths(&self) -> Vec<PathBuf> {
+        self.sources
+            .iter()
+            .map(|source| self.directory.join(source))
+            .collect()
+    }
+
+    /// A parser for the main script, with the manifest's sources and
+    /// variables.
+    pub fn parser(&self) -> Parser {
+        Parser::with_project(self)
+    }
+
+    /// Read and parse the main script.
+    pub fn parse(&self) -> Result<(Database, Vec<Warning>), ProjectError> {
+        let path = self.entry_path();
+        let script =
+            std::fs::read_to_string(&path).map_err(|error| ProjectError::Read { path, error })?;
+        Ok(self.parser().parse(script)?)
+    }
+}
+
+/// Read the manifest of the project in `directory` from `contents`, along
+/// with the line of each `sources` entry.
+fn read_manifest(directory: &Path, contents: &str) -> Result<(Project, Vec<usize>), ProjectError> {
+    let path = directory.join(MANIFEST_FILE);
+    let invalid = |line: usize, message: String| ProjectError::Invalid {
+        path: path.clone(),
+        line,
+        message,
+    };
+    let manifest: Manifest = toml::from_str(contents).map_err(|error| {
+        let line = error.span().map_or(1, |span| line_of(contents, span.start));
+        invalid(line, error.message().trim_end_matches('\n').to_string())
+    })?;
+
+    let entry = match manifest.story.entry {
+        Some(entry) if entry.get_ref().trim().is_empty() => {
+            return Err(invalid(
+                line_of(contents, entry.span().start),
+                "The entry script can't be empty.".to_string(),
+            ))
+        }
+        Some(entry) => PathBuf::from(entry.into_inner()),
+        None => PathBuf::from(DEFAULT_ENTRY),
+    };
+    let source_lines = manifest
+        .story
+        .sources
+        .iter()
+        .map(|source| line_of(contents, source.span().start))
+        .collect();
+
+    let project = Project {
+        directory: directory.to_path_buf(),
+        entry,
+        sources: manifest
+            .story
+            .sources
+            .into_iter()
+            .map(|source| PathBuf::from(source.into_inner()))
+            .collect(),
+        variables: manifest
+            .story
+            .variables
+            .into_iter()
+            .map(|variable| ProjectVariable {
+                line: line_of(contents, variable.span().start),
+                declaration: variable.into_inner(),
+            })
+            .collect(),
+        default_language: manifest.i18n.default_language,
+    };
+    Ok((project, source_lines))
+}
+
+/// The line number of byte `offset` of `contents`, starting at 1.
+fn line_of(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_field_with_its_line() {
+        let manifest = "[story]\nentry = \"story.cuentitos\"\nsources = [\"shared\"]\nvariables = [\n  \"int gold = 10\",\n  \"bool brave\",\n]\n\n[i18n]\ndefault_language = \"es\"\n";
+        let project = Project::from_toml(Path::new("game"), manifest).unwrap();
+        assert_eq!(project.entry_path(), Path::new("game/story.cuentitos"));
+        assert_eq!(project.source_paths(), [Path::new("game/shared")]);
+        assert_eq!(
+            project.variables,
+            [
+                ProjectVariable {
+                    declaration: "int gold = 10".to_string(),
+                    line: 5
+                },
+                ProjectVariable {
+                    declaration: "bool brave".to_string(),
+                    line: 6
+                },
+            ]
+        );
+        assert_eq!(project.default_language.as_deref(), Some("es"));
+
+        let project = Project::from_toml(Path::new("game"), "").unwrap();
+        assert_eq!(project.entry, Path::new(DEFAULT_ENTRY));
+        assert!(project.variables.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_manifests_with_their_line() {
+        let error = Project::from_toml(Path::new("game"), "[story]\nentyr = \"main\"").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("cuentitos.toml:2: ERROR: unknown field `entyr`"));
+
+        let error = Project::from_toml(Path::new("game"), "[story]\nentry = \" \"").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "cuentitos.toml:2: ERROR: The entry script can't be empty."
+        );
+
+        let error = Project::load(Path::new("does/not/exist")).unwrap_err();
+        assert!(matches!(error, ProjectError::Read { .. }));
+    }
+
+    #[test]
+    fn parses_the_entry_script_with_sources_and_variables() {
+        let dir = std::env::temp_dir().join(format!("cuentitos-project-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            "[story]\nentry = \"story.cuentitos\"\nsources = [\"shared\"]\nvariables = [\"int gold = 10\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("story.cuentitos"),
+            "--- variables\nint bonus = gold * 2\n---\n# hub: Hub\n  -> market\ninclude \"market.cuentitos\"",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("shared/market.cuentitos"),
+            "# market: Market\n  You have {gold} coins.",
+        )
+        .unwrap();
+
+        let project = Project::load(&dir).unwrap();
+        let (database, _warnings) = project.parse().unwrap();
+        let names: Vec<_> = database.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["gold", "bonus"]);
+        assert_eq!(
+            database.variables[1].default,
+            cuentitos_common::Value::Integer(20)
+        );
+        assert_eq!(database.files, ["shared/market.cuentitos"]);
+
+        std::fs::write(
+            dir.join("story.cuentitos"),
+            "--- variables\nint gold\n---\nHello.",
+        )
+        .unwrap();
+        assert_eq!(
+            project.parse().unwrap_err().to_string(),
+            "story.cuentitos:2: ERROR: Variable 'gold' is already declared in cuentitos.toml."
+        );
+
+        std::fs::write(dir.join("story.cuentitos"), "Hello.").unwrap();
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            "[story]\nentry = \"story.cuentitos\"\nvariables = [\n  \"int gold = 10\",\n  \"int gold\",\n]\n",
+        )
+        .unwrap();
+        let project = Project::load(&dir).unwrap();
+        assert_eq!(
+            project.parse().unwrap_err().to_string(),
+            "cuentitos.toml:5: ERROR: Duplicate variable name: 'gold' already declared. Previously declared at line 4."
+        );
+
+        std::fs::write(dir.join(MANIFEST_FILE), "[story]\nsources = [\"missing\"]").unwrap();
+        assert_eq!(
+            Project::load(&dir).unwrap_err().to_string(),
+            "cuentitos.toml:2: ERROR: Source directory 'missing' doesn't exist."
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -53,6 +53,48 @@ pub enum RuntimeError {
         file: Option<PathBuf>,
         line: usize,
     },
+    /// A chance weight came out below zero once its `(expression)` was
+    /// evaluated and its `freq` changes added. Carries the weight.
+    NegativeChanceWeight {
+        weight: i64,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A chance weight came out larger than the largest weight a script can
+    /// write, `(4294967295)`.
+    ChanceWeightOutOfRange { file: Option<PathBuf>, line: usize },
+    /// A menu was reached with every option hidden by a `req` or already
+    /// picked `[once]`, and no `[fallback]` to take instead. `line` is the
+    /// line the options are nested under.
+    NoOptionsAvailable { file: Option<PathBuf>, line: usize },
+    /// A `call` line or an expression called a function the game never
+    /// registered with [`Runtime::register_function`](crate::Runtime::register_function).
+    MissingFunction {
+        name: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// The game registered a function with other parameter or return kinds
+    /// than the script declares it with.
+    FunctionSignatureMismatch {
+        name: String,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// A function returned a value of another kind than it is declared to
+    /// return, or nothing where a value is expected. `found` is the kind it
+    /// returned, if any.
+    FunctionReturnMismatch {
+        name: String,
+        expected: Option<ValueKind>,
+        found: Option<ValueKind>,
+        file: Option<PathBuf>,
+        line: usize,
+    },
+    /// [`Runtime::set_language`](crate::Runtime::set_language) was given a
+    /// language with no translations, in itself or any tag it falls back
+    /// to.
+    UnknownLanguage { language: String },
 }
 
 impl fmt::Display for RuntimeError {
@@ -129,6 +171,95 @@ impl fmt::Display for RuntimeError {
                     prefix, line, name
                 )
             }
+            RuntimeError::NegativeChanceWeight { weight, file, line } => {
+                let prefix = file
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("<script>");
+                write!(
+                    f,
+                    "{}:{}: RUNTIME ERROR: Chance weight can't be negative, found {}.",
+                    prefix, line, weight
+                )
+            }
+            RuntimeError::ChanceWeightOutOfRange { file, line } => {
+                let prefix = file
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("<script>");
+                write!(
+                    f,
+                    "{}:{}: RUNTIME ERROR: Chance weight out of range.",
+                    prefix, line
+                )
+            }
+            RuntimeError::NoOptionsAvailable { file, line } => {
+                let prefix = file
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("<script>");
+                write!(
+                    f,
+                    "{}:{}: RUNTIME ERROR: No options available and no fallback.",
+                    prefix, line
+                )
+            }
+            RuntimeError::MissingFunction { name, file, line } => {
+                let prefix = file
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("<script>");
+                write!(
+                    f,
+                    "{}:{}: RUNTIME ERROR: Function '{}' isn't registered.",
+                    prefix, line, name
+                )
+            }
+            RuntimeError::FunctionSignatureMismatch { name, file, line } => {
+                let prefix = file
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("<script>");
+                write!(
+                    f,
+                    "{}:{}: RUNTIME ERROR: Function '{}' is registered with other kinds than the script declares.",
+                    prefix, line, name
+                )
+            }
+            RuntimeError::FunctionReturnMismatch {
+                name,
+                expected,
+                found,
+                file,
+                line,
+            } => {
+                let prefix = file
+                    .as_ref()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("<script>");
+                let kind = |kind: &Option<ValueKind>| match kind {
+                    Some(kind) => kind.to_string(),
+                    None => "nothing".to_string(),
+                };
+                write!(
+                    f,
+                    "{}:{}: RUNTIME ERROR: Function '{}' should return {}, returned {}.",
+                    prefix,
+                    line,
+                    name,
+                    kind(expected),
+                    kind(found)
+                )
+            }
+            RuntimeError::UnknownLanguage { language } => {
+                write!(f, "ERROR: No translations for language '{}'.", language)
+            }
         }
     }
 }
@@ -0,0 +1,97 @@
+//! Events the runtime reports as the story progresses.
+//!
+//! Hosts that need to react to the story, such as a game showing text,
+//! playing music on a section change or saving on a choice, call
+//! [`Runtime::record_events`](crate::Runtime::record_events) and drain
+//! [`Runtime::take_events`](crate::Runtime::take_events) after each step,
+//! instead of diffing the path.
+
+use crate::RuntimeError;
+use cuentitos_common::{BlockId, SectionId, Value, VariableId};
+
+/// Something that happened while the story ran. Events are queued in the
+/// order they happened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeEvent {
+    /// A line of text was shown, rendered as the reader sees it.
+    TextShown { block_id: BlockId, text: String },
+    /// A section was entered, by reaching it or jumping to it.
+    SectionEntered {
+        section_id: SectionId,
+        block_id: BlockId,
+    },
+    /// A menu is waiting for a choice. Holds the listed options in the
+    /// order they are numbered, from 1, with the text they are shown with.
+    OptionsPresented { options: Vec<(BlockId, String)> },
+    /// Option number `choice` of the menu was picked.
+    OptionChosen {
+        choice: usize,
+        block_id: BlockId,
+        text: String,
+    },
+    /// A menu listed no option, so its `[fallback]` option was entered
+    /// without waiting for a choice.
+    FallbackTaken { block_id: BlockId },
+    /// A variable was assigned, by a `set` line or by
+    /// [`Runtime::set_variable_value`](crate::Runtime::set_variable_value).
+    /// Reported when an assignment changes the value, not when it keeps it.
+    VariableChanged {
+        variable: VariableId,
+        old: Value,
+        new: Value,
+    },
+    /// A block and everything under it was skipped, or an option left out
+    /// of its menu, because one of its `req` lines failed.
+    RequirementSkipped { block_id: BlockId },
+    /// A `<->` jump went into a section, and will come back to
+    /// `return_block_id` when the section ends.
+    CallPushed {
+        section_id: SectionId,
+        return_block_id: BlockId,
+    },
+    /// The section of the innermost `<->` ended, and the story went back
+    /// to `return_block_id`. `-> START` and `-> RESTART` drop every pending
+    /// return without reporting it.
+    CallPopped {
+        section_id: SectionId,
+        return_block_id: BlockId,
+    },
+    /// The story reached `END`.
+    StoryEnded,
+    /// A runtime error stopped the story. The same error is kept for
+    /// [`Runtime::take_last_error`](crate::Runtime::take_last_error).
+    Error(RuntimeError),
+}
+
+/// The events queued since the host last took them. Nothing is queued
+/// until recording is turned on, so a host that never drains the queue
+/// doesn't grow it.
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    recording: bool,
+    events: Vec<RuntimeEvent>,
+}
+
+impl EventQueue {
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Turn recording on or off. Turning it off drops the queued events.
+    pub(crate) fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+        if !recording {
+            self.events.clear();
+        }
+    }
+
+    pub(crate) fn push(&mut self, event: RuntimeEvent) {
+        if self.recording {
+            self.events.push(event);
+        }
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<RuntimeEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,138 @@
+//! Functions the game provides to scripts.
+//!
+//! The script declares what it may call; the game registers the closures
+//! that answer. Calls are matched by name and checked against the kinds both
+//! sides agreed on before the closure runs, so a closure only ever sees
+//! arguments of the kinds it was registered with.
+
+use cuentitos_common::{Database, EvaluationError, FunctionId, FunctionSignature, Value};
+use std::collections::HashMap;
+
+type FunctionBody = Box<dyn Fn(&[Value]) -> Option<Value> + Send + Sync>;
+
+/// A closure registered with [`crate::Runtime::register_function`].
+struct HostFunction {
+    signature: FunctionSignature,
+    body: FunctionBody,
+}
+
+/// The registered functions, by name.
+#[derive(Default)]
+pub(crate) struct FunctionRegistry {
+    functions: HashMap<String, HostFunction>,
+}
+
+impl FunctionRegistry {
+    /// Register `body` as `signature.name`, replacing any function
+    /// registered under that name before.
+    pub(crate) fn register(&mut self, signature: FunctionSignature, body: FunctionBody) {
+        self.functions
+            .insert(signature.name.clone(), HostFunction { signature, body });
+    }
+
+    /// Run the declared function `function` with `arguments`.
+    ///
+    /// The registered closure must have the declared signature, the
+    /// arguments must have the declared parameter kinds, and the closure
+    /// must return a value of the declared return kind. A function declared
+    /// without a return kind may return anything; it is dropped.
+    pub(crate) fn call(
+        &self,
+        database: &Database,
+        function: FunctionId,
+        arguments: &[Value],
+    ) -> Result<Option<Value>, EvaluationError> {
+        let declared = &database.functions[function];
+        let Some(host) = self.functions.get(&declared.name) else {
+            return Err(EvaluationError::MissingFunction { function });
+        };
+        if host.signature != *declared {
+            return Err(EvaluationError::FunctionSignatureMismatch { function });
+        }
+        for (argument, &expected) in arguments.iter().zip(&declared.parameters) {
+            if argument.kind() != expected {
+                return Err(EvaluationError::TypeMismatch {
+                    expected,
+                    found: argument.kind(),
+                });
+            }
+        }
+        let result = (host.body)(arguments);
+        match declared.returns {
+            None => Ok(None),
+            Some(expected) => match result {
+                Some(value) if value.kind() == expected => Ok(Some(value)),
+                other => Err(EvaluationError::FunctionReturnMismatch {
+                    function,
+                    found: other.map(|value| value.kind()),
+                }),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuentitos_common::ValueKind;
+
+    fn registry() -> (Database, FunctionRegistry) {
+        let mut database = Database::new();
+        database.declare_function(FunctionSignature::new(
+            "has_item",
+            &[ValueKind::String],
+            Some(ValueKind::Boolean),
+        ));
+        let mut registry = FunctionRegistry::default();
+        registry.register(
+            FunctionSignature::new("has_item", &[ValueKind::String], Some(ValueKind::Boolean)),
+            Box::new(|arguments| Some(Value::Boolean(arguments[0] == Value::String("key".into())))),
+        );
+        (database, registry)
+    }
+
+    #[test]
+    fn calls_the_registered_closure() {
+        let (database, registry) = registry();
+        assert_eq!(
+            registry.call(&database, 0, &[Value::String("key".into())]),
+            Ok(Some(Value::Boolean(true)))
+        );
+        assert_eq!(
+            registry.call(&database, 0, &[Value::Integer(1)]),
+            Err(EvaluationError::TypeMismatch {
+                expected: ValueKind::String,
+                found: ValueKind::Integer,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_mismatched_functions() {
+        let (mut database, mut registry) = registry();
+        database.declare_function(FunctionSignature::new("play_sound", &[], None));
+        assert_eq!(
+            registry.call(&database, 1, &[]),
+            Err(EvaluationError::MissingFunction { function: 1 })
+        );
+        registry.register(
+            FunctionSignature::new("play_sound", &[ValueKind::String], None),
+            Box::new(|_| None),
+        );
+        assert_eq!(
+            registry.call(&database, 1, &[]),
+            Err(EvaluationError::FunctionSignatureMismatch { function: 1 })
+        );
+        registry.register(
+            FunctionSignature::new("has_item", &[ValueKind::String], Some(ValueKind::Boolean)),
+            Box::new(|_| Some(Value::Integer(1))),
+        );
+        assert_eq!(
+            registry.call(&database, 0, &[Value::String("key".into())]),
+            Err(EvaluationError::FunctionReturnMismatch {
+                function: 0,
+                found: Some(ValueKind::Integer),
+            })
+        );
+    }
+}
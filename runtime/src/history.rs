@@ -0,0 +1,216 @@
+//! Checkpoints [`Runtime::rewind`](crate::Runtime::rewind) goes back to.
+//!
+//! A checkpoint is a copy of the runtime's state, the same state a
+//! [`RuntimeSnapshot`](crate::RuntimeSnapshot) saves, taken before an
+//! option is selected and, when the host asks for it, before every step.
+//! The history keeps the newest checkpoints whose estimated sizes fit its
+//! byte cap, dropping the oldest first.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+
+use cuentitos_common::Value;
+
+use crate::RuntimeState;
+
+/// How many bytes of checkpoints a runtime keeps unless the host sets
+/// another cap with [`Runtime::set_history_cap`](crate::Runtime::set_history_cap).
+pub const DEFAULT_HISTORY_CAP: usize = 1 << 20;
+
+/// The runtime as it was before an option selection or a step.
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoint {
+    pub(crate) seed: u64,
+    pub(crate) running: bool,
+    pub(crate) state: RuntimeState,
+    /// [`estimated_size`] of `state`, kept so dropping a checkpoint doesn't
+    /// walk it again.
+    size: usize,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(seed: u64, running: bool, state: RuntimeState) -> Self {
+        let size = estimated_size(&state);
+        Self {
+            seed,
+            running,
+            state,
+            size,
+        }
+    }
+}
+
+/// The checkpoints kept, oldest first.
+#[derive(Debug)]
+pub(crate) struct History {
+    checkpoints: VecDeque<Checkpoint>,
+    /// Summed sizes of `checkpoints`.
+    size: usize,
+    cap: usize,
+    /// Take a checkpoint before every step, not only before selections.
+    every_step: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            checkpoints: VecDeque::new(),
+            size: 0,
+            cap: DEFAULT_HISTORY_CAP,
+            every_step: false,
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub(crate) fn every_step(&self) -> bool {
+        self.every_step
+    }
+
+    pub(crate) fn set_every_step(&mut self, every_step: bool) {
+        self.every_step = every_step;
+    }
+
+    /// Set the byte cap, dropping the oldest checkpoints that no longer
+    /// fit. A cap of 0 keeps no history.
+    pub(crate) fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+        self.trim();
+    }
+
+    /// Keep `checkpoint` as the newest. A checkpoint larger than the whole
+    /// cap isn't kept, and leaves the history empty.
+    pub(crate) fn push(&mut self, checkpoint: Checkpoint) {
+        self.size += checkpoint.size;
+        self.checkpoints.push_back(checkpoint);
+        self.trim();
+    }
+
+    /// Drop the `n` newest checkpoints and return the oldest of them, or
+    /// `None`, dropping nothing, if fewer than `n` are kept.
+    pub(crate) fn rewind(&mut self, n: usize) -> Option<Checkpoint> {
+        if n == 0 || n > self.checkpoints.len() {
+            return None;
+        }
+        let kept = self.checkpoints.len() - n;
+        let mut dropped = self.checkpoints.split_off(kept);
+        self.size -= dropped
+            .iter()
+            .map(|checkpoint| checkpoint.size)
+            .sum::<usize>();
+        dropped.pop_front()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.checkpoints.clear();
+        self.size = 0;
+    }
+
+    fn trim(&mut self) {
+        while self.size > self.cap {
+            let Some(oldest) = self.checkpoints.pop_front() else {
+                break;
+            };
+            self.size -= oldest.size;
+        }
+    }
+}
+
+/// Roughly how many bytes `state` holds, counting what its collections
+/// have allocated. Only meant to keep the history near its cap.
+fn estimated_size(state: &RuntimeState) -> usize {
+    size_of::<RuntimeState>()
+        + vec_size(&state.current_path)
+        + vec_size(&state.call_stack)
+        + vec_size(&state.current_options)
+        + vec_size(&state.current_option_texts)
+        + state
+            .current_option_texts
+            .iter()
+            .map(String::capacity)
+            .sum::<usize>()
+        + vec_size(&state.variable_values)
+        + state
+            .variable_values
+            .iter()
+            .map(|value| match value {
+                Value::String(text) => text.capacity(),
+                _ => 0,
+            })
+            .sum::<usize>()
+        + map_size(&state.chance_rolls)
+        + map_size(&state.bucket_draws)
+        + state
+            .bucket_draws
+            .values()
+            .map(|draws| vec_size(&draws.drawn))
+            .sum::<usize>()
+        + map_size(&state.frequency_adjustments)
+        + map_size(&state.path_odds)
+        + map_size(&state.path_text)
+        + state
+            .path_text
+            .values()
+            .map(String::capacity)
+            .sum::<usize>()
+        + map_size(&state.alternative_visits)
+        + set_size(&state.consumed_options)
+        + map_size(&state.visit_counts)
+}
+
+fn vec_size<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * size_of::<T>()
+}
+
+fn map_size<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * size_of::<(K, V)>()
+}
+
+fn set_size<T>(set: &HashSet<T>) -> usize {
+    set.capacity() * size_of::<T>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(path_length: usize) -> Checkpoint {
+        let mut state = RuntimeState::new(0);
+        state.current_path = vec![0; path_length];
+        Checkpoint::new(0, true, state)
+    }
+
+    #[test]
+    fn rewind_drops_the_newest_checkpoints() {
+        let mut history = History::default();
+        for length in 1..=3 {
+            history.push(checkpoint(length));
+        }
+        assert!(history.rewind(4).is_none());
+        assert!(history.rewind(0).is_none());
+        assert_eq!(history.len(), 3);
+
+        let restored = history.rewind(2).unwrap();
+        assert_eq!(restored.state.current_path.len(), 2);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.size, checkpoint(1).size);
+    }
+
+    #[test]
+    fn cap_drops_the_oldest_checkpoints() {
+        let mut history = History::default();
+        history.set_cap(checkpoint(100).size * 2);
+        for _ in 0..3 {
+            history.push(checkpoint(100));
+        }
+        assert_eq!(history.len(), 2);
+
+        history.set_cap(0);
+        assert_eq!(history.len(), 0);
+        assert_eq!(history.size, 0);
+    }
+}
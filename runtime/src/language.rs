@@ -0,0 +1,520 @@
+//! Showing a story in another language.
+//!
+//! A host registers the translations it has with
+//! [`Runtime::add_translation`], one [`Translation`] per language tag, and
+//! picks one with [`Runtime::set_language`]. Each string is looked up along
+//! the tag's fallback chain, `es-MX`, then `es`, then the script's own text,
+//! so a regional table only needs the strings that differ from its base
+//! language. [`Runtime::missing_translations`] lists the strings that fell
+//! all the way back to the script.
+//!
+//! Translated lines can use `{...}` like the script does, as long as the
+//! source line does too. Their alternatives share their counters with the
+//! source line's, by rank, so switching languages mid-story carries on where
+//! each sequence was.
+
+use std::collections::{BTreeSet, HashMap};
+
+use cuentitos_common::localization::{
+    string_keys, translatable_strings, Translation, TranslationIssue,
+};
+use cuentitos_common::{
+    AlternativesId, BlockType, Database, PathResolver, ResolvedPath, SectionId, StringId,
+    TextAlternatives, TextFragment, VisitsId,
+};
+use cuentitos_parser::parsers::interpolation_parser::{
+    parse_interpolation, InterpolationParseError,
+};
+
+use crate::error::RuntimeError;
+use crate::migration::collect_alternatives;
+use crate::Runtime;
+
+/// The language a runtime starts in, until the host calls
+/// [`Runtime::set_default_language`].
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// A parsed translated line: its fragments, the alternatives they use and
+/// the sections their `visits` reads count.
+type TranslatedLine = (Vec<TextFragment>, BTreeSet<AlternativesId>, Vec<SectionId>);
+
+/// The translations of one language, ready to render.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LanguageTable {
+    strings: HashMap<StringId, String>,
+    /// The fragments of every translated line whose source line has `{...}`.
+    fragments: HashMap<StringId, Vec<TextFragment>>,
+    /// Alternatives written in translated lines, numbered after the
+    /// database's own.
+    alternatives: Vec<TextAlternatives>,
+    /// The source alternatives each of `alternatives` counts visits with.
+    alternative_counters: Vec<AlternativesId>,
+    /// The section each `visits(path)` in a translated line counts,
+    /// numbered after the database's own.
+    visit_targets: Vec<SectionId>,
+}
+
+impl LanguageTable {
+    pub(crate) fn fragments(&self, string_id: StringId) -> Option<&[TextFragment]> {
+        self.fragments.get(&string_id).map(Vec::as_slice)
+    }
+}
+
+/// The alternatives fragment `id`, from the database or the translation
+/// that wrote it, and the id its visits are counted under.
+pub(crate) fn alternatives<'a>(
+    database: &'a Database,
+    language: Option<&'a LanguageTable>,
+    id: AlternativesId,
+) -> (&'a TextAlternatives, AlternativesId) {
+    match id.checked_sub(database.text_alternatives.len()) {
+        None => (&database.text_alternatives[id], id),
+        Some(index) => {
+            let language = language.expect("translated alternatives render with their language");
+            (
+                &language.alternatives[index],
+                language.alternative_counters[index],
+            )
+        }
+    }
+}
+
+/// The section the `visits(path)` expression `id` counts.
+pub(crate) fn visit_target(
+    database: &Database,
+    language: Option<&LanguageTable>,
+    id: VisitsId,
+) -> SectionId {
+    match id.checked_sub(database.visit_targets.len()) {
+        None => database.visit_targets[id],
+        Some(index) => {
+            language
+                .expect("translated visits evaluate with their language")
+                .visit_targets[index]
+        }
+    }
+}
+
+/// The first table along `chain` translating `string_id`. A free function
+/// so rendering can borrow the tables while it updates the state.
+pub(crate) fn translation_of<'a>(
+    languages: &'a HashMap<String, LanguageTable>,
+    chain: &[String],
+    string_id: StringId,
+) -> Option<&'a LanguageTable> {
+    chain
+        .iter()
+        .filter_map(|tag| languages.get(tag))
+        .find(|table| table.strings.contains_key(&string_id))
+}
+
+/// The tags of `language`'s chain that have to be translated: the ones
+/// before `default`, whose text the script already is.
+pub(crate) fn language_chain(language: &str, default: &str) -> Vec<String> {
+    let default = default.to_lowercase();
+    fallback_chain(language)
+        .into_iter()
+        .take_while(|tag| *tag != default)
+        .collect()
+}
+
+/// `tag` and the tags it falls back to, lowercased: `es-MX` gives `es-mx`
+/// and `es`. A subtag of one letter, like the `x` of `en-x-pirate`, only
+/// makes sense with what follows it, so it is dropped along with it.
+///
+/// Hosts loading translations from files use it to find the same tags the
+/// runtime looks strings up in.
+pub fn fallback_chain(tag: &str) -> Vec<String> {
+    let mut chain = vec![tag.to_lowercase()];
+    let mut current = tag.to_lowercase();
+    while let Some(dash) = current.rfind('-') {
+        current.truncate(dash);
+        if let Some(dash) = current.rfind('-').filter(|&dash| current.len() - dash == 2) {
+            current.truncate(dash);
+        }
+        chain.push(current.clone());
+    }
+    chain
+}
+
+impl Runtime {
+    /// Register the translations of `language`, a BCP 47 tag such as `es`
+    /// or `es-MX`, replacing any registered before. Returns the translated
+    /// lines that can't be shown, which are left out as if untranslated:
+    /// `{...}` that doesn't parse, a different number of alternatives than
+    /// the source line, or a `visits(path)` that names no section.
+    pub fn add_translation(
+        &mut self,
+        language: &str,
+        translation: Translation,
+    ) -> Vec<TranslationIssue> {
+        let mut scratch = self.database.clone();
+        let mut table = LanguageTable::default();
+        let mut issues = Vec::new();
+        let mut keys = string_keys(&self.database);
+
+        let mut strings: Vec<_> = translation.strings.into_iter().collect();
+        strings.sort_unstable_by_key(|(id, _)| *id);
+        for (id, text) in strings {
+            match self.translate_line(id, &text, &mut scratch, &mut table) {
+                Ok(()) => {
+                    table.strings.insert(id, text);
+                }
+                Err(message) => issues.push(TranslationIssue::Invalid {
+                    key: keys.remove(&id).unwrap_or_default(),
+                    text,
+                    message,
+                }),
+            }
+        }
+        table.alternatives = scratch
+            .text_alternatives
+            .split_off(self.database.text_alternatives.len());
+
+        self.languages.insert(language.to_lowercase(), table);
+        issues
+    }
+
+    /// Parse the `{...}` fragments of the translation `text` of `string_id`
+    /// into `table`. `scratch` is a copy of the database the fragments'
+    /// alternatives and `visits` reads are numbered in.
+    fn translate_line(
+        &self,
+        string_id: StringId,
+        text: &str,
+        scratch: &mut Database,
+        table: &mut LanguageTable,
+    ) -> Result<(), String> {
+        let Some(source) = self.database.text_fragments.get(&string_id) else {
+            if text.contains('{') {
+                return Err(
+                    "The source line has no '{...}', so its translation can't either.".to_string(),
+                );
+            }
+            return Ok(());
+        };
+
+        let alternatives_before = scratch.text_alternatives.len();
+        let visits_before = scratch.visit_paths.len();
+        let result = self.parse_translated_line(string_id, text, scratch, visits_before);
+        let (fragments, counters, targets) = match result {
+            Ok(parsed) => parsed,
+            Err(message) => {
+                scratch.text_alternatives.truncate(alternatives_before);
+                scratch.visit_paths.truncate(visits_before);
+                return Err(message);
+            }
+        };
+
+        let mut source_alternatives = BTreeSet::new();
+        collect_alternatives(source, &self.database, &mut source_alternatives);
+        if counters.len() != source_alternatives.len() {
+            scratch.text_alternatives.truncate(alternatives_before);
+            scratch.visit_paths.truncate(visits_before);
+            return Err(format!(
+                "Has {} alternatives, the source line has {}.",
+                counters.len(),
+                source_alternatives.len()
+            ));
+        }
+
+        table.fragments.insert(string_id, fragments);
+        table.alternative_counters.extend(source_alternatives);
+        table.visit_targets.extend(targets);
+        Ok(())
+    }
+
+    /// Parse `text` into fragments, returning them along with the
+    /// alternatives they added to `scratch` and the sections their new
+    /// `visits` reads count.
+    fn parse_translated_line(
+        &self,
+        string_id: StringId,
+        text: &str,
+        scratch: &mut Database,
+        visits_before: usize,
+    ) -> Result<TranslatedLine, String> {
+        let fragments = parse_interpolation(text, scratch)
+            .map_err(|err| match err {
+                InterpolationParseError::Unclosed => "Unclosed '{'.".to_string(),
+                InterpolationParseError::Malformed { text } => {
+                    format!(
+                        "Expected a variable name between '{{' and '}}', found '{}'.",
+                        text
+                    )
+                }
+                InterpolationParseError::UndefinedVariable { name } => {
+                    format!("Undefined variable: '{}'.", name)
+                }
+                InterpolationParseError::Condition { .. } => "Malformed condition.".to_string(),
+            })?
+            .unwrap_or_else(|| vec![TextFragment::Literal(text.to_string())]);
+
+        let mut alternatives = BTreeSet::new();
+        collect_alternatives(&fragments, scratch, &mut alternatives);
+
+        // `visits` paths resolve from the line's own section, as they do in
+        // the script.
+        let block_id = self.database.blocks.iter().position(|block| {
+            matches!(
+                block.block_type,
+                BlockType::String(id) | BlockType::Option(id) if id == string_id
+            )
+        });
+        let containing_section = block_id.and_then(|id| self.find_containing_section(id));
+        let resolver = PathResolver::new(&self.database, &self.database.section_registry);
+        let mut targets = Vec::new();
+        for path in &scratch.visit_paths[visits_before..] {
+            match resolver.resolve_path(path, containing_section) {
+                Ok(ResolvedPath::Section(section_id)) => targets.push(section_id),
+                _ => return Err(format!("'visits' needs a section, found '{}'.", path)),
+            }
+        }
+
+        Ok((fragments, alternatives, targets))
+    }
+
+    /// Show the story in `language`, a BCP 47 tag. Every string is looked
+    /// up in `language`, then in each shorter tag, `es-MX` then `es`, then
+    /// shown as the script wrote it. Takes effect from the next line shown;
+    /// the story carries on where it was.
+    ///
+    /// Fails with [`RuntimeError::UnknownLanguage`], keeping the current
+    /// language, if no tag in the chain has translations and none is the
+    /// [default language](Runtime::default_language).
+    pub fn set_language(&mut self, language: &str) -> Result<(), RuntimeError> {
+        let known = fallback_chain(language).iter().any(|tag| {
+            self.languages.contains_key(tag) || *tag == self.default_language.to_lowercase()
+        });
+        if !known {
+            return Err(RuntimeError::UnknownLanguage {
+                language: language.to_string(),
+            });
+        }
+        self.language = language.to_string();
+        self.language_chain = language_chain(&self.language, &self.default_language);
+        Ok(())
+    }
+
+    /// The language set with [`Runtime::set_language`], or the default
+    /// language if none was.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The language the script is written in. Strings fall back to the
+    /// script's text once the chain reaches it.
+    pub fn default_language(&self) -> &str {
+        &self.default_language
+    }
+
+    /// Set the language the script is written in, [`DEFAULT_LANGUAGE`]
+    /// until then. If the runtime was showing the old default language, it
+    /// now shows the new one.
+    pub fn set_default_language(&mut self, language: &str) {
+        if self.language.eq_ignore_ascii_case(&self.default_language) {
+            self.language = language.to_string();
+        }
+        self.default_language = language.to_string();
+        self.language_chain = language_chain(&self.language, &self.default_language);
+    }
+
+    /// The table translating `string_id` in the current language, if any.
+    pub(crate) fn translation_of(&self, string_id: StringId) -> Option<&LanguageTable> {
+        translation_of(&self.languages, &self.language_chain, string_id)
+    }
+
+    /// The text of `string_id` in the current language, unrendered. Falls
+    /// back to the script's text.
+    pub fn text(&self, string_id: StringId) -> &str {
+        match self.translation_of(string_id) {
+            Some(table) => &table.strings[&string_id],
+            None => &self.database.strings[string_id],
+        }
+    }
+
+    /// Every string a player can see that the current language shows as
+    /// the script wrote it, because no table in its chain translates it.
+    /// Empty when the chain reaches the default language, whose text the
+    /// script already is.
+    pub fn missing_translations(&self) -> Vec<StringId> {
+        if fallback_chain(&self.language).contains(&self.default_language.to_lowercase()) {
+            return Vec::new();
+        }
+        translatable_strings(&self.database)
+            .into_iter()
+            .filter(|&id| self.translation_of(id).is_none())
+            .collect()
+    }
+
+    /// The display path of `section_id`, `Hub \ Cave`, from its sections'
+    /// names in the current language.
+    pub fn section_path_text(&self, section_id: SectionId) -> String {
+        let mut names = Vec::new();
+        let mut block_id = Some(self.database.sections[section_id].block_id);
+        while let Some(id) = block_id {
+            let block = &self.database.blocks[id];
+            if let BlockType::Section(section_id) = block.block_type {
+                names.push(self.text(self.database.sections[section_id].name));
+            }
+            block_id = block.parent_id;
+        }
+        names.reverse();
+        names.join(" \\ ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cuentitos_common::localization::{
+        string_keys, Translation, TranslationIssue, TranslationRow,
+    };
+    use cuentitos_common::Database;
+
+    use super::fallback_chain;
+    use crate::{Runtime, RuntimeError};
+
+    const SCRIPT: &str = "--- variables\nint gold = 3\n---\n# square: Square\n  Hello.\n  You have {gold} coins, {&tick|tock}.\n  What now?\n    * Wait\n      -> square\n    * Leave";
+
+    fn translate(database: &Database, rows: &[(usize, &str)]) -> Translation {
+        let keys = string_keys(database);
+        let rows = rows.iter().map(|&(id, text)| TranslationRow {
+            key: keys[&id].clone(),
+            source: database.strings[id].clone(),
+            text: text.to_string(),
+        });
+        Translation::new(database, rows)
+    }
+
+    fn shown(runtime: &Runtime) -> Vec<String> {
+        (0..runtime.current_path().len())
+            .filter_map(|index| runtime.path_text(index).map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn fallback_chain_drops_subtags() {
+        assert_eq!(fallback_chain("es-MX"), vec!["es-mx", "es"]);
+        assert_eq!(
+            fallback_chain("zh-Hant-TW"),
+            vec!["zh-hant-tw", "zh-hant", "zh"]
+        );
+        assert_eq!(fallback_chain("en-x-pirate"), vec!["en-x-pirate", "en"]);
+        assert_eq!(fallback_chain("fr"), vec!["fr"]);
+    }
+
+    #[test]
+    fn switches_language_mid_story_with_fallback() {
+        let (database, _warnings) = cuentitos_parser::parse(SCRIPT).unwrap();
+        let es = translate(
+            &database,
+            &[
+                (0, "Plaza"),
+                (4, "Hola."),
+                (5, "Tienes {gold} monedas, {&tic|tac}."),
+                (6, "¿Y ahora?"),
+                (7, "Esperar"),
+                (8, "Irse"),
+            ],
+        );
+        let es_mx = translate(&database, &[(8, "Largarse")]);
+
+        let mut runtime = Runtime::new(database);
+        assert!(runtime.add_translation("es", es).is_empty());
+        assert!(runtime.add_translation("es-MX", es_mx).is_empty());
+        runtime.run();
+        runtime.skip();
+        assert_eq!(
+            shown(&runtime),
+            ["Hello.", "You have 3 coins, tick.", "What now?"]
+        );
+
+        runtime.set_language("es-MX").unwrap();
+        assert_eq!(runtime.language(), "es-MX");
+        assert!(runtime.missing_translations().is_empty());
+        runtime.select_option(1).unwrap();
+        runtime.skip();
+        assert_eq!(
+            shown(&runtime)[3..],
+            ["Wait", "Hola.", "Tienes 3 monedas, tac.", "¿Y ahora?"]
+        );
+        assert_eq!(runtime.option_text(1), Some("Esperar"));
+        assert_eq!(runtime.option_text(2), Some("Largarse"));
+        assert_eq!(runtime.section_path_text(0), "Plaza");
+
+        runtime.set_language("en").unwrap();
+        assert_eq!(runtime.text(8), "Leave");
+        assert_eq!(runtime.section_path_text(0), "Square");
+    }
+
+    #[test]
+    fn translated_visits_count_the_same_section() {
+        let script = "# square: Square\n  {visits(square) > 1 ? Back. : First.}\n  Again?\n    * Yes\n      -> square\n    * No";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let es = translate(
+            &database,
+            &[(4, "{visits(square) > 1 ? Otra vez. : Primera vez.}")],
+        );
+
+        let mut runtime = Runtime::new(database);
+        assert!(runtime.add_translation("es", es).is_empty());
+        runtime.set_language("es").unwrap();
+        runtime.run();
+        runtime.skip();
+        runtime.select_option(1).unwrap();
+        runtime.skip();
+        assert_eq!(
+            shown(&runtime),
+            ["Primera vez.", "Again?", "Yes", "Otra vez.", "Again?"]
+        );
+    }
+
+    #[test]
+    fn reports_missing_and_invalid_translations() {
+        let (database, _warnings) = cuentitos_parser::parse(SCRIPT).unwrap();
+        let es = translate(
+            &database,
+            &[
+                (4, "Hola {gold}."),
+                (5, "Tienes {gold} monedas."),
+                (8, "Irse"),
+            ],
+        );
+
+        let database_keys = string_keys(&database);
+        let mut runtime = Runtime::new(database);
+        let issues = runtime.add_translation("es", es);
+        assert_eq!(
+            issues.iter().map(|issue| issue.key()).collect::<Vec<_>>(),
+            [database_keys[&4].as_str(), database_keys[&5].as_str()]
+        );
+        let messages: Vec<_> = issues
+            .iter()
+            .map(|issue| match issue {
+                TranslationIssue::Invalid { message, .. } => message.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "The source line has no '{...}', so its translation can't either.",
+                "Has 0 alternatives, the source line has 1.",
+            ]
+        );
+
+        assert_eq!(
+            runtime.set_language("fr"),
+            Err(RuntimeError::UnknownLanguage {
+                language: "fr".to_string()
+            })
+        );
+        assert_eq!(runtime.language(), "en");
+        assert!(runtime.missing_translations().is_empty());
+
+        runtime.set_language("es-AR").unwrap();
+        assert_eq!(runtime.missing_translations(), vec![0, 4, 5, 6, 7]);
+        assert_eq!(runtime.text(4), "Hello.");
+        assert_eq!(runtime.text(8), "Irse");
+    }
+}
@@ -1,18 +1,66 @@
 use cuentitos_common::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 pub mod error;
+pub mod events;
+mod functions;
+mod history;
+mod language;
+mod loader;
+pub mod migration;
+mod rng;
+pub mod snapshot;
+mod watchers;
 pub use error::RuntimeError;
+use events::EventQueue;
+pub use events::RuntimeEvent;
+use functions::FunctionRegistry;
+pub use history::DEFAULT_HISTORY_CAP;
+use history::{Checkpoint, History};
+use language::LanguageTable;
+pub use language::{fallback_chain, DEFAULT_LANGUAGE};
+pub use migration::{BlockAnchor, MigrationIssue, MigrationReport};
+use rng::Rng;
+pub use snapshot::{RuntimeSnapshot, SnapshotError, SNAPSHOT_VERSION};
+pub use watchers::VariableChange;
+use watchers::VariableWatchers;
 
 /// Represents a call frame for <-> (call and return) commands
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CallFrame {
     return_block_id: BlockId,   // Block to return to after call completes
     called_section_id: BlockId, // The section that was called
 }
 
-/// Runtime state that can be reset
-#[derive(Debug, Clone)]
+/// Outcome of the last roll of a chance group, keyed by the group head in
+/// [`RuntimeState::chance_rolls`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct ChanceRoll {
+    /// The member picked, or `None` if the roll landed in the leftover of a
+    /// percentage group (or every candidate had weight zero).
+    winner: Option<BlockId>,
+    /// The winner's weight (or percentage) at the time of the roll.
+    weight: u64,
+    /// What the roll was out of: the summed weights of the candidates, or
+    /// 100 for a percentage group.
+    total: u64,
+}
+
+/// Draw state of one bucket, keyed by the bucket block in
+/// [`RuntimeState::bucket_draws`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BucketDraws {
+    /// Cards drawn since the bucket was last full, in draw order.
+    drawn: Vec<BlockId>,
+    /// The card drawn on the latest visit, or `None` if nothing could be
+    /// drawn (an exhausted `once` bucket, or every card's `req` failing).
+    current: Option<BlockId>,
+}
+
+/// Runtime state that can be reset, and saved as a [`RuntimeSnapshot`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct RuntimeState {
     program_counter: usize,
     previous_program_counter: usize,
@@ -20,6 +68,11 @@ struct RuntimeState {
     call_stack: Vec<CallFrame>,
     waiting_for_option_selection: bool,
     current_options: Vec<BlockId>, // IDs of available option blocks
+    /// The text of each entry of `current_options`, rendered when the menu
+    /// was built.
+    current_option_texts: Vec<String>,
+    /// Not saved in snapshots: a restored runtime starts with no error.
+    #[serde(skip)]
     last_error: Option<RuntimeError>,
     /// Current variable values, aligned index-for-index with
     /// `Database.variables`. `variable_values[i]` is the current value of the
@@ -29,10 +82,48 @@ struct RuntimeState {
     /// Typed so that adding new `Value` variants (bool, float, string)
     /// is strictly additive — no storage migration needed.
     variable_values: Vec<Value>,
+    /// Source of randomness for chance groups. Seeded from
+    /// [`Runtime::seed`] on every reset, so replaying a seed replays every
+    /// roll.
+    rng: Rng,
+    /// The most recent roll of every chance group visited so far, keyed by
+    /// the group's head block. Re-rolled each time the head is reached.
+    chance_rolls: HashMap<BlockId, ChanceRoll>,
+    /// Draw state of every bucket visited so far, keyed by the bucket block.
+    /// Cleared by a reset and by `-> RESTART`, which put every card back.
+    bucket_draws: HashMap<BlockId, BucketDraws>,
+    /// What the `freq` lines run so far added to the weight of the block
+    /// they're nested under, keyed by that block. Cleared by a reset and by
+    /// `-> RESTART`, like the bucket draws.
+    frequency_adjustments: HashMap<BlockId, i64>,
+    /// The odds every chance-picked entry of `current_path` was picked with,
+    /// keyed by its index in the path. Kept per entry because a group that is
+    /// visited again re-rolls, possibly with different weights.
+    path_odds: HashMap<usize, (u64, u64)>,
+    /// The text every interpolated line of `current_path` was shown with,
+    /// keyed by its index in the path. Rendered when the line is entered, so
+    /// a later `set` doesn't change text that was already shown.
+    path_text: HashMap<usize, String>,
+    /// How many times each `{a|b|c}` fragment has been rendered, keyed by
+    /// its index in `Database.text_alternatives`. Cleared by a reset and by
+    /// `-> RESTART`.
+    alternative_visits: HashMap<AlternativesId, usize>,
+    /// `[once]` options the player has picked, which no longer show up in
+    /// menus. Survives section jumps; cleared by a reset and by
+    /// `-> RESTART`.
+    consumed_options: HashSet<BlockId>,
+    /// How many times each block has been entered, keyed by the block. A
+    /// section's count is the count of its block; `visits(path)` reads it.
+    /// Cleared by a reset and by `-> RESTART`.
+    visit_counts: HashMap<BlockId, usize>,
+    /// Set when `step()` moved the program counter past a gated subtree
+    /// instead of onto an executed block. The block it landed on was never
+    /// entered, so a jump there must not fire.
+    skipped_gated_subtree: bool,
 }
 
 impl RuntimeState {
-    fn new() -> Self {
+    fn new(seed: u64) -> Self {
         Self {
             program_counter: 0,
             previous_program_counter: 0,
@@ -40,16 +131,69 @@ impl RuntimeState {
             call_stack: Vec::new(),
             waiting_for_option_selection: false,
             current_options: Vec::new(),
+            current_option_texts: Vec::new(),
             last_error: None,
             variable_values: Vec::new(),
+            rng: Rng::new(seed),
+            chance_rolls: HashMap::new(),
+            bucket_draws: HashMap::new(),
+            frequency_adjustments: HashMap::new(),
+            path_odds: HashMap::new(),
+            path_text: HashMap::new(),
+            alternative_visits: HashMap::new(),
+            consumed_options: HashSet::new(),
+            visit_counts: HashMap::new(),
+            skipped_gated_subtree: false,
         }
     }
 
-    fn with_start_block() -> Self {
-        let mut state = Self::new();
-        state.current_path.push(0); // START block
+    fn with_start_block(seed: u64) -> Self {
+        let mut state = Self::new(seed);
+        state.enter(0); // START block
         state
     }
+
+    /// Add `block_id` to the execution path and count the visit.
+    fn enter(&mut self, block_id: BlockId) {
+        self.current_path.push(block_id);
+        *self.visit_counts.entry(block_id).or_insert(0) += 1;
+    }
+}
+
+/// What expressions read at runtime: the current variable values, the
+/// visit counts of the sections named by `visits(path)`, and the functions
+/// the game registered.
+struct StateContext<'a> {
+    database: &'a Database,
+    /// The translation whose line is being rendered, for the `visits`
+    /// reads its own `{...}` fragments added.
+    language: Option<&'a LanguageTable>,
+    state: &'a RuntimeState,
+    functions: &'a FunctionRegistry,
+}
+
+impl<'a> EvaluationContext<'a> for StateContext<'a> {
+    fn value(&self, id: VariableId) -> &'a Value {
+        &self.state.variable_values[id]
+    }
+
+    fn visits(&self, id: VisitsId) -> usize {
+        let section_id = language::visit_target(self.database, self.language, id);
+        let section = &self.database.sections[section_id];
+        self.state
+            .visit_counts
+            .get(&section.block_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn call(
+        &self,
+        function: FunctionId,
+        arguments: &[Value],
+    ) -> Result<Option<Value>, EvaluationError> {
+        self.functions.call(self.database, function, arguments)
+    }
 }
 
 pub struct Runtime {
@@ -60,15 +204,51 @@ pub struct Runtime {
     /// `<file>:<line>: RUNTIME ERROR: ...`. None when the database came from
     /// an in-memory script (e.g. unit tests).
     file_path: Option<PathBuf>,
+    /// Seed the RNG is (re)built from on every reset. Picked at random on
+    /// construction unless the host calls [`Runtime::set_seed`].
+    seed: u64,
+    /// Checksum of the structure file the runtime was built from, which
+    /// strings files must name. None for a parsed database.
+    structure_checksum: Option<u64>,
+    /// Built from a structure file whose strings haven't been loaded yet.
+    strings_pending: bool,
+    /// Translations by lowercased language tag.
+    languages: HashMap<String, LanguageTable>,
+    /// The language set with [`Runtime::set_language`].
+    language: String,
+    /// The tags strings are looked up in, from [`language::language_chain`].
+    language_chain: Vec<String>,
+    /// The language the script is written in.
+    default_language: String,
+    /// Functions registered with [`Runtime::register_function`].
+    functions: FunctionRegistry,
+    /// Events queued for [`Runtime::take_events`].
+    events: EventQueue,
+    /// Watchers added with [`Runtime::watch_variable`].
+    watchers: VariableWatchers,
+    /// Checkpoints [`Runtime::rewind`] goes back to.
+    history: History,
 }
 
 impl Runtime {
     pub fn new(database: Database) -> Self {
+        let seed = Rng::entropy_seed();
         Self {
             database,
             running: false,
-            state: RuntimeState::new(),
+            state: RuntimeState::new(seed),
             file_path: None,
+            seed,
+            structure_checksum: None,
+            strings_pending: false,
+            languages: HashMap::new(),
+            language: DEFAULT_LANGUAGE.to_string(),
+            language_chain: Vec::new(),
+            default_language: DEFAULT_LANGUAGE.to_string(),
+            functions: FunctionRegistry::default(),
+            events: EventQueue::default(),
+            watchers: VariableWatchers::default(),
+            history: History::default(),
         }
     }
 
@@ -76,11 +256,35 @@ impl Runtime {
     /// runtime arithmetic errors to include `<file>:<line>:` prefixes that
     /// match the parse-time error format.
     pub fn with_file(database: Database, file_path: PathBuf) -> Self {
+        let seed = Rng::entropy_seed();
         Self {
             database,
             running: false,
-            state: RuntimeState::new(),
+            state: RuntimeState::new(seed),
             file_path: Some(file_path),
+            seed,
+            structure_checksum: None,
+            strings_pending: false,
+            languages: HashMap::new(),
+            language: DEFAULT_LANGUAGE.to_string(),
+            language_chain: Vec::new(),
+            default_language: DEFAULT_LANGUAGE.to_string(),
+            functions: FunctionRegistry::default(),
+            events: EventQueue::default(),
+            watchers: VariableWatchers::default(),
+            history: History::default(),
+        }
+    }
+
+    /// The script `block_id` was read from, for error prefixes: an included
+    /// script, found next to the main one, or the main script.
+    fn block_file(&self, block_id: BlockId) -> Option<PathBuf> {
+        match self.database.block_file(block_id) {
+            Some(included) => Some(match &self.file_path {
+                Some(main) => main.with_file_name(included),
+                None => PathBuf::from(included),
+            }),
+            None => self.file_path.clone(),
         }
     }
 
@@ -91,7 +295,8 @@ impl Runtime {
 
     pub fn stop(&mut self) {
         self.running = false;
-        self.state = RuntimeState::new();
+        self.state = RuntimeState::new(self.seed);
+        self.history.clear();
     }
 
     pub fn running(&self) -> bool {
@@ -103,11 +308,14 @@ impl Runtime {
     /// Unconditionally reinitializes `variable_values` from each variable's
     /// declared default — any runtime mutations made via
     /// [`Runtime::set_variable_value`] since the last reset are discarded.
+    /// The RNG is rebuilt from [`Runtime::seed`], so a reset replays the
+    /// same rolls. The [`rewind`](Self::rewind) history is cleared.
     pub fn reset(&mut self) {
+        self.history.clear();
         self.state = if !self.database.blocks.is_empty() {
-            RuntimeState::with_start_block()
+            RuntimeState::with_start_block(self.seed)
         } else {
-            RuntimeState::new()
+            RuntimeState::new(self.seed)
         };
         self.state.variable_values = self
             .database
@@ -117,6 +325,99 @@ impl Runtime {
             .collect();
     }
 
+    /// The seed the RNG was last built from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Register the function the script calls as `name`. `parameters` and
+    /// `returns` must match the kinds the script declared it with; `body`
+    /// is only ever given arguments of those kinds, and must return a value
+    /// of the `returns` kind. Registering a name again replaces the
+    /// function.
+    ///
+    /// `body` must be `Send` and `Sync`, so the runtime can still be moved
+    /// to another thread. A script calling a function that isn't registered
+    /// stops with a runtime error when the call is reached.
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        parameters: &[ValueKind],
+        returns: Option<ValueKind>,
+        body: impl Fn(&[Value]) -> Option<Value> + Send + Sync + 'static,
+    ) {
+        self.functions.register(
+            FunctionSignature::new(name, parameters, returns),
+            Box::new(body),
+        );
+    }
+
+    /// Start or stop queueing [`RuntimeEvent`]s for [`Runtime::take_events`].
+    /// Events aren't queued until this is turned on; turning it off drops
+    /// the events not taken yet.
+    pub fn record_events(&mut self, recording: bool) {
+        self.events.set_recording(recording);
+    }
+
+    /// Take the events queued since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<RuntimeEvent> {
+        self.events.take()
+    }
+
+    /// Also take a [`rewind`](Self::rewind) checkpoint before every
+    /// [`step`](Self::step) and [`skip`](Self::skip), not only before each
+    /// option selection.
+    pub fn checkpoint_every_step(&mut self, enabled: bool) {
+        self.history.set_every_step(enabled);
+    }
+
+    /// Keep at most about `bytes` of [`rewind`](Self::rewind) checkpoints,
+    /// dropping the oldest first. Defaults to [`DEFAULT_HISTORY_CAP`]; 0
+    /// turns the history off.
+    pub fn set_history_cap(&mut self, bytes: usize) {
+        self.history.set_cap(bytes);
+    }
+
+    /// How many checkpoints [`rewind`](Self::rewind) can go back.
+    pub fn checkpoint_count(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Go back `n` checkpoints, to the state the runtime was in before the
+    /// `n`th latest option selection (or step, with
+    /// [`checkpoint_every_step`](Self::checkpoint_every_step)). Variables,
+    /// the path, the call stack and the RNG are restored exactly, so the
+    /// same choices replay the same story. The checkpoints gone back over
+    /// are dropped.
+    ///
+    /// Returns `false`, leaving the runtime untouched, if fewer than `n`
+    /// checkpoints are kept. Like a restored snapshot, a rewound runtime
+    /// starts with no error; no events are queued and no watchers run.
+    pub fn rewind(&mut self, n: usize) -> bool {
+        let Some(checkpoint) = self.history.rewind(n) else {
+            return false;
+        };
+        self.seed = checkpoint.seed;
+        self.running = checkpoint.running;
+        self.state = checkpoint.state;
+        self.state.last_error = None;
+        true
+    }
+
+    fn checkpoint(&mut self) {
+        self.history
+            .push(Checkpoint::new(self.seed, self.running, self.state.clone()));
+    }
+
+    /// Reseed the RNG. Every roll from here on is fully determined by
+    /// `seed`: two runtimes given the same database, seed, and inputs
+    /// produce the same transcript on every platform. The seed is kept
+    /// across [`Runtime::reset`], so `run()` after `set_seed` replays it too.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.state.rng = Rng::new(seed);
+    }
+
     /// Returns the current value of every declared variable, in declaration order.
     pub fn variable_values(&self) -> &[Value] {
         &self.state.variable_values
@@ -137,12 +438,7 @@ impl Runtime {
     /// This is introduced now so `set` execution and external mutations share
     /// a single stable mutation point.
     pub fn set_variable_value(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
-        let id =
-            self.database
-                .variable_id(name)
-                .ok_or_else(|| RuntimeError::UndefinedVariable {
-                    name: name.to_string(),
-                })?;
+        let id = self.variable_id_or_error(name)?;
         // Reject writes that don't match the declared kind. Today there's
         // only one `Value` variant, so this is unreachable — it exists as a
         // guard for future kinds (bool/float/string) so the setter can't
@@ -152,10 +448,42 @@ impl Runtime {
                 name: name.to_string(),
             });
         }
-        self.state.variable_values[id] = value;
+        self.assign(id, value, None);
+        Ok(())
+    }
+
+    /// Run `watcher` whenever the variable `name` changes, from a `set` line
+    /// or from [`set_variable_value`](Self::set_variable_value). It gets
+    /// the old and new values and the line that made the change, and isn't
+    /// run for an assignment that keeps the value. It must be `Send`, so the
+    /// runtime can still be moved to another thread. Returns
+    /// [`RuntimeError::UndefinedVariable`] if no variable with that name has
+    /// been declared.
+    pub fn watch_variable(
+        &mut self,
+        name: &str,
+        watcher: impl FnMut(&VariableChange) + Send + 'static,
+    ) -> Result<(), RuntimeError> {
+        let id = self.variable_id_or_error(name)?;
+        self.watchers.watch(id, Box::new(watcher));
+        Ok(())
+    }
+
+    /// Drop every watcher added for the variable `name`.
+    pub fn unwatch_variable(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let id = self.variable_id_or_error(name)?;
+        self.watchers.unwatch(id);
         Ok(())
     }
 
+    fn variable_id_or_error(&self, name: &str) -> Result<VariableId, RuntimeError> {
+        self.database
+            .variable_id(name)
+            .ok_or_else(|| RuntimeError::UndefinedVariable {
+                name: name.to_string(),
+            })
+    }
+
     /// Find a section by its path string, resolving relative paths from current context
     pub fn find_section_by_path(&self, path: &str) -> Result<ResolvedPath, RuntimeError> {
         // Find the containing section based on current program counter
@@ -271,11 +599,132 @@ impl Runtime {
         &self.state.current_options
     }
 
+    /// Returns the odds the entry at `path_index` in [`current_path`] was
+    /// picked with, as `(weight, total)` — e.g. `(50, 100)` for a `(50)` line
+    /// next to another `(50)`, or for a `(50%)` line. Computed and
+    /// `freq`-adjusted weights are reported as they were at that roll. `None`
+    /// if the entry wasn't picked from a chance group.
+    ///
+    /// [`current_path`]: Self::current_path
+    pub fn chance_odds(&self, path_index: usize) -> Option<(u64, u64)> {
+        self.state.path_odds.get(&path_index).copied()
+    }
+
+    /// The text the entry at `path_index` in [`current_path`] was shown
+    /// with: its `{...}` fragments filled in as they were when it was
+    /// entered (or, for an option, when its menu was shown). `None` if the
+    /// entry isn't a text or option line.
+    ///
+    /// [`current_path`]: Self::current_path
+    pub fn path_text(&self, path_index: usize) -> Option<&str> {
+        if let Some(text) = self.state.path_text.get(&path_index) {
+            return Some(text);
+        }
+        let &block_id = self.state.current_path.get(path_index)?;
+        match self.database.blocks[block_id].block_type {
+            BlockType::String(string_id) | BlockType::Option(string_id) => {
+                Some(self.text(string_id))
+            }
+            _ => None,
+        }
+    }
+
+    /// The text of option number `choice` (1-based, as in
+    /// [`select_option`](Self::select_option)) of the menu waiting for a
+    /// selection, rendered when the menu was built.
+    pub fn option_text(&self, choice: usize) -> Option<&str> {
+        let index = choice.checked_sub(1)?;
+        self.state
+            .current_option_texts
+            .get(index)
+            .map(String::as_str)
+    }
+
+    /// Render the text or option line `string_id` with the current variable
+    /// values. Integers and booleans print as written in scripts, floats
+    /// with [`format_float`], and strings and enums as their bare contents.
+    /// Reading an enum that was never assigned is an error.
+    ///
+    /// Rendering counts as a visit: `{a|b|c}` alternatives in the line move
+    /// on to their next branch, and shuffled ones draw from the RNG. The
+    /// runtime renders every line it enters and every option of a menu
+    /// itself; read those back with [`path_text`] and [`option_text`].
+    ///
+    /// [`format_float`]: cuentitos_common::value::format_float
+    /// [`path_text`]: Self::path_text
+    /// [`option_text`]: Self::option_text
+    pub fn render_string(&mut self, string_id: StringId) -> Result<String, RuntimeError> {
+        let language = language::translation_of(&self.languages, &self.language_chain, string_id);
+        let fragments = match language {
+            Some(table) => table.fragments(string_id),
+            None => self
+                .database
+                .text_fragments
+                .get(&string_id)
+                .map(Vec::as_slice),
+        };
+        let Some(fragments) = fragments else {
+            return Ok(self.text(string_id).to_string());
+        };
+        let mut rendered = String::new();
+        if let Err(err) = render_fragments(
+            fragments,
+            &self.database,
+            language,
+            &self.functions,
+            &mut self.state,
+            &mut rendered,
+        ) {
+            let block_id = self.block_of_string(string_id);
+            return Err(self.evaluation_error_to_runtime(err, block_id));
+        }
+        Ok(rendered)
+    }
+
+    /// The text or option block showing `string_id`, or `START` if none
+    /// does.
+    fn block_of_string(&self, string_id: StringId) -> BlockId {
+        self.database
+            .blocks
+            .iter()
+            .position(|block| {
+                matches!(
+                    block.block_type,
+                    BlockType::String(id) | BlockType::Option(id) if id == string_id
+                )
+            })
+            .unwrap_or(0)
+    }
+
+    /// The odds `block_id` won its group's latest roll with, if it did.
+    fn latest_odds(&self, block_id: BlockId) -> Option<(u64, u64)> {
+        let &head = self.database.chance_group(block_id).first()?;
+        let roll = self.state.chance_rolls.get(&head)?;
+        if roll.winner != Some(block_id) {
+            return None;
+        }
+        Some((roll.weight, roll.total))
+    }
+
     /// Returns the current path of executed blocks
     pub fn current_path(&self) -> &[BlockId] {
         &self.state.current_path
     }
 
+    /// How many times the section `section_id` has been entered since the
+    /// last reset or `-> RESTART`. This is the count `visits(path)` reads.
+    pub fn visit_count(&self, section_id: SectionId) -> usize {
+        self.block_visit_count(self.database.sections[section_id].block_id)
+    }
+
+    /// How many times `block_id` has been entered since the last reset or
+    /// `-> RESTART`, counting every time it was added to [`current_path`].
+    ///
+    /// [`current_path`]: Self::current_path
+    pub fn block_visit_count(&self, block_id: BlockId) -> usize {
+        self.state.visit_counts.get(&block_id).copied().unwrap_or(0)
+    }
+
     /// Select an option by its number (1-based)
     /// Returns Ok(()) if successful, Err(message) if invalid choice
     pub fn select_option(&mut self, choice: usize) -> Result<(), String> {
@@ -286,12 +735,27 @@ impl Runtime {
         if choice == 0 || choice > self.state.current_options.len() {
             return Err(format!("Invalid option: {}", choice));
         }
+        self.checkpoint();
 
         // Get the selected option block ID (choice is 1-based, vec is 0-based)
         let selected_option_id = self.state.current_options[choice - 1];
+        if self.database.is_once_only_option(selected_option_id) {
+            self.state.consumed_options.insert(selected_option_id);
+        }
 
-        // Add selected option to execution path
-        self.state.current_path.push(selected_option_id);
+        // Add selected option to execution path, with the text it was shown
+        // with
+        let path_index = self.state.current_path.len();
+        let text = std::mem::take(&mut self.state.current_option_texts[choice - 1]);
+        if self.events.is_recording() {
+            self.events.push(RuntimeEvent::OptionChosen {
+                choice,
+                block_id: selected_option_id,
+                text: text.clone(),
+            });
+        }
+        self.state.path_text.insert(path_index, text);
+        self.enter(selected_option_id);
 
         // Move program counter to the selected option
         self.state.program_counter = selected_option_id;
@@ -299,6 +763,7 @@ impl Runtime {
         // Clear option selection state
         self.state.waiting_for_option_selection = false;
         self.state.current_options.clear();
+        self.state.current_option_texts.clear();
 
         Ok(())
     }
@@ -309,12 +774,16 @@ impl Runtime {
             return None;
         }
 
+        // The last descendant of a skipped subtree may itself be a jump (a
+        // `->` bucket card, or a `->` nested under a failing `req`). It was
+        // never entered, so traversal just moves past it.
+        let entered = !std::mem::take(&mut self.state.skipped_gated_subtree);
         let current_block = &self.database.blocks[self.state.program_counter];
 
         // Handle special goto variants
         match &current_block.block_type {
             // GoToAndBack: push to call stack and jump to section
-            BlockType::GoToAndBack(section_id) => {
+            BlockType::GoToAndBack(section_id) if entered => {
                 // Check maximum call stack depth to prevent infinite loops
                 const MAX_CALL_DEPTH: usize = 200;
                 if self.state.call_stack.len() >= MAX_CALL_DEPTH {
@@ -332,7 +801,7 @@ impl Runtime {
                             MAX_CALL_DEPTH, section_name
                         )
                     };
-                    self.state.last_error = Some(RuntimeError::InvalidPath { message });
+                    self.record_error(RuntimeError::InvalidPath { message });
                     // Jump to END to terminate execution
                     return Some(self.database.blocks.len() - 1);
                 }
@@ -349,25 +818,34 @@ impl Runtime {
                     return_block_id,
                     called_section_id: target_block_id,
                 });
+                self.events.push(RuntimeEvent::CallPushed {
+                    section_id: *section_id,
+                    return_block_id,
+                });
 
                 return Some(target_block_id);
             }
 
             // GoTo: jump to section
-            BlockType::GoTo(section_id) => {
+            BlockType::GoTo(section_id) if entered => {
                 let section = &self.database.sections[*section_id];
                 return Some(section.block_id);
             }
 
             // GoToStart: clear call stack and jump to START
-            BlockType::GoToStart => {
+            BlockType::GoToStart if entered => {
                 self.state.call_stack.clear();
                 return Some(0);
             }
 
             // GoToRestart: clear state (except current_path) and jump to START
-            BlockType::GoToRestart => {
+            BlockType::GoToRestart if entered => {
                 self.state.call_stack.clear();
+                self.state.bucket_draws.clear();
+                self.state.frequency_adjustments.clear();
+                self.state.consumed_options.clear();
+                self.state.alternative_visits.clear();
+                self.state.visit_counts.clear();
                 self.state.program_counter = 0;
                 self.state.previous_program_counter = 0;
                 // Don't touch current_path - let step() add block 0
@@ -375,7 +853,7 @@ impl Runtime {
             }
 
             // GoToEnd: jump to END
-            BlockType::GoToEnd => {
+            BlockType::GoToEnd if entered => {
                 return Some(self.database.blocks.len() - 1);
             }
 
@@ -390,7 +868,16 @@ impl Runtime {
             // If natural_next is outside the called section's subtree, return instead
             if self.is_outside_section(natural_next, frame.called_section_id) {
                 let return_id = frame.return_block_id;
+                let section_block_id = frame.called_section_id;
                 self.state.call_stack.pop();
+                if let BlockType::Section(section_id) =
+                    self.database.blocks[section_block_id].block_type
+                {
+                    self.events.push(RuntimeEvent::CallPopped {
+                        section_id,
+                        return_block_id: return_id,
+                    });
+                }
                 return Some(return_id);
             }
         }
@@ -426,11 +913,17 @@ impl Runtime {
                     BlockType::Option(_)
                 );
 
-                // Check if we're inside an option's subtree (recalculate for current_id)
-                let inside_option = self.is_inside_option_subtree(current_id);
+                // Check if we're leaving an option at this level. Only the
+                // option itself counts: a silent `req`/chance marker that
+                // precedes nested options is a sibling of those options, and
+                // moving past it must still reach them.
+                let exiting_option = matches!(
+                    self.database.blocks[current_id].block_type,
+                    BlockType::Option(_)
+                );
 
                 // If we're exiting an option and this sibling is an option, skip it
-                if inside_option && sibling_is_option {
+                if exiting_option && sibling_is_option {
                     continue;
                 }
 
@@ -491,6 +984,14 @@ impl Runtime {
     }
 
     pub fn step(&mut self) -> bool {
+        if self.history.every_step() && self.can_continue() {
+            self.checkpoint();
+        }
+        self.advance()
+    }
+
+    /// Take one [`step`](Self::step), without a checkpoint.
+    fn advance(&mut self) -> bool {
         if !self.can_continue() {
             return false;
         }
@@ -503,23 +1004,67 @@ impl Runtime {
                 return advanced;
             };
 
-            // Before entering `next_id`, evaluate any `req` children that
-            // gate it. A failing `req` skips `next_id` and its entire
-            // subtree without rendering anything; an evaluation error
-            // (overflow, div-by-zero) propagates as a runtime error.
-            match self.evaluate_requirement_gating(next_id) {
+            // Reaching the first option of a menu builds the menu: each
+            // option's `req`s decide whether it is listed. Options are
+            // therefore not gated one by one below — skipping a hidden first
+            // option would leave the whole menu. When nothing is listed the
+            // menu's `[fallback]` is entered in its place.
+            let mut next_id = next_id;
+            let mut took_fallback = false;
+            let gate = if matches!(
+                self.database.blocks[next_id].block_type,
+                BlockType::Option(_)
+            ) {
+                match self.collect_options_at(next_id) {
+                    // Stop on options — the CLI must prompt the user.
+                    Ok(None) => return advanced,
+                    Ok(Some(fallback_id)) => {
+                        next_id = fallback_id;
+                        took_fallback = true;
+                        self.events.push(RuntimeEvent::FallbackTaken {
+                            block_id: fallback_id,
+                        });
+                        Ok(true)
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            // Before entering `next_id`, resolve its chance group (if it
+            // has a `(N)` prefix) and evaluate any `req` children that gate
+            // it. A losing chance roll or a failing `req` skips `next_id`
+            // and its entire subtree without rendering anything; an
+            // evaluation error (overflow, div-by-zero) propagates as a
+            // runtime error.
+            else if self.evaluate_bucket_gating(next_id) {
+                self.evaluate_chance_gating(next_id).and_then(|picked| {
+                    if !picked {
+                        return Ok(false);
+                    }
+                    let passed = self.evaluate_requirement_gating(next_id)?;
+                    if !passed {
+                        self.events
+                            .push(RuntimeEvent::RequirementSkipped { block_id: next_id });
+                    }
+                    Ok(passed)
+                })
+            } else {
+                Ok(false)
+            };
+            match gate {
                 Ok(true) => {}
                 Ok(false) => {
                     let skip_to = self.last_descendant(next_id);
                     self.state.previous_program_counter = self.state.program_counter;
                     self.state.program_counter = skip_to;
+                    self.state.skipped_gated_subtree = true;
                     if !self.can_continue() {
                         return advanced;
                     }
                     continue;
                 }
                 Err(err) => {
-                    self.state.last_error = Some(err);
+                    self.record_error(err);
+                    self.state.waiting_for_option_selection = false;
                     let end_id = self.database.blocks.len() - 1;
                     self.state.previous_program_counter = self.state.program_counter;
                     self.state.program_counter = end_id;
@@ -527,13 +1072,18 @@ impl Runtime {
                 }
             }
 
-            // Stop on options — the CLI must prompt the user.
+            // Entering a bucket draws the card its gating lets through.
             if matches!(
                 self.database.blocks[next_id].block_type,
-                BlockType::Option(_)
+                BlockType::Bucket(_)
             ) {
-                self.collect_options_at(next_id);
-                return advanced;
+                if let Err(err) = self.draw_from_bucket(next_id) {
+                    self.record_error(err);
+                    let end_id = self.database.blocks.len() - 1;
+                    self.state.previous_program_counter = self.state.program_counter;
+                    self.state.program_counter = end_id;
+                    return advanced;
+                }
             }
 
             // Apply `set` side effects when stepping *onto* the block.
@@ -541,9 +1091,32 @@ impl Runtime {
             // terminate the outer loop) but do NOT push END onto
             // current_path (so render_path_from won't print END).
             if let BlockType::Set(set_id) = self.database.blocks[next_id].block_type {
-                let line = self.database.blocks[next_id].line;
-                if let Err(err) = self.apply_set(set_id, line) {
-                    self.state.last_error = Some(err);
+                if let Err(err) = self.apply_set(set_id, next_id) {
+                    self.record_error(err);
+                    let end_id = self.database.blocks.len() - 1;
+                    self.state.previous_program_counter = self.state.program_counter;
+                    self.state.program_counter = end_id;
+                    return advanced;
+                }
+            }
+
+            // A `call` runs its function on the same step, and a missing or
+            // mismatched function halts execution the same way.
+            if let BlockType::Call(call_id) = self.database.blocks[next_id].block_type {
+                if let Err(err) = self.apply_call(call_id, next_id) {
+                    self.record_error(err);
+                    let end_id = self.database.blocks.len() - 1;
+                    self.state.previous_program_counter = self.state.program_counter;
+                    self.state.program_counter = end_id;
+                    return advanced;
+                }
+            }
+
+            // A `freq` line is only reached when its parent was shown, so
+            // this is the moment the parent's weight changes.
+            if let BlockType::Frequency(frequency_id) = self.database.blocks[next_id].block_type {
+                if let Err(err) = self.apply_frequency(next_id, frequency_id) {
+                    self.record_error(err);
                     let end_id = self.database.blocks.len() - 1;
                     self.state.previous_program_counter = self.state.program_counter;
                     self.state.program_counter = end_id;
@@ -551,14 +1124,41 @@ impl Runtime {
                 }
             }
 
+            // Interpolated text is rendered now, with the values it is shown
+            // with.
+            let mut text = None;
+            if let BlockType::String(string_id) = self.database.blocks[next_id].block_type {
+                if self.database.text_fragments.contains_key(&string_id) {
+                    match self.render_string(string_id) {
+                        Ok(rendered) => text = Some(rendered),
+                        Err(err) => {
+                            self.record_error(err);
+                            let end_id = self.database.blocks.len() - 1;
+                            self.state.previous_program_counter = self.state.program_counter;
+                            self.state.program_counter = end_id;
+                            return advanced;
+                        }
+                    }
+                }
+            }
+
             self.state.previous_program_counter = self.state.program_counter;
             self.state.program_counter = next_id;
-            self.state.current_path.push(next_id);
+            if let Some(text) = text {
+                let path_index = self.state.current_path.len();
+                self.state.path_text.insert(path_index, text);
+            }
+            if let Some(odds) = self.latest_odds(next_id) {
+                let path_index = self.state.current_path.len();
+                self.state.path_odds.insert(path_index, odds);
+            }
+            self.enter(next_id);
             advanced = true;
 
             // Continue past silent blocks so a single `step()` lands on the
-            // next visible block. Any non-silent block ends the step.
-            if !Self::is_silent_block(&self.database.blocks[next_id].block_type) {
+            // next visible block. Any non-silent block ends the step. A
+            // fallback taken in place of a menu renders nothing either.
+            if !took_fallback && !Self::is_silent_block(&self.database.blocks[next_id].block_type) {
                 return true;
             }
             if !self.can_continue() {
@@ -567,11 +1167,52 @@ impl Runtime {
         }
     }
 
+    /// Add `block_id` to the path, and report entering it: a text line as
+    /// shown, with the text it was rendered with, a section as entered, and
+    /// `END` as the end of the story.
+    fn enter(&mut self, block_id: BlockId) {
+        self.state.enter(block_id);
+        if !self.events.is_recording() {
+            return;
+        }
+        let event = match self.database.blocks[block_id].block_type {
+            BlockType::String(_) => {
+                let path_index = self.state.current_path.len() - 1;
+                RuntimeEvent::TextShown {
+                    block_id,
+                    text: self.path_text(path_index).unwrap_or_default().to_string(),
+                }
+            }
+            BlockType::Section(section_id) => RuntimeEvent::SectionEntered {
+                section_id,
+                block_id,
+            },
+            BlockType::End => RuntimeEvent::StoryEnded,
+            _ => return,
+        };
+        self.events.push(event);
+    }
+
+    /// Keep `err` for [`Runtime::take_last_error`], and report it.
+    fn record_error(&mut self, err: RuntimeError) {
+        self.events.push(RuntimeEvent::Error(err.clone()));
+        self.state.last_error = Some(err);
+    }
+
     /// Blocks that produce no narrative output and should be traversed
-    /// transparently by a single `step()`. `Set` mutates a variable;
+    /// transparently by a single `step()`. `Set` mutates a variable, `Call`
+    /// runs a game function, `Frequency` changes its parent's weight;
     /// `Requirement` gates its parent and is itself never rendered.
     fn is_silent_block(block_type: &BlockType) -> bool {
-        matches!(block_type, BlockType::Set(_) | BlockType::Requirement(_))
+        matches!(
+            block_type,
+            BlockType::Set(_)
+                | BlockType::Call(_)
+                | BlockType::Requirement(_)
+                | BlockType::Chance(_)
+                | BlockType::Bucket(_)
+                | BlockType::Frequency(_)
+        )
     }
 
     /// Walk to the rightmost descendant of `block_id` so we can land
@@ -595,28 +1236,30 @@ impl Runtime {
     /// runtime errors don't fire when an earlier sibling already
     /// disqualified the parent.
     ///
-    /// Note: gating runs before the `Option` stop in `step()`, so failing-req
-    /// options are silently filtered out of the choice list — the runtime
-    /// walks past them and either presents the next passing option or, if
-    /// none remain, lands on the post-options content.
+    /// Options are not gated in `step()`; `collect_options_at` calls this
+    /// for each option to decide whether it is listed in the menu.
     fn evaluate_requirement_gating(&self, block_id: BlockId) -> Result<bool, RuntimeError> {
-        // The lookup closure captures `&self.state.variable_values` and
-        // is identical for every sibling `req`. Build it once outside
-        // the loop — same shape `apply_set` uses for its one-shot eval.
-        let lookup = cuentitos_common::variable_lookup(&self.state.variable_values);
+        // The context borrows the runtime state and is identical for every
+        // sibling `req`. Build it once outside the loop — same shape
+        // `apply_set` uses for its one-shot eval.
+        let lookup = StateContext {
+            database: &self.database,
+            language: None,
+            state: &self.state,
+            functions: &self.functions,
+        };
         for &child_id in &self.database.blocks[block_id].children {
             let BlockType::Requirement(requirement_id) = self.database.blocks[child_id].block_type
             else {
                 continue;
             };
             let expression = &self.database.requirements[requirement_id];
-            let line = self.database.blocks[child_id].line;
             // `BooleanExpression::evaluate` short-circuits internally for
             // `and`/`or`/`not`. Sibling `req`s remain implicitly ANDed by
             // the loop here — failing one short-circuits the whole gate.
             let outcome = match expression.evaluate(&lookup) {
                 Ok(value) => value,
-                Err(err) => return Err(self.evaluation_error_to_runtime(err, line)),
+                Err(err) => return Err(self.evaluation_error_to_runtime(err, child_id)),
             };
             if !outcome {
                 return Ok(false);
@@ -625,6 +1268,256 @@ impl Runtime {
         Ok(true)
     }
 
+    /// Decide whether `block_id` is entered as far as its chance group is
+    /// concerned. Blocks without a chance prefix always pass. Reaching the
+    /// group head rolls the group; every member (the head included) then
+    /// passes only if it is the recorded winner.
+    fn evaluate_chance_gating(&mut self, block_id: BlockId) -> Result<bool, RuntimeError> {
+        let Some(&head) = self.database.chance_group(block_id).first() else {
+            return Ok(true);
+        };
+        if head == block_id {
+            self.roll_chance_group(head)?;
+        }
+        Ok(self
+            .state
+            .chance_rolls
+            .get(&head)
+            .is_some_and(|roll| roll.winner == Some(block_id)))
+    }
+
+    /// Decide whether `block_id` is entered as far as an enclosing bucket is
+    /// concerned: a bucket card passes only if it is the card drawn on the
+    /// bucket's latest visit. Blocks outside a bucket (and the bucket's own
+    /// `req`/chance/`freq` markers) always pass.
+    fn evaluate_bucket_gating(&self, block_id: BlockId) -> bool {
+        let Some(parent_id) = self.database.blocks[block_id].parent_id else {
+            return true;
+        };
+        if !matches!(
+            self.database.blocks[parent_id].block_type,
+            BlockType::Bucket(_)
+        ) || matches!(
+            self.database.blocks[block_id].block_type,
+            BlockType::Requirement(_) | BlockType::Chance(_) | BlockType::Frequency(_)
+        ) {
+            return true;
+        }
+        self.state
+            .bucket_draws
+            .get(&parent_id)
+            .is_some_and(|draws| draws.current == Some(block_id))
+    }
+
+    /// Draw the next card of the bucket at `bucket_block_id`, uniformly among
+    /// the cards not drawn yet whose own `req`s pass. When none is left to
+    /// draw, a `refill` bucket puts every card back and draws again; a `once`
+    /// bucket draws nothing, so every card is skipped.
+    fn draw_from_bucket(&mut self, bucket_block_id: BlockId) -> Result<(), RuntimeError> {
+        let BlockType::Bucket(bucket_id) = self.database.blocks[bucket_block_id].block_type else {
+            return Ok(());
+        };
+        let refill = self.database.buckets[bucket_id].refill;
+        let cards = self.database.bucket_cards(bucket_block_id);
+        let mut drawable = Vec::new();
+        for &card_id in &cards {
+            if self.evaluate_requirement_gating(card_id)? {
+                drawable.push(card_id);
+            }
+        }
+
+        let draws = self.state.bucket_draws.entry(bucket_block_id).or_default();
+        let mut candidates: Vec<BlockId> = drawable
+            .iter()
+            .copied()
+            .filter(|card_id| !draws.drawn.contains(card_id))
+            .collect();
+        if candidates.is_empty() && refill == RefillPolicy::Refill {
+            draws.drawn.clear();
+            candidates = drawable;
+        }
+
+        let current = if candidates.is_empty() {
+            None
+        } else {
+            let index = self.state.rng.below(candidates.len() as u64) as usize;
+            Some(candidates[index])
+        };
+        let draws = self.state.bucket_draws.entry(bucket_block_id).or_default();
+        draws.drawn.extend(current);
+        draws.current = current;
+        Ok(())
+    }
+
+    /// Roll the chance group headed by `head` and record the outcome in
+    /// `chance_rolls`. Members whose own `req`s currently fail are left out
+    /// of the draw, so a weighted group picks among the members that could
+    /// actually be shown.
+    fn roll_chance_group(&mut self, head: BlockId) -> Result<(), RuntimeError> {
+        // The parser rejects groups that mix weights and percentages, so the
+        // head's form is the group's form.
+        let is_percentage = self
+            .database
+            .chance_id(head)
+            .is_some_and(|chance_id| self.database.chances[chance_id].is_percentage());
+        let group = self.database.chance_group(head).to_vec();
+        let mut candidates: Vec<(BlockId, u64)> = Vec::new();
+        for member_id in group {
+            let Some(chance_id) = self.database.chance_id(member_id) else {
+                continue;
+            };
+            if self.evaluate_requirement_gating(member_id)? {
+                let weight = self.chance_weight(member_id, chance_id)?;
+                candidates.push((member_id, weight));
+            }
+        }
+
+        let total = if is_percentage {
+            100
+        } else {
+            candidates.iter().map(|&(_, weight)| weight).sum()
+        };
+        let picked = if total == 0 {
+            None
+        } else {
+            let mut roll = self.state.rng.below(total);
+            candidates.into_iter().find(|&(_, weight)| {
+                if roll < weight {
+                    true
+                } else {
+                    roll -= weight;
+                    false
+                }
+            })
+        };
+
+        let (winner, weight) = match picked {
+            Some((member_id, weight)) => (Some(member_id), weight),
+            None => (None, 0),
+        };
+        self.state.chance_rolls.insert(
+            head,
+            ChanceRoll {
+                winner,
+                weight,
+                total,
+            },
+        );
+        Ok(())
+    }
+
+    /// The weight `member_id` takes part in its group's roll with: its
+    /// percentage, its literal weight, or its computed weight evaluated
+    /// against the current variables, plus whatever its `freq` lines added
+    /// so far. A weight must land between 0 and the largest literal weight.
+    fn chance_weight(&self, member_id: BlockId, chance_id: ChanceId) -> Result<u64, RuntimeError> {
+        let file = self.block_file(member_id);
+        let line = self.database.blocks[member_id].line;
+        let base = match &self.database.chances[chance_id] {
+            Chance::Percentage(percentage) => return Ok(u64::from(*percentage)),
+            Chance::Weight(weight) => i64::from(*weight),
+            Chance::Computed { expression, .. } => {
+                let lookup = StateContext {
+                    database: &self.database,
+                    language: None,
+                    state: &self.state,
+                    functions: &self.functions,
+                };
+                match cuentitos_common::evaluate(expression, &lookup) {
+                    Ok(value) => match value.as_ref() {
+                        Value::Integer(weight) => *weight,
+                        other => {
+                            return Err(RuntimeError::EvaluationTypeMismatch {
+                                expected: ValueKind::Integer,
+                                found: other.kind(),
+                                file: file.clone(),
+                                line,
+                            })
+                        }
+                    },
+                    Err(err) => return Err(self.evaluation_error_to_runtime(err, member_id)),
+                }
+            }
+        };
+        let adjustment = self
+            .state
+            .frequency_adjustments
+            .get(&member_id)
+            .copied()
+            .unwrap_or(0);
+        let Some(weight) = base.checked_add(adjustment) else {
+            return Err(RuntimeError::ChanceWeightOutOfRange {
+                file: file.clone(),
+                line,
+            });
+        };
+        if weight < 0 {
+            return Err(RuntimeError::NegativeChanceWeight {
+                weight,
+                file: file.clone(),
+                line,
+            });
+        }
+        if weight > i64::from(u32::MAX) {
+            return Err(RuntimeError::ChanceWeightOutOfRange {
+                file: file.clone(),
+                line,
+            });
+        }
+        Ok(weight as u64)
+    }
+
+    /// Run the `freq` line `block_id`: evaluate its expression and add the
+    /// result to the weight of the block it's nested under.
+    fn apply_frequency(
+        &mut self,
+        block_id: BlockId,
+        frequency_id: FrequencyId,
+    ) -> Result<(), RuntimeError> {
+        let file = self.block_file(block_id);
+        let line = self.database.blocks[block_id].line;
+        let Some(parent_id) = self.database.blocks[block_id].parent_id else {
+            return Ok(());
+        };
+        let delta = {
+            let lookup = StateContext {
+                database: &self.database,
+                language: None,
+                state: &self.state,
+                functions: &self.functions,
+            };
+            match cuentitos_common::evaluate(
+                &self.database.frequency_modifiers[frequency_id],
+                &lookup,
+            ) {
+                Ok(value) => match value.as_ref() {
+                    Value::Integer(delta) => *delta,
+                    other => {
+                        return Err(RuntimeError::EvaluationTypeMismatch {
+                            expected: ValueKind::Integer,
+                            found: other.kind(),
+                            file: file.clone(),
+                            line,
+                        })
+                    }
+                },
+                Err(err) => return Err(self.evaluation_error_to_runtime(err, block_id)),
+            }
+        };
+        let adjustment = self
+            .state
+            .frequency_adjustments
+            .entry(parent_id)
+            .or_insert(0);
+        *adjustment = adjustment
+            .checked_add(delta)
+            .ok_or(RuntimeError::IntegerOverflow {
+                file: file.clone(),
+                line,
+            })?;
+        Ok(())
+    }
+
     /// Translate an [`EvaluationError`] into a [`RuntimeError`] using the
     /// file/line context for this runtime. Today only `DivisionByZero` and
     /// `Overflow` are reachable through normal scripts; `TypeMismatch`
@@ -633,48 +1526,84 @@ impl Runtime {
     /// it is plumbed through to a typed [`RuntimeError::EvaluationTypeMismatch`]
     /// so the runtime path doesn't `panic!` once a second `Value`
     /// variant lands. TODO: covered once Float/String land in `Value`.
-    fn evaluation_error_to_runtime(&self, err: EvaluationError, line: usize) -> RuntimeError {
+    fn evaluation_error_to_runtime(&self, err: EvaluationError, block_id: BlockId) -> RuntimeError {
+        let file = self.block_file(block_id);
+        let line = self.database.blocks[block_id].line;
         match err {
-            EvaluationError::DivisionByZero => RuntimeError::DivisionByZero {
-                file: self.file_path.clone(),
-                line,
-            },
-            EvaluationError::Overflow => RuntimeError::IntegerOverflow {
-                file: self.file_path.clone(),
-                line,
-            },
-            EvaluationError::FloatOverflow => RuntimeError::FloatOverflow {
-                file: self.file_path.clone(),
-                line,
-            },
+            EvaluationError::DivisionByZero => RuntimeError::DivisionByZero { file, line },
+            EvaluationError::Overflow => RuntimeError::IntegerOverflow { file, line },
+            EvaluationError::FloatOverflow => RuntimeError::FloatOverflow { file, line },
             EvaluationError::TypeMismatch { expected, found } => {
                 RuntimeError::EvaluationTypeMismatch {
                     expected,
                     found,
-                    file: self.file_path.clone(),
+                    file,
                     line,
                 }
             }
             EvaluationError::UnsetEnum { variable } => RuntimeError::UnsetEnumRead {
                 name: self.database.variables[variable].name.clone(),
-                file: self.file_path.clone(),
+                file,
                 line,
             },
+            EvaluationError::MissingFunction { function } => RuntimeError::MissingFunction {
+                name: self.database.functions[function].name.clone(),
+                file,
+                line,
+            },
+            EvaluationError::FunctionSignatureMismatch { function } => {
+                RuntimeError::FunctionSignatureMismatch {
+                    name: self.database.functions[function].name.clone(),
+                    file,
+                    line,
+                }
+            }
+            EvaluationError::FunctionReturnMismatch { function, found } => {
+                let declared = &self.database.functions[function];
+                RuntimeError::FunctionReturnMismatch {
+                    name: declared.name.clone(),
+                    expected: declared.returns,
+                    found,
+                    file,
+                    line,
+                }
+            }
         }
     }
 
+    /// Evaluate the arguments of a `call` line and run its function. What
+    /// the function returns is dropped.
+    fn apply_call(&self, call_id: CallId, block_id: BlockId) -> Result<(), RuntimeError> {
+        let call = &self.database.calls[call_id];
+        let lookup = StateContext {
+            database: &self.database,
+            language: None,
+            state: &self.state,
+            functions: &self.functions,
+        };
+        cuentitos_common::evaluate_arguments(&call.arguments, &lookup)
+            .and_then(|arguments| lookup.call(call.function, &arguments))
+            .map(|_| ())
+            .map_err(|err| self.evaluation_error_to_runtime(err, block_id))
+    }
+
     /// Evaluate the RHS expression of a `set` against current variable values
     /// and apply the assignment operator to the target variable.
-    fn apply_set(&mut self, set_id: SetId, line: usize) -> Result<(), RuntimeError> {
+    fn apply_set(&mut self, set_id: SetId, block_id: BlockId) -> Result<(), RuntimeError> {
         // Read inputs through immutable borrows so we don't clone the
         // expression AST or the variable-values vector. The borrows end
         // before the final write to `self.state.variable_values`.
         let (operator, variable_id, rhs_value) = {
             let statement = &self.database.sets[set_id];
-            let lookup = cuentitos_common::variable_lookup(&self.state.variable_values);
+            let lookup = StateContext {
+                database: &self.database,
+                language: None,
+                state: &self.state,
+                functions: &self.functions,
+            };
             let rhs = match cuentitos_common::evaluate(&statement.expression, &lookup) {
                 Ok(value) => value.into_owned(),
-                Err(err) => return Err(self.evaluation_error_to_runtime(err, line)),
+                Err(err) => return Err(self.evaluation_error_to_runtime(err, block_id)),
             };
             (statement.operator, statement.variable_id, rhs)
         };
@@ -684,7 +1613,7 @@ impl Runtime {
         // pair via `BinaryOperator::apply` so checked arithmetic is shared
         // with `Expression::Binary`.
         if !operator.is_compound() {
-            self.state.variable_values[variable_id] = rhs_value;
+            self.assign(variable_id, rhs_value, Some(block_id));
             return Ok(());
         }
         let binary_operator = match operator {
@@ -696,42 +1625,131 @@ impl Runtime {
         };
         let new_value = binary_operator
             .apply(&self.state.variable_values[variable_id], &rhs_value)
-            .map_err(|err| self.evaluation_error_to_runtime(err, line))?;
-        self.state.variable_values[variable_id] = new_value;
+            .map_err(|err| self.evaluation_error_to_runtime(err, block_id))?;
+        self.assign(variable_id, new_value, Some(block_id));
         Ok(())
     }
 
-    /// Collect all option siblings starting from the first option
-    fn collect_options_at(&mut self, first_option_id: BlockId) {
+    /// Write a variable, from the `set` line `block_id` or, without one,
+    /// from the game, and report the write if it changed the value.
+    fn assign(&mut self, variable_id: VariableId, value: Value, block_id: Option<BlockId>) {
+        let old = std::mem::replace(&mut self.state.variable_values[variable_id], value);
+        let new = &self.state.variable_values[variable_id];
+        if old == *new {
+            return;
+        }
+        if self.watchers.is_watched(variable_id) {
+            let change = VariableChange {
+                name: self.database.variables[variable_id].name.clone(),
+                old: old.clone(),
+                new: new.clone(),
+                file: block_id.and_then(|block_id| self.block_file(block_id)),
+                line: block_id.map(|block_id| self.database.blocks[block_id].line),
+            };
+            self.watchers.notify(variable_id, &change);
+        }
+        if self.events.is_recording() {
+            self.events.push(RuntimeEvent::VariableChanged {
+                variable: variable_id,
+                old,
+                new: self.state.variable_values[variable_id].clone(),
+            });
+        }
+    }
+
+    /// Collect all option siblings starting from the first option. Options
+    /// whose `req` children fail right now, and `[once]` options already
+    /// picked, are left out, so the menu (and the numbers `select_option`
+    /// accepts) only holds choices the player can actually take.
+    ///
+    /// The `[fallback]` option is never listed. When nothing else is
+    /// visible it is returned instead of waiting for a selection, and if it
+    /// is missing (or its own `req` fails) the menu is a dead end.
+    fn collect_options_at(
+        &mut self,
+        first_option_id: BlockId,
+    ) -> Result<Option<BlockId>, RuntimeError> {
         self.state.current_options.clear();
+        self.state.current_option_texts.clear();
         self.state.waiting_for_option_selection = true;
 
         // Get parent to find all option siblings
-        if let Some(parent_id) = self.database.blocks[first_option_id].parent_id {
+        let mut visible = Vec::new();
+        let mut fallback = None;
+        let parent_id = self.database.blocks[first_option_id].parent_id;
+        if let Some(parent_id) = parent_id {
             let parent = &self.database.blocks[parent_id];
 
             // Find all consecutive option children
+            let mut in_options = false;
             for &child_id in &parent.children {
                 if matches!(
                     self.database.blocks[child_id].block_type,
                     BlockType::Option(_)
                 ) {
-                    self.state.current_options.push(child_id);
-                } else if !self.state.current_options.is_empty() {
+                    in_options = true;
+                    if self.database.is_fallback_option(child_id) {
+                        fallback = Some(child_id);
+                        continue;
+                    }
+                    if self.state.consumed_options.contains(&child_id) {
+                        continue;
+                    }
+                    if self.evaluate_requirement_gating(child_id)? {
+                        visible.push(child_id);
+                    } else {
+                        self.events
+                            .push(RuntimeEvent::RequirementSkipped { block_id: child_id });
+                    }
+                } else if in_options {
                     // Stop when we hit a non-option after options have started
                     break;
                 }
             }
         }
-    }
-
-    pub fn skip(&mut self) -> bool {
+        if !visible.is_empty() {
+            let mut texts = Vec::with_capacity(visible.len());
+            for &option_id in &visible {
+                if let BlockType::Option(string_id) = self.database.blocks[option_id].block_type {
+                    texts.push(self.render_string(string_id)?);
+                }
+            }
+            if self.events.is_recording() {
+                self.events.push(RuntimeEvent::OptionsPresented {
+                    options: visible.iter().copied().zip(texts.iter().cloned()).collect(),
+                });
+            }
+            self.state.current_options = visible;
+            self.state.current_option_texts = texts;
+            return Ok(None);
+        }
+
+        self.state.waiting_for_option_selection = false;
+        if let Some(fallback_id) = fallback {
+            if self.evaluate_requirement_gating(fallback_id)? {
+                return Ok(Some(fallback_id));
+            }
+        }
+        let parent_id = parent_id.unwrap_or(0);
+        Err(RuntimeError::NoOptionsAvailable {
+            file: self.block_file(parent_id),
+            line: self.database.blocks[parent_id].line,
+        })
+    }
+
+    /// Step until the story ends, waits for an option, or returns from the
+    /// `<->` it was in. Takes a single checkpoint when every step is
+    /// checkpointed.
+    pub fn skip(&mut self) -> bool {
+        if self.history.every_step() && self.can_continue() {
+            self.checkpoint();
+        }
         let initial_stack_depth = self.state.call_stack.len();
         let previous_program_counter = self.state.program_counter;
 
         // Keep stepping until we reach END, return from current call, or hit options
         while !self.has_ended() && self.can_continue() && !self.state.waiting_for_option_selection {
-            self.step();
+            self.advance();
 
             // If we started in a call, stop when we return from it
             if initial_stack_depth > 0 && self.state.call_stack.len() < initial_stack_depth {
@@ -770,7 +1788,7 @@ impl Runtime {
         // Set program counter to the section block
         self.state.program_counter = target_block_id;
         // Add to current path
-        self.state.current_path.push(target_block_id);
+        self.enter(target_block_id);
 
         Ok(())
     }
@@ -807,11 +1825,15 @@ impl Runtime {
             return_block_id,
             called_section_id: target_block_id,
         });
+        self.events.push(RuntimeEvent::CallPushed {
+            section_id,
+            return_block_id,
+        });
 
         // Set program counter to the section block
         self.state.program_counter = target_block_id;
         // Add to current path
-        self.state.current_path.push(target_block_id);
+        self.enter(target_block_id);
 
         Ok(())
     }
@@ -827,7 +1849,7 @@ impl Runtime {
 
         // Jump to block 0 (START)
         self.state.program_counter = 0;
-        self.state.current_path.push(0);
+        self.enter(0);
 
         Ok(())
     }
@@ -840,13 +1862,20 @@ impl Runtime {
 
         // Clear call stack
         self.state.call_stack.clear();
+        // Put every bucket card back, forget `freq` changes, offer `[once]`
+        // options again, start text alternatives over and forget visits
+        self.state.bucket_draws.clear();
+        self.state.frequency_adjustments.clear();
+        self.state.consumed_options.clear();
+        self.state.alternative_visits.clear();
+        self.state.visit_counts.clear();
 
         // Reset counters
         self.state.program_counter = 0;
         self.state.previous_program_counter = 0;
 
         // Jump to block 0 (START)
-        self.state.current_path.push(0);
+        self.enter(0);
 
         Ok(())
     }
@@ -860,12 +1889,77 @@ impl Runtime {
         // Jump to last block (END)
         let end_block_id = self.database.blocks.len() - 1;
         self.state.program_counter = end_block_id;
-        self.state.current_path.push(end_block_id);
+        self.enter(end_block_id);
 
         Ok(())
     }
 }
 
+/// Append the rendering of `fragments` to `out`. A free function over the
+/// runtime's fields so the fragments can stay borrowed from the database
+/// while alternatives update the state.
+fn render_fragments(
+    fragments: &[TextFragment],
+    database: &Database,
+    language: Option<&LanguageTable>,
+    functions: &FunctionRegistry,
+    state: &mut RuntimeState,
+    out: &mut String,
+) -> Result<(), EvaluationError> {
+    for fragment in fragments {
+        match fragment {
+            TextFragment::Literal(text) => out.push_str(text),
+            TextFragment::Variable(variable_id) => {
+                let value = &state.variable_values[*variable_id];
+                if let Value::EnumUnset { .. } = value {
+                    return Err(EvaluationError::UnsetEnum {
+                        variable: *variable_id,
+                    });
+                }
+                out.push_str(&value.to_string());
+            }
+            TextFragment::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let holds = {
+                    let context = StateContext {
+                        database,
+                        language,
+                        state,
+                        functions,
+                    };
+                    condition.evaluate(&context)?
+                };
+                let branch = if holds { then } else { otherwise };
+                render_fragments(branch, database, language, functions, state, out)?;
+            }
+            TextFragment::Alternatives(alternatives_id) => {
+                let (alternatives, counter) =
+                    language::alternatives(database, language, *alternatives_id);
+                let count = alternatives.branches.len();
+                let visits = state.alternative_visits.get(&counter).copied().unwrap_or(0);
+                let index = match alternatives.mode {
+                    AlternativesMode::Sequence => visits.min(count - 1),
+                    AlternativesMode::Cycle => visits % count,
+                    AlternativesMode::Shuffle => state.rng.below(count as u64) as usize,
+                };
+                state.alternative_visits.insert(counter, visits + 1);
+                render_fragments(
+                    &alternatives.branches[index],
+                    database,
+                    language,
+                    functions,
+                    state,
+                    out,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1949,4 +3043,839 @@ mod test {
         );
         assert!(runtime.has_ended(), "Should have reached END");
     }
+
+    /// Helper: run `script` to the end with the chance RNG seeded to `seed`
+    /// and collect the visible `String` lines.
+    fn run_seeded_and_collect_strings(script: &str, seed: u64) -> Vec<String> {
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.set_seed(seed);
+        runtime.skip();
+        runtime
+            .current_blocks()
+            .into_iter()
+            .filter_map(|b| match b.block_type {
+                BlockType::String(id) => Some(runtime.database.strings[id].clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weighted_chance_group_picks_exactly_one_member() {
+        let script = "Before.\n(1) Heads.\n(1) Tails.\nAfter.";
+        let mut seen_heads = false;
+        let mut seen_tails = false;
+        for seed in 0..64 {
+            let strings = run_seeded_and_collect_strings(script, seed);
+            assert_eq!(
+                strings.len(),
+                3,
+                "exactly one member per visit: {strings:?}"
+            );
+            assert_eq!(strings[0], "Before.");
+            assert_eq!(strings[2], "After.");
+            seen_heads |= strings[1] == "Heads.";
+            seen_tails |= strings[1] == "Tails.";
+        }
+        assert!(
+            seen_heads && seen_tails,
+            "both members should be picked eventually"
+        );
+    }
+
+    #[test]
+    fn percentage_one_off_is_sometimes_skipped() {
+        let script = "Before.\n(50%) Maybe.\nAfter.";
+        let mut shown = 0;
+        for seed in 0..64 {
+            let strings = run_seeded_and_collect_strings(script, seed);
+            if strings.contains(&"Maybe.".to_string()) {
+                shown += 1;
+            }
+        }
+        assert!(shown > 0 && shown < 64, "shown {shown} times out of 64");
+    }
+
+    #[test]
+    fn chance_odds_reports_weight_and_total_of_the_winner() {
+        let script = "(0) Never.\n(3) Always.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.step();
+
+        let picked_index = runtime.current_path().len() - 1;
+        assert_eq!(runtime.chance_odds(picked_index), Some((3, 3)));
+        // START wasn't picked from a group.
+        assert_eq!(runtime.chance_odds(0), None);
+    }
+
+    #[test]
+    fn options_after_chance_marker_are_presented() {
+        // The chance marker is the first child of the picked line, so the
+        // nested options are its siblings; stepping past the marker must
+        // still stop at them even inside an outer option's subtree.
+        let script = "Q\n  * Go\n    (1) Inner\n      * A\n        a\n      * B\n        b";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        runtime.select_option(1).unwrap();
+        runtime.skip();
+        assert!(runtime.is_waiting_for_option());
+        assert_eq!(runtime.get_current_options().len(), 2);
+    }
+
+    #[test]
+    fn set_seed_is_reported_and_survives_reset() {
+        let (database, _warnings) = cuentitos_parser::parse("Story.").unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.set_seed(1234);
+        runtime.run();
+        assert_eq!(runtime.seed(), 1234);
+        runtime.reset();
+        assert_eq!(runtime.seed(), 1234);
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_rolls() {
+        let script = "(1) A.\n(1) B.\n(1) C.\nThen.\n(50%) D.\nThen.\n(1) E.\n(1) F.";
+        for seed in 0..16 {
+            assert_eq!(
+                run_seeded_and_collect_strings(script, seed),
+                run_seeded_and_collect_strings(script, seed)
+            );
+        }
+
+        // `run()` rebuilds the RNG from the kept seed, so a second playthrough
+        // of the same runtime rolls the same way as the first.
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.set_seed(99);
+        runtime.run();
+        runtime.skip();
+        let first = runtime.current_path().to_vec();
+        runtime.run();
+        runtime.skip();
+        assert_eq!(runtime.current_path(), first.as_slice());
+    }
+
+    const DECK_SCRIPT: &str = "--- variables\nint draws = 0\n---\n# deck: Deck\nbucket\n  A.\n  B.\n  C.\nset draws += 1\nAgain.\n  req draws < 6\n  -> deck\nEnd.";
+
+    fn drawn_cards(strings: &[String]) -> Vec<&str> {
+        strings
+            .iter()
+            .map(String::as_str)
+            .filter(|s| matches!(*s, "A." | "B." | "C."))
+            .collect()
+    }
+
+    #[test]
+    fn bucket_draws_every_card_before_repeating() {
+        for seed in 0..32 {
+            let strings = run_seeded_and_collect_strings(DECK_SCRIPT, seed);
+            let cards = drawn_cards(&strings);
+            assert_eq!(cards.len(), 6, "one card per visit: {strings:?}");
+            for round in cards.chunks(3) {
+                let mut round = round.to_vec();
+                round.sort();
+                assert_eq!(round, ["A.", "B.", "C."], "seed {seed}: {cards:?}");
+            }
+            assert_eq!(strings.last().map(String::as_str), Some("End."));
+        }
+    }
+
+    #[test]
+    fn once_bucket_falls_through_when_empty() {
+        let script = DECK_SCRIPT.replace("bucket\n", "bucket once\n");
+        for seed in 0..32 {
+            let strings = run_seeded_and_collect_strings(&script, seed);
+            let mut cards = drawn_cards(&strings);
+            cards.sort();
+            assert_eq!(cards, ["A.", "B.", "C."], "seed {seed}: {strings:?}");
+        }
+    }
+
+    #[test]
+    fn goto_restart_puts_cards_back() {
+        let script = "bucket once\n  A.\n  B.\n";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        // The path keeps growing across restarts: one more card per playthrough
+        // proves the exhausted `once` bucket was refilled.
+        for playthrough in 1..=4 {
+            if playthrough > 1 {
+                runtime.goto_restart().unwrap();
+            }
+            runtime.skip();
+            let drawn = runtime
+                .current_blocks()
+                .iter()
+                .filter(|b| matches!(b.block_type, BlockType::String(_)))
+                .count();
+            assert_eq!(drawn, playthrough);
+        }
+    }
+
+    #[test]
+    fn skipped_subtree_ending_in_goto_does_not_jump() {
+        let script = "Start.\n  req false\n  -> a\nAfter.\n# a\nInside a.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.step();
+        let strings: Vec<_> = runtime
+            .current_blocks()
+            .into_iter()
+            .filter_map(|b| match b.block_type {
+                BlockType::String(id) => Some(runtime.database.strings[id].clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(strings, ["After."]);
+    }
+
+    #[test]
+    fn computed_weight_follows_current_variables() {
+        // `luck` starts at 0, so the first line can't be picked until the
+        // `set` makes its weight positive.
+        let script = "--- variables\nint luck = 0\n---\n(luck) Lucky.\n(1) Plain.\nset luck = 1000\n(luck) Lucky again.\n(0) Never.";
+        for seed in 0..16 {
+            let strings = run_seeded_and_collect_strings(script, seed);
+            assert_eq!(strings, ["Plain.", "Lucky again."], "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn freq_changes_the_weight_of_its_parent() {
+        let script = "--- variables\nint round = 0\n---\n# loop\n(1) Fading.\n  freq -1\n(1) Steady.\nset round += 1\nAgain.\n  req round < 4\n  -> loop";
+        for seed in 0..16 {
+            let strings = run_seeded_and_collect_strings(script, seed);
+            let fading = strings.iter().filter(|s| *s == "Fading.").count();
+            assert!(fading <= 1, "seed {seed}: {strings:?}");
+        }
+
+        // `-> RESTART` forgets the change, like it puts bucket cards back.
+        let (database, _warnings) = cuentitos_parser::parse("(1) Fading.\n  freq -1").unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        // Block 1 is the `(1) Fading.` line.
+        assert_eq!(runtime.state.frequency_adjustments.get(&1), Some(&-1));
+        runtime.goto_restart().unwrap();
+        assert!(runtime.state.frequency_adjustments.is_empty());
+    }
+
+    #[test]
+    fn negative_or_oversized_weight_is_a_runtime_error() {
+        let script = "--- variables\nint hope = 2\n---\n(hope - 3) Ship.\n(1) Sea.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert_eq!(
+            runtime.take_last_error(),
+            Some(RuntimeError::NegativeChanceWeight {
+                weight: -1,
+                file: None,
+                line: 4
+            })
+        );
+
+        let script = "--- variables\nint big = 4294967295\n---\n(big + 1) Roar.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert_eq!(
+            runtime.take_last_error(),
+            Some(RuntimeError::ChanceWeightOutOfRange {
+                file: None,
+                line: 4
+            })
+        );
+    }
+
+    const MERCHANT_SCRIPT: &str = "--- variables\nint gold = 5\n---\nHi.\n  * Sword\n    req gold >= 10\n    sword\n  * Bread\n    req gold >= 1\n    bread\n  * Leave\n    leave\nBye.";
+
+    #[test]
+    fn options_with_failing_req_are_not_listed() {
+        let (database, _warnings) = cuentitos_parser::parse(MERCHANT_SCRIPT).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert!(runtime.is_waiting_for_option());
+        let options: Vec<usize> = runtime
+            .get_current_options()
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect();
+        assert_eq!(options, [1, 2]);
+
+        // Numbers refer to the visible options only.
+        assert!(runtime.select_option(3).is_err());
+        runtime.select_option(2).unwrap();
+        runtime.skip();
+        let strings: Vec<String> = runtime
+            .current_blocks()
+            .into_iter()
+            .filter_map(|b| match b.block_type {
+                BlockType::String(id) => Some(runtime.database.strings[id].clone()),
+                _ => None,
+            })
+            .collect();
+        assert!(strings.contains(&"leave".to_string()), "{strings:?}");
+        assert!(runtime.has_ended());
+    }
+
+    #[test]
+    fn menu_with_no_visible_options_takes_the_fallback() {
+        let script = MERCHANT_SCRIPT.replace("int gold = 5", "int gold = 0");
+        let script = script.replace("  * Leave\n", "  * [fallback]\n");
+        let (database, _warnings) = cuentitos_parser::parse(&script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.step();
+        // One step goes from the question straight into the fallback.
+        assert!(runtime.step());
+        assert!(!runtime.is_waiting_for_option());
+        assert_eq!(
+            runtime.current_blocks().last().map(|b| &b.block_type),
+            Some(&BlockType::String(6))
+        );
+        assert_eq!(runtime.database.strings[6], "leave");
+
+        // Hosts see the menu resolved through the event queue.
+        let (database, _warnings) = cuentitos_parser::parse(&script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.record_events(true);
+        runtime.run();
+        runtime.step();
+        runtime.take_events();
+        runtime.step();
+        let fallback_id = runtime.current_path()[runtime.current_path().len() - 2];
+        assert_eq!(
+            runtime.take_events(),
+            [
+                RuntimeEvent::RequirementSkipped { block_id: 2 },
+                RuntimeEvent::RequirementSkipped { block_id: 5 },
+                RuntimeEvent::FallbackTaken {
+                    block_id: fallback_id
+                },
+                RuntimeEvent::TextShown {
+                    block_id: fallback_id + 1,
+                    text: "leave".to_string(),
+                },
+            ]
+        );
+
+        // The fallback stays out of a menu that has something to offer.
+        let script = script.replace("int gold = 0", "int gold = 5");
+        let (database, _warnings) = cuentitos_parser::parse(&script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert_eq!(runtime.get_current_options().len(), 1);
+    }
+
+    #[test]
+    fn menu_with_no_options_and_no_fallback_is_an_error() {
+        let script = MERCHANT_SCRIPT.replace("int gold = 5", "int gold = 0");
+        let script = script.replace("  * Leave\n    leave\n", "");
+        let (database, _warnings) = cuentitos_parser::parse(&script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert!(!runtime.is_waiting_for_option());
+        assert_eq!(
+            runtime.take_last_error(),
+            Some(RuntimeError::NoOptionsAvailable {
+                file: None,
+                line: 4
+            })
+        );
+    }
+
+    #[test]
+    fn once_option_stays_picked_until_restart() {
+        let script = "# hub: Hub\nWhat now?\n  * [once] Shop\n    Browse.\n  * Home\n    Rest.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert_eq!(runtime.get_current_options().len(), 2);
+        runtime.select_option(1).unwrap();
+        runtime.step();
+
+        // Jumping back to the section doesn't bring it back...
+        runtime.goto_section(0).unwrap();
+        runtime.skip();
+        assert_eq!(runtime.get_current_options().len(), 1);
+        runtime.select_option(1).unwrap();
+        runtime.step();
+
+        // ...but restarting does.
+        runtime.goto_restart().unwrap();
+        runtime.skip();
+        assert_eq!(runtime.get_current_options().len(), 2);
+    }
+
+    #[test]
+    fn interpolated_text_keeps_the_value_it_was_shown_with() {
+        let script = "--- variables\nint gold = 3\nfloat weight = 2.0\n---\nGold {gold}, weight {weight}.\nset gold = 10\nGold {gold}.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        let texts: Vec<&str> = (0..runtime.current_path().len())
+            .filter_map(|index| runtime.path_text(index))
+            .collect();
+        assert_eq!(texts, ["Gold 3, weight 2.0.", "Gold 10."]);
+        assert_eq!(runtime.render_string(0).unwrap(), "Gold 10, weight 2.0.");
+    }
+
+    #[test]
+    fn rendering_an_unset_enum_is_a_runtime_error() {
+        let script = "--- variables\nenum mood = happy, sad\n---\nYou feel {mood}.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        assert!(matches!(
+            runtime.render_string(0),
+            Err(RuntimeError::UnsetEnumRead { line: 4, .. })
+        ));
+        runtime.skip();
+        assert!(matches!(
+            runtime.take_last_error(),
+            Some(RuntimeError::UnsetEnumRead { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn alternatives_advance_per_rendering_until_restart() {
+        let script = "{one|two} and {&tick|tock}";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        let renders: Vec<String> = (0..3).map(|_| runtime.render_string(0).unwrap()).collect();
+        assert_eq!(renders, ["one and tick", "two and tock", "two and tick"]);
+        runtime.goto_restart().unwrap();
+        assert_eq!(runtime.render_string(0).unwrap(), "one and tick");
+    }
+
+    #[test]
+    fn option_text_is_rendered_when_the_menu_is_built() {
+        let script = "--- variables\nbool met = false\n---\nA stranger.\n  * {met ? Chat : Say hello}\n    set met = true\n  * {&Wait|Wait more}";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        assert_eq!(runtime.option_text(1), Some("Say hello"));
+        assert_eq!(runtime.option_text(2), Some("Wait"));
+        assert_eq!(runtime.option_text(3), None);
+        runtime.select_option(1).unwrap();
+        let option_index = runtime.current_path().len() - 1;
+        assert_eq!(runtime.path_text(option_index), Some("Say hello"));
+    }
+
+    #[test]
+    fn database_reloaded_from_json_runs_identically() {
+        let script = "--- variables\nint gold = 3\nfloat mood = 0.5\nbool lit = false\nstring name = \"Ana\"\nenum weather = sunny, rainy\n---\n# square: Square\n  {name} stands here. The sky is {lit ? bright : dim}.\n  set weather = rainy\n  set mood += 0.25\n  (2) A crow lands.\n    freq -1\n  (gold + visits(square)) A dog barks.\n  {&Tick.|Tock.}\n  bucket\n    A bell rings.\n    A cart rolls by.\n  What now?\n    * [once] Buy bread\n      set gold -= 1\n      -> square\n    * Wait\n      req weather = rainy\n      -> square\n    * [fallback] Leave";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let reloaded =
+            cuentitos_common::json::from_json(&cuentitos_common::json::to_json(&database)).unwrap();
+        assert_eq!(reloaded, database);
+
+        let play = |database: Database| {
+            let mut runtime = Runtime::new(database);
+            runtime.set_seed(3);
+            runtime.run();
+            for choice in [1, 1, 1] {
+                runtime.skip();
+                runtime.select_option(choice).unwrap();
+            }
+            runtime.skip();
+            let texts: Vec<_> = (0..runtime.current_path().len())
+                .map(|index| runtime.path_text(index).map(str::to_string))
+                .collect();
+            (
+                runtime.current_path().to_vec(),
+                texts,
+                runtime.variable_values().to_vec(),
+            )
+        };
+        assert_eq!(play(reloaded), play(database));
+    }
+
+    #[test]
+    fn visit_count_counts_section_entries_until_restart() {
+        let script = "# hub: Hub\n  At the hub.\n  Down.\n    req visits(hub) < 3\n    -> cave\n  -> END\n# cave: Cave\n  Dark.\n  -> hub";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let hub = database.section_registry["hub"];
+        let cave = database.section_registry["cave"];
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        assert_eq!(runtime.visit_count(hub), 0);
+        runtime.skip();
+        assert_eq!(runtime.visit_count(hub), 3);
+        assert_eq!(runtime.visit_count(cave), 2);
+        assert_eq!(runtime.block_visit_count(0), 1);
+
+        runtime.goto_restart().unwrap();
+        assert_eq!(runtime.visit_count(hub), 0);
+        assert_eq!(runtime.block_visit_count(0), 1);
+    }
+
+    fn parse_with_functions(script: &str) -> Database {
+        let mut parser = cuentitos_parser::Parser::new();
+        parser.declare_function(FunctionSignature::new(
+            "play_sound",
+            &[ValueKind::String],
+            None,
+        ));
+        parser.declare_function(FunctionSignature::new(
+            "has_item",
+            &[ValueKind::String],
+            Some(ValueKind::Boolean),
+        ));
+        parser.parse(script).unwrap().0
+    }
+
+    #[test]
+    fn call_runs_registered_function_when_reached() {
+        let database = parse_with_functions(
+            "You knock.\n  call play_sound(\"door\")\n  call play_sound(\"creak\")\nThe door opens.",
+        );
+        let played = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut runtime = Runtime::new(database);
+        let log = played.clone();
+        runtime.register_function("play_sound", &[ValueKind::String], None, move |args| {
+            log.lock().unwrap().push(args[0].to_string());
+            None
+        });
+        runtime.run();
+        runtime.step();
+        assert!(played.lock().unwrap().is_empty());
+        runtime.step();
+        assert_eq!(*played.lock().unwrap(), ["door", "creak"]);
+        let last = runtime.current_path().len() - 1;
+        assert_eq!(runtime.path_text(last), Some("The door opens."));
+    }
+
+    #[test]
+    fn runtime_with_functions_is_send() {
+        let database = parse_with_functions("Start.\n  call play_sound(\"door\")");
+        let mut runtime = Runtime::new(database);
+        runtime.register_function("play_sound", &[ValueKind::String], None, |_| None);
+        let runtime = std::thread::spawn(move || {
+            runtime.run();
+            runtime.skip();
+            runtime
+        })
+        .join()
+        .unwrap();
+        assert!(!runtime.has_error());
+    }
+
+    #[test]
+    fn function_reads_gate_blocks() {
+        let database = parse_with_functions(
+            "Locked.\n  req has_item(\"key\")\nBarred.\n  req has_item(\"bar\")\nOpen.\n  req not has_item(\"bar\")\nDone.",
+        );
+        let mut runtime = Runtime::new(database);
+        runtime.register_function(
+            "has_item",
+            &[ValueKind::String],
+            Some(ValueKind::Boolean),
+            |args| Some(Value::Boolean(args[0] == Value::String("key".into()))),
+        );
+        runtime.run();
+        runtime.skip();
+        let texts: Vec<_> = (0..runtime.current_path().len())
+            .filter_map(|index| runtime.path_text(index))
+            .collect();
+        assert_eq!(texts, ["Locked.", "Open.", "Done."]);
+        assert!(!runtime.has_error());
+    }
+
+    #[test]
+    fn missing_function_is_a_runtime_error() {
+        let database = parse_with_functions("Start.\n  call play_sound(\"door\")\nNever shown.");
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.skip();
+        let error = runtime.take_last_error().unwrap();
+        assert_eq!(
+            error,
+            RuntimeError::MissingFunction {
+                name: "play_sound".to_string(),
+                file: None,
+                line: 2,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "<script>:2: RUNTIME ERROR: Function 'play_sound' isn't registered."
+        );
+    }
+
+    #[test]
+    fn events_report_progress_in_order() {
+        let script = "--- variables\nint gold = 5\n---\n# Shop\nHi.\n  set gold = 7\nBuy?\n  * Sword\n    req gold >= 10\n    sword\n  * Bread ({gold})\n    bread\n<-> Outro\nBack.\n\n# Outro\nBye.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.record_events(true);
+        runtime.run();
+        runtime.skip();
+        runtime.select_option(1).unwrap();
+        runtime.skip();
+        use RuntimeEvent::*;
+        assert_eq!(
+            runtime.take_events(),
+            [
+                SectionEntered {
+                    section_id: 0,
+                    block_id: 1,
+                },
+                TextShown {
+                    block_id: 2,
+                    text: "Hi.".to_string(),
+                },
+                VariableChanged {
+                    variable: 0,
+                    old: Value::Integer(5),
+                    new: Value::Integer(7),
+                },
+                TextShown {
+                    block_id: 4,
+                    text: "Buy?".to_string(),
+                },
+                RequirementSkipped { block_id: 5 },
+                OptionsPresented {
+                    options: vec![(8, "Bread (7)".to_string())],
+                },
+                OptionChosen {
+                    choice: 1,
+                    block_id: 8,
+                    text: "Bread (7)".to_string(),
+                },
+                TextShown {
+                    block_id: 9,
+                    text: "bread".to_string(),
+                },
+                CallPushed {
+                    section_id: 1,
+                    return_block_id: 11,
+                },
+                SectionEntered {
+                    section_id: 1,
+                    block_id: 12,
+                },
+                TextShown {
+                    block_id: 13,
+                    text: "Bye.".to_string(),
+                },
+                CallPopped {
+                    section_id: 1,
+                    return_block_id: 11,
+                },
+                TextShown {
+                    block_id: 11,
+                    text: "Back.".to_string(),
+                },
+                SectionEntered {
+                    section_id: 1,
+                    block_id: 12,
+                },
+                TextShown {
+                    block_id: 13,
+                    text: "Bye.".to_string(),
+                },
+                StoryEnded,
+            ]
+        );
+        assert!(runtime.take_events().is_empty());
+    }
+
+    #[test]
+    fn events_are_only_queued_while_recording() {
+        let script = "Start.\nHidden.\n  req 1 > 2\nMiddle.\n  set x = 1 / 0\nNever shown.";
+        let script = format!("--- variables\nint x = 0\n---\n{script}");
+        let (database, _warnings) = cuentitos_parser::parse(&script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.run();
+        runtime.step();
+        assert!(runtime.take_events().is_empty());
+
+        runtime.record_events(true);
+        runtime.skip();
+        let events = runtime.take_events();
+        let error = runtime.take_last_error().unwrap();
+        assert_eq!(
+            events,
+            [
+                RuntimeEvent::RequirementSkipped { block_id: 2 },
+                RuntimeEvent::TextShown {
+                    block_id: 4,
+                    text: "Middle.".to_string(),
+                },
+                RuntimeEvent::Error(error),
+            ]
+        );
+
+        runtime.step();
+        runtime.record_events(false);
+        assert!(runtime.take_events().is_empty());
+    }
+
+    #[test]
+    fn assignments_that_keep_the_value_are_not_reported() {
+        let script = "--- variables\nint gold = 5\n---\nHi.\n  set gold = 5\n  set gold += 0\nBye.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.record_events(true);
+        runtime.run();
+        runtime.skip();
+        runtime
+            .set_variable_value("gold", Value::Integer(5))
+            .unwrap();
+        assert!(!runtime
+            .take_events()
+            .iter()
+            .any(|event| matches!(event, RuntimeEvent::VariableChanged { .. })));
+
+        runtime
+            .set_variable_value("gold", Value::Integer(6))
+            .unwrap();
+        assert_eq!(
+            runtime.take_events(),
+            [RuntimeEvent::VariableChanged {
+                variable: 0,
+                old: Value::Integer(5),
+                new: Value::Integer(6),
+            }]
+        );
+    }
+
+    #[test]
+    fn watchers_get_changes_with_their_line() {
+        let script = "--- variables\nint health = 10\nint gold = 0\n---\nHit.\n  set health -= 3\n  set gold = 1\nRest.\n  set health = 7\nDone.";
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = changes.clone();
+        runtime
+            .watch_variable("health", move |change| {
+                log.lock().unwrap().push(change.clone())
+            })
+            .unwrap();
+        runtime.run();
+        runtime.skip();
+        runtime
+            .set_variable_value("health", Value::Integer(12))
+            .unwrap();
+        assert_eq!(
+            *changes.lock().unwrap(),
+            [
+                VariableChange {
+                    name: "health".to_string(),
+                    old: Value::Integer(10),
+                    new: Value::Integer(7),
+                    file: None,
+                    line: Some(6),
+                },
+                VariableChange {
+                    name: "health".to_string(),
+                    old: Value::Integer(7),
+                    new: Value::Integer(12),
+                    file: None,
+                    line: None,
+                },
+            ]
+        );
+
+        runtime.unwatch_variable("health").unwrap();
+        runtime
+            .set_variable_value("health", Value::Integer(1))
+            .unwrap();
+        assert_eq!(changes.lock().unwrap().len(), 2);
+        assert_eq!(
+            runtime.watch_variable("mana", |_| {}),
+            Err(RuntimeError::UndefinedVariable {
+                name: "mana".to_string()
+            })
+        );
+    }
+
+    const REWIND_SCRIPT: &str = "--- variables\nint gold = 0\n---\n# Main\n<-> Shop\nDone.\n-> END\n\n# Shop\n(1) Heads.\n(1) Tails.\nBuy?\n  * Sword\n    set gold += 1\n  * Bread\n    set gold += 2\n(1) Win.\n(1) Lose.\n(1) Draw.";
+
+    #[test]
+    fn rewind_restores_the_state_before_a_selection() {
+        let (database, _warnings) = cuentitos_parser::parse(REWIND_SCRIPT).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.set_seed(7);
+        runtime.run();
+        runtime.skip();
+        assert!(runtime.is_waiting_for_option());
+        let before = runtime.snapshot();
+        assert_eq!(runtime.checkpoint_count(), 0);
+
+        runtime.select_option(1).unwrap();
+        while runtime.step() {}
+        assert!(runtime.has_ended());
+        let ending = runtime.current_path().to_vec();
+        assert_eq!(runtime.checkpoint_count(), 1);
+
+        assert!(!runtime.rewind(2));
+        assert!(runtime.rewind(1));
+        assert_eq!(runtime.checkpoint_count(), 0);
+        assert!(runtime.is_waiting_for_option());
+        assert_eq!(runtime.current_path(), before.current_path());
+        assert_eq!(runtime.variable_values(), before.variable_values());
+        assert_eq!(runtime.state.call_stack.len(), 1);
+        assert_eq!(runtime.state.rng, before.state.rng);
+
+        runtime.select_option(1).unwrap();
+        while runtime.step() {}
+        assert_eq!(runtime.current_path(), ending);
+        assert_eq!(runtime.variable_value("gold"), Some(&Value::Integer(1)));
+
+        assert!(runtime.rewind(1));
+        runtime.select_option(2).unwrap();
+        while runtime.step() {}
+        assert_eq!(runtime.variable_value("gold"), Some(&Value::Integer(2)));
+    }
+
+    #[test]
+    fn step_checkpoints_are_optional_and_capped() {
+        let (database, _warnings) = cuentitos_parser::parse(REWIND_SCRIPT).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.set_seed(7);
+        runtime.checkpoint_every_step(true);
+        runtime.run();
+        runtime.step();
+        let after_first_step = runtime.current_path().to_vec();
+        runtime.step();
+        runtime.step();
+        assert_eq!(runtime.checkpoint_count(), 3);
+        assert!(runtime.rewind(2));
+        assert_eq!(runtime.current_path(), after_first_step);
+
+        runtime.skip();
+        assert_eq!(runtime.checkpoint_count(), 2);
+
+        runtime.set_history_cap(0);
+        assert_eq!(runtime.checkpoint_count(), 0);
+        runtime.select_option(1).unwrap();
+        assert!(!runtime.rewind(1));
+
+        runtime.set_history_cap(DEFAULT_HISTORY_CAP);
+        runtime.step();
+        assert_eq!(runtime.checkpoint_count(), 1);
+        runtime.run();
+        assert_eq!(runtime.checkpoint_count(), 0);
+    }
 }
@@ -0,0 +1,105 @@
+//! Loading a story compiled to the binary format of
+//! [`cuentitos_common::binary`].
+//!
+//! [`Runtime::from_structure`] builds a runtime from the structure file
+//! alone, so a game can start running the story's logic while the text is
+//! still loading. [`Runtime::load_strings`] attaches the strings file once it
+//! is available; until then every text reads as empty, except the literal
+//! parts of lines with `{...}` fragments, which the structure keeps.
+
+use cuentitos_common::binary::{self, BinaryError};
+
+use crate::Runtime;
+
+impl Runtime {
+    /// Build a runtime from a structure file. Its texts are empty until
+    /// [`Runtime::load_strings`] is called.
+    pub fn from_structure(bytes: &[u8]) -> Result<Runtime, BinaryError> {
+        let structure = binary::read_structure(bytes)?;
+        let mut runtime = Runtime::new(structure.database);
+        runtime.structure_checksum = Some(structure.checksum);
+        runtime.strings_pending = true;
+        Ok(runtime)
+    }
+
+    /// Attach a strings file written for the structure this runtime was
+    /// built from. Can be called at any point, even mid-story; the story
+    /// carries on with the new texts, while lines already shown keep the
+    /// text they were shown with. A runtime built from a parsed
+    /// database already has its strings and rejects any file with
+    /// [`BinaryError::StringsMismatch`].
+    pub fn load_strings(&mut self, bytes: &[u8]) -> Result<(), BinaryError> {
+        let checksum = self
+            .structure_checksum
+            .ok_or(BinaryError::StringsMismatch)?;
+        self.database.strings = binary::read_strings(bytes, checksum)?;
+        self.strings_pending = false;
+        Ok(())
+    }
+
+    /// Whether the runtime was built with [`Runtime::from_structure`] and
+    /// is still waiting for [`Runtime::load_strings`].
+    pub fn strings_pending(&self) -> bool {
+        self.strings_pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cuentitos_common::binary::{self, BinaryError, FileKind};
+    use cuentitos_common::Value;
+
+    use crate::Runtime;
+
+    const SCRIPT: &str = "--- variables\nint gold = 3\n---\n# square: Square\n  You have {gold} coins.\n  What now?\n    * Buy bread\n      set gold -= 1\n      -> square\n    * Leave";
+
+    #[test]
+    fn runs_the_structure_before_strings_arrive() {
+        let (database, _warnings) = cuentitos_parser::parse(SCRIPT).unwrap();
+        let files = binary::write(&database);
+
+        let mut runtime = Runtime::from_structure(&files.structure).unwrap();
+        assert!(runtime.strings_pending());
+        runtime.run();
+        runtime.skip();
+        assert_eq!(runtime.path_text(2), Some("You have 3 coins."));
+        assert_eq!(runtime.path_text(3), Some(""));
+        assert_eq!(runtime.option_text(2), Some(""));
+
+        runtime.load_strings(&files.strings).unwrap();
+        assert!(!runtime.strings_pending());
+        assert_eq!(runtime.path_text(3), Some("What now?"));
+        runtime.select_option(1).unwrap();
+        runtime.skip();
+        assert_eq!(runtime.variable_value("gold"), Some(&Value::Integer(2)));
+        assert_eq!(runtime.option_text(2), Some("Leave"));
+        assert_eq!(runtime.database, database);
+    }
+
+    #[test]
+    fn rejects_strings_from_another_story() {
+        let (database, _warnings) = cuentitos_parser::parse(SCRIPT).unwrap();
+        let files = binary::write(&database);
+        let (other, _warnings) = cuentitos_parser::parse("Another story.").unwrap();
+        let other = binary::write(&other);
+
+        let mut runtime = Runtime::from_structure(&files.structure).unwrap();
+        assert_eq!(
+            runtime.load_strings(&other.strings),
+            Err(BinaryError::StringsMismatch)
+        );
+        assert_eq!(
+            runtime.load_strings(&files.structure),
+            Err(BinaryError::WrongFileKind {
+                expected: FileKind::Strings
+            })
+        );
+        assert!(runtime.strings_pending());
+
+        let mut parsed = Runtime::new(database);
+        assert_eq!(
+            parsed.load_strings(&files.strings),
+            Err(BinaryError::StringsMismatch)
+        );
+    }
+}
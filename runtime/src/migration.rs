@@ -0,0 +1,593 @@
+//! Loading a save into an edited story.
+//!
+//! Block ids are positions in the database, so adding a line near the top
+//! of a script shifts every id below it and a plain [`Runtime::restore`]
+//! rejects the save. [`Runtime::migrate`] instead follows the
+//! [`BlockAnchor`] the snapshot recorded for every block it refers to: the
+//! section the block sits in, by id path, what the block is and shows, and
+//! how many blocks of that section show the same before it. Lines added or
+//! removed anywhere else leave an anchor pointing at the same line; a line
+//! whose text changed is reported as removed rather than guessed at.
+//!
+//! Variables are matched by name. Whatever can't be carried over is dropped
+//! and listed in the returned [`MigrationReport`].
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use cuentitos_common::{
+    fnv1a, AlternativesId, Block, BlockId, BlockType, Database, SectionId, TextFragment,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::{referenced_blocks, value_fits};
+use crate::{Runtime, RuntimeSnapshot, SnapshotError};
+
+/// Where a block sits in the story, in terms that survive edits elsewhere
+/// in the script.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum BlockAnchor {
+    /// A block of a section, skipping blocks inside its subsections.
+    /// `section` is the section's id path, or `None` for blocks before the
+    /// first section.
+    InSection {
+        section: Option<String>,
+        /// A hash of the block's kind and what it shows or does: the text
+        /// of a line or option, the target of a jump, the variable of a
+        /// `set`. Blocks nested under a line, like `set` and `req`, also
+        /// hash the line.
+        content: u64,
+        /// How many blocks of the section with the same `content` come
+        /// before this one.
+        occurrence: usize,
+        /// The block's position in the section, counting the section's own
+        /// block as 0. Shown to writers, but not used to find the block in
+        /// an edited story.
+        offset: usize,
+    },
+    /// The `END` block.
+    End,
+}
+
+impl BlockAnchor {
+    /// What identifies the block in an edited story: everything but its
+    /// offset, which any line added above it changes.
+    fn identity(&self) -> Option<(&Option<String>, u64, usize)> {
+        match self {
+            BlockAnchor::InSection {
+                section,
+                content,
+                occurrence,
+                ..
+            } => Some((section, *content, *occurrence)),
+            BlockAnchor::End => None,
+        }
+    }
+}
+
+impl fmt::Display for BlockAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockAnchor::InSection {
+                section: Some(section),
+                offset,
+                ..
+            } => write!(f, "{} +{}", section, offset),
+            BlockAnchor::InSection {
+                section: None,
+                offset,
+                ..
+            } => write!(f, "START +{}", offset),
+            BlockAnchor::End => write!(f, "END"),
+        }
+    }
+}
+
+/// Where a `{a|b|c}` fragment sits: the line showing it and its rank among
+/// the line's fragments.
+pub(crate) type AlternativeAnchor = (BlockAnchor, usize);
+
+/// The anchor of every block of `database`, indexed by block id.
+pub(crate) fn block_anchors(database: &Database) -> Vec<BlockAnchor> {
+    let mut section_of: Vec<Option<SectionId>> = Vec::with_capacity(database.blocks.len());
+    let mut contents: Vec<u64> = Vec::with_capacity(database.blocks.len());
+    let mut next_offset: HashMap<Option<SectionId>, usize> = HashMap::new();
+    let mut occurrences: HashMap<(Option<SectionId>, u64), usize> = HashMap::new();
+    let mut anchors = Vec::with_capacity(database.blocks.len());
+    for block in &database.blocks {
+        // Parents always come before their children, so their section and
+        // content are already known.
+        let section = match block.block_type {
+            BlockType::Section(section_id) => Some(section_id),
+            _ => block.parent_id.and_then(|parent| section_of[parent]),
+        };
+        section_of.push(section);
+        let content = block_content(database, block, &contents);
+        contents.push(content);
+        if block.block_type == BlockType::End {
+            anchors.push(BlockAnchor::End);
+            continue;
+        }
+        let offset = next_offset.entry(section).or_insert(0);
+        let occurrence = occurrences.entry((section, content)).or_insert(0);
+        anchors.push(BlockAnchor::InSection {
+            section: section.map(|id| section_path(database, id).to_string()),
+            content,
+            occurrence: *occurrence,
+            offset: *offset,
+        });
+        *offset += 1;
+        *occurrence += 1;
+    }
+    anchors
+}
+
+/// The [`BlockAnchor::InSection`] content hash of `block`, given the hashes
+/// of the blocks before it.
+fn block_content(database: &Database, block: &Block, contents: &[u64]) -> u64 {
+    let description = match block.block_type {
+        BlockType::Start => "start".to_string(),
+        BlockType::String(id) => format!("text {}", database.strings[id]),
+        BlockType::Option(id) => format!("option {}", database.strings[id]),
+        BlockType::Section(_) => "section".to_string(),
+        BlockType::GoTo(id) => format!("-> {}", section_path(database, id)),
+        BlockType::GoToAndBack(id) => format!("<-> {}", section_path(database, id)),
+        BlockType::GoToStart => "-> START".to_string(),
+        BlockType::GoToRestart => "-> RESTART".to_string(),
+        BlockType::GoToEnd => "-> END".to_string(),
+        BlockType::Set(id) => {
+            format!(
+                "set {}",
+                database.variables[database.sets[id].variable_id].name
+            )
+        }
+        BlockType::Call(id) => {
+            format!(
+                "call {}",
+                database.functions[database.calls[id].function].name
+            )
+        }
+        BlockType::Requirement(_) => "req".to_string(),
+        BlockType::Chance(_) => "chance".to_string(),
+        BlockType::Bucket(_) => "bucket".to_string(),
+        BlockType::Frequency(_) => "freq".to_string(),
+        BlockType::End => "end".to_string(),
+    };
+    let mut bytes = description.into_bytes();
+    // Lines and options stand on their own text, so editing a line doesn't
+    // lose the lines nested under it. Everything else is told apart by the
+    // line it belongs to.
+    if !matches!(
+        block.block_type,
+        BlockType::String(_) | BlockType::Option(_) | BlockType::Section(_)
+    ) {
+        if let Some(parent) = block.parent_id {
+            bytes.extend(contents[parent].to_le_bytes());
+        }
+    }
+    fnv1a(&bytes)
+}
+
+fn section_path(database: &Database, section: SectionId) -> &str {
+    &database.strings[database.sections[section].id_path]
+}
+
+/// The anchor of every text alternatives fragment of `database`.
+pub(crate) fn alternative_anchors(
+    database: &Database,
+    anchors: &[BlockAnchor],
+) -> HashMap<AlternativesId, AlternativeAnchor> {
+    let mut result = HashMap::new();
+    for (block_id, block) in database.blocks.iter().enumerate() {
+        let (BlockType::String(string_id) | BlockType::Option(string_id)) = block.block_type else {
+            continue;
+        };
+        let Some(fragments) = database.text_fragments.get(&string_id) else {
+            continue;
+        };
+        let mut ids = BTreeSet::new();
+        collect_alternatives(fragments, database, &mut ids);
+        for (rank, id) in ids.into_iter().enumerate() {
+            result.insert(id, (anchors[block_id].clone(), rank));
+        }
+    }
+    result
+}
+
+pub(crate) fn collect_alternatives(
+    fragments: &[TextFragment],
+    database: &Database,
+    ids: &mut BTreeSet<AlternativesId>,
+) {
+    for fragment in fragments {
+        match fragment {
+            TextFragment::Literal(_) | TextFragment::Variable(_) => {}
+            TextFragment::Conditional {
+                then, otherwise, ..
+            } => {
+                collect_alternatives(then, database, ids);
+                collect_alternatives(otherwise, database, ids);
+            }
+            TextFragment::Alternatives(id) => {
+                ids.insert(*id);
+                for branch in &database.text_alternatives[*id].branches {
+                    collect_alternatives(branch, database, ids);
+                }
+            }
+        }
+    }
+}
+
+/// Something [`Runtime::migrate`] couldn't carry over to the new story.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MigrationIssue {
+    /// A block the save refers to no longer exists. Its entries in the
+    /// execution path, the call stack and the menu were dropped, along with
+    /// its visit count and any roll, draw or `freq` change kept for it.
+    BlockRemoved { anchor: BlockAnchor },
+    /// A saved variable is no longer declared.
+    VariableRemoved { name: String },
+    /// A variable changed kind or enum variants, so it starts over from its
+    /// new default.
+    VariableReset { name: String },
+    /// A `{a|b|c}` fragment no longer exists, so its visits were dropped.
+    AlternativesRemoved { anchor: BlockAnchor },
+}
+
+impl fmt::Display for MigrationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationIssue::BlockRemoved { anchor } => {
+                write!(f, "Block '{}' no longer exists.", anchor)
+            }
+            MigrationIssue::VariableRemoved { name } => {
+                write!(f, "Variable '{}' is no longer declared.", name)
+            }
+            MigrationIssue::VariableReset { name } => {
+                write!(f, "Variable '{}' changed type and was reset.", name)
+            }
+            MigrationIssue::AlternativesRemoved { anchor } => {
+                write!(f, "Text alternatives in '{}' no longer exist.", anchor)
+            }
+        }
+    }
+}
+
+/// What [`Runtime::migrate`] had to drop, in a stable order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+    pub issues: Vec<MigrationIssue>,
+}
+
+impl MigrationReport {
+    /// True when everything in the save was carried over.
+    pub fn is_complete(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Runtime {
+    /// Load `snapshot` into this runtime even if the story changed since
+    /// it was taken, remapping blocks through their anchors and variables
+    /// by name. Fails only if the block the story was on no longer exists,
+    /// or the snapshot is damaged; everything else that can't be carried
+    /// over is listed in the report. The runtime is left untouched on error.
+    pub fn migrate(&mut self, snapshot: RuntimeSnapshot) -> Result<MigrationReport, SnapshotError> {
+        snapshot.check_version()?;
+        let RuntimeSnapshot {
+            variables,
+            anchors,
+            alternative_anchors: saved_alternatives,
+            seed,
+            running,
+            mut state,
+            ..
+        } = snapshot;
+        let mut issues = BTreeSet::new();
+
+        let new_anchors = block_anchors(&self.database);
+        let new_ids: HashMap<_, BlockId> = new_anchors
+            .iter()
+            .enumerate()
+            .map(|(id, anchor)| (anchor.identity(), id))
+            .collect();
+        let mut blocks: HashMap<BlockId, BlockId> = HashMap::new();
+        for old in referenced_blocks(&state) {
+            let Some(anchor) = anchors.get(&old) else {
+                return Err(SnapshotError::Corrupt {
+                    message: format!("block {} has no anchor", old),
+                });
+            };
+            match new_ids.get(&anchor.identity()) {
+                Some(&new) => {
+                    blocks.insert(old, new);
+                }
+                None => {
+                    issues.insert(MigrationIssue::BlockRemoved {
+                        anchor: anchor.clone(),
+                    });
+                }
+            }
+        }
+        let map = |old: &BlockId| blocks.get(old).copied();
+
+        if state.variable_values.len() != variables.len() {
+            return Err(SnapshotError::Corrupt {
+                message: format!(
+                    "{} values for {} variables",
+                    state.variable_values.len(),
+                    variables.len()
+                ),
+            });
+        }
+        let Some(program_counter) = map(&state.program_counter) else {
+            return Err(SnapshotError::PositionRemoved {
+                anchor: anchors[&state.program_counter].clone(),
+            });
+        };
+        state.program_counter = program_counter;
+        state.previous_program_counter =
+            map(&state.previous_program_counter).unwrap_or(program_counter);
+
+        // Dropping path entries shifts the ones after them, and the odds and
+        // texts kept by path index with them.
+        let mut path = Vec::with_capacity(state.current_path.len());
+        let mut path_odds = HashMap::new();
+        let mut path_text = HashMap::new();
+        for (index, old) in state.current_path.iter().enumerate() {
+            let Some(new) = map(old) else {
+                continue;
+            };
+            if let Some(odds) = state.path_odds.remove(&index) {
+                path_odds.insert(path.len(), odds);
+            }
+            if let Some(text) = state.path_text.remove(&index) {
+                path_text.insert(path.len(), text);
+            }
+            path.push(new);
+        }
+        state.current_path = path;
+        state.path_odds = path_odds;
+        state.path_text = path_text;
+
+        state.call_stack.retain_mut(|frame| {
+            match (map(&frame.return_block_id), map(&frame.called_section_id)) {
+                (Some(return_block_id), Some(called_section_id)) => {
+                    frame.return_block_id = return_block_id;
+                    frame.called_section_id = called_section_id;
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        let (options, texts) = state
+            .current_options
+            .iter()
+            .zip(std::mem::take(&mut state.current_option_texts))
+            .filter_map(|(option, text)| Some((map(option)?, text)))
+            .unzip();
+        state.current_options = options;
+        state.current_option_texts = texts;
+        state.waiting_for_option_selection &= !state.current_options.is_empty();
+
+        state.chance_rolls = state
+            .chance_rolls
+            .into_iter()
+            .filter_map(|(head, mut roll)| {
+                if let Some(winner) = roll.winner {
+                    roll.winner = Some(map(&winner)?);
+                }
+                Some((map(&head)?, roll))
+            })
+            .collect();
+        state.bucket_draws = state
+            .bucket_draws
+            .into_iter()
+            .filter_map(|(bucket, mut draws)| {
+                draws.drawn = draws.drawn.iter().filter_map(map).collect();
+                draws.current = draws.current.as_ref().and_then(map);
+                Some((map(&bucket)?, draws))
+            })
+            .collect();
+        state.frequency_adjustments = state
+            .frequency_adjustments
+            .into_iter()
+            .filter_map(|(block, delta)| Some((map(&block)?, delta)))
+            .collect();
+        state.consumed_options = state.consumed_options.iter().filter_map(map).collect();
+        state.visit_counts = state
+            .visit_counts
+            .into_iter()
+            .filter_map(|(block, count)| Some((map(&block)?, count)))
+            .collect();
+
+        let new_alternatives: HashMap<AlternativeAnchor, AlternativesId> =
+            alternative_anchors(&self.database, &new_anchors)
+                .into_iter()
+                .map(|(id, anchor)| (anchor, id))
+                .collect();
+        state.alternative_visits = state
+            .alternative_visits
+            .into_iter()
+            .filter_map(|(old, visits)| {
+                let anchor = saved_alternatives.get(&old)?;
+                match new_alternatives.get(anchor) {
+                    Some(&new) => Some((new, visits)),
+                    None => {
+                        issues.insert(MigrationIssue::AlternativesRemoved {
+                            anchor: anchor.0.clone(),
+                        });
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let mut saved_values: HashMap<String, _> = variables
+            .into_iter()
+            .map(|(name, _)| name)
+            .zip(std::mem::take(&mut state.variable_values))
+            .collect();
+        state.variable_values = self
+            .database
+            .variables
+            .iter()
+            .map(|variable| match saved_values.remove(&variable.name) {
+                Some(value) if value_fits(&value, &variable.default) => value,
+                Some(_) => {
+                    issues.insert(MigrationIssue::VariableReset {
+                        name: variable.name.clone(),
+                    });
+                    variable.initial_value()
+                }
+                None => variable.initial_value(),
+            })
+            .collect();
+        issues.extend(
+            saved_values
+                .into_keys()
+                .map(|name| MigrationIssue::VariableRemoved { name }),
+        );
+
+        self.seed = seed;
+        self.running = running;
+        self.state = state;
+        self.history.clear();
+        Ok(MigrationReport {
+            issues: issues.into_iter().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuentitos_common::Value;
+
+    const SCRIPT: &str = "--- variables\nint gold = 0\nbool lit = false\n---\n# hub: Hub\n  A hall.\n  set gold += 1\n  What now?\n    * Go down\n      -> cave\n    * Leave\n# cave: Cave\n  {Dark.|Still dark.}\n  -> hub";
+
+    fn runtime(script: &str) -> Runtime {
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.set_seed(1);
+        runtime.run();
+        runtime
+    }
+
+    fn saved_back_at_the_hub_menu() -> RuntimeSnapshot {
+        let mut runtime = runtime(SCRIPT);
+        runtime.skip();
+        runtime.select_option(1).unwrap();
+        runtime.skip();
+        runtime.snapshot()
+    }
+
+    fn current_line(runtime: &Runtime) -> &str {
+        let path = runtime.current_path();
+        runtime.path_text(path.len() - 1).unwrap()
+    }
+
+    #[test]
+    fn anchors_follow_blocks_across_edits_in_other_sections() {
+        let edited = SCRIPT
+            .replace(
+                "# hub: Hub",
+                "# intro: Intro\n  Welcome.\n  -> hub\n# hub: Hub",
+            )
+            .replace("Still dark.}", "Still dark.}\n  Water drips.");
+        let mut runtime = runtime(&edited);
+        let report = runtime.migrate(saved_back_at_the_hub_menu()).unwrap();
+        assert!(report.is_complete(), "{:?}", report);
+
+        let cave = runtime.database.section_registry["cave"];
+        assert_eq!(runtime.visit_count(cave), 1);
+        assert_eq!(runtime.variable_value("gold"), Some(&Value::Integer(2)));
+        assert_eq!(runtime.option_text(1), Some("Go down"));
+        assert_eq!(current_line(&runtime), "What now?");
+
+        runtime.select_option(1).unwrap();
+        runtime.step();
+        while runtime
+            .path_text(runtime.current_path().len() - 1)
+            .is_none()
+        {
+            runtime.step();
+        }
+        assert_eq!(current_line(&runtime), "Still dark.");
+    }
+
+    #[test]
+    fn reports_what_could_not_be_migrated() {
+        let edited = SCRIPT
+            .replace("bool lit = false", "int lit = 0")
+            .replace("int gold = 0\n", "")
+            .replace("set gold += 1", "set lit += 1")
+            .replace("{Dark.|Still dark.}", "Dark.");
+        let mut runtime = runtime(&edited);
+        let report = runtime.migrate(saved_back_at_the_hub_menu()).unwrap();
+        assert!(report.issues.contains(&MigrationIssue::VariableRemoved {
+            name: "gold".to_string()
+        }));
+        assert!(report.issues.contains(&MigrationIssue::VariableReset {
+            name: "lit".to_string()
+        }));
+        assert!(report
+            .issues
+            .iter()
+            .any(|issue| matches!(issue, MigrationIssue::AlternativesRemoved { .. })));
+        assert_eq!(runtime.variable_value("lit"), Some(&Value::Integer(0)));
+    }
+
+    #[test]
+    fn fails_when_the_current_block_is_gone() {
+        let edited = SCRIPT.replace(
+            "  What now?\n    * Go down\n      -> cave\n    * Leave\n",
+            "",
+        );
+        let mut runtime = runtime(&edited);
+        assert!(matches!(
+            runtime.migrate(saved_back_at_the_hub_menu()),
+            Err(SnapshotError::PositionRemoved { .. })
+        ));
+    }
+
+    #[test]
+    fn anchors_follow_blocks_across_lines_added_above_them() {
+        let edited = SCRIPT.replace("# hub: Hub\n", "# hub: Hub\n  Intro line.\n");
+        let mut runtime = runtime(&edited);
+        let report = runtime.migrate(saved_back_at_the_hub_menu()).unwrap();
+        assert!(report.is_complete(), "{:?}", report);
+        assert_eq!(current_line(&runtime), "What now?");
+        assert_eq!(runtime.option_text(1), Some("Go down"));
+        assert_eq!(runtime.variable_value("gold"), Some(&Value::Integer(2)));
+
+        runtime.select_option(1).unwrap();
+        assert_eq!(current_line(&runtime), "Go down");
+        runtime.step();
+        while runtime
+            .path_text(runtime.current_path().len() - 1)
+            .is_none()
+        {
+            runtime.step();
+        }
+        assert_eq!(current_line(&runtime), "Still dark.");
+    }
+
+    #[test]
+    fn edited_lines_are_not_matched_by_position() {
+        let edited = SCRIPT.replace("What now?", "Where to?");
+        assert!(matches!(
+            runtime(&edited).migrate(saved_back_at_the_hub_menu()),
+            Err(SnapshotError::PositionRemoved { .. })
+        ));
+
+        let edited = SCRIPT.replace("A hall.", "A long hall.");
+        let mut runtime = runtime(&edited);
+        let report = runtime.migrate(saved_back_at_the_hub_menu()).unwrap();
+        assert!(report.issues.contains(&MigrationIssue::BlockRemoved {
+            anchor: block_anchors(&cuentitos_parser::parse(SCRIPT).unwrap().0)[2].clone(),
+        }));
+        assert_eq!(current_line(&runtime), "What now?");
+    }
+}
@@ -0,0 +1,116 @@
+//! Pseudo-random number generator used to resolve chance groups.
+//!
+//! A self-contained PCG32 (`XSH RR` output, 64-bit state) so the sequence
+//! only depends on integer arithmetic — no floating point, no platform
+//! word size, no third-party crate whose stream might change between
+//! releases.
+
+use serde::{Deserialize, Serialize};
+use std::hash::{BuildHasher, Hasher};
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Build a generator whose sequence is fully determined by `seed`.
+    pub(crate) fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// A per-process random seed, used when the host never picks one.
+    pub(crate) fn entropy_seed() -> u64 {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        hasher.finish()
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        let previous = self.state;
+        self.state = previous.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((previous >> 18) ^ previous) >> 27) as u32;
+        let rotation = (previous >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let high = u64::from(self.next_u32());
+        let low = u64::from(self.next_u32());
+        (high << 32) | low
+    }
+
+    /// Uniform integer in `0..bound`. Rejection-samples so every value is
+    /// equally likely. `bound` must be non-zero.
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        debug_assert!(bound > 0, "Rng::below called with an empty range");
+        // Values below `threshold` would make the low end of the range
+        // slightly more likely; draw again when one comes up.
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = self.next_u64();
+            if value >= threshold {
+                return value % bound;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_produces_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn sequence_is_pinned() {
+        // Transcripts recorded with `seed <n>` in compatibility tests depend
+        // on this exact stream. If this test breaks, every seeded transcript
+        // breaks with it.
+        let mut rng = Rng::new(42);
+        let values: Vec<u32> = (0..4).map(|_| rng.next_u32()).collect();
+        assert_eq!(
+            values,
+            [3_270_867_926, 1_795_671_209, 1_924_641_435, 1_143_034_755]
+        );
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        let mut a = Rng::new(1);
+        let mut b = Rng::new(2);
+        let a_values: Vec<u32> = (0..8).map(|_| a.next_u32()).collect();
+        let b_values: Vec<u32> = (0..8).map(|_| b.next_u32()).collect();
+        assert_ne!(a_values, b_values);
+    }
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut rng = Rng::new(7);
+        for bound in 1..50 {
+            for _ in 0..20 {
+                assert!(rng.below(bound) < bound);
+            }
+        }
+    }
+
+    #[test]
+    fn below_one_is_always_zero() {
+        let mut rng = Rng::new(7);
+        assert_eq!(rng.below(1), 0);
+    }
+}
@@ -0,0 +1,474 @@
+//! Saving and restoring a runtime mid-story.
+//!
+//! [`Runtime::snapshot`] captures everything a [`Runtime`] needs to carry on
+//! where it stopped: the program counter, the execution path, the call
+//! stack, the menu waiting for a choice, variable values, the RNG and every
+//! visit, draw and roll counter. [`RuntimeSnapshot`] is `serde`-serializable,
+//! so a host can write it in whatever format its saves use.
+//!
+//! A snapshot refers to blocks and variables by index, so it only makes
+//! sense for the database it was taken from. [`Runtime::restore`] checks the
+//! snapshot's block count, variable declarations and the [`BlockAnchor`]s
+//! it recorded for the blocks it refers to against the loaded database, and
+//! rejects it with a [`SnapshotError`] if they differ. To load a save into
+//! an edited story, use [`Runtime::migrate`] instead, which follows those
+//! anchors to where the blocks moved.
+//!
+//! The layout of the saved state is the runtime's own, so every snapshot
+//! carries [`SNAPSHOT_VERSION`], and both reject one written with another
+//! version instead of misreading it.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use cuentitos_common::{AlternativesId, BlockId, Value, ValueKind};
+use serde::{Deserialize, Serialize};
+
+use crate::migration::{alternative_anchors, block_anchors, AlternativeAnchor, BlockAnchor};
+use crate::{Runtime, RuntimeState};
+
+/// The version of the saved state's layout written by [`Runtime::snapshot`]
+/// and the only one [`Runtime::restore`] and [`Runtime::migrate`] read. Any
+/// change to the fields of the runtime's state bumps it.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A saved [`Runtime`], taken with [`Runtime::snapshot`] and loaded back
+/// with [`Runtime::restore`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeSnapshot {
+    /// [`SNAPSHOT_VERSION`] when the snapshot was taken. Serialized first, so
+    /// a host can read it before the rest.
+    version: u32,
+    /// How many blocks the database had.
+    block_count: usize,
+    /// Name and kind of every declared variable, in declaration order.
+    pub(crate) variables: Vec<(String, ValueKind)>,
+    /// Where every block the state refers to sat in the story.
+    pub(crate) anchors: HashMap<BlockId, BlockAnchor>,
+    /// Where every text alternatives fragment the state counts sat.
+    pub(crate) alternative_anchors: HashMap<AlternativesId, AlternativeAnchor>,
+    pub(crate) seed: u64,
+    pub(crate) running: bool,
+    pub(crate) state: RuntimeState,
+}
+
+impl RuntimeSnapshot {
+    /// The [`SNAPSHOT_VERSION`] the snapshot was written with.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The block the runtime was on.
+    pub fn program_counter(&self) -> BlockId {
+        self.state.program_counter
+    }
+
+    /// Every block entered so far, as [`Runtime::current_path`] returned it.
+    pub fn current_path(&self) -> &[BlockId] {
+        &self.state.current_path
+    }
+
+    /// The value of every declared variable, in declaration order.
+    pub fn variable_values(&self) -> &[Value] {
+        &self.state.variable_values
+    }
+
+    /// The seed the runtime's RNG was built from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Reject a snapshot written with another [`SNAPSHOT_VERSION`].
+    pub(crate) fn check_version(&self) -> Result<(), SnapshotError> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion {
+                found: self.version,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Why [`Runtime::restore`] rejected a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    /// The snapshot was written with another [`SNAPSHOT_VERSION`].
+    UnsupportedVersion { found: u32 },
+    /// The snapshot was taken from a database with a different number of
+    /// blocks.
+    BlockCountMismatch { snapshot: usize, database: usize },
+    /// The variable at `position` is declared differently, or only exists
+    /// on one side. Each side is written as a declaration, e.g. `int gold`,
+    /// or `None` when missing.
+    VariableMismatch {
+        position: usize,
+        snapshot: Option<String>,
+        database: Option<String>,
+    },
+    /// A saved variable value doesn't fit its declaration, e.g. an enum
+    /// value that isn't one of the declared variants.
+    InvalidValue { name: String },
+    /// A block the snapshot refers to sits somewhere else in the story, so
+    /// the story was edited since the snapshot was taken. Such a save can
+    /// still be loaded with [`Runtime::migrate`].
+    BlockMismatch {
+        block: BlockId,
+        snapshot: BlockAnchor,
+        database: BlockAnchor,
+    },
+    /// [`Runtime::migrate`] found no block at the anchor the story was on.
+    PositionRemoved { anchor: BlockAnchor },
+    /// The snapshot contradicts itself, e.g. it refers to a block past the
+    /// block count it was saved with. Only an edited or damaged save can do
+    /// this.
+    Corrupt { message: String },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion { found } => write!(
+                f,
+                "ERROR: Unsupported snapshot version {} (expected {}).",
+                found, SNAPSHOT_VERSION
+            ),
+            SnapshotError::BlockCountMismatch { snapshot, database } => write!(
+                f,
+                "ERROR: Snapshot was taken from a story with {} blocks, this one has {}.",
+                snapshot, database
+            ),
+            SnapshotError::VariableMismatch {
+                position,
+                snapshot,
+                database,
+            } => write!(
+                f,
+                "ERROR: Variable {} is '{}' in the snapshot but '{}' in the story.",
+                position + 1,
+                snapshot.as_deref().unwrap_or("missing"),
+                database.as_deref().unwrap_or("missing")
+            ),
+            SnapshotError::InvalidValue { name } => {
+                write!(f, "ERROR: Snapshot has an invalid value for '{}'.", name)
+            }
+            SnapshotError::BlockMismatch {
+                block,
+                snapshot,
+                database,
+            } => write!(
+                f,
+                "ERROR: Block {} is '{}' in the snapshot but '{}' in the story.",
+                block, snapshot, database
+            ),
+            SnapshotError::PositionRemoved { anchor } => write!(
+                f,
+                "ERROR: The story was saved at '{}', which no longer exists.",
+                anchor
+            ),
+            SnapshotError::Corrupt { message } => {
+                write!(f, "ERROR: Snapshot is damaged: {}.", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Runtime {
+    /// Capture the runtime's current state. Restoring it later, on a
+    /// runtime over the same database, continues exactly from here,
+    /// including the rolls a seed would produce next.
+    pub fn snapshot(&self) -> RuntimeSnapshot {
+        let anchors = block_anchors(&self.database);
+        let alternatives = alternative_anchors(&self.database, &anchors);
+        RuntimeSnapshot {
+            version: SNAPSHOT_VERSION,
+            block_count: self.database.blocks.len(),
+            variables: self
+                .database
+                .variables
+                .iter()
+                .map(|variable| (variable.name.clone(), variable.kind()))
+                .collect(),
+            anchors: referenced_blocks(&self.state)
+                .into_iter()
+                .map(|block| (block, anchors[block].clone()))
+                .collect(),
+            alternative_anchors: self
+                .state
+                .alternative_visits
+                .keys()
+                .filter_map(|id| Some((*id, alternatives.get(id)?.clone())))
+                .collect(),
+            seed: self.seed,
+            running: self.running,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Replace the runtime's state with `snapshot`. The runtime is left
+    /// untouched if the snapshot doesn't match the loaded database.
+    pub fn restore(&mut self, snapshot: RuntimeSnapshot) -> Result<(), SnapshotError> {
+        self.validate_snapshot(&snapshot)?;
+        self.seed = snapshot.seed;
+        self.running = snapshot.running;
+        self.state = snapshot.state;
+        self.history.clear();
+        Ok(())
+    }
+
+    fn validate_snapshot(&self, snapshot: &RuntimeSnapshot) -> Result<(), SnapshotError> {
+        snapshot.check_version()?;
+        let block_count = self.database.blocks.len();
+        if snapshot.block_count != block_count {
+            return Err(SnapshotError::BlockCountMismatch {
+                snapshot: snapshot.block_count,
+                database: block_count,
+            });
+        }
+
+        let declared = &self.database.variables;
+        let positions = declared.len().max(snapshot.variables.len());
+        for position in 0..positions {
+            let saved = snapshot.variables.get(position);
+            let variable = declared.get(position);
+            let matches = match (saved, variable) {
+                (Some((name, kind)), Some(variable)) => {
+                    *name == variable.name && *kind == variable.kind()
+                }
+                _ => false,
+            };
+            if !matches {
+                return Err(SnapshotError::VariableMismatch {
+                    position,
+                    snapshot: saved.map(|(name, kind)| declaration(name, *kind)),
+                    database: variable.map(|variable| declaration(&variable.name, variable.kind())),
+                });
+            }
+        }
+
+        let state = &snapshot.state;
+        if state.variable_values.len() != declared.len() {
+            return Err(SnapshotError::Corrupt {
+                message: format!(
+                    "{} values for {} variables",
+                    state.variable_values.len(),
+                    declared.len()
+                ),
+            });
+        }
+        for (value, variable) in state.variable_values.iter().zip(declared) {
+            if !value_fits(value, &variable.default) {
+                return Err(SnapshotError::InvalidValue {
+                    name: variable.name.clone(),
+                });
+            }
+        }
+
+        if state.current_option_texts.len() != state.current_options.len() {
+            return Err(SnapshotError::Corrupt {
+                message: format!(
+                    "{} option texts for {} options",
+                    state.current_option_texts.len(),
+                    state.current_options.len()
+                ),
+            });
+        }
+
+        // The same number of blocks doesn't make the same story: every
+        // block the state refers to must still be where it was saved.
+        let anchors = block_anchors(&self.database);
+        for block in referenced_blocks(state) {
+            if block >= block_count {
+                return Err(SnapshotError::Corrupt {
+                    message: format!("block {} doesn't exist", block),
+                });
+            }
+            let Some(saved) = snapshot.anchors.get(&block) else {
+                return Err(SnapshotError::Corrupt {
+                    message: format!("block {} has no anchor", block),
+                });
+            };
+            if *saved != anchors[block] {
+                return Err(SnapshotError::BlockMismatch {
+                    block,
+                    snapshot: saved.clone(),
+                    database: anchors[block].clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every block `state` refers to: its position, path, call stack and menu,
+/// and the blocks its rolls, draws and counters are kept for.
+pub(crate) fn referenced_blocks(state: &RuntimeState) -> BTreeSet<BlockId> {
+    let mut blocks = BTreeSet::from([state.program_counter, state.previous_program_counter]);
+    blocks.extend(&state.current_path);
+    blocks.extend(&state.current_options);
+    for frame in &state.call_stack {
+        blocks.extend([frame.return_block_id, frame.called_section_id]);
+    }
+    for (head, roll) in &state.chance_rolls {
+        blocks.insert(*head);
+        blocks.extend(roll.winner);
+    }
+    for (bucket, draws) in &state.bucket_draws {
+        blocks.insert(*bucket);
+        blocks.extend(&draws.drawn);
+        blocks.extend(draws.current);
+    }
+    blocks.extend(state.frequency_adjustments.keys());
+    blocks.extend(&state.consumed_options);
+    blocks.extend(state.visit_counts.keys());
+    blocks
+}
+
+pub(crate) fn declaration(name: &str, kind: ValueKind) -> String {
+    format!("{} {}", kind.keyword(), name)
+}
+
+/// Whether a saved `value` can be the value of a variable declared with
+/// `default`: same kind and, for enums, the same variants.
+pub(crate) fn value_fits(value: &Value, default: &Value) -> bool {
+    if value.kind() != default.kind() || value.enum_variants() != default.enum_variants() {
+        return false;
+    }
+    match value {
+        Value::Enum { variants, value } => variants.contains(value),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime(script: &str) -> Runtime {
+        let (database, _warnings) = cuentitos_parser::parse(script).unwrap();
+        let mut runtime = Runtime::new(database);
+        runtime.set_seed(7);
+        runtime.run();
+        runtime
+    }
+
+    const SCRIPT: &str = "--- variables\nint gold = 0\n---\n# hub: Hub\n  (1) Heads.\n  (1) Tails.\n  set gold += 1\n  What now?\n    * Again\n      -> hub\n    * Leave";
+
+    #[test]
+    fn restored_runtime_continues_like_the_original() {
+        let mut original = runtime(SCRIPT);
+        original.skip();
+        original.select_option(1).unwrap();
+        original.skip();
+
+        let saved = serde_json::to_string(&original.snapshot()).unwrap();
+        let mut restored = runtime(SCRIPT);
+        restored
+            .restore(serde_json::from_str(&saved).unwrap())
+            .unwrap();
+        assert_eq!(restored.current_path(), original.current_path());
+        assert_eq!(restored.variable_value("gold"), Some(&Value::Integer(2)));
+        assert_eq!(restored.option_text(1), Some("Again"));
+
+        for runtime in [&mut original, &mut restored] {
+            runtime.select_option(1).unwrap();
+            runtime.skip();
+        }
+        assert_eq!(restored.current_path(), original.current_path());
+    }
+
+    #[test]
+    fn rejects_a_snapshot_from_another_story() {
+        let snapshot = runtime(SCRIPT).snapshot();
+
+        let mut shorter = runtime("--- variables\nint gold = 0\n---\nHello.");
+        assert!(matches!(
+            shorter.restore(snapshot.clone()),
+            Err(SnapshotError::BlockCountMismatch { .. })
+        ));
+
+        let renamed = SCRIPT.replace("gold", "coins");
+        assert_eq!(
+            runtime(&renamed).restore(snapshot.clone()),
+            Err(SnapshotError::VariableMismatch {
+                position: 0,
+                snapshot: Some("int gold".to_string()),
+                database: Some("int coins".to_string()),
+            })
+        );
+
+        let retyped = SCRIPT
+            .replace("int gold = 0", "float gold = 0.0")
+            .replace("+= 1", "+= 1.0");
+        assert!(matches!(
+            runtime(&retyped).restore(snapshot),
+            Err(SnapshotError::VariableMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_story_edited_to_the_same_number_of_blocks() {
+        let mut original = runtime(SCRIPT);
+        original.skip();
+        let snapshot = original.snapshot();
+
+        let renamed = SCRIPT.replace("hub", "home");
+        let mut edited = runtime(&renamed);
+        assert_eq!(edited.database.blocks.len(), original.database.blocks.len());
+        let error = edited.restore(snapshot).unwrap_err();
+        assert!(
+            matches!(error, SnapshotError::BlockMismatch { .. }),
+            "{:?}",
+            error
+        );
+        assert!(error
+            .to_string()
+            .contains("'hub +0' in the snapshot but 'home +0'"));
+    }
+
+    #[test]
+    fn rejects_a_menu_without_a_text_per_option() {
+        let mut original = runtime(SCRIPT);
+        original.skip();
+        let mut snapshot = original.snapshot();
+        snapshot.state.current_option_texts.pop();
+
+        assert_eq!(
+            runtime(SCRIPT).restore(snapshot),
+            Err(SnapshotError::Corrupt {
+                message: "1 option texts for 2 options".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_snapshot_from_another_version() {
+        let saved = serde_json::to_string(&runtime(SCRIPT).snapshot()).unwrap();
+        assert!(saved.starts_with(&format!("{{\"version\":{},", SNAPSHOT_VERSION)));
+        let saved = saved.replacen(
+            &format!("\"version\":{}", SNAPSHOT_VERSION),
+            &format!("\"version\":{}", SNAPSHOT_VERSION + 1),
+            1,
+        );
+        let snapshot: RuntimeSnapshot = serde_json::from_str(&saved).unwrap();
+        assert_eq!(snapshot.version(), SNAPSHOT_VERSION + 1);
+
+        let mut restored = runtime(SCRIPT);
+        let error = restored.restore(snapshot.clone()).unwrap_err();
+        assert_eq!(
+            error,
+            SnapshotError::UnsupportedVersion {
+                found: SNAPSHOT_VERSION + 1
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "ERROR: Unsupported snapshot version 2 (expected 1)."
+        );
+        assert!(matches!(
+            restored.migrate(snapshot),
+            Err(SnapshotError::UnsupportedVersion { found: 2 })
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+//! Closures the game runs when a variable changes.
+//!
+//! A watcher is registered for one variable by name, and runs whenever a
+//! `set` line or [`crate::Runtime::set_variable_value`] gives that variable
+//! a different value. Resetting the story and restoring a snapshot don't
+//! run watchers.
+
+use cuentitos_common::{Value, VariableId};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+type Watcher = Box<dyn FnMut(&VariableChange) + Send>;
+
+/// A change passed to the watchers of a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableChange {
+    /// The variable's name, as declared.
+    pub name: String,
+    pub old: Value,
+    pub new: Value,
+    /// The script the `set` line is in, as in
+    /// [`RuntimeError`](crate::RuntimeError) locations.
+    pub file: Option<PathBuf>,
+    /// The line of the `set` that made the change, or `None` when the game
+    /// made it with [`crate::Runtime::set_variable_value`].
+    pub line: Option<usize>,
+}
+
+/// The registered watchers, by variable.
+#[derive(Default)]
+pub(crate) struct VariableWatchers {
+    watchers: HashMap<VariableId, Vec<Watcher>>,
+}
+
+impl VariableWatchers {
+    /// Add `watcher` to the ones `variable` already has.
+    pub(crate) fn watch(&mut self, variable: VariableId, watcher: Watcher) {
+        self.watchers.entry(variable).or_default().push(watcher);
+    }
+
+    /// Drop every watcher of `variable`.
+    pub(crate) fn unwatch(&mut self, variable: VariableId) {
+        self.watchers.remove(&variable);
+    }
+
+    pub(crate) fn is_watched(&self, variable: VariableId) -> bool {
+        self.watchers.contains_key(&variable)
+    }
+
+    /// Run the watchers of `variable`, in the order they were registered.
+    pub(crate) fn notify(&mut self, variable: VariableId, change: &VariableChange) {
+        for watcher in self.watchers.get_mut(&variable).into_iter().flatten() {
+            watcher(change);
+        }
+    }
+}